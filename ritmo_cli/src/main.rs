@@ -1,13 +1,233 @@
+use std::io::{BufRead, IsTerminal, Write};
 use std::path::PathBuf;
-use ritmo_db_core::create_full_database_library;
+use std::time::Duration;
+use clap::{Args, Parser, Subcommand};
+use ritmo_core::dto::BookDto;
+use ritmo_core::service::import_service::{
+    DuplicateLayer, DuplicateMatch, DuplicatePolicies, DuplicatePolicy, FileReport, ImportOptions, ImportService,
+    ImportStatus, SameFilePolicy,
+};
+use ritmo_core::service::storage_service::StorageService;
+use ritmo_core::service::watch_service::{WatchOptions, WatchService};
+use ritmo_db::bulk_import::BulkImporter;
+use ritmo_db::upgrade::upgrade_library;
+use ritmo_db_core::{create_full_database_library, LibraryConfig};
+use ritmo_db_core::maintenance::{prune_change_log, recompute_normalized_keys, recompute_sort_keys};
+
+#[derive(Parser)]
+#[command(name = "ritmo", about = "Gestione della biblioteca Ritmo")]
+struct Cli {
+    /// Cartella della biblioteca
+    #[arg(long, default_value = "./mia_libreria")]
+    root: PathBuf,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Politiche per i libri già presenti: skip, attach, edition o ask
+#[derive(Args)]
+struct DuplicateArgs {
+    /// File identico a uno già in biblioteca (solo skip o ask)
+    #[arg(long, default_value = "skip")]
+    on_same_file: SameFilePolicy,
+    /// Stesso ISBN o identificatore
+    #[arg(long, default_value = "attach")]
+    on_same_identifier: DuplicatePolicy,
+    /// Titolo simile dello stesso autore
+    #[arg(long, default_value = "ask")]
+    on_similar_title: DuplicatePolicy,
+}
+
+impl DuplicateArgs {
+    fn policies(&self) -> DuplicatePolicies {
+        DuplicatePolicies {
+            hash: self.on_same_file,
+            identifier: self.on_same_identifier,
+            title: self.on_similar_title,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Ricalcola le chiavi normalizzate di persone e alias
+    NormalizeKeys,
+    /// Ricalcola le chiavi di ordinamento di titoli e autori
+    SortKeys,
+    /// Aggiorna i dati di una biblioteca creata con una versione precedente
+    Migrate,
+    /// Elimina dal registro delle modifiche le righe più vecchie di qualche giorno
+    PruneChangeLog {
+        /// Giorni di registro da conservare
+        #[arg(long, default_value_t = 30)]
+        days: u32,
+    },
+    /// Importa libri da file o cartelle
+    Import {
+        /// File o cartelle da importare
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+        /// Mostra cosa verrebbe creato senza modificare la biblioteca
+        #[arg(long)]
+        dry_run: bool,
+        /// Non entra nelle sottocartelle
+        #[arg(long)]
+        no_recursive: bool,
+        #[command(flatten)]
+        duplicates: DuplicateArgs,
+    },
+    /// Importa automaticamente i file depositati in una cartella, fino a Ctrl-C
+    Watch {
+        /// Cartella di arrivo
+        inbox: PathBuf,
+        /// Secondi senza modifiche dopo i quali un file è considerato completo
+        #[arg(long, default_value_t = 2)]
+        settle: u64,
+        #[command(flatten)]
+        duplicates: DuplicateArgs,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let root = PathBuf::from("./mia_libreria");
+    let cli = Cli::parse();
 
-    let db = create_full_database_library(&root).await?;
+    let db = create_full_database_library(&cli.root).await?;
 
-    println!("Database creato. Versione: {}", db.metadata().version);
+    match cli.command {
+        Some(Command::NormalizeKeys) => {
+            let report = recompute_normalized_keys(db.pool()).await?;
+            println!("Chiavi ricalcolate: {} persone, {} alias", report.people, report.aliases);
+        }
+        Some(Command::SortKeys) => {
+            let report = recompute_sort_keys(db.pool()).await?;
+            println!(
+                "Chiavi di ordinamento ricalcolate: {} persone, {} libri, {} contenuti",
+                report.people, report.books, report.contents
+            );
+        }
+        Some(Command::Migrate) => {
+            let report = upgrade_library(db.pool()).await?;
+            println!("File copiati in book_files: {}", report.book_files);
+            println!("Date convertite: {}", report.dates);
+            println!("Identificatori registrati: {}", report.identifiers);
+            if !report.invalid_isbns.is_empty() {
+                println!("Libri con ISBN non valido: {:?}", report.invalid_isbns);
+            }
+            println!("Copie cartacee create: {}", report.copies);
+        }
+        Some(Command::PruneChangeLog { days }) => {
+            let older_than = chrono::Utc::now().timestamp() - i64::from(days) * 86_400;
+            let removed = prune_change_log(db.pool(), older_than).await?;
+            println!("Righe eliminate dal registro delle modifiche: {}", removed);
+        }
+        Some(Command::Import { paths, dry_run, no_recursive, duplicates }) => {
+            let storage = StorageService::from_config(&LibraryConfig::new(&cli.root));
+            let options = ImportOptions {
+                dry_run,
+                recursive: !no_recursive,
+                duplicates: duplicates.policies(),
+                ..Default::default()
+            };
+            let mut service = ImportService::new(BulkImporter::new(db.pool()), storage).with_options(options);
+            // Senza un terminale non c'è nessuno a cui chiedere: i duplicati restano da decidere
+            if std::io::stdin().is_terminal() {
+                service = service.with_resolver(ask_duplicate);
+            }
+            let report = service.import_paths(&paths).await;
+            for file in &report.files {
+                print_file(file);
+            }
+            println!(
+                "{}{} aggiunti, {} come formato, {} duplicati, {} da decidere, {} errori",
+                if report.dry_run { "(prova) " } else { "" },
+                report.added(),
+                report.attached(),
+                report.duplicates(),
+                report.awaiting_decision(),
+                report.failed()
+            );
+        }
+        Some(Command::Watch { inbox, settle, duplicates }) => {
+            let storage = StorageService::from_config(&LibraryConfig::new(&cli.root));
+            let import = ImportOptions { duplicates: duplicates.policies(), ..Default::default() };
+            let options = WatchOptions { settle: Duration::from_secs(settle), import, ..Default::default() };
+            let mut service = WatchService::new(BulkImporter::new(db.pool()), storage, inbox)?.with_options(options);
+            println!("In attesa di file in {} (Ctrl-C per uscire)", service.inbox().display());
+            let shutdown = async {
+                let _ = tokio::signal::ctrl_c().await;
+            };
+            service
+                .run(shutdown, |file| {
+                    print_file(&file.report);
+                    println!("    spostato in {}", file.moved_to.display());
+                })
+                .await?;
+        }
+        None => println!("Database creato. Versione: {}", db.metadata().version),
+    }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Riga di resoconto di un file importato, con le entità create
+fn print_file(file: &FileReport) {
+    let title = file.title.as_deref().unwrap_or("?");
+    match &file.status {
+        ImportStatus::Added { book_id } => println!("+ {} \"{}\" (libro {})", file.path.display(), title, book_id),
+        ImportStatus::Duplicate { book_id: Some(id), .. } => {
+            println!("= {} \"{}\" già presente (libro {})", file.path.display(), title, id)
+        }
+        ImportStatus::Duplicate { duplicate_of, .. } => println!(
+            "= {} \"{}\" copia di {}",
+            file.path.display(),
+            title,
+            duplicate_of.as_ref().map(|p| p.display().to_string()).unwrap_or_default()
+        ),
+        ImportStatus::Attached { book_id } => {
+            println!("& {} \"{}\" aggiunto come formato (libro {})", file.path.display(), title, book_id)
+        }
+        ImportStatus::AwaitingDecision { book_id } => {
+            println!("? {} \"{}\" da decidere (libro {})", file.path.display(), title, book_id)
+        }
+        ImportStatus::Failed { reason } => println!("! {}: {}", file.path.display(), reason),
+    }
+    if let Some(decision) = &file.decision {
+        println!(
+            "    {} di \"{}\" (libro {}, {:.0}%): {}{}",
+            decision.found.layer,
+            decision.found.title,
+            decision.found.book_id,
+            decision.found.score * 100.0,
+            decision.policy,
+            if decision.asked { " su richiesta" } else { "" }
+        );
+    }
+    for entity in &file.created {
+        println!("    nuovo in {}: {}", entity.table, entity.name);
+    }
+}
+
+/// Chiede all'utente cosa fare di un duplicato; una risposta vuota o non valida lo lascia da decidere
+fn ask_duplicate(book: &BookDto, found: &DuplicateMatch) -> DuplicatePolicy {
+    // Un file identico non può diventare un formato o un'edizione
+    let same_file = found.layer == DuplicateLayer::Hash;
+    let choices = if same_file { "[s]alta" } else { "[s]alta, [f]ormato, [e]dizione" };
+    let trash = if found.in_trash { ", nel cestino" } else { "" };
+    print!(
+        "\"{}\" sembra già presente come \"{}\" (libro {}{}, {}). {}, invio per decidere dopo: ",
+        book.name, found.title, found.book_id, trash, found.layer, choices
+    );
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    if std::io::stdin().lock().read_line(&mut answer).is_err() {
+        return DuplicatePolicy::Ask;
+    }
+    match answer.trim().to_lowercase().as_str() {
+        "s" => DuplicatePolicy::Skip,
+        "f" if !same_file => DuplicatePolicy::AttachFormat,
+        "e" if !same_file => DuplicatePolicy::NewEdition,
+        _ => DuplicatePolicy::Ask,
+    }
+}
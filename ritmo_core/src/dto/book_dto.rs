@@ -1,7 +1,7 @@
 use crate::dto::{ContentDto, PersonDto, TagDto};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BookDto {
     pub name: String,
     pub original_title: Option<String>,
@@ -21,11 +21,21 @@ pub struct BookDto {
     pub series_id: Option<i64>,
     pub series_is_new: bool,
 
-    pub series_index: Option<i64>,
+    pub series_index: Option<f64>,
 
     pub publication_date: Option<i64>,
+    // Data come scritta nei metadati ("1957", "1957-06"): ha la precisione che il timestamp perde
+    pub publication_date_text: Option<String>,
     pub acquisition_date: Option<i64>,
+    // Provenienza: shop, gift, download, library_sale, other
+    pub acquisition_source: Option<String>,
+    pub acquisition_vendor: Option<String>,
+    pub acquisition_price: Option<f64>,
+    pub acquisition_currency: Option<String>,
+    pub provenance: Option<String>,
     pub isbn: Option<String>,
+    // Altri identificatori nella forma schema:valore (asin:B000FA5ZEG, doi:10.1000/182)
+    pub identifiers: Vec<String>,
 
     pub notes: Option<String>,
     pub has_cover: bool,
@@ -1,7 +1,7 @@
 use crate::dto::{LanguageDto, TagDto};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ContentDto {
     pub name: String,
     pub original_title: Option<String>,
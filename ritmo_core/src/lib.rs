@@ -3,7 +3,7 @@ pub mod storage;
 pub use dto::*;
 pub use storage::create_storage_dirs;
 
-//pub mod service;
+pub mod service;
 
 //use std::fs;
 //use std::path::{Path, PathBuf};
@@ -0,0 +1,533 @@
+//! Importazione di libri da file.
+//!
+//! Per ogni file: lettura dei metadati (`metadata`), costruzione del `BookDto`, controllo dei
+//! duplicati per hash del contenuto, salvataggio del libro con tutte le entità collegate in una
+//! transazione e archiviazione del file prima del commit. Il database è raggiunto tramite
+//! `ImportTarget`, implementato in `ritmo_db`, perché `ritmo_core` non dipende dai modelli.
+//! In modalità prova (`dry_run`) la transazione viene annullata e nessun file viene copiato,
+//! ma il resoconto elenca comunque le entità che verrebbero create.
+//!
+//! I duplicati sono cercati a tre livelli, dal più certo al meno certo: stesso contenuto del file,
+//! stesso identificatore (ISBN), titolo simile dello stesso autore. Per ogni livello
+//! `DuplicatePolicies` indica cosa fare, e la decisione presa finisce nel resoconto del file.
+
+use crate::dto::BookDto;
+use crate::service::metadata::read_metadata;
+use crate::service::storage_service::{StorageService, StoredFile};
+use ritmo_errors::{RitmoErr, RitmoResult};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Estensioni importate quando si passa una cartella
+pub const DEFAULT_EXTENSIONS: &[&str] = &["epub", "pdf", "mobi", "azw3", "fb2", "djvu", "cbz", "cbr", "txt"];
+
+/// Azione eseguita subito prima del commit; se fallisce la transazione viene annullata
+pub type BeforeCommit = Box<dyn FnOnce() -> RitmoResult<()> + Send>;
+
+/// Entità creata (o che verrebbe creata) durante l'importazione di un libro
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CreatedEntity {
+    /// Tabella della nuova riga (`people`, `publishers`, `series`, ...)
+    pub table: &'static str,
+    pub name: String,
+}
+
+/// Esito del salvataggio di un libro
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Persisted {
+    /// Id del libro; in modalità prova è quello che avrebbe avuto
+    pub book_id: i64,
+    pub contents: usize,
+    pub created: Vec<CreatedEntity>,
+}
+
+/// Livello a cui è stato riconosciuto un duplicato
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DuplicateLayer {
+    /// Stesso contenuto del file (SHA-256)
+    Hash,
+    /// Stesso ISBN o altro identificatore, anche in forma equivalente
+    Identifier,
+    /// Titolo simile con lo stesso primo autore
+    Title,
+}
+
+impl fmt::Display for DuplicateLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Hash => "stesso file",
+            Self::Identifier => "stesso identificatore",
+            Self::Title => "titolo e autore simili",
+        })
+    }
+}
+
+/// Cosa fare di un libro che risulta già in biblioteca
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DuplicatePolicy {
+    /// Non importarlo
+    Skip,
+    /// Aggiungere il file al libro esistente come formato alternativo
+    AttachFormat,
+    /// Importarlo come libro nuovo, i cui contenuti entrano nelle opere di quello esistente
+    NewEdition,
+    /// Chiedere al `DuplicateResolver`; senza risolutore il file resta in attesa di una decisione
+    Ask,
+}
+
+impl fmt::Display for DuplicatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Skip => "saltato",
+            Self::AttachFormat => "aggiunto come formato",
+            Self::NewEdition => "nuova edizione",
+            Self::Ask => "da decidere",
+        })
+    }
+}
+
+impl FromStr for DuplicatePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "skip" => Ok(Self::Skip),
+            "attach" | "format" => Ok(Self::AttachFormat),
+            "edition" => Ok(Self::NewEdition),
+            "ask" => Ok(Self::Ask),
+            other => Err(format!("politica sconosciuta: {} (skip, attach, edition, ask)", other)),
+        }
+    }
+}
+
+/// Cosa fare di un file identico a uno già archiviato: non c'è niente da aggiungere come formato
+/// o come edizione, quindi si può solo saltarlo o chiedere
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SameFilePolicy {
+    Skip,
+    Ask,
+}
+
+impl From<SameFilePolicy> for DuplicatePolicy {
+    fn from(policy: SameFilePolicy) -> Self {
+        match policy {
+            SameFilePolicy::Skip => Self::Skip,
+            SameFilePolicy::Ask => Self::Ask,
+        }
+    }
+}
+
+impl FromStr for SameFilePolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.parse::<DuplicatePolicy>()? {
+            DuplicatePolicy::Skip => Ok(Self::Skip),
+            DuplicatePolicy::Ask => Ok(Self::Ask),
+            other => Err(format!("un file identico non può essere {} (skip, ask)", other)),
+        }
+    }
+}
+
+/// Politica per ciascun livello di riconoscimento
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DuplicatePolicies {
+    pub hash: SameFilePolicy,
+    pub identifier: DuplicatePolicy,
+    pub title: DuplicatePolicy,
+}
+
+impl Default for DuplicatePolicies {
+    fn default() -> Self {
+        Self { hash: SameFilePolicy::Skip, identifier: DuplicatePolicy::AttachFormat, title: DuplicatePolicy::Ask }
+    }
+}
+
+impl DuplicatePolicies {
+    pub fn for_layer(&self, layer: DuplicateLayer) -> DuplicatePolicy {
+        match layer {
+            DuplicateLayer::Hash => self.hash.into(),
+            DuplicateLayer::Identifier => self.identifier,
+            DuplicateLayer::Title => self.title,
+        }
+    }
+}
+
+/// Libro già presente che corrisponde a quello da importare
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateMatch {
+    pub layer: DuplicateLayer,
+    pub book_id: i64,
+    /// Titolo del libro esistente
+    pub title: String,
+    /// Somiglianza tra 0 e 1; 1 per hash e identificatori
+    pub score: f64,
+    /// true se il libro esistente è nel cestino
+    pub in_trash: bool,
+}
+
+/// Decisione presa per un duplicato, riportata nel resoconto
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateDecision {
+    pub found: DuplicateMatch,
+    /// Politica applicata
+    pub policy: DuplicatePolicy,
+    /// true se la politica è stata scelta dal `DuplicateResolver`
+    pub asked: bool,
+}
+
+/// Sceglie cosa fare di un duplicato quando la politica del livello è `Ask`.
+/// Restituire di nuovo `Ask` lascia il file in attesa; per un file identico (`DuplicateLayer::Hash`)
+/// le scelte valide sono solo `Skip` e `Ask`, e le altre valgono come `Skip`.
+pub type DuplicateResolver = Box<dyn FnMut(&BookDto, &DuplicateMatch) -> DuplicatePolicy + Send>;
+
+/// Dove salvare il libro importato
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Placement {
+    NewBook,
+    /// Libro nuovo, con i contenuti collegati alle opere di quelli del libro indicato
+    EditionOf(i64),
+    /// Solo il file, aggiunto ai formati del libro indicato
+    FormatOf(i64),
+}
+
+/// Destinazione dei libri importati
+pub trait ImportTarget {
+    /// Libro già presente che corrisponde a `book`, cercato per livelli nell'ordine di
+    /// `DuplicateLayer`; il DTO ha già `file_hash` e `file_size`
+    fn find_duplicate(&mut self, book: &BookDto) -> impl Future<Output = RitmoResult<Option<DuplicateMatch>>> + Send;
+
+    /// Salva il libro secondo `placement` in un'unica transazione e chiama `before_commit` prima
+    /// di confermarla. Con `dry_run` la transazione viene sempre annullata e `before_commit`
+    /// non viene chiamato.
+    fn persist(
+        &mut self,
+        book: &BookDto,
+        placement: Placement,
+        dry_run: bool,
+        before_commit: BeforeCommit,
+    ) -> impl Future<Output = RitmoResult<Persisted>> + Send;
+}
+
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Simula l'importazione senza modificare database e archivio
+    pub dry_run: bool,
+    /// Entra nelle sottocartelle
+    pub recursive: bool,
+    /// Estensioni (minuscole, senza punto) dei file cercati nelle cartelle;
+    /// i file indicati direttamente vengono importati comunque
+    pub extensions: Vec<String>,
+    pub duplicates: DuplicatePolicies,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            recursive: true,
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            duplicates: DuplicatePolicies::default(),
+        }
+    }
+}
+
+/// Esito dell'importazione di un file
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportStatus {
+    /// Libro nuovo, anche come nuova edizione di un duplicato
+    Added { book_id: i64 },
+    /// File aggiunto come formato a un libro esistente
+    Attached { book_id: i64 },
+    /// Non importato: già in biblioteca, o già importato in questa esecuzione
+    Duplicate { book_id: Option<i64>, duplicate_of: Option<PathBuf> },
+    /// Duplicato per cui serve una decisione che nessuno ha preso
+    AwaitingDecision { book_id: i64 },
+    Failed { reason: String },
+}
+
+/// Resoconto di un singolo file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileReport {
+    pub path: PathBuf,
+    pub title: Option<String>,
+    pub status: ImportStatus,
+    pub file_link: Option<String>,
+    pub created: Vec<CreatedEntity>,
+    /// Presente se il libro è stato riconosciuto come duplicato
+    pub decision: Option<DuplicateDecision>,
+}
+
+/// Resoconto di un'importazione, un elemento per file nell'ordine di elaborazione
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    pub files: Vec<FileReport>,
+}
+
+impl ImportReport {
+    pub fn added(&self) -> usize {
+        self.count(|s| matches!(s, ImportStatus::Added { .. }))
+    }
+
+    pub fn attached(&self) -> usize {
+        self.count(|s| matches!(s, ImportStatus::Attached { .. }))
+    }
+
+    pub fn duplicates(&self) -> usize {
+        self.count(|s| matches!(s, ImportStatus::Duplicate { .. }))
+    }
+
+    pub fn awaiting_decision(&self) -> usize {
+        self.count(|s| matches!(s, ImportStatus::AwaitingDecision { .. }))
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(|s| matches!(s, ImportStatus::Failed { .. }))
+    }
+
+    fn count(&self, filter: impl Fn(&ImportStatus) -> bool) -> usize {
+        self.files.iter().filter(|f| filter(&f.status)).count()
+    }
+}
+
+pub struct ImportService<T> {
+    target: T,
+    storage: StorageService,
+    options: ImportOptions,
+    resolver: Option<DuplicateResolver>,
+    /// Hash dei file già elaborati in questa esecuzione, per riconoscere le copie anche in prova
+    seen: HashMap<String, PathBuf>,
+}
+
+impl<T: ImportTarget> ImportService<T> {
+    pub fn new(target: T, storage: StorageService) -> Self {
+        Self { target, storage, options: ImportOptions::default(), resolver: None, seen: HashMap::new() }
+    }
+
+    pub fn with_options(mut self, options: ImportOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Risolutore dei duplicati con politica `Ask`, ad esempio una domanda all'utente
+    pub fn with_resolver(mut self, resolver: impl FnMut(&BookDto, &DuplicateMatch) -> DuplicatePolicy + Send + 'static) -> Self {
+        self.resolver = Some(Box::new(resolver));
+        self
+    }
+
+    pub fn into_target(self) -> T {
+        self.target
+    }
+
+    /// Importa file e cartelle. Un errore su un file non interrompe gli altri e finisce nel resoconto.
+    pub async fn import_paths(&mut self, paths: &[PathBuf]) -> ImportReport {
+        let mut report = ImportReport { dry_run: self.options.dry_run, files: Vec::new() };
+        for path in paths {
+            if path.is_dir() {
+                match self.collect_files(path) {
+                    Ok(files) => {
+                        for file in files {
+                            report.files.push(self.import_file(&file).await);
+                        }
+                    }
+                    Err(e) => report.files.push(failed(path, None, e)),
+                }
+            } else {
+                report.files.push(self.import_file(path).await);
+            }
+        }
+        report
+    }
+
+    /// Importa un singolo file
+    pub async fn import_file(&mut self, path: &Path) -> FileReport {
+        self.import_with(path, None).await
+    }
+
+    /// Importa un file di cui il chiamante ha già calcolato hash e dimensione, senza rileggerlo
+    /// per l'hash. Se nel frattempo il contenuto cambia, l'archiviazione fallisce.
+    pub async fn import_hashed(&mut self, path: &Path, file_hash: String, file_size: i64) -> FileReport {
+        self.import_with(path, Some((file_hash, file_size))).await
+    }
+
+    async fn import_with(&mut self, path: &Path, hashed: Option<(String, i64)>) -> FileReport {
+        let mut book = match read_metadata(path) {
+            Ok(book) => book,
+            Err(e) => return failed(path, None, e),
+        };
+        let title = Some(book.name.clone());
+        match self.import_book(path, &mut book, hashed).await {
+            Ok(report) => report,
+            Err(e) => failed(path, title, e),
+        }
+    }
+
+    async fn import_book(
+        &mut self,
+        path: &Path,
+        book: &mut BookDto,
+        hashed: Option<(String, i64)>,
+    ) -> RitmoResult<FileReport> {
+        let (file_hash, file_size) = match hashed {
+            Some(hashed) => hashed,
+            None => {
+                let source = path.to_path_buf();
+                blocking(move || StorageService::hash_file(&source)).await?
+            }
+        };
+        let mut report = FileReport {
+            path: path.to_path_buf(),
+            title: Some(book.name.clone()),
+            status: ImportStatus::Duplicate { book_id: None, duplicate_of: None },
+            file_link: None,
+            created: Vec::new(),
+            decision: None,
+        };
+
+        book.file_hash = Some(file_hash.clone());
+        book.file_size = Some(file_size);
+        let found = self.target.find_duplicate(book).await?;
+
+        // Un file identico già visto in questa esecuzione (in prova non è nel database) conta più
+        // di una somiglianza trovata ad altri livelli
+        if found.as_ref().is_none_or(|f| f.layer != DuplicateLayer::Hash) {
+            if let Some(first) = self.seen.get(&file_hash) {
+                report.status = ImportStatus::Duplicate { book_id: None, duplicate_of: Some(first.clone()) };
+                return Ok(report);
+            }
+        }
+
+        let mut placement = Placement::NewBook;
+        if let Some(found) = found {
+            let decision = self.decide(book, found);
+            let book_id = decision.found.book_id;
+            match decision.policy {
+                DuplicatePolicy::Skip => {
+                    report.status = ImportStatus::Duplicate { book_id: Some(book_id), duplicate_of: None }
+                }
+                DuplicatePolicy::Ask => report.status = ImportStatus::AwaitingDecision { book_id },
+                DuplicatePolicy::AttachFormat => placement = Placement::FormatOf(book_id),
+                DuplicatePolicy::NewEdition => placement = Placement::EditionOf(book_id),
+            }
+            report.decision = Some(decision);
+            if placement == Placement::NewBook {
+                return Ok(report);
+            }
+        }
+
+        let storage = self.storage.clone();
+        let source = path.to_path_buf();
+        let hash = file_hash.clone();
+        let file_link = blocking(move || storage.link_for(&hash, &source)).await?;
+        book.file_link = Some(file_link.clone());
+
+        // Il file viene copiato a transazione ancora aperta: se la copia fallisce il libro non resta
+        let stored: Arc<Mutex<Option<StoredFile>>> = Arc::default();
+        let before_commit: BeforeCommit = {
+            let storage = self.storage.clone();
+            let source = path.to_path_buf();
+            let stored = Arc::clone(&stored);
+            let expected = file_link.clone();
+            let hash = file_hash.clone();
+            Box::new(move || {
+                let file = storage.store_hashed(&source, &hash)?;
+                let moved = file.file_link != expected;
+                let link = file.file_link.clone();
+                *stored.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+                if moved {
+                    return Err(RitmoErr::ImportError(format!("il file è stato archiviato come {}", link)));
+                }
+                Ok(())
+            })
+        };
+
+        let result = self.target.persist(book, placement, self.options.dry_run, before_commit).await;
+        let stored = stored.lock().unwrap_or_else(|e| e.into_inner()).take();
+        let persisted = match result {
+            Ok(persisted) => persisted,
+            Err(e) => {
+                // Il libro non è stato salvato: la copia appena fatta non serve più
+                if let Some(file) = stored.filter(|f| !f.deduplicated) {
+                    let _ = fs::remove_file(self.storage.resolve(&file.file_link));
+                }
+                return Err(e);
+            }
+        };
+
+        self.seen.insert(file_hash, path.to_path_buf());
+        report.status = match placement {
+            Placement::FormatOf(_) => ImportStatus::Attached { book_id: persisted.book_id },
+            _ => ImportStatus::Added { book_id: persisted.book_id },
+        };
+        report.file_link = Some(file_link);
+        report.created = persisted.created;
+        Ok(report)
+    }
+
+    /// Politica da applicare a un duplicato, chiedendo al risolutore se il livello lo prevede
+    fn decide(&mut self, book: &BookDto, found: DuplicateMatch) -> DuplicateDecision {
+        let mut policy = self.options.duplicates.for_layer(found.layer);
+        let mut asked = false;
+        if policy == DuplicatePolicy::Ask {
+            if let Some(resolver) = self.resolver.as_mut() {
+                policy = resolver(book, &found);
+                asked = true;
+            }
+        }
+        // La configurazione non lo permette, ma il risolutore può rispondere qualsiasi cosa
+        if found.layer == DuplicateLayer::Hash && policy != DuplicatePolicy::Ask {
+            policy = DuplicatePolicy::Skip;
+        }
+        DuplicateDecision { found, policy, asked }
+    }
+
+    /// File della cartella con un'estensione ammessa, in ordine di percorso
+    fn collect_files(&self, dir: &Path) -> RitmoResult<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut pending = vec![dir.to_path_buf()];
+        while let Some(dir) = pending.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    if self.options.recursive {
+                        pending.push(path);
+                    }
+                    continue;
+                }
+                let accepted = path
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| self.options.extensions.iter().any(|x| x.eq_ignore_ascii_case(e)));
+                if accepted {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+}
+
+fn failed(path: &Path, title: Option<String>, error: RitmoErr) -> FileReport {
+    FileReport {
+        path: path.to_path_buf(),
+        title,
+        status: ImportStatus::Failed { reason: error.to_string() },
+        file_link: None,
+        created: Vec::new(),
+        decision: None,
+    }
+}
+
+/// Esegue un'operazione sui file fuori dai thread del runtime
+async fn blocking<R: Send + 'static>(f: impl FnOnce() -> RitmoResult<R> + Send + 'static) -> RitmoResult<R> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| RitmoErr::OtherError(format!("operazione sui file interrotta: {}", e)))?
+}
@@ -0,0 +1,414 @@
+//! Lettura dei metadati di un file da importare.
+//!
+//! Per gli EPUB la fonte è il package OPF interno, trovato tramite `META-INF/container.xml`.
+//! Se manca (o il libro non è un EPUB) si usa un file OPF accanto al libro: `<nome>.opf`, oppure
+//! il `metadata.opf` che Calibre scrive in ogni cartella, purché riguardi proprio questo file.
+//! In ultima istanza i dati vengono dal nome del file, nella forma "Autore - Titolo.epub"
+//! o solo "Titolo.epub".
+
+use crate::dto::{BookDto, ContentDto, LanguageDto, PersonDto, TagDto};
+use crate::service::import_service::DEFAULT_EXTENSIONS;
+use chrono::NaiveDate;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Ruolo dei creatori senza `opf:role`
+const DEFAULT_ROLE: &str = "aut";
+
+/// Ruolo di lingua assegnato alle lingue lette dai metadati: è quella dell'edizione
+const LANGUAGE_ROLE: &str = "Actual";
+
+/// Descrittore dell'EPUB che indica dove si trova il package OPF
+const EPUB_CONTAINER: &str = "META-INF/container.xml";
+
+/// File OPF da cui leggere i metadati di `path`, se esiste.
+/// Il `metadata.opf` di cartella vale solo se `path` è l'unico libro della cartella
+/// o se il suo manifest cita il file.
+pub fn sidecar_for(path: &Path) -> Option<PathBuf> {
+    let own = path.with_extension("opf");
+    if own != path && own.is_file() {
+        return Some(own);
+    }
+    let dir = path.parent()?;
+    let calibre = dir.join("metadata.opf");
+    if !calibre.is_file() {
+        return None;
+    }
+    if books_in(dir).ok()?.iter().all(|other| other == path) {
+        return Some(calibre);
+    }
+    let file_name = path.file_name()?.to_str()?;
+    let xml = fs::read_to_string(&calibre).ok()?;
+    references(&xml, file_name).then_some(calibre)
+}
+
+/// Costruisce il DTO del libro dai metadati disponibili per `path`.
+/// Il formato è sempre l'estensione del file, in maiuscolo.
+pub fn read_metadata(path: &Path) -> RitmoResult<BookDto> {
+    let embedded = epub_package(path).and_then(|xml| parse_opf(&xml).ok());
+    let mut book = match (embedded, sidecar_for(path)) {
+        (Some(book), _) => book,
+        (None, Some(opf)) => parse_opf(&fs::read_to_string(&opf)?)?,
+        (None, None) => from_file_name(path)?,
+    };
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        book.format_name = ext.to_uppercase();
+    }
+    Ok(book)
+}
+
+/// Package OPF contenuto in un EPUB, se `path` è un EPUB leggibile
+fn epub_package(path: &Path) -> Option<String> {
+    let is_epub = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("epub"));
+    if !is_epub {
+        return None;
+    }
+    let mut archive = zip::ZipArchive::new(File::open(path).ok()?).ok()?;
+    let container = zip_entry(&mut archive, EPUB_CONTAINER)?;
+    let package = elements(&container, "rootfile")
+        .into_iter()
+        .filter(|(attrs, _)| {
+            attribute(attrs, "media-type").is_none_or(|m| m == "application/oebps-package+xml")
+        })
+        .find_map(|(attrs, _)| attribute(attrs, "full-path"))?;
+    zip_entry(&mut archive, &package)
+}
+
+fn zip_entry(archive: &mut zip::ZipArchive<File>, name: &str) -> Option<String> {
+    let mut entry = archive.by_name(name).ok()?;
+    let mut text = String::new();
+    entry.read_to_string(&mut text).ok()?;
+    Some(text)
+}
+
+/// File della cartella con un'estensione da libro
+fn books_in(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut books = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_book = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| DEFAULT_EXTENSIONS.iter().any(|b| b.eq_ignore_ascii_case(e)));
+        if is_book && path.is_file() {
+            books.push(path);
+        }
+    }
+    Ok(books)
+}
+
+/// true se un `href` del documento OPF (manifest o guide) punta a un file con quel nome
+fn references(xml: &str, file_name: &str) -> bool {
+    ["item", "reference"].iter().flat_map(|tag| elements(xml, tag)).any(|(attrs, _)| {
+        attribute(attrs, "href").is_some_and(|href| href.rsplit('/').next() == Some(file_name))
+    })
+}
+
+/// Metadati ricavati dal nome del file
+pub fn from_file_name(path: &Path) -> RitmoResult<BookDto> {
+    let stem = path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(|s| s.replace('_', " "))
+        .filter(|s| !s.trim().is_empty())
+        .ok_or_else(|| RitmoErr::PathError(format!("nome di file non valido: {}", path.display())))?;
+    let (author, title) = match stem.split_once(" - ") {
+        Some((author, title)) if !author.trim().is_empty() && !title.trim().is_empty() => {
+            (Some(author.trim().to_string()), title.trim().to_string())
+        }
+        _ => (None, stem.trim().to_string()),
+    };
+    let authors: Vec<(String, String)> = author.into_iter().map(|a| (a, DEFAULT_ROLE.to_string())).collect();
+    Ok(assemble(title, authors, Vec::new(), Vec::new()))
+}
+
+/// Metadati di un documento OPF (package di EPUB o `metadata.opf` di Calibre)
+pub fn parse_opf(xml: &str) -> RitmoResult<BookDto> {
+    let title = elements(xml, "dc:title")
+        .into_iter()
+        .map(|(_, text)| text)
+        .find(|t| !t.is_empty())
+        .ok_or_else(|| RitmoErr::ImportError("il file OPF non ha un titolo".to_string()))?;
+    let creators = elements(xml, "dc:creator")
+        .into_iter()
+        .filter(|(_, name)| !name.is_empty())
+        .map(|(attrs, name)| (name, attribute(attrs, "opf:role").unwrap_or_else(|| DEFAULT_ROLE.to_string())))
+        .collect();
+    let languages = texts(xml, "dc:language");
+    let subjects = texts(xml, "dc:subject");
+
+    let mut book = assemble(title, creators, languages, subjects);
+    book.publisher_name = texts(xml, "dc:publisher").into_iter().next().unwrap_or_default();
+    let date = texts(xml, "dc:date").into_iter().find(|d| parse_date(d).is_some());
+    book.publication_date = date.as_deref().and_then(parse_date);
+    book.publication_date_text = date;
+    for (attrs, text) in elements(xml, "dc:identifier") {
+        if text.is_empty() {
+            continue;
+        }
+        let scheme = attribute(attrs, "opf:scheme").unwrap_or_default();
+        let lower = text.to_lowercase();
+        let isbn = if scheme.eq_ignore_ascii_case("isbn") {
+            Some(text.clone())
+        } else {
+            lower
+                .strip_prefix("urn:isbn:")
+                .or_else(|| lower.strip_prefix("isbn:"))
+                .map(|isbn| isbn.to_string())
+        };
+        match isbn {
+            Some(isbn) if book.isbn.is_none() => book.isbn = Some(isbn),
+            Some(_) => {}
+            // Gli altri identificatori diventano "schema:valore", come li interpreta `Identifier::parse`
+            None if scheme.is_empty() => book.identifiers.push(text),
+            None => book.identifiers.push(format!("{}:{}", scheme.to_lowercase(), text)),
+        }
+    }
+    for (attrs, _) in elements(xml, "meta") {
+        let content = attribute(attrs, "content").unwrap_or_default();
+        match attribute(attrs, "name").as_deref() {
+            Some("calibre:series") => book.series_name = content,
+            Some("calibre:series_index") => book.series_index = content.parse().ok(),
+            _ => {}
+        }
+    }
+    Ok(book)
+}
+
+/// Libro con un unico contenuto dello stesso titolo, attribuito agli autori
+fn assemble(title: String, creators: Vec<(String, String)>, languages: Vec<String>, subjects: Vec<String>) -> BookDto {
+    let content = ContentDto {
+        name: title.clone(),
+        people: creators
+            .iter()
+            .filter(|(_, role)| role == DEFAULT_ROLE)
+            .map(|(name, _)| name.clone())
+            .collect(),
+        languages: languages
+            .into_iter()
+            .map(|name| LanguageDto { name, role: LANGUAGE_ROLE.to_string() })
+            .collect(),
+        ..Default::default()
+    };
+    BookDto {
+        name: title,
+        people: creators
+            .into_iter()
+            .map(|(person_name, person_role)| PersonDto { person_id: None, person_name, person_role })
+            .collect(),
+        tags: subjects
+            .into_iter()
+            .map(|name| TagDto { name, is_book_tag: true, is_content_tag: false })
+            .collect(),
+        contents: vec![content],
+        ..Default::default()
+    }
+}
+
+/// Data OPF ("1957", "1957-06", "1957-06-15T00:00:00+00:00") come timestamp
+fn parse_date(text: &str) -> Option<i64> {
+    let date = text.get(..10).unwrap_or(text);
+    let mut parts = date.split('-').map(|p| p.parse::<u32>().ok());
+    let year = parts.next()?? as i32;
+    let month = parts.next().flatten().unwrap_or(1);
+    let day = parts.next().flatten().unwrap_or(1);
+    Some(NaiveDate::from_ymd_opt(year, month, day)?.and_hms_opt(0, 0, 0)?.and_utc().timestamp())
+}
+
+fn texts(xml: &str, tag: &str) -> Vec<String> {
+    elements(xml, tag).into_iter().map(|(_, text)| text).filter(|t| !t.is_empty()).collect()
+}
+
+/// Elementi `<tag ...>testo</tag>` o `<tag .../>` del documento, con attributi e testo.
+/// Basta per i metadati OPF, che non annidano elementi dentro quelli letti qui.
+fn elements<'a>(xml: &'a str, tag: &str) -> Vec<(&'a str, String)> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut found = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        if !after.starts_with(|c: char| c.is_whitespace() || c == '>' || c == '/') {
+            rest = after;
+            continue;
+        }
+        let Some(gt) = after.find('>') else { break };
+        let attrs = &after[..gt];
+        if let Some(attrs) = attrs.strip_suffix('/') {
+            found.push((attrs, String::new()));
+            rest = &after[gt + 1..];
+            continue;
+        }
+        let body = &after[gt + 1..];
+        let Some(end) = body.find(&close) else { break };
+        found.push((attrs, unescape(body[..end].trim())));
+        rest = &body[end + close.len()..];
+    }
+    found
+}
+
+/// Valore di un attributo, tra virgolette doppie o singole
+fn attribute(attrs: &str, name: &str) -> Option<String> {
+    for quote in ['"', '\''] {
+        let pattern = format!("{}={}", name, quote);
+        let mut search = attrs;
+        while let Some(pos) = search.find(&pattern) {
+            let preceded = search[..pos].chars().next_back().is_none_or(char::is_whitespace);
+            let value = &search[pos + pattern.len()..];
+            if preceded {
+                return value.find(quote).map(|end| unescape(&value[..end]));
+            }
+            search = value;
+        }
+    }
+    None
+}
+
+/// Sostituisce le entità XML predefinite e i riferimenti numerici
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        let tail = &rest[amp..];
+        let Some(semi) = tail.find(';') else {
+            out.push_str(tail);
+            return out;
+        };
+        let entity = &tail[1..semi];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .map(|hex| u32::from_str_radix(hex, 16))
+                .or_else(|| entity.strip_prefix('#').map(|dec| dec.parse()))
+                .and_then(|code| code.ok())
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => out.push(c),
+            None => out.push_str(&tail[..=semi]),
+        }
+        rest = &tail[semi + 1..];
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPF: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+  <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
+    <dc:title>Il barone rampante</dc:title>
+    <dc:creator opf:role="aut" opf:file-as="Calvino, Italo">Italo Calvino</dc:creator>
+    <dc:creator opf:role="ill">Tullio Pericoli</dc:creator>
+    <dc:publisher>Einaudi &amp; C.</dc:publisher>
+    <dc:date>1957-06-15T00:00:00+00:00</dc:date>
+    <dc:language>it</dc:language>
+    <dc:identifier opf:scheme="ISBN">9788804668237</dc:identifier>
+    <dc:identifier opf:scheme="AMAZON">B00HVW7LMA</dc:identifier>
+    <dc:identifier>urn:uuid:2b6c4a4e-0c6a-4d57-9c7c-0f0d1c3e2a10</dc:identifier>
+    <dc:subject>Narrativa</dc:subject>
+    <meta name="calibre:series" content="I nostri antenati"/>
+    <meta name="calibre:series_index" content="2.0"/>
+  </metadata>
+</package>"#;
+
+    #[test]
+    fn test_opf_metadata() {
+        let book = parse_opf(OPF).unwrap();
+        assert_eq!(book.name, "Il barone rampante");
+        assert_eq!(book.publisher_name, "Einaudi & C.");
+        assert_eq!(book.isbn.as_deref(), Some("9788804668237"));
+        assert_eq!(book.identifiers, ["amazon:B00HVW7LMA", "urn:uuid:2b6c4a4e-0c6a-4d57-9c7c-0f0d1c3e2a10"]);
+        assert_eq!(book.series_name, "I nostri antenati");
+        assert_eq!(book.series_index, Some(2.0));
+        assert_eq!(book.publication_date, parse_date("1957-06-15"));
+        let people: Vec<(&str, &str)> =
+            book.people.iter().map(|p| (p.person_name.as_str(), p.person_role.as_str())).collect();
+        assert_eq!(people, [("Italo Calvino", "aut"), ("Tullio Pericoli", "ill")]);
+        assert_eq!(book.contents[0].people, ["Italo Calvino"]);
+        assert_eq!(book.contents[0].languages[0].name, "it");
+        assert_eq!(book.tags[0].name, "Narrativa");
+    }
+
+    #[test]
+    fn test_file_name_metadata() {
+        let book = from_file_name(Path::new("/libri/Italo Calvino - Il cavaliere inesistente.epub")).unwrap();
+        assert_eq!(book.name, "Il cavaliere inesistente");
+        assert_eq!(book.people[0].person_name, "Italo Calvino");
+        let book = from_file_name(Path::new("Le_citta_invisibili.pdf")).unwrap();
+        assert_eq!(book.name, "Le citta invisibili");
+        assert!(book.people.is_empty());
+        assert_eq!(unescape("&#x41;&#66;&bogus;"), "AB&bogus;");
+    }
+
+    fn write_epub(path: &Path, opf: &str) {
+        use std::io::Write;
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        zip.start_file("mimetype", options).unwrap();
+        zip.write_all(b"application/epub+zip").unwrap();
+        zip.start_file(EPUB_CONTAINER, options).unwrap();
+        zip.write_all(
+            br#"<?xml version="1.0"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+  <rootfiles>
+    <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+  </rootfiles>
+</container>"#,
+        )
+        .unwrap();
+        zip.start_file("OEBPS/content.opf", options).unwrap();
+        zip.write_all(opf.as_bytes()).unwrap();
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_embedded_opf_comes_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let epub = dir.path().join("Calvino - Altro titolo.epub");
+        write_epub(&epub, &OPF.replace("1957-06-15T00:00:00+00:00", "1957-06"));
+        fs::write(dir.path().join("metadata.opf"), OPF.replace("Il barone rampante", "Titolo di Calibre")).unwrap();
+
+        let book = read_metadata(&epub).unwrap();
+        assert_eq!(book.name, "Il barone rampante");
+        assert_eq!(book.format_name, "EPUB");
+        assert_eq!(book.publication_date_text.as_deref(), Some("1957-06"));
+        assert_eq!(book.publication_date, parse_date("1957-06-01"));
+
+        // Un EPUB senza package leggibile ricade sul metadata.opf della cartella
+        fs::write(&epub, b"non un archivio zip").unwrap();
+        assert_eq!(read_metadata(&epub).unwrap().name, "Titolo di Calibre");
+    }
+
+    #[test]
+    fn test_folder_opf_must_match_the_book() {
+        let dir = tempfile::tempdir().unwrap();
+        let first = dir.path().join("Italo Calvino - Il cavaliere inesistente.pdf");
+        let second = dir.path().join("Le città invisibili.pdf");
+        fs::write(&first, b"pdf").unwrap();
+        let calibre = dir.path().join("metadata.opf");
+        fs::write(&calibre, OPF).unwrap();
+        // Unico libro della cartella
+        assert_eq!(sidecar_for(&first), Some(calibre.clone()));
+
+        // Con due libri il metadata.opf vale solo per quello citato nel manifest
+        fs::write(&second, b"pdf").unwrap();
+        assert_eq!(sidecar_for(&first), None);
+        assert_eq!(read_metadata(&first).unwrap().name, "Il cavaliere inesistente");
+        let manifest = r#"<manifest><item id="book" href="Le città invisibili.pdf"/></manifest></package>"#;
+        fs::write(&calibre, OPF.replace("</package>", manifest)).unwrap();
+        assert_eq!(sidecar_for(&second), Some(calibre));
+        assert_eq!(sidecar_for(&first), None);
+    }
+}
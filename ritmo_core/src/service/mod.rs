@@ -1 +1,4 @@
+pub mod import_service;
+pub mod metadata;
 pub mod storage_service;
+pub mod watch_service;
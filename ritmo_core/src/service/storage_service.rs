@@ -1,9 +1,217 @@
+//! Archivio dei file della biblioteca, indirizzato per contenuto.
+//!
+//! Ogni file è salvato una sola volta in `storage/books/ab/cd/<sha256>.<estensione>`, dove `ab` e
+//! `cd` sono i primi caratteri dell'hash del contenuto. La copia viene scritta in `storage/temp` e
+//! spostata al percorso definitivo con un rename, così un'importazione interrotta non lascia mai
+//! un file a metà nell'archivio. Le operazioni sono bloccanti: dal codice async vanno chiamate
+//! con `tokio::task::spawn_blocking`.
+
 use crate::dto::BookDto;
-use ritmo_errors::RitmoResult;
+use ritmo_db_core::LibraryConfig;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Cartella dei libri, relativa a `storage_path`
+pub const BOOKS_DIR: &str = "books";
+
+/// Cartella delle copie in corso, relativa a `storage_path`
+pub const TEMP_DIR: &str = "temp";
+
+const COPY_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Contatore per i nomi dei file temporanei, unici anche tra thread dello stesso processo
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Esito di `StorageService::store`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredFile {
+    /// Percorso relativo a `storage_path`, sempre con '/' come separatore
+    pub file_link: String,
+    /// SHA-256 del contenuto, in esadecimale
+    pub file_hash: String,
+    pub file_size: i64,
+    /// true se un file identico era già in archivio e non è stato copiato di nuovo
+    pub deduplicated: bool,
+}
+
+/// File temporaneo eliminato al drop, a meno che non sia stato spostato nell'archivio
+struct TempFile {
+    path: PathBuf,
+    kept: bool,
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        if !self.kept {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StorageService {
+    storage_path: PathBuf,
+}
+
+impl StorageService {
+    pub fn new(storage_path: impl Into<PathBuf>) -> Self {
+        Self { storage_path: storage_path.into() }
+    }
+
+    pub fn from_config(config: &LibraryConfig) -> Self {
+        Self::new(&config.storage_path)
+    }
+
+    pub fn storage_path(&self) -> &Path {
+        &self.storage_path
+    }
+
+    /// Percorso assoluto di un file dell'archivio a partire dal suo `file_link`
+    pub fn resolve(&self, file_link: &str) -> PathBuf {
+        file_link.split('/').fold(self.storage_path.clone(), |path, part| path.join(part))
+    }
+
+    /// Hash SHA-256 e dimensione del contenuto di un file
+    pub fn hash_file(path: &Path) -> RitmoResult<(String, i64)> {
+        let mut file = File::open(path)?;
+        copy_hashing(&mut file, None)
+    }
+
+    /// Cerca in archivio un file con l'hash indicato, con qualsiasi estensione
+    pub fn find(&self, file_hash: &str) -> RitmoResult<Option<String>> {
+        let shard = self.shard(file_hash)?;
+        let dir = self.resolve(&shard);
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        for entry in entries {
+            let name = entry?.file_name();
+            let Some(name) = name.to_str() else { continue };
+            if name.split('.').next() == Some(file_hash) {
+                return Ok(Some(format!("{}/{}", shard, name)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Copia `source` nell'archivio e ne restituisce percorso, hash e dimensione.
+    /// Se lo stesso contenuto è già presente non viene copiato di nuovo e il link è quello esistente.
+    pub fn store(&self, source: &Path) -> RitmoResult<StoredFile> {
+        if !source.is_file() {
+            return Err(RitmoErr::PathError(format!("{} non è un file", source.display())));
+        }
+        let (file_hash, _) = Self::hash_file(source)?;
+        self.store_hashed(source, &file_hash)
+    }
+
+    /// Come `store`, per un file di cui si conosce già l'hash: se il contenuto è in archivio il file
+    /// non viene nemmeno letto. La copia ricalcola l'hash e fallisce se il file è cambiato nel frattempo.
+    pub fn store_hashed(&self, source: &Path, file_hash: &str) -> RitmoResult<StoredFile> {
+        if !source.is_file() {
+            return Err(RitmoErr::PathError(format!("{} non è un file", source.display())));
+        }
+        if let Some(file_link) = self.find(file_hash)? {
+            let file_size = fs::metadata(self.resolve(&file_link))?.len() as i64;
+            return Ok(StoredFile { file_link, file_hash: file_hash.to_string(), file_size, deduplicated: true });
+        }
+
+        let temp_dir = self.storage_path.join(TEMP_DIR);
+        fs::create_dir_all(&temp_dir)?;
+        let mut temp = TempFile {
+            path: temp_dir.join(format!(
+                "{}-{}.part",
+                std::process::id(),
+                TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+            )),
+            kept: false,
+        };
+
+        let (copied_hash, file_size) = {
+            let mut input = File::open(source)?;
+            let mut output = OpenOptions::new().write(true).create_new(true).open(&temp.path)?;
+            let hashed = copy_hashing(&mut input, Some(&mut output))?;
+            output.sync_all()?;
+            hashed
+        };
+        if copied_hash != file_hash {
+            return Err(RitmoErr::InvalidInput(format!(
+                "il contenuto di {} è cambiato durante la copia",
+                source.display()
+            )));
+        }
+
+        let file_link = self.new_link(file_hash, source)?;
+        let destination = self.resolve(&file_link);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        // `temp` è dentro `storage_path`, quindi sullo stesso filesystem: il rename è atomico
+        fs::rename(&temp.path, &destination)?;
+        temp.kept = true;
+        Ok(StoredFile { file_link, file_hash: file_hash.to_string(), file_size, deduplicated: false })
+    }
+
+    /// Link che `store` assegnerà a un file con quel contenuto: quello già in archivio, se c'è
+    pub fn link_for(&self, file_hash: &str, source: &Path) -> RitmoResult<String> {
+        match self.find(file_hash)? {
+            Some(file_link) => Ok(file_link),
+            None => self.new_link(file_hash, source),
+        }
+    }
+
+    fn new_link(&self, file_hash: &str, source: &Path) -> RitmoResult<String> {
+        let file_name = match extension(source) {
+            Some(ext) => format!("{}.{}", file_hash, ext),
+            None => file_hash.to_string(),
+        };
+        Ok(format!("{}/{}", self.shard(file_hash)?, file_name))
+    }
+
+    /// Cartella di un hash, relativa a `storage_path`: `books/ab/cd`
+    fn shard(&self, file_hash: &str) -> RitmoResult<String> {
+        if file_hash.len() < 4 || !file_hash.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(RitmoErr::InvalidInput(format!("hash non valido: {}", file_hash)));
+        }
+        Ok(format!("{}/{}/{}", BOOKS_DIR, &file_hash[0..2], &file_hash[2..4]))
+    }
+}
+
+/// Estensione del file in minuscolo, solo se composta da caratteri alfanumerici
+fn extension(path: &Path) -> Option<String> {
+    let ext = path.extension()?.to_str()?.to_lowercase();
+    (!ext.is_empty() && ext.chars().all(|c| c.is_ascii_alphanumeric())).then_some(ext)
+}
+
+/// Legge `input` fino alla fine calcolando l'hash; se c'è, scrive gli stessi byte in `output`
+fn copy_hashing(input: &mut File, mut output: Option<&mut File>) -> RitmoResult<(String, i64)> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; COPY_BUFFER_SIZE];
+    let mut size = 0i64;
+    loop {
+        let read = input.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        if let Some(output) = output.as_deref_mut() {
+            output.write_all(&buffer[..read])?;
+        }
+        size += read as i64;
+    }
+    Ok((hex::encode(hasher.finalize()), size))
+}
 
-pub fn book_persistence(book: &mut BookDto) -> RitmoResult<()> {
-    book.file_link = Some("ab/cb/abcdefghijklmnopqrstuvwxyz0123456789012345678901".to_string());
-    book.file_size = Some(100000);
-    book.file_hash = Some("abcdefghijklmnopqrstuvwxyz0123456789012345678901".to_string());
-    Ok(())
+/// Archivia il file del libro e ne riporta link, dimensione e hash nel DTO
+pub fn book_persistence(storage: &StorageService, book: &mut BookDto, source: &Path) -> RitmoResult<StoredFile> {
+    let stored = storage.store(source)?;
+    book.file_link = Some(stored.file_link.clone());
+    book.file_size = Some(stored.file_size);
+    book.file_hash = Some(stored.file_hash.clone());
+    Ok(stored)
 }
@@ -0,0 +1,334 @@
+//! Importazione automatica da una cartella di arrivo.
+//!
+//! `WatchService` osserva la cartella (solo il primo livello) con `notify` e considera pronto un
+//! file quando dimensione e data di modifica restano ferme per `settle`: così un download ancora
+//! in corso non viene importato a metà. Ogni file pronto passa da `ImportService` e poi viene
+//! spostato in `processed/` o in `failed/`, insieme al suo `.opf` se c'è. I duplicati la cui
+//! politica è `Ask` finiscono in `review/`.
+//!
+//! L'esito di ogni file importato o riconosciuto come duplicato è annotato per hash del contenuto
+//! in `.ritmo-watch.json` dentro la cartella, prima dello spostamento. Dopo un riavvio un file già
+//! annotato, anche se lasciato in arrivo da un'interruzione o depositato di nuovo, viene solo
+//! spostato e non reimportato. I fallimenti non vengono annotati: un file tolto da `failed/` e
+//! depositato di nuovo viene riprovato.
+
+use crate::service::import_service::{FileReport, ImportOptions, ImportService, ImportStatus, ImportTarget};
+use crate::service::storage_service::StorageService;
+use notify::{RecursiveMode, Watcher};
+use ritmo_errors::{RitmoErr, RitmoResult};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Sottocartella dei file importati o già presenti in biblioteca
+pub const PROCESSED_DIR: &str = "processed";
+
+/// Sottocartella dei file che non è stato possibile importare
+pub const FAILED_DIR: &str = "failed";
+
+/// Sottocartella dei duplicati per cui la politica chiede una decisione: non c'è nessuno a cui
+/// chiederla, quindi restano lì finché non vengono importati a mano o ridepositati
+pub const REVIEW_DIR: &str = "review";
+
+/// Registro degli esiti, dentro la cartella osservata
+pub const RECORD_FILE: &str = ".ritmo-watch.json";
+
+/// Estensioni usate dai browser e dai client di sincronizzazione per i file in scrittura
+const PARTIAL_EXTENSIONS: &[&str] = &["part", "crdownload", "download", "tmp", "partial"];
+
+#[derive(Debug, Clone)]
+pub struct WatchOptions {
+    /// Tempo senza modifiche dopo il quale un file è considerato completo
+    pub settle: Duration,
+    /// Ogni quanto vengono ricontrollati i file in attesa
+    pub poll_interval: Duration,
+    /// Opzioni passate all'importazione; `dry_run` e `recursive` non vengono usati
+    pub import: ImportOptions,
+}
+
+impl Default for WatchOptions {
+    fn default() -> Self {
+        Self { settle: Duration::from_secs(2), poll_interval: Duration::from_millis(500), import: ImportOptions::default() }
+    }
+}
+
+/// Esito annotato nel registro per un contenuto
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecordEntry {
+    /// Nome del file quando è stato elaborato la prima volta
+    pub file_name: String,
+    pub outcome: Outcome,
+    pub book_id: Option<i64>,
+    /// Timestamp dell'elaborazione
+    pub processed_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Added,
+    Attached,
+    Duplicate,
+}
+
+/// File elaborato dalla cartella di arrivo
+#[derive(Debug, Clone, PartialEq)]
+pub struct WatchedFile {
+    /// Resoconto dell'importazione; per i file già nel registro lo stato è ricostruito da questo
+    pub report: FileReport,
+    /// Percorso dopo lo spostamento in `processed/` o `failed/`
+    pub moved_to: PathBuf,
+    /// true se il contenuto era già nel registro e non è stato reimportato
+    pub from_record: bool,
+}
+
+/// File in attesa che la scrittura finisca
+struct Pending {
+    changed_at: Instant,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+pub struct WatchService<T> {
+    import: ImportService<T>,
+    inbox: PathBuf,
+    options: WatchOptions,
+    record: BTreeMap<String, RecordEntry>,
+    pending: HashMap<PathBuf, Pending>,
+}
+
+impl<T: ImportTarget> WatchService<T> {
+    /// Prepara le sottocartelle e carica il registro della cartella `inbox`
+    pub fn new(target: T, storage: StorageService, inbox: impl Into<PathBuf>) -> RitmoResult<Self> {
+        let inbox = inbox.into();
+        if !inbox.is_dir() {
+            return Err(RitmoErr::PathError(format!("{} non è una cartella", inbox.display())));
+        }
+        // Gli eventi di `notify` hanno percorsi assoluti
+        let inbox = inbox.canonicalize()?;
+        fs::create_dir_all(inbox.join(PROCESSED_DIR))?;
+        fs::create_dir_all(inbox.join(FAILED_DIR))?;
+        fs::create_dir_all(inbox.join(REVIEW_DIR))?;
+        let record = match fs::read_to_string(inbox.join(RECORD_FILE)) {
+            Ok(text) => serde_json::from_str(&text)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            import: ImportService::new(target, storage),
+            inbox,
+            options: WatchOptions::default(),
+            record,
+            pending: HashMap::new(),
+        })
+    }
+
+    pub fn with_options(mut self, options: WatchOptions) -> Self {
+        let import = ImportOptions { dry_run: false, recursive: false, ..options.import.clone() };
+        self.import = self.import.with_options(import);
+        self.options = options;
+        self
+    }
+
+    pub fn inbox(&self) -> &Path {
+        &self.inbox
+    }
+
+    /// Esiti annotati finora, per hash del contenuto
+    pub fn record(&self) -> &BTreeMap<String, RecordEntry> {
+        &self.record
+    }
+
+    /// Osserva la cartella finché `shutdown` non si completa, chiamando `on_file` per ogni file
+    /// elaborato. I file già presenti all'avvio vengono elaborati come quelli nuovi.
+    /// Gli errori del watcher dopo l'avvio finiscono nel log e non interrompono il ciclo.
+    pub async fn run<F>(&mut self, shutdown: F, mut on_file: impl FnMut(&WatchedFile)) -> RitmoResult<()>
+    where
+        F: Future<Output = ()>,
+    {
+        let (tx, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            let _ = tx.send(event);
+        })
+        .map_err(watch_error)?;
+        watcher.watch(&self.inbox, RecursiveMode::NonRecursive).map_err(watch_error)?;
+        self.scan()?;
+
+        let mut ticks = tokio::time::interval(self.options.poll_interval);
+        tokio::pin!(shutdown);
+        loop {
+            tokio::select! {
+                _ = &mut shutdown => return Ok(()),
+                Some(event) = events.recv() => match event {
+                    Ok(event) => {
+                        for path in event.paths {
+                            self.touch(path);
+                        }
+                    }
+                    // Un evento perso (ad esempio per la coda del kernel piena) non ferma la
+                    // sorveglianza: si rilegge la cartella per ritrovare i file che annunciava
+                    Err(e) => {
+                        tracing::warn!("Errore dalla sorveglianza di {}: {}", self.inbox.display(), e);
+                        if let Err(e) = self.scan() {
+                            tracing::warn!("Impossibile rileggere {}: {}", self.inbox.display(), e);
+                        }
+                    }
+                },
+                _ = ticks.tick() => {
+                    for file in self.process_ready().await {
+                        on_file(&file);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Mette in attesa i file presenti nella cartella; restituisce quanti ne sono in attesa
+    pub fn scan(&mut self) -> RitmoResult<usize> {
+        for entry in fs::read_dir(&self.inbox)? {
+            self.touch(entry?.path());
+        }
+        Ok(self.pending.len())
+    }
+
+    /// Elabora, in ordine di percorso, i file in attesa la cui scrittura risulta finita.
+    /// Un file che non si riesce ad annotare o spostare viene segnalato nel log e lasciato in
+    /// arrivo, senza fermare gli altri: sarà ripreso alla prossima modifica o al prossimo avvio.
+    pub async fn process_ready(&mut self) -> Vec<WatchedFile> {
+        let now = Instant::now();
+        let mut ready = Vec::new();
+        self.pending.retain(|path, pending| {
+            let Ok(meta) = fs::metadata(path) else { return false };
+            let modified = meta.modified().ok();
+            if meta.len() != pending.size || modified != pending.modified {
+                *pending = Pending { changed_at: now, size: meta.len(), modified };
+            } else if now.duration_since(pending.changed_at) >= self.options.settle {
+                ready.push(path.clone());
+                return false;
+            }
+            true
+        });
+        ready.sort();
+
+        let mut processed = Vec::with_capacity(ready.len());
+        for path in ready {
+            match self.process(&path).await {
+                Ok(file) => processed.extend(file),
+                Err(e) => tracing::warn!("{} non elaborato: {}", path.display(), e),
+            }
+        }
+        processed
+    }
+
+    /// Importa un file pronto, lo annota nel registro e lo sposta nella sottocartella del suo esito.
+    /// Gli errori di importazione finiscono nel resoconto; quelli restituiti riguardano
+    /// registro e spostamenti, senza i quali il file verrebbe rielaborato.
+    async fn process(&mut self, path: &Path) -> RitmoResult<Option<WatchedFile>> {
+        let source = path.to_path_buf();
+        let hashed = tokio::task::spawn_blocking(move || StorageService::hash_file(&source))
+            .await
+            .map_err(|e| RitmoErr::OtherError(format!("calcolo dell'hash interrotto: {}", e)))?;
+        let (file_hash, file_size) = match hashed {
+            Ok(hashed) => hashed,
+            Err(_) => {
+                // Illeggibile per ora (rimosso, bloccato da chi lo scrive): torna in attesa
+                self.touch(path.to_path_buf());
+                return Ok(None);
+            }
+        };
+
+        let (report, from_record) = match self.record.get(&file_hash) {
+            Some(entry) => (recorded_report(path, entry), true),
+            None => {
+                let report = self.import.import_hashed(path, file_hash.clone(), file_size).await;
+                let recorded = match &report.status {
+                    ImportStatus::Added { book_id } => Some((Outcome::Added, Some(*book_id))),
+                    ImportStatus::Attached { book_id } => Some((Outcome::Attached, Some(*book_id))),
+                    ImportStatus::Duplicate { book_id, .. } => Some((Outcome::Duplicate, *book_id)),
+                    // Non sono esiti definitivi: se il file torna va elaborato di nuovo
+                    ImportStatus::Failed { .. } | ImportStatus::AwaitingDecision { .. } => None,
+                };
+                if let Some((outcome, book_id)) = recorded {
+                    let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+                    let processed_at = chrono::Utc::now().timestamp();
+                    self.record.insert(file_hash, RecordEntry { file_name, outcome, book_id, processed_at });
+                    self.save_record()?;
+                }
+                (report, false)
+            }
+        };
+
+        let folder = match report.status {
+            ImportStatus::Failed { .. } => FAILED_DIR,
+            ImportStatus::AwaitingDecision { .. } => REVIEW_DIR,
+            _ => PROCESSED_DIR,
+        };
+        let moved_to = move_into(path, &self.inbox.join(folder))?;
+        let sidecar = path.with_extension("opf");
+        if sidecar.is_file() {
+            move_into(&sidecar, &self.inbox.join(folder))?;
+        }
+        Ok(Some(WatchedFile { report, moved_to, from_record }))
+    }
+
+    /// Mette in attesa `path` se è un file del primo livello con un'estensione importabile
+    fn touch(&mut self, path: PathBuf) {
+        if path.parent() != Some(self.inbox.as_path()) || !self.accepts(&path) {
+            return;
+        }
+        let Ok(meta) = fs::metadata(&path) else {
+            self.pending.remove(&path);
+            return;
+        };
+        if !meta.is_file() {
+            return;
+        }
+        let pending = Pending { changed_at: Instant::now(), size: meta.len(), modified: meta.modified().ok() };
+        self.pending.insert(path, pending);
+    }
+
+    fn accepts(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else { return false };
+        if name.starts_with('.') {
+            return false;
+        }
+        let Some(ext) = path.extension().and_then(|e| e.to_str()) else { return false };
+        !PARTIAL_EXTENSIONS.iter().any(|p| p.eq_ignore_ascii_case(ext))
+            && self.options.import.extensions.iter().any(|x| x.eq_ignore_ascii_case(ext))
+    }
+
+    /// Riscrive il registro passando da un file temporaneo, per non lasciarlo mai troncato
+    fn save_record(&self) -> RitmoResult<()> {
+        let path = self.inbox.join(RECORD_FILE);
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, serde_json::to_vec_pretty(&self.record)?)?;
+        fs::rename(&temp, &path)?;
+        Ok(())
+    }
+}
+
+/// Sposta `path` in `folder`, aggiungendo " (n)" al nome se esiste già un file uguale
+fn move_into(path: &Path, folder: &Path) -> RitmoResult<PathBuf> {
+    let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+    let ext = path.extension().map(|e| format!(".{}", e.to_string_lossy())).unwrap_or_default();
+    let mut destination = folder.join(format!("{}{}", stem, ext));
+    let mut n = 1;
+    while destination.exists() {
+        destination = folder.join(format!("{} ({}){}", stem, n, ext));
+        n += 1;
+    }
+    fs::rename(path, &destination)?;
+    Ok(destination)
+}
+
+fn recorded_report(path: &Path, entry: &RecordEntry) -> FileReport {
+    let status = ImportStatus::Duplicate { book_id: entry.book_id, duplicate_of: None };
+    FileReport { path: path.to_path_buf(), title: None, status, file_link: None, created: Vec::new(), decision: None }
+}
+
+fn watch_error(error: notify::Error) -> RitmoErr {
+    RitmoErr::IoError(format!("osservazione della cartella: {}", error))
+}
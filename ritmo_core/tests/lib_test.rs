@@ -72,13 +72,13 @@ fn test_library_config_validate() {
     // Prima dell'inizializzazione, la validazione dovrebbe fallire
     let validate_result = config.validate();
     assert!(validate_result.is_ok());
-    assert_eq!(validate_result.unwrap(), false);
+    assert!(!validate_result.unwrap());
 
     // Dopo l'inizializzazione, la validazione dovrebbe riuscire
     config.initialize().expect("Inizializzazione fallita");
     let validate_result = config.validate();
     assert!(validate_result.is_ok());
-    assert_eq!(validate_result.unwrap(), true);
+    assert!(validate_result.unwrap());
 }
 
 #[tokio::test]
@@ -101,6 +101,7 @@ async fn test_initialize_database_and_pool() {
     // CREA IL FILE VUOTO prima del pool!
     std::fs::OpenOptions::new()
         .create(true)
+        .truncate(false)
         .write(true)
         .open(&canonical_db_file)
         .expect("Impossibile creare il file del database");
@@ -122,7 +123,7 @@ async fn test_initialize_database_and_pool() {
     );
 
     let pool = pool_result.unwrap();
-    let version_row: (i64,) = sqlx::query_as!("PRAGMA user_version")
+    let version_row: (i64,) = sqlx::query_as("PRAGMA user_version")
         .fetch_one(&pool)
         .await
         .expect("Query fallita");
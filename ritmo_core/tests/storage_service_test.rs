@@ -0,0 +1,100 @@
+use ritmo_core::dto::BookDto;
+use ritmo_core::service::storage_service::{book_persistence, StorageService, StoredFile};
+use ritmo_db_core::LibraryConfig;
+use std::fs;
+use tempfile::tempdir;
+
+#[test]
+fn test_files_are_stored_by_content_hash() {
+    let temp_dir = tempdir().expect("Impossibile creare directory temporanea");
+    let config = LibraryConfig::new(temp_dir.path().join("biblioteca"));
+    config.initialize().expect("Inizializzazione fallita");
+    let storage = StorageService::from_config(&config);
+
+    let source = temp_dir.path().join("Il Barone Rampante.PDF");
+    fs::write(&source, b"contenuto del libro").unwrap();
+    let mut book = BookDto { name: "Il barone rampante".to_string(), ..Default::default() };
+    let stored = book_persistence(&storage, &mut book, &source).unwrap();
+
+    let (hash, size) = StorageService::hash_file(&source).unwrap();
+    assert_eq!((stored.file_hash.as_str(), stored.file_size), (hash.as_str(), size));
+    assert_eq!(stored.file_size, 19);
+    assert_eq!(stored.file_link, format!("books/{}/{}/{}.pdf", &hash[0..2], &hash[2..4], hash));
+    assert!(!stored.deduplicated);
+    assert_eq!(fs::read(storage.resolve(&stored.file_link)).unwrap(), b"contenuto del libro");
+    assert_eq!(book.file_link.as_deref(), Some(stored.file_link.as_str()));
+    assert_eq!(book.file_hash.as_deref(), Some(hash.as_str()));
+    assert_eq!(book.file_size, Some(19));
+    // Nessun file temporaneo resta dopo la copia
+    assert_eq!(fs::read_dir(config.storage_path.join("temp")).unwrap().count(), 0);
+}
+
+#[test]
+fn test_identical_files_are_stored_once() {
+    let temp_dir = tempdir().expect("Impossibile creare directory temporanea");
+    let storage = StorageService::new(temp_dir.path().join("storage"));
+    let first = temp_dir.path().join("copia1.epub");
+    let second = temp_dir.path().join("copia2.epub");
+    let other = temp_dir.path().join("altro.epub");
+    fs::write(&first, b"stesso contenuto").unwrap();
+    fs::write(&second, b"stesso contenuto").unwrap();
+    fs::write(&other, b"contenuto diverso").unwrap();
+
+    let a = storage.store(&first).unwrap();
+    let b = storage.store(&second).unwrap();
+    let c = storage.store(&other).unwrap();
+    assert!(b.deduplicated);
+    assert_eq!(a.file_link, b.file_link);
+    assert_ne!(a.file_link, c.file_link);
+    assert_eq!(storage.find(&a.file_hash).unwrap(), Some(a.file_link.clone()));
+    assert!(storage.store(&temp_dir.path().join("mancante.epub")).is_err());
+    assert_eq!(fs::read_dir(storage.storage_path().join("temp")).unwrap().count(), 0);
+}
+
+#[test]
+fn test_link_depends_only_on_content() {
+    let temp_dir = tempdir().expect("Impossibile creare directory temporanea");
+    let storage = StorageService::new(temp_dir.path().join("storage"));
+    let source = temp_dir.path().join("Il Pendolo di Foucault.epub");
+    fs::write(&source, b"prima stesura").unwrap();
+
+    // Stesso contenuto con un altro nome: stesso hash e nessuna seconda copia
+    let mut first = BookDto { name: "Il pendolo di Foucault".to_string(), ..Default::default() };
+    let stored = book_persistence(&storage, &mut first, &source).unwrap();
+    let renamed = temp_dir.path().join("foucault.epub");
+    fs::rename(&source, &renamed).unwrap();
+    let mut second = BookDto { name: "Altro titolo".to_string(), ..Default::default() };
+    let again = book_persistence(&storage, &mut second, &renamed).unwrap();
+    assert!(again.deduplicated);
+    assert_eq!(again.file_hash, stored.file_hash);
+    assert_eq!(again.file_link, stored.file_link);
+    assert_eq!(second.file_size, Some(13));
+
+    // Contenuto diverso: hash e link diversi, con la copia precedente intatta
+    fs::write(&renamed, b"seconda stesura").unwrap();
+    let changed = storage.store(&renamed).unwrap();
+    assert!(!changed.deduplicated);
+    assert_ne!(changed.file_hash, stored.file_hash);
+    assert_ne!(changed.file_link, stored.file_link);
+    assert_eq!(fs::read(storage.resolve(&stored.file_link)).unwrap(), b"prima stesura");
+}
+
+#[test]
+fn test_store_hashed_checks_the_content() {
+    let temp_dir = tempdir().expect("Impossibile creare directory temporanea");
+    let storage = StorageService::new(temp_dir.path().join("storage"));
+    let source = temp_dir.path().join("libro.epub");
+    fs::write(&source, b"contenuto originale").unwrap();
+    let (hash, _) = StorageService::hash_file(&source).unwrap();
+
+    // Il file cambia tra il calcolo dell'hash e la copia: niente in archivio
+    fs::write(&source, b"contenuto modificato").unwrap();
+    assert!(storage.store_hashed(&source, &hash).is_err());
+    assert_eq!(storage.find(&hash).unwrap(), None);
+    assert_eq!(fs::read_dir(storage.storage_path().join("temp")).unwrap().count(), 0);
+
+    let stored = storage.store(&source).unwrap();
+    let again = storage.store_hashed(&source, &stored.file_hash).unwrap();
+    assert!(again.deduplicated);
+    assert_eq!(again, StoredFile { deduplicated: true, ..stored });
+}
@@ -0,0 +1,110 @@
+//! Benchmark dell'importazione massiva su una biblioteca sintetica.
+//!
+//! `cargo bench -p ritmo_db --bench bulk_import` importa `RITMO_BENCH_BOOKS` libri (predefinito 100000),
+//! ognuno con due contenuti, due persone e tre tag, e confronta il risultato con l'importazione
+//! di un libro per transazione (`BulkImporter::import_one`, quella dell'importazione da file) sugli
+//! stessi dati. Termina con errore se lo scarto è inferiore a `TARGET_SPEEDUP` o se 100000 libri
+//! richiedono più di `TARGET_100K`; con meno libri il tempo per 100000 è stimato dalla velocità misurata.
+
+use ritmo_core::dto::{BookDto, PersonDto, TagDto};
+use ritmo_core::service::import_service::Placement;
+use ritmo_core::ContentDto;
+use ritmo_db::bulk_import::{BulkImportOptions, BulkImporter};
+use ritmo_db_core::connection::create_connection_pool;
+use sqlx::SqlitePool;
+use std::time::{Duration, Instant};
+
+/// Quante volte l'importazione massiva deve essere più veloce di quella con un libro per transazione
+const TARGET_SPEEDUP: f64 = 2.0;
+/// Tempo massimo per importare 100000 libri
+const TARGET_100K: Duration = Duration::from_secs(120);
+/// Libri importati uno per transazione per il confronto
+const BASELINE_SAMPLE: usize = 2000;
+
+fn synthetic_book(i: usize) -> BookDto {
+    let content = |n: usize| ContentDto {
+        name: format!("Racconto {} di {}", n, i),
+        type_name: "Racconto".to_string(),
+        people: vec![format!("Autore {}", (i + n) % 5000)],
+        tags: vec![TagDto { name: format!("tema {}", (i + n) % 300), is_book_tag: false, is_content_tag: true }],
+        ..Default::default()
+    };
+    BookDto {
+        name: format!("Libro sintetico {}", i),
+        publisher_name: format!("Editore {}", i % 200),
+        format_name: ["epub", "pdf", "mobi"][i % 3].to_string(),
+        series_name: if i.is_multiple_of(4) { format!("Serie {}", i % 1000) } else { String::new() },
+        series_index: i.is_multiple_of(4).then(|| (i / 1000 + 1) as f64),
+        isbn: Some(format!("978{:010}", i)),
+        people: vec![
+            PersonDto { person_id: None, person_name: format!("Autore {}", i % 5000), person_role: "aut".to_string() },
+            PersonDto { person_id: None, person_name: format!("Traduttore {}", i % 800), person_role: "trl".to_string() },
+        ],
+        tags: (0..3)
+            .map(|t| TagDto { name: format!("genere {}", (i + t * 7) % 120), is_book_tag: true, is_content_tag: false })
+            .collect(),
+        contents: vec![content(1), content(2)],
+        ..Default::default()
+    }
+}
+
+async fn fresh_pool(dir: &tempfile::TempDir, name: &str) -> SqlitePool {
+    let db_path = dir.path().join(name);
+    std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/assets/template.db"), &db_path).expect("template mancante");
+    create_connection_pool(&db_path, false).await.expect("database non apribile")
+}
+
+/// Gli stessi libri importati uno per transazione, con editori, persone, tag e contenuti
+async fn baseline(pool: &SqlitePool, books: usize) -> f64 {
+    let mut importer = BulkImporter::new(pool);
+    let started = Instant::now();
+    for i in 0..books {
+        importer.import_one(&synthetic_book(i), Placement::NewBook, false, Box::new(|| Ok(()))).await.unwrap();
+    }
+    books as f64 / started.elapsed().as_secs_f64()
+}
+
+#[tokio::main]
+async fn main() {
+    let books: usize = std::env::var("RITMO_BENCH_BOOKS").ok().and_then(|v| v.parse().ok()).unwrap_or(100_000);
+    let dir = tempfile::tempdir().unwrap();
+
+    let pool = fresh_pool(&dir, "baseline.db").await;
+    let sample = BASELINE_SAMPLE.min(books);
+    let per_row = baseline(&pool, sample).await;
+    println!("un libro per commit: {:>10.0} libri/s ({} libri)", per_row, sample);
+    pool.close().await;
+
+    for defer_indexes in [false, true] {
+        let pool = fresh_pool(&dir, &format!("bulk_{}.db", defer_indexes)).await;
+        let report = BulkImporter::new(&pool)
+            .with_options(BulkImportOptions { defer_indexes, ..Default::default() })
+            .on_progress(|p| eprint!("\r{} / {} ({:.0} libri/s)", p.processed, p.total.unwrap_or(0), p.rate()))
+            .import((0..books).map(synthetic_book))
+            .await
+            .unwrap();
+        eprintln!();
+        let rate = report.books as f64 / report.elapsed.as_secs_f64();
+        let for_100k = Duration::from_secs_f64(100_000.0 / rate);
+        println!(
+            "bulk (indici {}): {:>10.0} libri/s ({} libri, {} contenuti, {} persone, {:.1?}; 100k libri in {:.0?}{})",
+            if defer_indexes { "rimandati" } else { "attivi   " },
+            rate,
+            report.books,
+            report.contents,
+            report.people_created,
+            report.elapsed,
+            for_100k,
+            if books < 100_000 { ", stima" } else { "" }
+        );
+        if rate < per_row * TARGET_SPEEDUP {
+            eprintln!("obiettivo non raggiunto: almeno {:.0} libri/s", per_row * TARGET_SPEEDUP);
+            std::process::exit(1);
+        }
+        if for_100k > TARGET_100K {
+            eprintln!("obiettivo non raggiunto: 100k libri in al massimo {:?}", TARGET_100K);
+            std::process::exit(1);
+        }
+        pool.close().await;
+    }
+}
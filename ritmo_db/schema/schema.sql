@@ -11,13 +11,22 @@ CREATE TABLE IF NOT EXISTS "audit_log" (
 	"id"	INTEGER,
 	"table_name"	TEXT NOT NULL,
 	"record_id"	INTEGER NOT NULL,
-	"operation"	TEXT NOT NULL CHECK("operation" IN ('INSERT', 'UPDATE', 'DELETE')),
+	"operation"	TEXT NOT NULL CHECK("operation" IN ('INSERT', 'UPDATE', 'DELETE', 'MERGE')),
 	"old_values"	TEXT,
 	"new_values"	TEXT,
 	"timestamp"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
 	"user_id"	TEXT,
 	PRIMARY KEY("id" AUTOINCREMENT)
 );
+CREATE TABLE IF NOT EXISTS "change_log" (
+	"id"	INTEGER,
+	"table_name"	TEXT NOT NULL,
+	"operation"	TEXT NOT NULL CHECK("operation" IN ('created', 'updated', 'deleted', 'linked', 'unlinked')),
+	"entity_id"	INTEGER NOT NULL,
+	"related_id"	INTEGER,
+	"changed_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
 CREATE TABLE IF NOT EXISTS "stats_cache" (
 	"id"	INTEGER,
 	"cache_key"	TEXT NOT NULL UNIQUE,
@@ -51,21 +60,34 @@ CREATE TABLE IF NOT EXISTS "series" (
 	"completed"	INTEGER NOT NULL DEFAULT 0 CHECK("completed" IN (0, 1)),
 	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
 	"updated_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
-	PRIMARY KEY("id" AUTOINCREMENT)
+	"parent_id"	INTEGER CHECK("parent_id" <> "id"),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	FOREIGN KEY("parent_id") REFERENCES "series"("id") ON DELETE SET NULL
 );
 CREATE TABLE IF NOT EXISTS "roles" (
 	"id"	INTEGER,
+	"code"	TEXT UNIQUE,
 	"name"	TEXT NOT NULL UNIQUE,
 	"description"	TEXT,
 	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
 	PRIMARY KEY("id" AUTOINCREMENT)
 );
+CREATE TABLE IF NOT EXISTS "role_labels" (
+	"role_id"	INTEGER NOT NULL,
+	"language"	TEXT NOT NULL,
+	"label"	TEXT NOT NULL,
+	PRIMARY KEY("role_id","language"),
+	FOREIGN KEY("role_id") REFERENCES "roles"("id") ON DELETE CASCADE
+);
 CREATE TABLE IF NOT EXISTS "tags" (
 	"id"	INTEGER,
 	"name"	TEXT NOT NULL UNIQUE,
 	"description"	TEXT,
 	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
-	PRIMARY KEY("id" AUTOINCREMENT)
+	"parent_id"	INTEGER CHECK("parent_id" <> "id"),
+	"category"	TEXT CHECK("category" IN ('genre', 'mood', 'setting', 'personal')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	FOREIGN KEY("parent_id") REFERENCES "tags"("id") ON DELETE SET NULL
 );
 CREATE TABLE IF NOT EXISTS "types" (
 	"id"	INTEGER,
@@ -88,6 +110,7 @@ CREATE TABLE IF NOT EXISTS "people" (
 	"death_date"	INTEGER,
 	"biography"	TEXT,
 	"normalized_key"	TEXT,
+	"sort_name"	TEXT,
 	"confidence"	REAL NOT NULL DEFAULT 1.0 CHECK("confidence" >= 0.0 AND "confidence" <= 1.0),
 	"source"	TEXT NOT NULL DEFAULT 'biblioteca',
 	"verified"	INTEGER NOT NULL DEFAULT 0 CHECK("verified" IN (0, 1)),
@@ -106,6 +129,16 @@ CREATE TABLE IF NOT EXISTS "aliases" (
 	FOREIGN KEY("person_id") REFERENCES "people"("id") ON DELETE CASCADE,
 	UNIQUE("person_id","name")
 );
+CREATE TABLE IF NOT EXISTS "pen_names" (
+	"pen_name_id"	INTEGER NOT NULL,
+	"person_id"	INTEGER NOT NULL,
+	"notes"	TEXT,
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("pen_name_id","person_id"),
+	CHECK("pen_name_id" <> "person_id"),
+	FOREIGN KEY("pen_name_id") REFERENCES "people"("id") ON DELETE CASCADE,
+	FOREIGN KEY("person_id") REFERENCES "people"("id") ON DELETE CASCADE
+);
 CREATE TABLE IF NOT EXISTS "ml_data" (
 	"id"	INTEGER,
 	"data_type"	TEXT NOT NULL UNIQUE,
@@ -132,7 +165,7 @@ CREATE TABLE IF NOT EXISTS "books" (
 	"publisher_id"	INTEGER,
 	"format_id"	INTEGER,
 	"series_id"	INTEGER,
-	"series_index"	INTEGER CHECK("series_index" > 0),
+	"series_index"	REAL CHECK("series_index" >= 0),
 	"publication_date"	INTEGER,
 	"last_modified_date"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
 	"isbn"	TEXT,
@@ -144,11 +177,113 @@ CREATE TABLE IF NOT EXISTS "books" (
 	"file_size"	INTEGER,
 	"file_hash"	TEXT,
 	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	"deleted_at"	INTEGER,
+	"read_status"	TEXT NOT NULL DEFAULT 'unread' CHECK("read_status" IN ('unread', 'reading', 'read', 'abandoned')),
+	"rating"	REAL CHECK("rating" BETWEEN 0 AND 5),
+	"sort_title"	TEXT,
+	"sort_author"	TEXT,
 	PRIMARY KEY("id" AUTOINCREMENT),
 	FOREIGN KEY("format_id") REFERENCES "formats"("id") ON DELETE SET NULL,
 	FOREIGN KEY("publisher_id") REFERENCES "publishers"("id") ON DELETE SET NULL,
 	FOREIGN KEY("series_id") REFERENCES "series"("id") ON DELETE SET NULL
 );
+CREATE TABLE IF NOT EXISTS "book_files" (
+	"id"	INTEGER,
+	"book_id"	INTEGER NOT NULL,
+	"format_id"	INTEGER,
+	"file_link"	TEXT NOT NULL UNIQUE,
+	"file_size"	INTEGER,
+	"file_hash"	TEXT,
+	"is_preferred"	INTEGER NOT NULL DEFAULT 0 CHECK("is_preferred" IN (0, 1)),
+	"added_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	FOREIGN KEY("book_id") REFERENCES "books"("id") ON DELETE CASCADE,
+	FOREIGN KEY("format_id") REFERENCES "formats"("id") ON DELETE SET NULL
+);
+CREATE TABLE IF NOT EXISTS "book_identifiers" (
+	"id"	INTEGER,
+	"book_id"	INTEGER NOT NULL,
+	"scheme"	TEXT NOT NULL,
+	"value"	TEXT NOT NULL,
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	UNIQUE("book_id","scheme","value"),
+	FOREIGN KEY("book_id") REFERENCES "books"("id") ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS "reading_sessions" (
+	"id"	INTEGER,
+	"book_id"	INTEGER NOT NULL,
+	"status"	TEXT NOT NULL DEFAULT 'reading' CHECK("status" IN ('reading', 'read', 'abandoned')),
+	"progress"	REAL NOT NULL DEFAULT 0 CHECK("progress" BETWEEN 0 AND 100),
+	"started_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	"finished_at"	INTEGER,
+	"notes"	TEXT,
+	PRIMARY KEY("id" AUTOINCREMENT),
+	FOREIGN KEY("book_id") REFERENCES "books"("id") ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS "locations" (
+	"id"	INTEGER,
+	"name"	TEXT NOT NULL,
+	"parent_id"	INTEGER,
+	"description"	TEXT,
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	UNIQUE("parent_id","name"),
+	FOREIGN KEY("parent_id") REFERENCES "locations"("id") ON DELETE SET NULL
+);
+CREATE TABLE IF NOT EXISTS "book_copies" (
+	"id"	INTEGER,
+	"book_id"	INTEGER NOT NULL,
+	"location_id"	INTEGER,
+	"condition"	TEXT CHECK("condition" IN ('new', 'fine', 'very_good', 'good', 'fair', 'poor')),
+	"binding"	TEXT,
+	"edition"	TEXT,
+	"printing"	TEXT,
+	"notes"	TEXT,
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	FOREIGN KEY("book_id") REFERENCES "books"("id") ON DELETE CASCADE,
+	FOREIGN KEY("location_id") REFERENCES "locations"("id") ON DELETE SET NULL
+);
+CREATE TABLE IF NOT EXISTS "loans" (
+	"id"	INTEGER,
+	"copy_id"	INTEGER NOT NULL,
+	"borrower"	TEXT NOT NULL,
+	"lent_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	"due_at"	INTEGER,
+	"returned_at"	INTEGER,
+	"notes"	TEXT,
+	PRIMARY KEY("id" AUTOINCREMENT),
+	FOREIGN KEY("copy_id") REFERENCES "book_copies"("id") ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS "copy_events" (
+	"id"	INTEGER,
+	"copy_id"	INTEGER NOT NULL,
+	"event"	TEXT NOT NULL CHECK("event" IN ('added', 'moved', 'lent', 'returned', 'condition')),
+	"location_id"	INTEGER,
+	"details"	TEXT,
+	"happened_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	FOREIGN KEY("copy_id") REFERENCES "book_copies"("id") ON DELETE CASCADE,
+	FOREIGN KEY("location_id") REFERENCES "locations"("id") ON DELETE SET NULL
+);
+CREATE TABLE IF NOT EXISTS "acquisitions" (
+	"id"	INTEGER,
+	"book_id"	INTEGER NOT NULL,
+	"copy_id"	INTEGER,
+	"file_id"	INTEGER,
+	"acquired_at"	INTEGER,
+	"source"	TEXT NOT NULL DEFAULT 'other' CHECK("source" IN ('shop', 'gift', 'download', 'library_sale', 'other')),
+	"vendor"	TEXT,
+	"price"	REAL CHECK("price" >= 0),
+	"currency"	TEXT,
+	"provenance"	TEXT,
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	FOREIGN KEY("book_id") REFERENCES "books"("id") ON DELETE CASCADE,
+	FOREIGN KEY("copy_id") REFERENCES "book_copies"("id") ON DELETE SET NULL,
+	FOREIGN KEY("file_id") REFERENCES "book_files"("id") ON DELETE SET NULL
+);
 CREATE TABLE IF NOT EXISTS "contents" (
 	"id"	INTEGER,
 	"name"	TEXT NOT NULL,
@@ -159,8 +294,37 @@ CREATE TABLE IF NOT EXISTS "contents" (
 	"notes"	TEXT,
 	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
 	"updated_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	"deleted_at"	INTEGER,
+	"rating"	REAL CHECK("rating" BETWEEN 0 AND 5),
+	"work_id"	INTEGER,
+	"series_id"	INTEGER,
+	"series_index"	REAL CHECK("series_index" >= 0),
+	"sort_title"	TEXT,
+	"sort_author"	TEXT,
 	PRIMARY KEY("id" AUTOINCREMENT),
-	FOREIGN KEY("type_id") REFERENCES "types"("id") ON DELETE SET NULL
+	FOREIGN KEY("type_id") REFERENCES "types"("id") ON DELETE SET NULL,
+	FOREIGN KEY("work_id") REFERENCES "works"("id") ON DELETE SET NULL,
+	FOREIGN KEY("series_id") REFERENCES "series"("id") ON DELETE SET NULL
+);
+CREATE TABLE IF NOT EXISTS "works" (
+	"id"	INTEGER,
+	"title"	TEXT NOT NULL,
+	"original_language"	TEXT,
+	"first_published"	INTEGER,
+	"notes"	TEXT,
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	"updated_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+CREATE TABLE IF NOT EXISTS "content_relations" (
+	"content_id"	INTEGER NOT NULL,
+	"related_content_id"	INTEGER NOT NULL,
+	"relation"	TEXT NOT NULL CHECK("relation" IN ('translation_of', 'revision_of', 'excerpt_of')),
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("content_id","related_content_id","relation"),
+	CHECK("content_id" <> "related_content_id"),
+	FOREIGN KEY("content_id") REFERENCES "contents"("id") ON DELETE CASCADE,
+	FOREIGN KEY("related_content_id") REFERENCES "contents"("id") ON DELETE CASCADE
 );
 CREATE TABLE IF NOT EXISTS "x_books_contents" (
 	"book_id"	INTEGER NOT NULL,
@@ -208,6 +372,56 @@ CREATE TABLE IF NOT EXISTS "x_contents_languages" (
 	FOREIGN KEY("content_id") REFERENCES "contents"("id") ON DELETE CASCADE,
 	FOREIGN KEY("language_id") REFERENCES "running_languages"("id") ON DELETE CASCADE
 );
+CREATE TABLE IF NOT EXISTS "collections" (
+	"id"	INTEGER,
+	"name"	TEXT NOT NULL,
+	"kind"	TEXT NOT NULL DEFAULT 'manual' CHECK("kind" IN ('manual', 'smart')),
+	"parent_id"	INTEGER,
+	"position"	INTEGER NOT NULL DEFAULT 0,
+	"description"	TEXT,
+	"rules"	TEXT,
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	"updated_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	CHECK("kind" = 'manual' OR "rules" IS NOT NULL),
+	FOREIGN KEY("parent_id") REFERENCES "collections"("id") ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS "collection_items" (
+	"id"	INTEGER,
+	"collection_id"	INTEGER NOT NULL,
+	"book_id"	INTEGER,
+	"content_id"	INTEGER,
+	"position"	INTEGER NOT NULL,
+	"added_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	CHECK(("book_id" IS NULL) <> ("content_id" IS NULL)),
+	FOREIGN KEY("collection_id") REFERENCES "collections"("id") ON DELETE CASCADE,
+	FOREIGN KEY("book_id") REFERENCES "books"("id") ON DELETE CASCADE,
+	FOREIGN KEY("content_id") REFERENCES "contents"("id") ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS "reading_orders" (
+	"id"	INTEGER,
+	"series_id"	INTEGER NOT NULL,
+	"name"	TEXT NOT NULL,
+	"description"	TEXT,
+	"created_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	"updated_at"	INTEGER NOT NULL DEFAULT (strftime('%s', 'now')),
+	PRIMARY KEY("id" AUTOINCREMENT),
+	UNIQUE("series_id","name"),
+	FOREIGN KEY("series_id") REFERENCES "series"("id") ON DELETE CASCADE
+);
+CREATE TABLE IF NOT EXISTS "reading_order_items" (
+	"id"	INTEGER,
+	"order_id"	INTEGER NOT NULL,
+	"book_id"	INTEGER,
+	"content_id"	INTEGER,
+	"position"	INTEGER NOT NULL,
+	PRIMARY KEY("id" AUTOINCREMENT),
+	CHECK(("book_id" IS NULL) <> ("content_id" IS NULL)),
+	FOREIGN KEY("order_id") REFERENCES "reading_orders"("id") ON DELETE CASCADE,
+	FOREIGN KEY("book_id") REFERENCES "books"("id") ON DELETE CASCADE,
+	FOREIGN KEY("content_id") REFERENCES "contents"("id") ON DELETE CASCADE
+);
 CREATE TABLE IF NOT EXISTS "metadata" (
 	"version"		TEXT NOT NULL,
 	"updated_at"  	INTEGER NOT NULL,
@@ -226,6 +440,9 @@ CREATE INDEX IF NOT EXISTS "idx_publishers_name_search" ON "publishers" (
 CREATE INDEX IF NOT EXISTS "idx_tags_name_search" ON "tags" (
 	"name" COLLATE NOCASE
 );
+CREATE INDEX IF NOT EXISTS "idx_tags_parent" ON "tags" (
+	"parent_id"
+) WHERE "parent_id" IS NOT NULL;
 CREATE INDEX IF NOT EXISTS "idx_people_dates" ON "people" (
 	"birth_date",
 	"death_date"
@@ -240,11 +457,20 @@ CREATE INDEX IF NOT EXISTS "idx_aliases_person_lookup" ON "aliases" (
 	"person_id",
 	"name"
 );
+CREATE INDEX IF NOT EXISTS "idx_pen_names_person" ON "pen_names" (
+	"person_id"
+);
+CREATE INDEX IF NOT EXISTS "idx_role_labels_label" ON "role_labels" (
+	"label" COLLATE NOCASE
+);
 CREATE INDEX IF NOT EXISTS "idx_audit_log_lookup" ON "audit_log" (
 	"table_name",
 	"record_id",
 	"timestamp"
 );
+CREATE INDEX IF NOT EXISTS "idx_change_log_changed_at" ON "change_log" (
+	"changed_at"
+);
 CREATE INDEX IF NOT EXISTS "idx_audit_log_timestamp" ON "audit_log" (
 	"timestamp"
 );
@@ -267,6 +493,19 @@ CREATE INDEX IF NOT EXISTS "idx_books_name_search" ON "books" (
 CREATE INDEX IF NOT EXISTS "idx_contents_name_search" ON "contents" (
 	"name" COLLATE NOCASE
 );
+CREATE INDEX IF NOT EXISTS "idx_books_sort_title" ON "books" (
+	"sort_title"
+);
+CREATE INDEX IF NOT EXISTS "idx_contents_sort_title" ON "contents" (
+	"sort_title"
+);
+CREATE INDEX IF NOT EXISTS "idx_books_sort_author" ON "books" (
+	"sort_author",
+	"sort_title"
+);
+CREATE INDEX IF NOT EXISTS "idx_people_sort_name" ON "people" (
+	"sort_name"
+);
 CREATE INDEX IF NOT EXISTS "idx_books_search_optimized" ON "books" (
 	"name",
 	"publication_date",
@@ -300,6 +539,100 @@ CREATE INDEX IF NOT EXISTS "idx_contents_dates" ON "contents" (
 	"publication_date",
 	"created_at"
 );
+CREATE INDEX IF NOT EXISTS "idx_book_files_book" ON "book_files" (
+	"book_id",
+	"format_id"
+);
+CREATE INDEX IF NOT EXISTS "idx_book_files_hash" ON "book_files" (
+	"file_hash"
+) WHERE "file_hash" IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS "idx_book_files_preferred" ON "book_files" (
+	"book_id"
+) WHERE "is_preferred" = 1;
+CREATE INDEX IF NOT EXISTS "idx_book_identifiers_lookup" ON "book_identifiers" (
+	"scheme",
+	"value"
+);
+CREATE INDEX IF NOT EXISTS "idx_reading_sessions_book" ON "reading_sessions" (
+	"book_id",
+	"started_at"
+);
+CREATE INDEX IF NOT EXISTS "idx_books_read_status" ON "books" (
+	"read_status"
+);
+CREATE INDEX IF NOT EXISTS "idx_book_copies_book" ON "book_copies" (
+	"book_id"
+);
+CREATE INDEX IF NOT EXISTS "idx_book_copies_location" ON "book_copies" (
+	"location_id"
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "idx_loans_open" ON "loans" (
+	"copy_id"
+) WHERE "returned_at" IS NULL;
+CREATE INDEX IF NOT EXISTS "idx_loans_due" ON "loans" (
+	"due_at"
+) WHERE "returned_at" IS NULL;
+CREATE INDEX IF NOT EXISTS "idx_copy_events_copy" ON "copy_events" (
+	"copy_id",
+	"happened_at"
+);
+CREATE INDEX IF NOT EXISTS "idx_acquisitions_book" ON "acquisitions" (
+	"book_id"
+);
+CREATE INDEX IF NOT EXISTS "idx_acquisitions_date" ON "acquisitions" (
+	"acquired_at",
+	"source"
+);
+CREATE INDEX IF NOT EXISTS "idx_collections_parent" ON "collections" (
+	"parent_id",
+	"position"
+);
+CREATE INDEX IF NOT EXISTS "idx_collection_items_order" ON "collection_items" (
+	"collection_id",
+	"position"
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "idx_collection_items_book" ON "collection_items" (
+	"collection_id",
+	"book_id"
+) WHERE "book_id" IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS "idx_collection_items_content" ON "collection_items" (
+	"collection_id",
+	"content_id"
+) WHERE "content_id" IS NOT NULL;
+CREATE INDEX IF NOT EXISTS "idx_reading_order_items_order" ON "reading_order_items" (
+	"order_id",
+	"position"
+);
+CREATE UNIQUE INDEX IF NOT EXISTS "idx_reading_order_items_book" ON "reading_order_items" (
+	"order_id",
+	"book_id"
+) WHERE "book_id" IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS "idx_reading_order_items_content" ON "reading_order_items" (
+	"order_id",
+	"content_id"
+) WHERE "content_id" IS NOT NULL;
+CREATE INDEX IF NOT EXISTS "idx_contents_series_lookup" ON "contents" (
+	"series_id",
+	"series_index"
+) WHERE "series_id" IS NOT NULL;
+CREATE INDEX IF NOT EXISTS "idx_series_parent" ON "series" (
+	"parent_id"
+) WHERE "parent_id" IS NOT NULL;
+CREATE INDEX IF NOT EXISTS "idx_contents_work" ON "contents" (
+	"work_id"
+) WHERE "work_id" IS NOT NULL;
+CREATE INDEX IF NOT EXISTS "idx_content_relations_related" ON "content_relations" (
+	"related_content_id"
+);
+CREATE INDEX IF NOT EXISTS "idx_works_title" ON "works" (
+	"title" COLLATE NOCASE
+);
+CREATE INDEX IF NOT EXISTS "idx_books_trash" ON "books" (
+	"deleted_at"
+) WHERE "deleted_at" IS NOT NULL;
+CREATE INDEX IF NOT EXISTS "idx_contents_trash" ON "contents" (
+	"deleted_at"
+) WHERE "deleted_at" IS NOT NULL;
 CREATE INDEX IF NOT EXISTS "idx_books_people_roles_person_role" ON "x_books_people_roles" (
 	"person_id",
 	"role_id"
@@ -336,19 +669,18 @@ CREATE INDEX IF NOT EXISTS "idx_contents_languages_by_language" ON "x_contents_l
 	"language_id",
 	"content_id"
 );
-CREATE TRIGGER normalize_person_name
-    BEFORE INSERT ON people
-    FOR EACH ROW
-    WHEN NEW.normalized_key IS NULL
-BEGIN
-    UPDATE people SET normalized_key = LOWER(TRIM(NEW.name)) WHERE id = NEW.id;
-END;
 CREATE TRIGGER update_people_timestamp
     AFTER UPDATE ON people
     FOR EACH ROW
 BEGIN
     UPDATE people SET updated_at = strftime('%s', 'now') WHERE id = NEW.id;
 END;
+CREATE TRIGGER update_reading_orders_timestamp
+    AFTER UPDATE ON reading_orders
+    FOR EACH ROW
+BEGIN
+    UPDATE reading_orders SET updated_at = strftime('%s', 'now') WHERE id = NEW.id;
+END;
 CREATE TRIGGER update_series_timestamp
     AFTER UPDATE ON series
     FOR EACH ROW
@@ -361,13 +693,6 @@ CREATE TRIGGER update_publishers_timestamp
 BEGIN
     UPDATE publishers SET updated_at = strftime('%s', 'now') WHERE id = NEW.id;
 END;
-CREATE TRIGGER normalize_alias_name
-    BEFORE INSERT ON aliases
-    FOR EACH ROW
-    WHEN NEW.alias_normalized IS NULL
-BEGIN
-    UPDATE aliases SET alias_normalized = LOWER(TRIM(NEW.name)) WHERE id = NEW.id;
-END;
 CREATE TRIGGER update_config_timestamp
     AFTER UPDATE ON system_config
     FOR EACH ROW
@@ -445,8 +770,10 @@ SELECT
     ROUND(AVG(rating), 2) as avg_rating,
     COUNT(CASE WHEN read_status = 'read' THEN 1 END) as read_count,
     COUNT(CASE WHEN read_status = 'reading' THEN 1 END) as reading_count,
-    COUNT(CASE WHEN read_status = 'unread' THEN 1 END) as unread_count
+    COUNT(CASE WHEN read_status = 'unread' THEN 1 END) as unread_count,
+    COUNT(CASE WHEN read_status = 'abandoned' THEN 1 END) as abandoned_count
 FROM books
+WHERE deleted_at IS NULL
 UNION ALL
 SELECT
     'contents' as entity_type,
@@ -457,8 +784,10 @@ SELECT
     ROUND(AVG(rating), 2) as avg_rating,
     0 as read_count,
     0 as reading_count,
-    0 as unread_count
+    0 as unread_count,
+    0 as abandoned_count
 FROM contents
+WHERE deleted_at IS NULL
 UNION ALL
 SELECT
     'people' as entity_type,
@@ -469,7 +798,8 @@ SELECT
     0 as avg_rating,
     0 as read_count,
     0 as reading_count,
-    0 as unread_count
+    0 as unread_count,
+    0 as abandoned_count
 FROM people
 UNION ALL
 SELECT
@@ -481,7 +811,8 @@ SELECT
     0 as avg_rating,
     0 as read_count,
     0 as reading_count,
-    0 as unread_count
+    0 as unread_count,
+    0 as abandoned_count
 FROM series;
 CREATE VIEW PossibleDuplicates AS
 SELECT
@@ -508,11 +839,12 @@ SELECT
     b.created_at
 FROM books b
 LEFT JOIN series s ON b.series_id = s.id
-WHERE b.id NOT IN (
+WHERE b.deleted_at IS NULL
+  AND b.id NOT IN (
     SELECT DISTINCT book_id
     FROM x_books_people_roles bpr
     JOIN roles r ON bpr.role_id = r.id
-    WHERE r.name IN ('Autore', 'Author', 'Scrittore')
+    WHERE r.code = 'aut'
 );
 CREATE VIEW ContentsWithoutAuthor AS
 SELECT
@@ -523,11 +855,12 @@ SELECT
     c.created_at
 FROM contents c
 LEFT JOIN types t ON c.type_id = t.id
-WHERE c.id NOT IN (
+WHERE c.deleted_at IS NULL
+  AND c.id NOT IN (
     SELECT DISTINCT content_id
     FROM x_contents_people_roles cpr
     JOIN roles r ON cpr.role_id = r.id
-    WHERE r.name IN ('Autore', 'Author', 'Scrittore')
+    WHERE r.code = 'aut'
 );
 CREATE VIEW BooksSearchOptimized AS
 SELECT
@@ -554,12 +887,13 @@ LEFT JOIN publishers pub ON b.publisher_id = pub.id
 LEFT JOIN x_books_people_roles bpr ON b.id = bpr.book_id
 LEFT JOIN people p ON bpr.person_id = p.id
 LEFT JOIN roles r ON bpr.role_id = r.id
-WHERE r.name IN ('Autore', 'Author', 'Scrittore')
+WHERE b.deleted_at IS NULL
+  AND (r.code = 'aut'
    OR bpr.role_id = (
        SELECT MIN(role_id)
        FROM x_books_people_roles
        WHERE book_id = b.id
-   );
+   ));
 CREATE VIEW ContentsSearchOptimized AS
 SELECT
     c.id,
@@ -577,12 +911,13 @@ LEFT JOIN types t ON c.type_id = t.id
 LEFT JOIN x_contents_people_roles cpr ON c.id = cpr.content_id
 LEFT JOIN people p ON cpr.person_id = p.id
 LEFT JOIN roles r ON cpr.role_id = r.id
-WHERE r.name IN ('Autore', 'Author', 'Scrittore')
+WHERE c.deleted_at IS NULL
+  AND (r.code = 'aut'
    OR cpr.role_id = (
        SELECT MIN(role_id)
        FROM x_contents_people_roles
        WHERE content_id = c.id
-   );
+   ));
 CREATE VIEW ContentsFullDetails AS
 SELECT
     c.id AS content_id,
@@ -606,6 +941,7 @@ LEFT JOIN x_contents_tags ct ON c.id = ct.content_id
 LEFT JOIN tags tag ON ct.tag_id = tag.id
 LEFT JOIN x_contents_languages cl ON c.id = cl.content_id
 LEFT JOIN running_languages rl ON cl.language_id = rl.id
+WHERE c.deleted_at IS NULL
 GROUP BY c.id;
 CREATE VIEW BooksFullDetails AS
 SELECT
@@ -626,6 +962,7 @@ SELECT
     b.series_index,
     pub.name AS publisher_name,
     f.name AS format_name,
+    GROUP_CONCAT(DISTINCT ff.name) AS file_formats,
     GROUP_CONCAT(DISTINCT p.id) AS person_ids,
     GROUP_CONCAT(DISTINCT p.name) AS person_names,
     GROUP_CONCAT(DISTINCT r.name) AS role_names,
@@ -636,13 +973,16 @@ FROM books b
 LEFT JOIN publishers pub ON b.publisher_id = pub.id
 LEFT JOIN formats f ON b.format_id = f.id
 LEFT JOIN series s ON b.series_id = s.id
+LEFT JOIN book_files bf ON b.id = bf.book_id
+LEFT JOIN formats ff ON bf.format_id = ff.id
 LEFT JOIN x_books_people_roles bpr ON b.id = bpr.book_id
 LEFT JOIN people p ON bpr.person_id = p.id
 LEFT JOIN roles r ON bpr.role_id = r.id
 LEFT JOIN x_books_tags bt ON b.id = bt.book_id
 LEFT JOIN tags tag ON bt.tag_id = tag.id
 LEFT JOIN x_books_contents bc ON b.id = bc.book_id
-LEFT JOIN contents c ON bc.content_id = c.id
+LEFT JOIN contents c ON bc.content_id = c.id AND c.deleted_at IS NULL
+WHERE b.deleted_at IS NULL
 GROUP BY b.id;
 CREATE VIEW StatsOverview AS
 SELECT
@@ -652,6 +992,7 @@ SELECT
     COUNT(CASE WHEN has_paper = 1 THEN 1 END) as with_paper,
     0 as dummy_field
 FROM books
+WHERE deleted_at IS NULL
 UNION ALL
 SELECT
     'contents' as entity_type,
@@ -660,6 +1001,7 @@ SELECT
     0 as with_paper,
     0 as dummy_field
 FROM contents
+WHERE deleted_at IS NULL
 UNION ALL
 SELECT
     'people' as entity_type,
@@ -676,4 +1018,733 @@ SELECT
     0 as with_paper,
     0 as dummy_field
 FROM series;
+CREATE VIEW Trash AS
+SELECT
+    'books' as entity_type,
+    b.id,
+    b.name,
+    b.deleted_at,
+    b.file_link
+FROM books b
+WHERE b.deleted_at IS NOT NULL
+UNION ALL
+SELECT
+    'contents' as entity_type,
+    c.id,
+    c.name,
+    c.deleted_at,
+    NULL as file_link
+FROM contents c
+WHERE c.deleted_at IS NOT NULL;
+CREATE TRIGGER change_log_books_insert
+    AFTER INSERT ON books
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('books', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_books_update
+    AFTER UPDATE OF name, original_title, publisher_id, format_id, series_id, series_index, publication_date, isbn, pages, notes, has_cover, has_paper, file_link, file_size, file_hash, created_at, deleted_at, read_status, rating, sort_title, sort_author ON books
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('books', CASE WHEN NEW.deleted_at IS NOT NULL AND OLD.deleted_at IS NULL THEN 'deleted' ELSE 'updated' END, NEW.id);
+END;
+CREATE TRIGGER change_log_books_delete
+    AFTER DELETE ON books
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('books', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_contents_insert
+    AFTER INSERT ON contents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('contents', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_contents_update
+    AFTER UPDATE ON contents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('contents', CASE WHEN NEW.deleted_at IS NOT NULL AND OLD.deleted_at IS NULL THEN 'deleted' ELSE 'updated' END, NEW.id);
+END;
+CREATE TRIGGER change_log_contents_delete
+    AFTER DELETE ON contents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('contents', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_people_insert
+    AFTER INSERT ON people
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('people', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_people_update
+    AFTER UPDATE OF name, display_name, given_name, surname, middle_names, title, suffix, nationality, birth_date, death_date, biography, normalized_key, sort_name, confidence, source, verified, created_at ON people
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('people', 'updated', NEW.id);
+END;
+CREATE TRIGGER change_log_people_delete
+    AFTER DELETE ON people
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('people', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_publishers_insert
+    AFTER INSERT ON publishers
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('publishers', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_publishers_update
+    AFTER UPDATE OF name, country, website, notes, created_at ON publishers
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('publishers', 'updated', NEW.id);
+END;
+CREATE TRIGGER change_log_publishers_delete
+    AFTER DELETE ON publishers
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('publishers', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_series_insert
+    AFTER INSERT ON series
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('series', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_series_update
+    AFTER UPDATE OF name, description, total_books, completed, created_at, parent_id ON series
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('series', 'updated', NEW.id);
+END;
+CREATE TRIGGER change_log_series_delete
+    AFTER DELETE ON series
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('series', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_tags_insert
+    AFTER INSERT ON tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('tags', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_tags_update
+    AFTER UPDATE ON tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('tags', 'updated', NEW.id);
+END;
+CREATE TRIGGER change_log_tags_delete
+    AFTER DELETE ON tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('tags', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_formats_insert
+    AFTER INSERT ON formats
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('formats', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_formats_update
+    AFTER UPDATE ON formats
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('formats', 'updated', NEW.id);
+END;
+CREATE TRIGGER change_log_formats_delete
+    AFTER DELETE ON formats
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('formats', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_types_insert
+    AFTER INSERT ON types
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('types', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_types_update
+    AFTER UPDATE ON types
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('types', 'updated', NEW.id);
+END;
+CREATE TRIGGER change_log_types_delete
+    AFTER DELETE ON types
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('types', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_works_insert
+    AFTER INSERT ON works
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('works', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_works_update
+    AFTER UPDATE ON works
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('works', 'updated', NEW.id);
+END;
+CREATE TRIGGER change_log_works_delete
+    AFTER DELETE ON works
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('works', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_collections_insert
+    AFTER INSERT ON collections
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('collections', 'created', NEW.id);
+END;
+CREATE TRIGGER change_log_collections_update
+    AFTER UPDATE ON collections
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('collections', 'updated', NEW.id);
+END;
+CREATE TRIGGER change_log_collections_delete
+    AFTER DELETE ON collections
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id) VALUES ('collections', 'deleted', OLD.id);
+END;
+CREATE TRIGGER change_log_x_books_contents_insert
+    AFTER INSERT ON x_books_contents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_contents', 'linked', NEW.book_id, NEW.content_id);
+END;
+CREATE TRIGGER change_log_x_books_contents_update
+    AFTER UPDATE ON x_books_contents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_contents', 'unlinked', OLD.book_id, OLD.content_id);
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_contents', 'linked', NEW.book_id, NEW.content_id);
+END;
+CREATE TRIGGER change_log_x_books_contents_delete
+    AFTER DELETE ON x_books_contents
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_contents', 'unlinked', OLD.book_id, OLD.content_id);
+END;
+CREATE TRIGGER change_log_x_books_people_roles_insert
+    AFTER INSERT ON x_books_people_roles
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_people_roles', 'linked', NEW.book_id, NEW.person_id);
+END;
+CREATE TRIGGER change_log_x_books_people_roles_update
+    AFTER UPDATE ON x_books_people_roles
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_people_roles', 'unlinked', OLD.book_id, OLD.person_id);
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_people_roles', 'linked', NEW.book_id, NEW.person_id);
+END;
+CREATE TRIGGER change_log_x_books_people_roles_delete
+    AFTER DELETE ON x_books_people_roles
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_people_roles', 'unlinked', OLD.book_id, OLD.person_id);
+END;
+CREATE TRIGGER change_log_x_books_tags_insert
+    AFTER INSERT ON x_books_tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_tags', 'linked', NEW.book_id, NEW.tag_id);
+END;
+CREATE TRIGGER change_log_x_books_tags_update
+    AFTER UPDATE ON x_books_tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_tags', 'unlinked', OLD.book_id, OLD.tag_id);
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_tags', 'linked', NEW.book_id, NEW.tag_id);
+END;
+CREATE TRIGGER change_log_x_books_tags_delete
+    AFTER DELETE ON x_books_tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_books_tags', 'unlinked', OLD.book_id, OLD.tag_id);
+END;
+CREATE TRIGGER change_log_x_contents_languages_insert
+    AFTER INSERT ON x_contents_languages
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_languages', 'linked', NEW.content_id, NEW.language_id);
+END;
+CREATE TRIGGER change_log_x_contents_languages_update
+    AFTER UPDATE ON x_contents_languages
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_languages', 'unlinked', OLD.content_id, OLD.language_id);
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_languages', 'linked', NEW.content_id, NEW.language_id);
+END;
+CREATE TRIGGER change_log_x_contents_languages_delete
+    AFTER DELETE ON x_contents_languages
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_languages', 'unlinked', OLD.content_id, OLD.language_id);
+END;
+CREATE TRIGGER change_log_x_contents_people_roles_insert
+    AFTER INSERT ON x_contents_people_roles
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_people_roles', 'linked', NEW.content_id, NEW.person_id);
+END;
+CREATE TRIGGER change_log_x_contents_people_roles_update
+    AFTER UPDATE ON x_contents_people_roles
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_people_roles', 'unlinked', OLD.content_id, OLD.person_id);
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_people_roles', 'linked', NEW.content_id, NEW.person_id);
+END;
+CREATE TRIGGER change_log_x_contents_people_roles_delete
+    AFTER DELETE ON x_contents_people_roles
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_people_roles', 'unlinked', OLD.content_id, OLD.person_id);
+END;
+CREATE TRIGGER change_log_x_contents_tags_insert
+    AFTER INSERT ON x_contents_tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_tags', 'linked', NEW.content_id, NEW.tag_id);
+END;
+CREATE TRIGGER change_log_x_contents_tags_update
+    AFTER UPDATE ON x_contents_tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_tags', 'unlinked', OLD.content_id, OLD.tag_id);
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_tags', 'linked', NEW.content_id, NEW.tag_id);
+END;
+CREATE TRIGGER change_log_x_contents_tags_delete
+    AFTER DELETE ON x_contents_tags
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('x_contents_tags', 'unlinked', OLD.content_id, OLD.tag_id);
+END;
+CREATE TRIGGER change_log_book_files_insert
+    AFTER INSERT ON book_files
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_files', 'created', NEW.id, NEW.book_id);
+END;
+CREATE TRIGGER change_log_book_files_update
+    AFTER UPDATE ON book_files
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_files', 'updated', NEW.id, NEW.book_id);
+END;
+CREATE TRIGGER change_log_book_files_delete
+    AFTER DELETE ON book_files
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_files', 'deleted', OLD.id, OLD.book_id);
+END;
+CREATE TRIGGER change_log_book_identifiers_insert
+    AFTER INSERT ON book_identifiers
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_identifiers', 'created', NEW.id, NEW.book_id);
+END;
+CREATE TRIGGER change_log_book_identifiers_update
+    AFTER UPDATE ON book_identifiers
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_identifiers', 'updated', NEW.id, NEW.book_id);
+END;
+CREATE TRIGGER change_log_book_identifiers_delete
+    AFTER DELETE ON book_identifiers
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_identifiers', 'deleted', OLD.id, OLD.book_id);
+END;
+CREATE TRIGGER change_log_pen_names_insert
+    AFTER INSERT ON pen_names
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('pen_names', 'linked', NEW.person_id, NEW.pen_name_id);
+END;
+CREATE TRIGGER change_log_pen_names_update
+    AFTER UPDATE ON pen_names
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('pen_names', 'unlinked', OLD.person_id, OLD.pen_name_id);
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('pen_names', 'linked', NEW.person_id, NEW.pen_name_id);
+END;
+CREATE TRIGGER change_log_pen_names_delete
+    AFTER DELETE ON pen_names
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('pen_names', 'unlinked', OLD.person_id, OLD.pen_name_id);
+END;
+CREATE TRIGGER change_log_reading_orders_insert
+    AFTER INSERT ON reading_orders
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('reading_orders', 'created', NEW.id, NEW.series_id);
+END;
+CREATE TRIGGER change_log_reading_orders_update
+    AFTER UPDATE OF series_id, name, description, created_at ON reading_orders
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('reading_orders', 'updated', NEW.id, NEW.series_id);
+END;
+CREATE TRIGGER change_log_reading_orders_delete
+    AFTER DELETE ON reading_orders
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('reading_orders', 'deleted', OLD.id, OLD.series_id);
+END;
+CREATE TRIGGER change_log_reading_order_items_insert
+    AFTER INSERT ON reading_order_items
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('reading_order_items', 'created', NEW.id, NEW.order_id);
+END;
+CREATE TRIGGER change_log_reading_order_items_update
+    AFTER UPDATE ON reading_order_items
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('reading_order_items', 'updated', NEW.id, NEW.order_id);
+END;
+CREATE TRIGGER change_log_reading_order_items_delete
+    AFTER DELETE ON reading_order_items
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('reading_order_items', 'deleted', OLD.id, OLD.order_id);
+END;
+CREATE TRIGGER change_log_book_copies_insert
+    AFTER INSERT ON book_copies
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_copies', 'created', NEW.id, NEW.book_id);
+END;
+CREATE TRIGGER change_log_book_copies_update
+    AFTER UPDATE ON book_copies
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_copies', 'updated', NEW.id, NEW.book_id);
+END;
+CREATE TRIGGER change_log_book_copies_delete
+    AFTER DELETE ON book_copies
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('book_copies', 'deleted', OLD.id, OLD.book_id);
+END;
+CREATE TRIGGER change_log_loans_insert
+    AFTER INSERT ON loans
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('loans', 'created', NEW.id, NEW.copy_id);
+END;
+CREATE TRIGGER change_log_loans_update
+    AFTER UPDATE ON loans
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('loans', 'updated', NEW.id, NEW.copy_id);
+END;
+CREATE TRIGGER change_log_loans_delete
+    AFTER DELETE ON loans
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('loans', 'deleted', OLD.id, OLD.copy_id);
+END;
+CREATE TRIGGER change_log_acquisitions_insert
+    AFTER INSERT ON acquisitions
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('acquisitions', 'created', NEW.id, NEW.book_id);
+END;
+CREATE TRIGGER change_log_acquisitions_update
+    AFTER UPDATE ON acquisitions
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('acquisitions', 'updated', NEW.id, NEW.book_id);
+END;
+CREATE TRIGGER change_log_acquisitions_delete
+    AFTER DELETE ON acquisitions
+    FOR EACH ROW
+BEGIN
+    INSERT INTO change_log (table_name, operation, entity_id, related_id) VALUES ('acquisitions', 'deleted', OLD.id, OLD.book_id);
+END;
+CREATE VIEW SeriesCompletion AS
+SELECT
+    s.id AS series_id,
+    s.total_books,
+    COUNT(DISTINCT m.series_index) AS owned_numbered
+FROM series s
+LEFT JOIN (
+    SELECT series_id, series_index FROM books WHERE deleted_at IS NULL
+    UNION ALL
+    SELECT series_id, series_index FROM contents WHERE deleted_at IS NULL
+) m ON m.series_id = s.id
+    AND m.series_index = CAST(m.series_index AS INTEGER)
+    AND m.series_index BETWEEN 1 AND s.total_books
+GROUP BY s.id, s.total_books;
+CREATE TRIGGER series_completed_books_insert
+    AFTER INSERT ON books
+    FOR EACH ROW
+    WHEN NEW.series_id IS NOT NULL
+BEGIN
+    UPDATE series SET completed = (
+        SELECT v.owned_numbered >= v.total_books FROM SeriesCompletion v WHERE v.series_id = series.id
+    )
+    WHERE id IN (NEW.series_id) AND total_books > 0;
+END;
+CREATE TRIGGER series_completed_books_update
+    AFTER UPDATE OF series_id, series_index, deleted_at ON books
+    FOR EACH ROW
+    WHEN NEW.series_id IS NOT NULL OR OLD.series_id IS NOT NULL
+BEGIN
+    UPDATE series SET completed = (
+        SELECT v.owned_numbered >= v.total_books FROM SeriesCompletion v WHERE v.series_id = series.id
+    )
+    WHERE id IN (NEW.series_id, OLD.series_id) AND total_books > 0;
+END;
+CREATE TRIGGER series_completed_books_delete
+    AFTER DELETE ON books
+    FOR EACH ROW
+    WHEN OLD.series_id IS NOT NULL
+BEGIN
+    UPDATE series SET completed = (
+        SELECT v.owned_numbered >= v.total_books FROM SeriesCompletion v WHERE v.series_id = series.id
+    )
+    WHERE id IN (OLD.series_id) AND total_books > 0;
+END;
+CREATE TRIGGER series_completed_contents_insert
+    AFTER INSERT ON contents
+    FOR EACH ROW
+    WHEN NEW.series_id IS NOT NULL
+BEGIN
+    UPDATE series SET completed = (
+        SELECT v.owned_numbered >= v.total_books FROM SeriesCompletion v WHERE v.series_id = series.id
+    )
+    WHERE id IN (NEW.series_id) AND total_books > 0;
+END;
+CREATE TRIGGER series_completed_contents_update
+    AFTER UPDATE OF series_id, series_index, deleted_at ON contents
+    FOR EACH ROW
+    WHEN NEW.series_id IS NOT NULL OR OLD.series_id IS NOT NULL
+BEGIN
+    UPDATE series SET completed = (
+        SELECT v.owned_numbered >= v.total_books FROM SeriesCompletion v WHERE v.series_id = series.id
+    )
+    WHERE id IN (NEW.series_id, OLD.series_id) AND total_books > 0;
+END;
+CREATE TRIGGER series_completed_contents_delete
+    AFTER DELETE ON contents
+    FOR EACH ROW
+    WHEN OLD.series_id IS NOT NULL
+BEGIN
+    UPDATE series SET completed = (
+        SELECT v.owned_numbered >= v.total_books FROM SeriesCompletion v WHERE v.series_id = series.id
+    )
+    WHERE id IN (OLD.series_id) AND total_books > 0;
+END;
+CREATE TRIGGER series_completed_total_update
+    AFTER UPDATE OF total_books ON series
+    FOR EACH ROW
+BEGIN
+    UPDATE series SET completed = (
+        SELECT v.owned_numbered >= v.total_books FROM SeriesCompletion v WHERE v.series_id = series.id
+    )
+    WHERE id IN (NEW.id) AND total_books > 0;
+END;
+INSERT INTO "system_config" ("key", "value", "description") VALUES
+	('date_encoding', 'partial', 'Codifica delle colonne data (PartialDate)');
+CREATE TRIGGER sort_author_x_books_people_roles_insert
+    AFTER INSERT ON x_books_people_roles
+    FOR EACH ROW
+BEGIN
+    UPDATE books SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id = NEW.book_id AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+END;
+CREATE TRIGGER sort_author_x_books_people_roles_delete
+    AFTER DELETE ON x_books_people_roles
+    FOR EACH ROW
+BEGIN
+    UPDATE books SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id = OLD.book_id AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+END;
+CREATE TRIGGER sort_author_x_books_people_roles_update
+    AFTER UPDATE ON x_books_people_roles
+    FOR EACH ROW
+BEGIN
+    UPDATE books SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id = OLD.book_id AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+    UPDATE books SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id = NEW.book_id AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+END;
+CREATE TRIGGER sort_author_x_contents_people_roles_insert
+    AFTER INSERT ON x_contents_people_roles
+    FOR EACH ROW
+BEGIN
+    UPDATE contents SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id = NEW.content_id AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+END;
+CREATE TRIGGER sort_author_x_contents_people_roles_delete
+    AFTER DELETE ON x_contents_people_roles
+    FOR EACH ROW
+BEGIN
+    UPDATE contents SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id = OLD.content_id AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+END;
+CREATE TRIGGER sort_author_x_contents_people_roles_update
+    AFTER UPDATE ON x_contents_people_roles
+    FOR EACH ROW
+BEGIN
+    UPDATE contents SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id = OLD.content_id AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+    UPDATE contents SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id = NEW.content_id AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+END;
+CREATE TRIGGER sort_author_people_update
+    AFTER UPDATE OF sort_name ON people
+    FOR EACH ROW
+    WHEN NEW.sort_name IS NOT OLD.sort_name
+BEGIN
+    UPDATE books SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id IN (SELECT book_id FROM x_books_people_roles WHERE person_id = NEW.id) AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_books_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.book_id = books.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+    UPDATE contents SET sort_author = (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid))
+    WHERE id IN (SELECT content_id FROM x_contents_people_roles WHERE person_id = NEW.id) AND sort_author IS NOT (SELECT group_concat(sort_name, ' & ') FROM (
+            SELECT p.sort_name FROM x_contents_people_roles x
+            JOIN people p ON p.id = x.person_id
+            JOIN roles r ON r.id = x.role_id
+            WHERE x.content_id = contents.id AND r.code = 'aut'
+            ORDER BY x.rowid));
+END;
+INSERT INTO "roles" ("code", "name", "description") VALUES
+	('aut', 'Author', 'Person chiefly responsible for the intellectual content of the work'),
+	('trl', 'Translator', 'Person who renders the text from one language into another'),
+	('edt', 'Editor', 'Person who prepares the work for publication or curates a collection'),
+	('ill', 'Illustrator', 'Person who conceives the illustrations of the work'),
+	('nrt', 'Narrator', 'Person who reads the text aloud in an audio edition'),
+	('aui', 'Author of introduction', 'Person who writes the introduction or preface'),
+	('aft', 'Author of afterword', 'Person who writes the afterword or postface'),
+	('ann', 'Annotator', 'Person who writes notes or comments on the text'),
+	('com', 'Compiler', 'Person who selects and arranges the contents of an anthology'),
+	('cov', 'Cover designer', 'Person who designs the cover of the book'),
+	('pht', 'Photographer', 'Person who takes the photographs in the work'),
+	('ctb', 'Contributor', 'Person with a generic or secondary contribution to the work');
+WITH "labels" ("code", "language", "label") AS (VALUES
+	('aut', 'en', 'Author'), ('aut', 'it', 'Autore'), ('aut', 'fr', 'Auteur'), ('aut', 'de', 'Autor'), ('aut', 'es', 'Autor'),
+	('trl', 'en', 'Translator'), ('trl', 'it', 'Traduttore'), ('trl', 'fr', 'Traducteur'), ('trl', 'de', 'Übersetzer'), ('trl', 'es', 'Traductor'),
+	('edt', 'en', 'Editor'), ('edt', 'it', 'Curatore'), ('edt', 'fr', 'Éditeur scientifique'), ('edt', 'de', 'Herausgeber'), ('edt', 'es', 'Editor'),
+	('ill', 'en', 'Illustrator'), ('ill', 'it', 'Illustratore'), ('ill', 'fr', 'Illustrateur'), ('ill', 'de', 'Illustrator'), ('ill', 'es', 'Ilustrador'),
+	('nrt', 'en', 'Narrator'), ('nrt', 'it', 'Narratore'), ('nrt', 'fr', 'Narrateur'), ('nrt', 'de', 'Erzähler'), ('nrt', 'es', 'Narrador'),
+	('aui', 'en', 'Author of introduction'), ('aui', 'it', 'Autore dell''introduzione'), ('aui', 'fr', 'Préfacier'), ('aui', 'de', 'Verfasser der Einleitung'), ('aui', 'es', 'Autor de la introducción'),
+	('aft', 'en', 'Author of afterword'), ('aft', 'it', 'Autore della postfazione'), ('aft', 'fr', 'Postfacier'), ('aft', 'de', 'Verfasser des Nachworts'), ('aft', 'es', 'Autor del epílogo'),
+	('ann', 'en', 'Annotator'), ('ann', 'it', 'Annotatore'), ('ann', 'fr', 'Annotateur'), ('ann', 'de', 'Kommentator'), ('ann', 'es', 'Anotador'),
+	('com', 'en', 'Compiler'), ('com', 'it', 'Compilatore'), ('com', 'fr', 'Compilateur'), ('com', 'de', 'Kompilator'), ('com', 'es', 'Compilador'),
+	('cov', 'en', 'Cover designer'), ('cov', 'it', 'Copertinista'), ('cov', 'fr', 'Concepteur de la couverture'), ('cov', 'de', 'Umschlaggestalter'), ('cov', 'es', 'Diseñador de cubierta'),
+	('pht', 'en', 'Photographer'), ('pht', 'it', 'Fotografo'), ('pht', 'fr', 'Photographe'), ('pht', 'de', 'Fotograf'), ('pht', 'es', 'Fotógrafo'),
+	('ctb', 'en', 'Contributor'), ('ctb', 'it', 'Collaboratore'), ('ctb', 'fr', 'Collaborateur'), ('ctb', 'de', 'Mitwirkender'), ('ctb', 'es', 'Colaborador')
+)
+INSERT INTO "role_labels" ("role_id", "language", "label")
+SELECT r."id", l."language", l."label"
+FROM "labels" l
+JOIN "roles" r ON r."code" = l."code";
 COMMIT;
@@ -0,0 +1,648 @@
+//! Importazione massiva di cataloghi molto grandi.
+//!
+//! I `save` dei modelli fanno un INSERT in autocommit per riga; per centinaia di migliaia di libri
+//! `BulkImporter` raggruppa invece i libri in transazioni da `batch_size` righe su un'unica connessione,
+//! così le istruzioni preparate restano nella cache della connessione e vengono riusate.
+//! Gli id di editori, formati, serie, tag, tipi, ruoli e persone vengono risolti una volta sola
+//! e tenuti in memoria per tutta l'importazione; le persone sono riconosciute per chiave normalizzata.
+
+use crate::duplicates::find_duplicate;
+use crate::models::{Acquisition, Book, BookFile, BookIdentifier, Content, Role, Work};
+use ritmo_core::dto::BookDto;
+use ritmo_core::service::import_service::{
+    BeforeCommit, CreatedEntity, DuplicateMatch, ImportTarget, Persisted, Placement,
+};
+use ritmo_core::ContentDto;
+use ritmo_db_core::maintenance::{restore_deferred_indexes, DeferredIndexesLock, DEFERRED_INDEXES_KEY};
+use ritmo_db_core::normalize::normalize_key;
+use ritmo_db_core::sorting::sort_name;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{Acquire, QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Ruolo assegnato ai nomi in `ContentDto::people`, che non indicano il ruolo
+const DEFAULT_CONTENT_ROLE: &str = "aut";
+
+/// Righe per INSERT di collegamenti, ben sotto il limite di parametri di SQLite
+const LINK_ROWS_PER_STATEMENT: usize = 500;
+
+/// Tabelle i cui indici secondari possono essere rimandati a fine importazione.
+/// Non comprende le tabelle di lookup (persone, editori, serie, tag), che servono durante l'importazione.
+const DEFERRABLE_TABLES: &[&str] = &[
+    "books",
+    "contents",
+    "book_files",
+    "book_identifiers",
+    "acquisitions",
+    "change_log",
+    "x_books_contents",
+    "x_books_people_roles",
+    "x_books_tags",
+    "x_contents_people_roles",
+    "x_contents_tags",
+    "x_contents_languages",
+];
+
+#[derive(Debug, Clone)]
+pub struct BulkImportOptions {
+    /// Libri per transazione
+    pub batch_size: usize,
+    /// Elimina gli indici secondari non univoci prima dell'importazione e li ricrea alla fine
+    pub defer_indexes: bool,
+}
+
+impl Default for BulkImportOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 2000,
+            defer_indexes: false,
+        }
+    }
+}
+
+/// Avanzamento, notificato dopo il commit di ogni transazione
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BulkProgress {
+    pub processed: usize,
+    /// Totale atteso, se l'iteratore lo conosce
+    pub total: Option<usize>,
+    pub elapsed: Duration,
+}
+
+impl BulkProgress {
+    /// Libri importati al secondo
+    pub fn rate(&self) -> f64 {
+        let secs = self.elapsed.as_secs_f64();
+        if secs > 0.0 {
+            self.processed as f64 / secs
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Riepilogo di un'importazione
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BulkImportReport {
+    pub books: usize,
+    pub contents: usize,
+    pub people_created: usize,
+    pub publishers_created: usize,
+    pub series_created: usize,
+    pub tags_created: usize,
+    pub formats_created: usize,
+    pub types_created: usize,
+    /// Ruoli non riconosciuti né per codice né per nome o etichetta, sostituiti con l'autore
+    pub unresolved_roles: Vec<String>,
+    /// Lingue non presenti in `running_languages`, ignorate
+    pub unresolved_languages: Vec<String>,
+    /// Righe create nelle tabelle di lookup, nell'ordine di creazione
+    pub created: Vec<CreatedEntity>,
+    pub elapsed: Duration,
+}
+
+/// Tabelle di lookup risolte per nome
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Lookup {
+    Publisher,
+    Format,
+    Series,
+    Tag,
+    Type,
+}
+
+impl Lookup {
+    fn table(&self) -> &'static str {
+        match self {
+            Self::Publisher => "publishers",
+            Self::Format => "formats",
+            Self::Series => "series",
+            Self::Tag => "tags",
+            Self::Type => "types",
+        }
+    }
+}
+
+/// Id già risolti, per nome senza distinzione di maiuscole
+#[derive(Default, Clone)]
+struct LookupCache {
+    names: HashMap<(Lookup, String), i64>,
+    /// Persone per chiave normalizzata
+    people: HashMap<String, i64>,
+    roles: HashMap<String, i64>,
+    languages: HashMap<(String, String), Option<i64>>,
+}
+
+/// Callback chiamata dopo il commit di ogni transazione
+pub type ProgressCallback = Box<dyn FnMut(&BulkProgress) + Send>;
+
+/// Righe di collegamento accumulate durante la transazione e scritte con INSERT a più righe
+#[derive(Default)]
+struct PendingLinks {
+    book_people: Vec<[i64; 3]>,
+    book_tags: Vec<[i64; 2]>,
+    book_contents: Vec<[i64; 2]>,
+    content_people: Vec<[i64; 3]>,
+    content_tags: Vec<[i64; 2]>,
+    content_languages: Vec<[i64; 2]>,
+}
+
+impl PendingLinks {
+    async fn flush(&mut self, conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+        insert_links(conn, "x_books_people_roles (book_id, person_id, role_id)", &mut self.book_people).await?;
+        insert_links(conn, "x_books_tags (book_id, tag_id)", &mut self.book_tags).await?;
+        insert_links(conn, "x_books_contents (book_id, content_id)", &mut self.book_contents).await?;
+        insert_links(conn, "x_contents_people_roles (content_id, person_id, role_id)", &mut self.content_people).await?;
+        insert_links(conn, "x_contents_tags (content_id, tag_id)", &mut self.content_tags).await?;
+        insert_links(conn, "x_contents_languages (content_id, language_id)", &mut self.content_languages).await?;
+        Ok(())
+    }
+}
+
+pub struct BulkImporter {
+    pool: SqlitePool,
+    options: BulkImportOptions,
+    cache: LookupCache,
+    links: PendingLinks,
+    report: BulkImportReport,
+    progress: Option<ProgressCallback>,
+}
+
+impl BulkImporter {
+    pub fn new(pool: &SqlitePool) -> Self {
+        Self {
+            pool: pool.clone(),
+            options: BulkImportOptions::default(),
+            cache: LookupCache::default(),
+            links: PendingLinks::default(),
+            report: BulkImportReport::default(),
+            progress: None,
+        }
+    }
+
+    pub fn with_options(mut self, options: BulkImportOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn on_progress(mut self, callback: impl FnMut(&BulkProgress) + Send + 'static) -> Self {
+        self.progress = Some(Box::new(callback));
+        self
+    }
+
+    /// Importa i libri con i loro contenuti, persone, tag e lingue.
+    /// In caso di errore la transazione in corso viene annullata, mentre quelle già confermate restano;
+    /// gli indici rimandati vengono ricreati comunque. Con `defer_indexes` l'importazione tiene il
+    /// lock sugli indici rimandati, e fallisce se un'altra importazione di questo tipo è in corso.
+    pub async fn import<I>(mut self, books: I) -> RitmoResult<BulkImportReport>
+    where
+        I: IntoIterator<Item = BookDto>,
+    {
+        let started = Instant::now();
+        let mut conn = self.pool.acquire().await?;
+        let lock = if self.options.defer_indexes {
+            let busy = || RitmoErr::ImportError("un'altra importazione con indici rimandati è in corso".to_string());
+            Some(DeferredIndexesLock::try_acquire(&mut conn).await?.ok_or_else(busy)?)
+        } else {
+            None
+        };
+        // Prima quelli lasciati da un'importazione precedente interrotta, che altrimenti andrebbero persi
+        match &lock {
+            Some(lock) => {
+                lock.restore(&mut conn).await?;
+                drop_secondary_indexes(&mut conn).await?;
+            }
+            None => {
+                restore_deferred_indexes(&mut conn).await?;
+            }
+        }
+
+        let result = self.run(&mut conn, books.into_iter(), started).await;
+        if let Some(lock) = lock {
+            lock.restore(&mut conn).await?;
+        }
+        result?;
+
+        self.report.elapsed = started.elapsed();
+        Ok(self.report)
+    }
+
+    async fn run(
+        &mut self,
+        conn: &mut SqliteConnection,
+        mut books: impl Iterator<Item = BookDto>,
+        started: Instant,
+    ) -> RitmoResult<()> {
+        let total = match books.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
+        };
+        let batch_size = self.options.batch_size.max(1);
+        let mut processed = 0;
+        loop {
+            let batch: Vec<BookDto> = books.by_ref().take(batch_size).collect();
+            if batch.is_empty() {
+                break;
+            }
+            let mut tx = conn.begin().await?;
+            let mut report = self.report.clone();
+            for mut dto in batch {
+                self.insert_book(&mut tx, &mut dto, &mut report).await?;
+                processed += 1;
+            }
+            self.links.flush(&mut tx).await?;
+            tx.commit().await?;
+            // Solo ora gli id creati nella transazione sono definitivi
+            self.report = report;
+
+            if let Some(callback) = self.progress.as_mut() {
+                callback(&BulkProgress {
+                    processed,
+                    total,
+                    elapsed: started.elapsed(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    async fn insert_book(
+        &mut self,
+        conn: &mut SqliteConnection,
+        dto: &mut BookDto,
+        report: &mut BulkImportReport,
+    ) -> RitmoResult<i64> {
+        let mut book = Book::from_dto(dto);
+        if book.publisher_id.is_none() {
+            book.publisher_id = self.lookup(conn, Lookup::Publisher, &dto.publisher_name, report).await?;
+        }
+        if book.format_id.is_none() {
+            book.format_id = self.lookup(conn, Lookup::Format, &dto.format_name, report).await?;
+        }
+        if book.series_id.is_none() {
+            book.series_id = self.lookup(conn, Lookup::Series, &dto.series_name, report).await?;
+        }
+        let book_id = book.insert(conn).await?;
+        report.books += 1;
+
+        Acquisition::insert_from_dto(conn, book_id, dto).await?;
+        BookIdentifier::insert_from_dto(conn, book_id, dto).await?;
+        for person in &dto.people {
+            let person_id = match person.person_id {
+                Some(id) => Some(id),
+                None => self.person(conn, &person.person_name, report).await?,
+            };
+            let Some(person_id) = person_id else { continue };
+            let role_id = self.role(conn, &person.person_role, report).await?;
+            self.links.book_people.push([book_id, person_id, role_id]);
+        }
+        for tag in dto.tags.iter().filter(|t| t.is_book_tag || !t.is_content_tag) {
+            let Some(tag_id) = self.lookup(conn, Lookup::Tag, &tag.name, report).await? else { continue };
+            self.links.book_tags.push([book_id, tag_id]);
+        }
+        for content in &dto.contents {
+            let content_id = self.insert_content(conn, content, report).await?;
+            self.links.book_contents.push([book_id, content_id]);
+        }
+        Ok(book_id)
+    }
+
+    async fn insert_content(
+        &mut self,
+        conn: &mut SqliteConnection,
+        dto: &ContentDto,
+        report: &mut BulkImportReport,
+    ) -> RitmoResult<i64> {
+        let mut content = Content::from_dto(dto);
+        if content.type_id.is_none() {
+            content.type_id = self.lookup(conn, Lookup::Type, &dto.type_name, report).await?;
+        }
+        let content_id = content.insert(conn).await?;
+        report.contents += 1;
+
+        if !dto.people.is_empty() {
+            let role_id = self.role(conn, DEFAULT_CONTENT_ROLE, report).await?;
+            for name in &dto.people {
+                let Some(person_id) = self.person(conn, name, report).await? else { continue };
+                self.links.content_people.push([content_id, person_id, role_id]);
+            }
+        }
+        for tag in &dto.tags {
+            let Some(tag_id) = self.lookup(conn, Lookup::Tag, &tag.name, report).await? else { continue };
+            self.links.content_tags.push([content_id, tag_id]);
+        }
+        for language in &dto.languages {
+            let Some(language_id) = self.language(conn, &language.name, &language.role, report).await? else {
+                continue;
+            };
+            self.links.content_languages.push([content_id, language_id]);
+        }
+        Ok(content_id)
+    }
+
+    /// Id della riga con quel nome, creata se manca; None per nomi vuoti
+    async fn lookup(
+        &mut self,
+        conn: &mut SqliteConnection,
+        kind: Lookup,
+        name: &str,
+        report: &mut BulkImportReport,
+    ) -> RitmoResult<Option<i64>> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Ok(None);
+        }
+        let key = (kind, name.to_lowercase());
+        if let Some(&id) = self.cache.names.get(&key) {
+            return Ok(Some(id));
+        }
+        let table = kind.table();
+        let existing: Option<i64> =
+            sqlx::query_scalar(&format!("SELECT id FROM {} WHERE name = ? COLLATE NOCASE LIMIT 1", table))
+                .bind(name)
+                .fetch_optional(&mut *conn)
+                .await?;
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                let result = sqlx::query(&format!("INSERT INTO {} (name) VALUES (?)", table))
+                    .bind(name)
+                    .execute(&mut *conn)
+                    .await?;
+                match kind {
+                    Lookup::Publisher => report.publishers_created += 1,
+                    Lookup::Format => report.formats_created += 1,
+                    Lookup::Series => report.series_created += 1,
+                    Lookup::Tag => report.tags_created += 1,
+                    Lookup::Type => report.types_created += 1,
+                }
+                report.created.push(CreatedEntity { table, name: name.to_string() });
+                result.last_insert_rowid()
+            }
+        };
+        self.cache.names.insert(key, id);
+        Ok(Some(id))
+    }
+
+    /// Persona con la stessa chiave normalizzata del nome, creata se manca;
+    /// "Garcia Marquez, Gabriel" e "García Márquez Gabriel" sono la stessa persona
+    async fn person(
+        &mut self,
+        conn: &mut SqliteConnection,
+        name: &str,
+        report: &mut BulkImportReport,
+    ) -> RitmoResult<Option<i64>> {
+        let key = normalize_key(name);
+        if key.is_empty() {
+            return Ok(None);
+        }
+        if let Some(&id) = self.cache.people.get(&key) {
+            return Ok(Some(id));
+        }
+        let existing = sqlx::query_scalar!(
+            "SELECT id AS \"id!\" FROM people WHERE normalized_key = ? COLLATE NOCASE ORDER BY id LIMIT 1",
+            key
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        let id = match existing {
+            Some(id) => id,
+            None => {
+                let name = name.trim();
+                let sort_name = sort_name(name, None, None);
+                let result = sqlx::query!(
+                    "INSERT INTO people (name, normalized_key, sort_name) VALUES (?, ?, ?)",
+                    name,
+                    key,
+                    sort_name
+                )
+                .execute(&mut *conn)
+                .await?;
+                report.people_created += 1;
+                report.created.push(CreatedEntity { table: "people", name: name.to_string() });
+                result.last_insert_rowid()
+            }
+        };
+        self.cache.people.insert(key, id);
+        Ok(Some(id))
+    }
+
+    /// Ruolo per codice (`aut`), nome (`Author`) o etichetta (`Autore`). Un ruolo sconosciuto
+    /// non viene creato, perché senza codice nessuna ricerca lo troverebbe: vale come autore.
+    async fn role(&mut self, conn: &mut SqliteConnection, role: &str, report: &mut BulkImportReport) -> RitmoResult<i64> {
+        let role = role.trim();
+        let role = if role.is_empty() { DEFAULT_CONTENT_ROLE } else { role };
+        let key = role.to_lowercase();
+        if let Some(&id) = self.cache.roles.get(&key) {
+            return Ok(id);
+        }
+        let id = match Role::find_by_label_in(conn, role).await? {
+            Some(found) => found.id,
+            None => {
+                report.unresolved_roles.push(role.to_string());
+                Role::find_by_label_in(conn, DEFAULT_CONTENT_ROLE).await?.and_then(|r| r.id)
+            }
+        };
+        let id = id.ok_or_else(|| RitmoErr::NoResultsError(format!("ruolo {} mancante", DEFAULT_CONTENT_ROLE)))?;
+        self.cache.roles.insert(key, id);
+        Ok(id)
+    }
+
+    /// Salva un solo libro in una transazione propria, chiamando `before_commit` prima del commit.
+    /// Con `dry_run` la transazione viene annullata dopo aver scritto tutto, così gli id e le
+    /// entità create sono quelli reali. Se la transazione non viene confermata le cache tornano
+    /// come prima, perché gli id creati nel frattempo non esistono più.
+    pub async fn import_one(
+        &mut self,
+        dto: &BookDto,
+        placement: Placement,
+        dry_run: bool,
+        before_commit: BeforeCommit,
+    ) -> RitmoResult<Persisted> {
+        let cache = self.cache.clone();
+        let result = self.import_one_tx(dto, placement, dry_run, before_commit).await;
+        if result.is_err() || dry_run {
+            self.cache = cache;
+            self.links = PendingLinks::default();
+        }
+        result
+    }
+
+    async fn import_one_tx(
+        &mut self,
+        dto: &BookDto,
+        placement: Placement,
+        dry_run: bool,
+        before_commit: BeforeCommit,
+    ) -> RitmoResult<Persisted> {
+        let mut conn = self.pool.acquire().await?;
+        let mut tx = conn.begin().await?;
+        let mut report = BulkImportReport::default();
+        let book_id = match placement {
+            Placement::FormatOf(book_id) => {
+                self.attach_file(&mut tx, dto, book_id, &mut report).await?;
+                book_id
+            }
+            _ => self.insert_book(&mut tx, &mut dto.clone(), &mut report).await?,
+        };
+        self.links.flush(&mut tx).await?;
+        if let Placement::EditionOf(original_id) = placement {
+            link_edition(&mut tx, original_id, book_id).await?;
+        }
+        if dry_run {
+            tx.rollback().await?;
+        } else {
+            tokio::task::spawn_blocking(before_commit)
+                .await
+                .map_err(|e| RitmoErr::OtherError(format!("operazione prima del commit interrotta: {}", e)))??;
+            tx.commit().await?;
+        }
+        Ok(Persisted { book_id, contents: report.contents, created: report.created })
+    }
+
+    /// Aggiunge il file del DTO ai formati di un libro esistente
+    async fn attach_file(
+        &mut self,
+        conn: &mut SqliteConnection,
+        dto: &BookDto,
+        book_id: i64,
+        report: &mut BulkImportReport,
+    ) -> RitmoResult<()> {
+        let Some(file_link) = dto.file_link.clone() else {
+            return Err(RitmoErr::ImportError(format!("\"{}\" non ha un file da aggiungere", dto.name)));
+        };
+        let format_id = match dto.format_id {
+            Some(id) => Some(id),
+            None => self.lookup(conn, Lookup::Format, &dto.format_name, report).await?,
+        };
+        let file = BookFile {
+            book_id,
+            format_id,
+            file_link,
+            file_size: dto.file_size,
+            file_hash: dto.file_hash.clone(),
+            ..Default::default()
+        };
+        file.insert(conn).await?;
+        Ok(())
+    }
+
+    /// Lingua per nome o codice ISO nel ruolo indicato; le lingue sconosciute non vengono create
+    async fn language(
+        &mut self,
+        conn: &mut SqliteConnection,
+        name: &str,
+        role: &str,
+        report: &mut BulkImportReport,
+    ) -> RitmoResult<Option<i64>> {
+        let key = (name.trim().to_lowercase(), role.trim().to_lowercase());
+        if let Some(&id) = self.cache.languages.get(&key) {
+            return Ok(id);
+        }
+        let id = sqlx::query_scalar!(
+            "SELECT id AS \"id!\" FROM running_languages
+             WHERE (official_name = ?1 COLLATE NOCASE OR iso_code_2char = ?1 COLLATE NOCASE
+                    OR iso_code_3char = ?1 COLLATE NOCASE)
+               AND language_role = ?2 COLLATE NOCASE
+             LIMIT 1",
+            key.0,
+            key.1
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        if id.is_none() {
+            report.unresolved_languages.push(name.to_string());
+        }
+        self.cache.languages.insert(key, id);
+        Ok(id)
+    }
+}
+
+impl ImportTarget for BulkImporter {
+    async fn find_duplicate(&mut self, book: &BookDto) -> RitmoResult<Option<DuplicateMatch>> {
+        find_duplicate(&self.pool, book).await
+    }
+
+    async fn persist(
+        &mut self,
+        book: &BookDto,
+        placement: Placement,
+        dry_run: bool,
+        before_commit: BeforeCommit,
+    ) -> RitmoResult<Persisted> {
+        self.import_one(book, placement, dry_run, before_commit).await
+    }
+}
+
+/// Mette i contenuti della nuova edizione nelle opere di quelli dell'originale, nell'ordine
+/// in cui compaiono nei due libri; le opere mancanti vengono create
+async fn link_edition(conn: &mut SqliteConnection, original_id: i64, edition_id: i64) -> RitmoResult<()> {
+    let original = sqlx::query_scalar!(
+        "SELECT content_id FROM x_books_contents WHERE book_id = ? ORDER BY rowid",
+        original_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    let edition = sqlx::query_scalar!(
+        "SELECT content_id FROM x_books_contents WHERE book_id = ? ORDER BY rowid",
+        edition_id
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    for (original, edition) in original.into_iter().zip(edition) {
+        let work_id = Work::ensure_for_content(conn, original).await?;
+        sqlx::query!("UPDATE contents SET work_id = ? WHERE id = ?", work_id, edition)
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Scrive e svuota `rows`, a blocchi di `LINK_ROWS_PER_STATEMENT` righe
+async fn insert_links<const N: usize>(
+    conn: &mut SqliteConnection,
+    target: &str,
+    rows: &mut Vec<[i64; N]>,
+) -> Result<(), sqlx::Error> {
+    for chunk in rows.chunks(LINK_ROWS_PER_STATEMENT) {
+        let mut qb = QueryBuilder::<Sqlite>::new(format!("INSERT OR IGNORE INTO {} ", target));
+        qb.push_values(chunk, |mut b, row| {
+            for id in row {
+                b.push_bind(*id);
+            }
+        });
+        qb.build().execute(&mut *conn).await?;
+    }
+    rows.clear();
+    Ok(())
+}
+
+/// Elimina gli indici secondari non univoci delle tabelle importate.
+/// Nella stessa transazione le definizioni vengono salvate in `system_config`, così un'importazione
+/// interrotta non li perde: li ricrea `restore_deferred_indexes` alla prossima apertura del database.
+async fn drop_secondary_indexes(conn: &mut SqliteConnection) -> RitmoResult<()> {
+    let mut tx = conn.begin().await?;
+    let rows = sqlx::query!(
+        "SELECT name AS \"name!\", tbl_name AS \"tbl_name!\", sql AS \"sql!\" FROM sqlite_master
+         WHERE type = 'index' AND sql IS NOT NULL AND sql NOT LIKE 'CREATE UNIQUE%'"
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+    let mut definitions = Vec::new();
+    for row in rows.into_iter().filter(|r| DEFERRABLE_TABLES.contains(&r.tbl_name.as_str())) {
+        sqlx::query(&format!("DROP INDEX \"{}\"", row.name)).execute(&mut *tx).await?;
+        definitions.push(row.sql);
+    }
+    let pending = serde_json::to_string(&definitions)?;
+    sqlx::query!(
+        "INSERT INTO system_config (key, value, description) VALUES (?, ?, 'Indici rimandati da ricreare')
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        DEFERRED_INDEXES_KEY,
+        pending
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(())
+}
@@ -0,0 +1,170 @@
+//! Riconoscimento dei libri già presenti, usato dall'importazione da file.
+//!
+//! I livelli sono provati dal più sicuro al meno sicuro e vince il primo che trova qualcosa:
+//! hash del file, identificatori equivalenti (l'ISBN e gli altri identificatori del DTO, cercati
+//! in `book_identifiers`), e infine titolo simile con lo stesso primo autore. Per il titolo i
+//! candidati sono i libri il cui `sort_author` comincia con il cognome del primo autore,
+//! confrontati sulle chiavi di ordinamento, che ignorano articoli, maiuscole e diacritici.
+//!
+//! Tutti i livelli considerano anche i libri nel cestino, finché non vengono eliminati
+//! definitivamente: il loro file è ancora in archivio e appartiene a loro, quindi un file
+//! identico non potrebbe comunque diventare un altro libro. A parità di livello un libro fuori
+//! dal cestino ha la precedenza, e `DuplicateMatch::in_trash` dice quale è stato trovato.
+
+use crate::identifiers::Identifier;
+use crate::models::{BookIdentifier, Role};
+use ritmo_core::dto::BookDto;
+use ritmo_core::service::import_service::{DuplicateLayer, DuplicateMatch};
+use ritmo_db_core::sorting::{sort_name, sort_title};
+use ritmo_errors::RitmoResult;
+use sqlx::SqlitePool;
+
+/// Somiglianza minima tra le chiavi dei titoli perché due libri dello stesso autore
+/// siano considerati lo stesso
+pub const TITLE_SIMILARITY: f64 = 0.85;
+
+/// Codice del ruolo degli autori, gli unici usati per il confronto
+const AUTHOR_ROLE: &str = "aut";
+
+/// Primo libro già presente che corrisponde a `book`
+pub async fn find_duplicate(pool: &SqlitePool, book: &BookDto) -> RitmoResult<Option<DuplicateMatch>> {
+    if let Some(file_hash) = book.file_hash.as_deref() {
+        let found = sqlx::query!(
+            "SELECT b.id AS \"id!\", b.name, b.deleted_at IS NOT NULL AS \"in_trash!: bool\"
+             FROM book_files bf JOIN books b ON b.id = bf.book_id
+             WHERE bf.file_hash = ? ORDER BY b.deleted_at IS NOT NULL, b.id LIMIT 1",
+            file_hash
+        )
+        .fetch_optional(pool)
+        .await?;
+        if let Some(row) = found {
+            return Ok(Some(exact(DuplicateLayer::Hash, row.id, row.name, row.in_trash)));
+        }
+    }
+
+    // Un identificatore non valido non identifica niente
+    let identifiers = book.isbn.iter().chain(&book.identifiers).filter_map(|text| Identifier::parse(text).ok());
+    for identifier in identifiers {
+        if let Some(&book_id) = BookIdentifier::find_book_ids_with_trash(pool, &identifier).await?.first() {
+            let row = sqlx::query!(
+                "SELECT name, deleted_at IS NOT NULL AS \"in_trash!: bool\" FROM books WHERE id = ?",
+                book_id
+            )
+            .fetch_one(pool)
+            .await?;
+            return Ok(Some(exact(DuplicateLayer::Identifier, book_id, row.name, row.in_trash)));
+        }
+    }
+
+    find_by_title(pool, book).await
+}
+
+/// Libro dello stesso primo autore con il titolo più simile, se supera `TITLE_SIMILARITY`
+async fn find_by_title(pool: &SqlitePool, book: &BookDto) -> RitmoResult<Option<DuplicateMatch>> {
+    // I ruoli del DTO possono essere codici, nomi o etichette localizzate: si risolvono come
+    // fa l'importazione
+    let mut author_roles = Vec::new();
+    for person in &book.people {
+        let role = Role::find_by_label(pool, &person.person_role).await?;
+        if role.and_then(|r| r.code).as_deref() == Some(AUTHOR_ROLE) {
+            author_roles.push(person.person_role.as_str());
+        }
+    }
+    let Some(author) = first_author(book, &author_roles) else { return Ok(None) };
+    let author = sort_name(author, None, None);
+    let surname = author.split(',').next().unwrap_or_default().trim().to_string();
+    if surname.is_empty() {
+        return Ok(None);
+    }
+    let language = book.contents.first().and_then(|c| c.languages.first()).map(|l| l.name.as_str());
+    let title = sort_title(&book.name, language);
+
+    // Tutte le chiavi che cominciano con il cognome: `sort_author` è indicizzato
+    let upper = format!("{}{}", surname, char::MAX);
+    let candidates = sqlx::query!(
+        "SELECT id AS \"id!\", name, sort_title, sort_author AS \"sort_author!\",
+                deleted_at IS NOT NULL AS \"in_trash!: bool\"
+         FROM books WHERE sort_author >= ? AND sort_author < ?
+         ORDER BY deleted_at IS NOT NULL, id",
+        surname,
+        upper
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut best: Option<DuplicateMatch> = None;
+    for row in candidates {
+        let same_author = row.sort_author.split(" & ").next().and_then(|a| a.split(',').next()).map(str::trim)
+            == Some(surname.as_str());
+        if !same_author {
+            continue;
+        }
+        let existing = row.sort_title.unwrap_or_else(|| sort_title(&row.name, None));
+        let score = similarity(&title, &existing);
+        if score >= TITLE_SIMILARITY && best.as_ref().is_none_or(|b| score > b.score) {
+            let (book_id, title, in_trash) = (row.id, row.name, row.in_trash);
+            best = Some(DuplicateMatch { layer: DuplicateLayer::Title, book_id, title, score, in_trash });
+        }
+    }
+    Ok(best)
+}
+
+/// Primo autore del libro, o in mancanza del primo contenuto.
+/// `author_roles` sono i ruoli del libro che corrispondono all'autore.
+fn first_author<'a>(book: &'a BookDto, author_roles: &[&str]) -> Option<&'a str> {
+    book.people
+        .iter()
+        .find(|p| author_roles.contains(&p.person_role.as_str()))
+        .map(|p| p.person_name.as_str())
+        .or_else(|| book.contents.first()?.people.first().map(String::as_str))
+        .filter(|name| !name.trim().is_empty())
+}
+
+fn exact(layer: DuplicateLayer, book_id: i64, title: String, in_trash: bool) -> DuplicateMatch {
+    DuplicateMatch { layer, book_id, title, score: 1.0, in_trash }
+}
+
+/// Somiglianza tra due testi, da 0 a 1: uno meno la distanza di Levenshtein
+/// (in caratteri) divisa per la lunghezza del più lungo
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let longest = a.len().max(b.len());
+    if longest == 0 {
+        return 1.0;
+    }
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    1.0 - previous[b.len()] as f64 / longest as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similarity() {
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("barone rampante", "barone rampante"), 1.0);
+        assert_eq!(similarity("abc", ""), 0.0);
+        // Un refuso in un titolo lungo resta sopra la soglia, un titolo diverso no
+        assert!(similarity("barone rampante", "barone rampnate") >= 0.85);
+        assert!(similarity("barone rampante", "visconte dimezzato") < 0.5);
+    }
+
+    #[test]
+    fn test_first_author_falls_back_to_contents() {
+        let mut book = BookDto::default();
+        assert_eq!(first_author(&book, &[]), None);
+        book.contents.push(ritmo_core::ContentDto { people: vec!["Italo Calvino".to_string()], ..Default::default() });
+        assert_eq!(first_author(&book, &[]), Some("Italo Calvino"));
+    }
+}
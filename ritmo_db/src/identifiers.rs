@@ -0,0 +1,352 @@
+//! Identificatori bibliografici (ISBN, ISSN, ASIN, DOI, ...): normalizzazione, validazione
+//! dei checksum e conversione ISBN-10 ↔ ISBN-13.
+
+use ritmo_errors::{RitmoErr, RitmoResult};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum IdentifierScheme {
+    Isbn10,
+    Isbn13,
+    Issn,
+    Asin,
+    Doi,
+    Oclc,
+    Lccn,
+    /// Standard Book Number, il predecessore a 9 cifre dell'ISBN-10
+    Sbn,
+    /// Schema arbitrario, sempre in minuscolo
+    Other(String),
+}
+
+impl IdentifierScheme {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Isbn10 => "isbn10",
+            Self::Isbn13 => "isbn13",
+            Self::Issn => "issn",
+            Self::Asin => "asin",
+            Self::Doi => "doi",
+            Self::Oclc => "oclc",
+            Self::Lccn => "lccn",
+            Self::Sbn => "sbn",
+            Self::Other(s) => s,
+        }
+    }
+
+    pub fn parse(scheme: &str) -> Self {
+        let scheme = scheme.trim().to_lowercase();
+        match scheme.replace(['-', '_', ' '], "").as_str() {
+            "isbn10" => Self::Isbn10,
+            "isbn13" => Self::Isbn13,
+            "issn" => Self::Issn,
+            "asin" | "amazon" => Self::Asin,
+            "doi" => Self::Doi,
+            "oclc" => Self::Oclc,
+            "lccn" => Self::Lccn,
+            "sbn" => Self::Sbn,
+            _ => Self::Other(scheme),
+        }
+    }
+}
+
+impl fmt::Display for IdentifierScheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Identificatore già normalizzato e validato
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Identifier {
+    pub scheme: IdentifierScheme,
+    pub value: String,
+}
+
+impl Identifier {
+    /// Normalizza e valida `raw` secondo lo schema indicato
+    pub fn new(scheme: IdentifierScheme, raw: &str) -> RitmoResult<Self> {
+        let value = match &scheme {
+            IdentifierScheme::Isbn10 => {
+                let v = normalize_isbn(raw);
+                check(is_valid_isbn10(&v), "ISBN-10", raw)?;
+                v
+            }
+            IdentifierScheme::Isbn13 => {
+                let v = normalize_isbn(raw);
+                check(is_valid_isbn13(&v), "ISBN-13", raw)?;
+                v
+            }
+            IdentifierScheme::Sbn => {
+                let v = normalize_isbn(raw);
+                check(v.len() == 9 && is_valid_isbn10(&format!("0{}", v)), "SBN", raw)?;
+                v
+            }
+            IdentifierScheme::Issn => {
+                let v = normalize_isbn(raw);
+                check(is_valid_issn(&v), "ISSN", raw)?;
+                v
+            }
+            IdentifierScheme::Asin => {
+                let v = raw.trim().to_uppercase();
+                check(v.len() == 10 && v.chars().all(|c| c.is_ascii_alphanumeric()), "ASIN", raw)?;
+                v
+            }
+            IdentifierScheme::Doi => {
+                let v = normalize_doi(raw);
+                check(v.starts_with("10.") && v.contains('/'), "DOI", raw)?;
+                v
+            }
+            IdentifierScheme::Oclc => {
+                let v = normalize_oclc(raw);
+                check(!v.is_empty() && v.chars().all(|c| c.is_ascii_digit()), "OCLC", raw)?;
+                v
+            }
+            IdentifierScheme::Lccn => {
+                let v = normalize_lccn(raw);
+                check(!v.is_empty(), "LCCN", raw)?;
+                v
+            }
+            IdentifierScheme::Other(_) => {
+                let v = raw.trim().to_string();
+                check(!v.is_empty(), scheme.as_str(), raw)?;
+                v
+            }
+        };
+        Ok(Self { scheme, value })
+    }
+
+    /// Interpreta un testo libero: `schema:valore` (es. `doi:10.1000/182`, `asin:B000FA5ZEG`)
+    /// oppure un ISBN senza prefisso, riconosciuto dalla lunghezza.
+    pub fn parse(text: &str) -> RitmoResult<Self> {
+        let text = text.trim();
+        if let Some((scheme, value)) = text.split_once(':') {
+            let scheme = IdentifierScheme::parse(scheme);
+            return match scheme {
+                // "isbn:..." senza specificare la lunghezza
+                IdentifierScheme::Other(ref s) if s == "isbn" => Self::parse(value),
+                // "urn:isbn:..."
+                IdentifierScheme::Other(ref s) if s == "urn" => Self::parse(value),
+                _ => Self::new(scheme, value),
+            };
+        }
+        match normalize_isbn(text).len() {
+            13 => Self::new(IdentifierScheme::Isbn13, text),
+            10 => Self::new(IdentifierScheme::Isbn10, text),
+            9 => Self::new(IdentifierScheme::Sbn, text),
+            _ => Err(RitmoErr::InvalidInput(format!(
+                "identificatore non riconosciuto: {}",
+                text
+            ))),
+        }
+    }
+
+    /// Tutte le forme equivalenti dell'identificatore, da usare nelle ricerche:
+    /// un ISBN-10 corrisponde anche al suo ISBN-13 e all'eventuale SBN, e viceversa.
+    pub fn equivalents(&self) -> Vec<Identifier> {
+        let mut forms = vec![self.clone()];
+        let isbn10 = match self.scheme {
+            IdentifierScheme::Isbn10 => Some(self.value.clone()),
+            IdentifierScheme::Isbn13 => isbn13_to_10(&self.value),
+            IdentifierScheme::Sbn => Some(format!("0{}", self.value)),
+            _ => None,
+        };
+        if let Some(isbn10) = isbn10 {
+            let mut push = |scheme, value: String| {
+                let id = Identifier { scheme, value };
+                if !forms.contains(&id) {
+                    forms.push(id);
+                }
+            };
+            if let Some(isbn13) = isbn10_to_13(&isbn10) {
+                push(IdentifierScheme::Isbn13, isbn13);
+            }
+            if let Some(sbn) = isbn10.strip_prefix('0') {
+                push(IdentifierScheme::Sbn, sbn.to_string());
+            }
+            push(IdentifierScheme::Isbn10, isbn10);
+        }
+        forms
+    }
+}
+
+impl fmt::Display for Identifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.scheme, self.value)
+    }
+}
+
+fn check(valid: bool, kind: &str, raw: &str) -> RitmoResult<()> {
+    if valid {
+        Ok(())
+    } else {
+        Err(RitmoErr::InvalidInput(format!("{} non valido: {}", kind, raw)))
+    }
+}
+
+/// Rimuove trattini e spazi e porta in maiuscolo la cifra di controllo 'X'
+pub fn normalize_isbn(raw: &str) -> String {
+    raw.chars()
+        .filter(|c| !matches!(c, '-' | ' ' | '\u{2010}' | '\u{2011}' | '\u{2013}'))
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+pub fn is_valid_isbn10(isbn: &str) -> bool {
+    let chars: Vec<char> = isbn.chars().collect();
+    if chars.len() != 10 {
+        return false;
+    }
+    let mut sum = 0;
+    for (i, c) in chars.iter().enumerate() {
+        let digit = match c {
+            'X' if i == 9 => 10,
+            c => match c.to_digit(10) {
+                Some(d) => d,
+                None => return false,
+            },
+        };
+        sum += digit * (10 - i as u32);
+    }
+    sum % 11 == 0
+}
+
+pub fn is_valid_isbn13(isbn: &str) -> bool {
+    if isbn.len() != 13 || !isbn.chars().all(|c| c.is_ascii_digit()) {
+        return false;
+    }
+    isbn13_check_digit(&isbn[..12]) == isbn.chars().last()
+}
+
+pub fn is_valid_issn(issn: &str) -> bool {
+    let chars: Vec<char> = issn.chars().collect();
+    if chars.len() != 8 {
+        return false;
+    }
+    let mut sum = 0;
+    for (i, c) in chars.iter().enumerate() {
+        let digit = match c {
+            'X' if i == 7 => 10,
+            c => match c.to_digit(10) {
+                Some(d) => d,
+                None => return false,
+            },
+        };
+        sum += digit * (8 - i as u32);
+    }
+    sum % 11 == 0
+}
+
+fn isbn13_check_digit(first12: &str) -> Option<char> {
+    let mut sum = 0;
+    for (i, c) in first12.chars().enumerate() {
+        let d = c.to_digit(10)?;
+        sum += if i % 2 == 0 { d } else { d * 3 };
+    }
+    char::from_digit((10 - sum % 10) % 10, 10)
+}
+
+fn isbn10_check_digit(first9: &str) -> Option<char> {
+    let mut sum = 0;
+    for (i, c) in first9.chars().enumerate() {
+        sum += c.to_digit(10)? * (10 - i as u32);
+    }
+    match (11 - sum % 11) % 11 {
+        10 => Some('X'),
+        d => char::from_digit(d, 10),
+    }
+}
+
+/// Converte un ISBN-10 valido nell'ISBN-13 corrispondente (prefisso 978)
+pub fn isbn10_to_13(isbn10: &str) -> Option<String> {
+    let isbn10 = normalize_isbn(isbn10);
+    if !is_valid_isbn10(&isbn10) {
+        return None;
+    }
+    let body = format!("978{}", &isbn10[..9]);
+    let check = isbn13_check_digit(&body)?;
+    Some(format!("{}{}", body, check))
+}
+
+/// Converte un ISBN-13 valido in ISBN-10; possibile solo per il prefisso 978
+pub fn isbn13_to_10(isbn13: &str) -> Option<String> {
+    let isbn13 = normalize_isbn(isbn13);
+    if !is_valid_isbn13(&isbn13) || !isbn13.starts_with("978") {
+        return None;
+    }
+    let body = &isbn13[3..12];
+    let check = isbn10_check_digit(body)?;
+    Some(format!("{}{}", body, check))
+}
+
+fn normalize_doi(raw: &str) -> String {
+    let v = raw.trim();
+    let lower = v.to_lowercase();
+    let stripped = ["https://doi.org/", "http://doi.org/", "https://dx.doi.org/", "http://dx.doi.org/", "doi:"]
+        .iter()
+        .find_map(|prefix| lower.strip_prefix(prefix))
+        .unwrap_or(&lower);
+    stripped.trim().to_string()
+}
+
+fn normalize_oclc(raw: &str) -> String {
+    let v = raw.trim().to_lowercase();
+    let v = ["(ocolc)", "ocm", "ocn", "on"]
+        .iter()
+        .find_map(|prefix| v.strip_prefix(prefix))
+        .unwrap_or(&v);
+    v.trim().trim_start_matches('0').to_string()
+}
+
+/// Normalizzazione LCCN secondo le regole della Library of Congress:
+/// niente spazi, e la parte dopo il trattino riempita a sinistra con zeri fino a 6 cifre.
+fn normalize_lccn(raw: &str) -> String {
+    let v: String = raw.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+    let v = v.split('/').next().unwrap_or("").to_string();
+    match v.split_once('-') {
+        Some((prefix, serial)) if serial.chars().all(|c| c.is_ascii_digit()) => {
+            format!("{}{:0>6}", prefix, serial)
+        }
+        _ => v,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_isbn_validation_and_normalization() {
+        assert!(is_valid_isbn13("9788845292866"));
+        assert!(!is_valid_isbn13("9788845292867"));
+        assert!(is_valid_isbn10("080442957X"));
+        assert!(!is_valid_isbn10("0804429570"));
+
+        let id = Identifier::parse("978-88-452-9286-6").unwrap();
+        assert_eq!(id.scheme, IdentifierScheme::Isbn13);
+        assert_eq!(id.value, "9788845292866");
+        assert!(Identifier::parse("978-88-452-9286-7").is_err());
+    }
+
+    #[test]
+    fn test_isbn_conversion() {
+        assert_eq!(isbn10_to_13("0-306-40615-2").as_deref(), Some("9780306406157"));
+        assert_eq!(isbn13_to_10("9780306406157").as_deref(), Some("0306406152"));
+        assert_eq!(isbn13_to_10("9791032305690"), None);
+
+        let forms = Identifier::parse("0306406152").unwrap().equivalents();
+        assert!(forms.contains(&Identifier { scheme: IdentifierScheme::Isbn13, value: "9780306406157".into() }));
+        assert!(forms.contains(&Identifier { scheme: IdentifierScheme::Sbn, value: "306406152".into() }));
+    }
+
+    #[test]
+    fn test_other_schemes() {
+        assert_eq!(Identifier::parse("issn:0378-5955").unwrap().value, "03785955");
+        assert!(Identifier::parse("issn:0378-5954").is_err());
+        assert_eq!(Identifier::parse("doi:https://doi.org/10.1000/XYZ").unwrap().value, "10.1000/xyz");
+        assert_eq!(Identifier::parse("oclc:ocm00012345").unwrap().value, "12345");
+        assert_eq!(Identifier::parse("lccn:85-2").unwrap().value, "85000002");
+        let custom = Identifier::parse("Goodreads:12345").unwrap();
+        assert_eq!(custom.scheme, IdentifierScheme::Other("goodreads".into()));
+    }
+}
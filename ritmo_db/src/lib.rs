@@ -1,5 +1,12 @@
 // ritmo_db/src/lib.rs
+pub mod bulk_import;
+pub mod duplicates;
+pub mod identifiers;
 pub mod models;
+mod ordering;
+pub mod partial_date;
+pub mod smart_filter;
+pub mod upgrade;
 
 // Re-export delle funzioni più comuni per comodità
 pub use models::*;
@@ -0,0 +1,234 @@
+use chrono::{TimeZone, Utc};
+use ritmo_core::dto::BookDto;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{FromRow, SqliteConnection};
+
+/// Provenienza di un acquisto (colonna `acquisitions.source`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcquisitionSource {
+    Shop,
+    Gift,
+    Download,
+    LibrarySale,
+    Other,
+}
+
+impl AcquisitionSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Shop => "shop",
+            Self::Gift => "gift",
+            Self::Download => "download",
+            Self::LibrarySale => "library_sale",
+            Self::Other => "other",
+        }
+    }
+
+    pub fn parse(source: &str) -> RitmoResult<Self> {
+        match source.trim().to_lowercase().replace([' ', '-'], "_").as_str() {
+            "shop" => Ok(Self::Shop),
+            "gift" => Ok(Self::Gift),
+            "download" => Ok(Self::Download),
+            "library_sale" => Ok(Self::LibrarySale),
+            "other" => Ok(Self::Other),
+            other => Err(RitmoErr::InvalidInput(format!("provenienza sconosciuta: {}", other))),
+        }
+    }
+}
+
+/// Acquisizione di un libro: quando, da dove e a che prezzo è entrato in libreria.
+/// Può riferirsi al libro in generale, a una copia cartacea (`copy_id`) o a un file (`file_id`).
+#[derive(Debug, Clone, FromRow, Default)]
+pub struct Acquisition {
+    pub id: Option<i64>,
+    pub book_id: i64,
+    pub copy_id: Option<i64>,
+    pub file_id: Option<i64>,
+    pub acquired_at: Option<i64>,
+    /// Vedi `AcquisitionSource`
+    pub source: String,
+    /// Negozio, sito o persona da cui proviene il libro
+    pub vendor: Option<String>,
+    pub price: Option<f64>,
+    /// Codice ISO 4217 (EUR, USD, ...)
+    pub currency: Option<String>,
+    /// Note libere sulla provenienza (dediche, ex libris, precedenti proprietari...)
+    pub provenance: Option<String>,
+    pub created_at: i64,
+}
+
+/// Totale speso per una provenienza in una valuta
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpendingBySource {
+    pub source: String,
+    pub currency: Option<String>,
+    pub total: f64,
+    pub count: i64,
+}
+
+impl Acquisition {
+    /// Dati di acquisizione portati dal DTO, se ce ne sono. `book_id` va impostato
+    /// dopo aver salvato il libro.
+    pub fn from_dto(dto: &BookDto) -> RitmoResult<Option<Self>> {
+        if dto.acquisition_date.is_none()
+            && dto.acquisition_source.is_none()
+            && dto.acquisition_vendor.is_none()
+            && dto.acquisition_price.is_none()
+            && dto.acquisition_currency.is_none()
+            && dto.provenance.is_none()
+        {
+            return Ok(None);
+        }
+        let source = match &dto.acquisition_source {
+            Some(source) => AcquisitionSource::parse(source)?,
+            None => AcquisitionSource::Other,
+        };
+        Ok(Some(Self {
+            acquired_at: dto.acquisition_date,
+            source: source.as_str().to_string(),
+            vendor: dto.acquisition_vendor.clone(),
+            price: dto.acquisition_price,
+            currency: dto.acquisition_currency.clone(),
+            provenance: dto.provenance.clone(),
+            ..Default::default()
+        }))
+    }
+
+    /// Registra l'acquisizione descritta dal DTO per il libro appena salvato, se il DTO ne porta una
+    pub(crate) async fn insert_from_dto(
+        conn: &mut SqliteConnection,
+        book_id: i64,
+        dto: &BookDto,
+    ) -> RitmoResult<Option<i64>> {
+        let Some(acquisition) = Self::from_dto(dto)? else { return Ok(None) };
+        let acquisition = Self { book_id, ..acquisition };
+        Ok(Some(acquisition.insert(conn).await?))
+    }
+
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> RitmoResult<i64> {
+        let mut conn = pool.acquire().await?;
+        self.insert(&mut conn).await
+    }
+
+    /// Come `save`, ma sulla connessione (o transazione) del chiamante
+    pub(crate) async fn insert(&self, conn: &mut SqliteConnection) -> RitmoResult<i64> {
+        AcquisitionSource::parse(&self.source)?;
+        let now = Utc::now().timestamp();
+        let result = sqlx::query!(
+            "INSERT INTO acquisitions (
+                book_id, copy_id, file_id, acquired_at, source, vendor, price, currency, provenance, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            self.book_id,
+            self.copy_id,
+            self.file_id,
+            self.acquired_at,
+            self.source,
+            self.vendor,
+            self.price,
+            self.currency,
+            self.provenance,
+            now
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<Acquisition>, sqlx::Error> {
+        let acquisition = sqlx::query_as!(Acquisition, "SELECT * FROM acquisitions WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(acquisition)
+    }
+
+    pub async fn update(&self, pool: &sqlx::SqlitePool) -> RitmoResult<u64> {
+        AcquisitionSource::parse(&self.source)?;
+        let result = sqlx::query!(
+            "UPDATE acquisitions SET
+                copy_id = ?, file_id = ?, acquired_at = ?, source = ?, vendor = ?, price = ?,
+                currency = ?, provenance = ?
+             WHERE id = ?",
+            self.copy_id,
+            self.file_id,
+            self.acquired_at,
+            self.source,
+            self.vendor,
+            self.price,
+            self.currency,
+            self.provenance,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM acquisitions WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_by_book(pool: &sqlx::SqlitePool, book_id: i64) -> Result<Vec<Acquisition>, sqlx::Error> {
+        let acquisitions = sqlx::query_as!(
+            Acquisition,
+            "SELECT * FROM acquisitions WHERE book_id = ? ORDER BY acquired_at, id",
+            book_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(acquisitions)
+    }
+
+    /// Acquisizioni con data nell'intervallo `[from, to)` (timestamp Unix)
+    pub async fn list_between(pool: &sqlx::SqlitePool, from: i64, to: i64) -> Result<Vec<Acquisition>, sqlx::Error> {
+        let acquisitions = sqlx::query_as!(
+            Acquisition,
+            "SELECT a.* FROM acquisitions a
+             JOIN books b ON b.id = a.book_id
+             WHERE a.acquired_at >= ? AND a.acquired_at < ? AND b.deleted_at IS NULL
+             ORDER BY a.acquired_at, a.id",
+            from,
+            to
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(acquisitions)
+    }
+
+    /// Acquisizioni dell'anno solare indicato (UTC)
+    pub async fn list_by_year(pool: &sqlx::SqlitePool, year: i32) -> RitmoResult<Vec<Acquisition>> {
+        let bound = |y: i32| {
+            Utc.with_ymd_and_hms(y, 1, 1, 0, 0, 0)
+                .single()
+                .map(|d| d.timestamp())
+                .ok_or_else(|| RitmoErr::InvalidInput(format!("anno non valido: {}", y)))
+        };
+        Ok(Self::list_between(pool, bound(year)?, bound(year + 1)?).await?)
+    }
+
+    /// Totale speso per provenienza, separato per valuta. Le acquisizioni senza prezzo
+    /// non vengono conteggiate.
+    pub async fn total_spent_by_source(pool: &sqlx::SqlitePool) -> Result<Vec<SpendingBySource>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT a.source, a.currency, SUM(a.price) AS \"total!: f64\", COUNT(*) AS \"count!: i64\"
+             FROM acquisitions a
+             JOIN books b ON b.id = a.book_id
+             WHERE a.price IS NOT NULL AND b.deleted_at IS NULL
+             GROUP BY a.source, a.currency
+             ORDER BY a.source, a.currency"
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| SpendingBySource {
+                source: r.source,
+                currency: r.currency,
+                total: r.total,
+                count: r.count,
+            })
+            .collect())
+    }
+}
@@ -1,5 +1,9 @@
 use ritmo_core::dto::AliasDto;
+use ritmo_db_core::normalize::normalize_key;
 use sqlx::FromRow;
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 
 #[derive(Debug, Clone, FromRow, Default)]
 pub struct Alias {
@@ -17,13 +21,15 @@ impl Alias {
         Alias::default()
     }
 
+    /// Memorizza l'alias; se manca, la chiave normalizzata viene calcolata dal nome
     pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
         let now = chrono::Utc::now().timestamp();
+        let alias_normalized = self.alias_normalized.clone().unwrap_or_else(|| normalize_key(&self.name));
         let result = sqlx::query!(
             "INSERT INTO aliases (name, person_id, alias_normalized, created_at) VALUES (?, ?, ?, ?)",
             self.name,
             self.person_id, // Assumendo che person_id sia già Option<i64>
-            self.alias_normalized,
+            alias_normalized,
             now
         )
         .execute(pool)
@@ -108,4 +114,21 @@ impl Alias {
         .await?;
         Ok(aliases)
     }
+
+    /// Come `search`, ma in streaming
+    pub fn stream_search<'a>(pool: &'a sqlx::SqlitePool, pattern: &str) -> BoxStream<'a, Result<Alias, sqlx::Error>> {
+        let search_pattern = format!("%{}%", pattern);
+        Box::pin(try_stream! {
+            let mut rows = sqlx::query_as!(
+                Alias,
+                "SELECT * FROM aliases WHERE name LIKE ? OR alias_normalized LIKE ? ORDER BY name",
+                search_pattern,
+                search_pattern
+            )
+            .fetch(pool);
+            while let Some(alias) = rows.try_next().await? {
+                yield alias;
+            }
+        })
+    }
 }
@@ -0,0 +1,185 @@
+use crate::models::CopyEvent;
+use sqlx::{FromRow, SqliteConnection};
+
+/// Copia cartacea di un libro. Un libro può averne più di una (edizioni diverse, doppioni).
+///
+/// `books.has_paper` resta allineato: vale 1 finché il libro ha almeno una copia.
+/// I dati di acquisto stanno in `acquisitions`, collegati tramite `copy_id`.
+#[derive(Debug, Clone, FromRow, Default)]
+pub struct BookCopy {
+    pub id: Option<i64>,
+    pub book_id: i64,
+    pub location_id: Option<i64>,
+    /// 'new', 'fine', 'very_good', 'good', 'fair' o 'poor'
+    pub condition: Option<String>,
+    /// Rilegatura (es. "rigida", "brossura", "tascabile")
+    pub binding: Option<String>,
+    pub edition: Option<String>,
+    pub printing: Option<String>,
+    pub notes: Option<String>,
+    pub created_at: i64,
+}
+
+impl BookCopy {
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let mut tx = pool.begin().await?;
+        let id = sqlx::query!(
+            "INSERT INTO book_copies (
+                book_id, location_id, condition, binding, edition, printing, notes, created_at
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            self.book_id,
+            self.location_id,
+            self.condition,
+            self.binding,
+            self.edition,
+            self.printing,
+            self.notes,
+            now
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        CopyEvent::record(&mut tx, id, "added", self.location_id, None, now).await?;
+        Self::sync_has_paper(&mut tx, self.book_id).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<BookCopy>, sqlx::Error> {
+        let copy = sqlx::query_as!(BookCopy, "SELECT * FROM book_copies WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(copy)
+    }
+
+    /// Aggiorna i dati descrittivi della copia. Per cambiare collocazione usare `move_to`,
+    /// così lo spostamento finisce nella storia della copia.
+    pub async fn update(&self, pool: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let mut tx = pool.begin().await?;
+        let previous = sqlx::query_scalar!("SELECT condition FROM book_copies WHERE id = ?", self.id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let result = sqlx::query!(
+            "UPDATE book_copies SET
+                condition = ?, binding = ?, edition = ?, printing = ?, notes = ?
+             WHERE id = ?",
+            self.condition,
+            self.binding,
+            self.edition,
+            self.printing,
+            self.notes,
+            self.id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if let (Some(id), Some(previous)) = (self.id, previous) {
+            if previous != self.condition {
+                let details = self.condition.as_deref();
+                CopyEvent::record(&mut tx, id, "condition", None, details, now).await?;
+            }
+        }
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Sposta la copia in un altro luogo (None = collocazione sconosciuta)
+    pub async fn move_to(pool: &sqlx::SqlitePool, id: i64, location_id: Option<i64>) -> Result<u64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let mut tx = pool.begin().await?;
+        let result = sqlx::query!(
+            "UPDATE book_copies SET location_id = ? WHERE id = ?",
+            location_id,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        if result.rows_affected() > 0 {
+            CopyEvent::record(&mut tx, id, "moved", location_id, None, now).await?;
+        }
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Elimina la copia (venduta, regalata, persa), insieme ai suoi prestiti e alla sua storia
+    pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let Some(book_id) = sqlx::query_scalar!("SELECT book_id FROM book_copies WHERE id = ?", id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(0);
+        };
+        let result = sqlx::query!("DELETE FROM book_copies WHERE id = ?", id)
+            .execute(&mut *tx)
+            .await?;
+        Self::sync_has_paper(&mut tx, book_id).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn sync_has_paper(conn: &mut SqliteConnection, book_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE books SET has_paper = EXISTS (SELECT 1 FROM book_copies WHERE book_id = ?1) WHERE id = ?1",
+            book_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list_by_book(pool: &sqlx::SqlitePool, book_id: i64) -> Result<Vec<BookCopy>, sqlx::Error> {
+        let copies = sqlx::query_as!(
+            BookCopy,
+            "SELECT * FROM book_copies WHERE book_id = ? ORDER BY created_at, id",
+            book_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(copies)
+    }
+
+    /// Copie che si trovano nel luogo indicato o in uno dei luoghi in esso contenuti
+    pub async fn list_by_location(pool: &sqlx::SqlitePool, location_id: i64) -> Result<Vec<BookCopy>, sqlx::Error> {
+        let copies = sqlx::query_as!(
+            BookCopy,
+            "WITH RECURSIVE tree(id, depth) AS (
+                SELECT id, 0 FROM locations WHERE id = ?
+                UNION ALL
+                SELECT l.id, t.depth + 1 FROM locations l JOIN tree t ON l.parent_id = t.id
+                WHERE t.depth < 32
+             )
+             SELECT c.* FROM book_copies c
+             WHERE c.location_id IN (SELECT id FROM tree)
+             ORDER BY c.location_id, c.id",
+            location_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(copies)
+    }
+
+    /// Crea una copia senza dettagli per i libri segnati con `has_paper = 1` che non ne hanno
+    /// ancora nessuna. È idempotente.
+    pub async fn migrate_from_has_paper(pool: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let result = sqlx::query!(
+            "INSERT INTO book_copies (book_id, created_at)
+             SELECT b.id, b.created_at FROM books b
+             WHERE b.has_paper = 1
+               AND NOT EXISTS (SELECT 1 FROM book_copies c WHERE c.book_id = b.id)"
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO copy_events (copy_id, event, happened_at)
+             SELECT c.id, 'added', c.created_at FROM book_copies c
+             WHERE NOT EXISTS (SELECT 1 FROM copy_events e WHERE e.copy_id = c.id)"
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+}
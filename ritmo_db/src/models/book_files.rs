@@ -0,0 +1,280 @@
+use sqlx::{FromRow, SqliteConnection};
+
+/// File di un libro in un certo formato (un libro può averne più di uno, es. EPUB e PDF).
+///
+/// Le colonne `file_link`, `format_id`, `file_size` e `file_hash` di `books` restano allineate
+/// al file preferito, così le query e le viste esistenti continuano a funzionare.
+#[derive(Debug, Clone, FromRow, Default)]
+pub struct BookFile {
+    pub id: Option<i64>,
+    pub book_id: i64,
+    pub format_id: Option<i64>,
+    /// Percorso relativo alla directory di storage della libreria
+    pub file_link: String,
+    pub file_size: Option<i64>,
+    pub file_hash: Option<String>,
+    pub is_preferred: i64,
+    pub added_at: i64,
+}
+
+impl BookFile {
+    /// Aggiunge il file al libro. Il primo file di un libro diventa automaticamente il preferito.
+    pub async fn add(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let id = self.insert(&mut tx).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Inserimento all'interno di una transazione già aperta
+    pub(crate) async fn insert(&self, conn: &mut SqliteConnection) -> Result<i64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let id = sqlx::query!(
+            "INSERT INTO book_files (book_id, format_id, file_link, file_size, file_hash, is_preferred, added_at)
+             VALUES (?, ?, ?, ?, ?, 0, ?)",
+            self.book_id,
+            self.format_id,
+            self.file_link,
+            self.file_size,
+            self.file_hash,
+            now
+        )
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+
+        let has_preferred = sqlx::query_scalar!(
+            "SELECT id FROM book_files WHERE book_id = ? AND is_preferred = 1",
+            self.book_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        .is_some();
+        if self.is_preferred == 1 || !has_preferred {
+            Self::mark_preferred(conn, self.book_id, id).await?;
+        }
+        Ok(id)
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<BookFile>, sqlx::Error> {
+        let file = sqlx::query_as!(
+            BookFile,
+            "SELECT * FROM book_files WHERE id = ?",
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(file)
+    }
+
+    /// Rimuove il file dal libro e restituisce il record eliminato, così il chiamante
+    /// può cancellare il file fisico. Se era il preferito, ne viene scelto un altro.
+    pub async fn remove(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<BookFile>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let Some(file) = sqlx::query_as!(BookFile, "SELECT * FROM book_files WHERE id = ?", id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(None);
+        };
+        sqlx::query!("DELETE FROM book_files WHERE id = ?", id)
+            .execute(&mut *tx)
+            .await?;
+        if file.is_preferred == 1 {
+            let next = sqlx::query_scalar!(
+                "SELECT id AS \"id!\" FROM book_files WHERE book_id = ? ORDER BY added_at, id LIMIT 1",
+                file.book_id
+            )
+            .fetch_optional(&mut *tx)
+            .await?;
+            match next {
+                Some(next_id) => {
+                    Self::mark_preferred(&mut tx, file.book_id, next_id).await?;
+                }
+                None => Self::sync_book_columns(&mut tx, file.book_id).await?,
+            }
+        }
+        tx.commit().await?;
+        Ok(Some(file))
+    }
+
+    /// Sostituisce i file del libro nello stesso formato con questo, restituendo quelli rimossi.
+    /// Se uno dei file sostituiti era il preferito, il nuovo ne prende il posto.
+    pub async fn replace_format(&self, pool: &sqlx::SqlitePool) -> Result<Vec<BookFile>, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let replaced = sqlx::query_as!(
+            BookFile,
+            "SELECT * FROM book_files WHERE book_id = ? AND format_id IS ?",
+            self.book_id,
+            self.format_id
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM book_files WHERE book_id = ? AND format_id IS ?",
+            self.book_id,
+            self.format_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        let was_preferred = replaced.iter().any(|f| f.is_preferred == 1);
+        let new_file = BookFile {
+            is_preferred: if was_preferred { 1 } else { self.is_preferred },
+            ..self.clone()
+        };
+        new_file.insert(&mut tx).await?;
+        tx.commit().await?;
+        Ok(replaced)
+    }
+
+    /// Imposta il file preferito del libro
+    pub async fn set_preferred(pool: &sqlx::SqlitePool, book_id: i64, file_id: i64) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let updated = Self::mark_preferred(&mut tx, book_id, file_id).await?;
+        tx.commit().await?;
+        Ok(updated)
+    }
+
+    async fn mark_preferred(conn: &mut SqliteConnection, book_id: i64, file_id: i64) -> Result<u64, sqlx::Error> {
+        sqlx::query!(
+            "UPDATE book_files SET is_preferred = 0 WHERE book_id = ? AND is_preferred = 1 AND id <> ?",
+            book_id,
+            file_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        let updated = sqlx::query!(
+            "UPDATE book_files SET is_preferred = 1 WHERE id = ? AND book_id = ?",
+            file_id,
+            book_id
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+        Self::sync_book_columns(conn, book_id).await?;
+        Ok(updated)
+    }
+
+    /// Riporta in `books` i dati del file preferito (o li azzera se il libro non ha più file)
+    async fn sync_book_columns(conn: &mut SqliteConnection, book_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE books SET
+                file_link = (SELECT file_link FROM book_files WHERE book_id = ?1 AND is_preferred = 1),
+                file_size = (SELECT file_size FROM book_files WHERE book_id = ?1 AND is_preferred = 1),
+                file_hash = (SELECT file_hash FROM book_files WHERE book_id = ?1 AND is_preferred = 1),
+                format_id = COALESCE(
+                    (SELECT format_id FROM book_files WHERE book_id = ?1 AND is_preferred = 1),
+                    format_id
+                )
+             WHERE id = ?1",
+            book_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    /// File preferito del libro
+    pub async fn preferred(pool: &sqlx::SqlitePool, book_id: i64) -> Result<Option<BookFile>, sqlx::Error> {
+        let file = sqlx::query_as!(
+            BookFile,
+            "SELECT * FROM book_files WHERE book_id = ? ORDER BY is_preferred DESC, added_at, id LIMIT 1",
+            book_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(file)
+    }
+
+    /// Sceglie il file seguendo un ordine di formati (es. `["EPUB", "AZW3", "PDF"]`);
+    /// se nessun formato corrisponde restituisce il preferito.
+    pub async fn pick_format(
+        pool: &sqlx::SqlitePool,
+        book_id: i64,
+        formats: &[&str],
+    ) -> Result<Option<BookFile>, sqlx::Error> {
+        let files = Self::list_with_format_names(pool, book_id).await?;
+        for wanted in formats {
+            if let Some((file, _)) = files
+                .iter()
+                .find(|(_, name)| name.as_deref().is_some_and(|n| n.eq_ignore_ascii_case(wanted)))
+            {
+                return Ok(Some(file.clone()));
+            }
+        }
+        Self::preferred(pool, book_id).await
+    }
+
+    async fn list_with_format_names(
+        pool: &sqlx::SqlitePool,
+        book_id: i64,
+    ) -> Result<Vec<(BookFile, Option<String>)>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT bf.id, bf.book_id, bf.format_id, bf.file_link, bf.file_size, bf.file_hash,
+                    bf.is_preferred, bf.added_at, f.name AS \"format_name?\"
+             FROM book_files bf
+             LEFT JOIN formats f ON bf.format_id = f.id
+             WHERE bf.book_id = ?
+             ORDER BY bf.is_preferred DESC, bf.added_at, bf.id",
+            book_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                let file = BookFile {
+                    id: r.id,
+                    book_id: r.book_id,
+                    format_id: r.format_id,
+                    file_link: r.file_link,
+                    file_size: r.file_size,
+                    file_hash: r.file_hash,
+                    is_preferred: r.is_preferred,
+                    added_at: r.added_at,
+                };
+                (file, r.format_name)
+            })
+            .collect())
+    }
+
+    pub async fn list_by_book(pool: &sqlx::SqlitePool, book_id: i64) -> Result<Vec<BookFile>, sqlx::Error> {
+        let files = sqlx::query_as!(
+            BookFile,
+            "SELECT * FROM book_files WHERE book_id = ? ORDER BY is_preferred DESC, added_at, id",
+            book_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(files)
+    }
+
+    pub async fn list_by_hash(pool: &sqlx::SqlitePool, file_hash: &str) -> Result<Vec<BookFile>, sqlx::Error> {
+        let files = sqlx::query_as!(
+            BookFile,
+            "SELECT * FROM book_files WHERE file_hash = ? ORDER BY added_at, id",
+            file_hash
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(files)
+    }
+
+    /// Copia in `book_files` i libri che hanno ancora solo il file in `books.file_link`.
+    /// È idempotente: i file già presenti non vengono duplicati.
+    pub async fn migrate_from_books(pool: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO book_files (book_id, format_id, file_link, file_size, file_hash, is_preferred, added_at)
+             SELECT b.id, b.format_id, b.file_link, b.file_size, b.file_hash,
+                    NOT EXISTS (SELECT 1 FROM book_files p WHERE p.book_id = b.id AND p.is_preferred = 1),
+                    b.created_at
+             FROM books b
+             WHERE b.file_link IS NOT NULL
+               AND NOT EXISTS (SELECT 1 FROM book_files f WHERE f.file_link = b.file_link)"
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,155 @@
+use crate::identifiers::Identifier;
+use ritmo_core::dto::BookDto;
+use sqlx::{FromRow, SqliteConnection};
+
+/// Identificatore esterno di un libro (ISBN, ASIN, DOI, ...), memorizzato già normalizzato.
+///
+/// La colonna `books.isbn` resta come testo libero per compatibilità; le ricerche esatte
+/// passano da questa tabella.
+#[derive(Debug, Clone, FromRow, PartialEq, Eq)]
+pub struct BookIdentifier {
+    pub id: Option<i64>,
+    pub book_id: i64,
+    pub scheme: String,
+    pub value: String,
+    pub created_at: i64,
+}
+
+impl BookIdentifier {
+    /// Associa l'identificatore al libro; se è già presente restituisce l'id esistente
+    pub async fn add(pool: &sqlx::SqlitePool, book_id: i64, identifier: &Identifier) -> Result<i64, sqlx::Error> {
+        let mut conn = pool.acquire().await?;
+        Self::insert(&mut conn, book_id, identifier).await
+    }
+
+    pub(crate) async fn insert(
+        conn: &mut SqliteConnection,
+        book_id: i64,
+        identifier: &Identifier,
+    ) -> Result<i64, sqlx::Error> {
+        let scheme = identifier.scheme.as_str();
+        sqlx::query!(
+            "INSERT OR IGNORE INTO book_identifiers (book_id, scheme, value) VALUES (?, ?, ?)",
+            book_id,
+            scheme,
+            identifier.value
+        )
+        .execute(&mut *conn)
+        .await?;
+        let id = sqlx::query_scalar!(
+            "SELECT id AS \"id!\" FROM book_identifiers WHERE book_id = ? AND scheme = ? AND value = ?",
+            book_id,
+            scheme,
+            identifier.value
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        Ok(id)
+    }
+
+    /// Associa al libro gli identificatori del DTO oltre all'ISBN, che salva `Book::insert`.
+    /// Quelli non validi vengono ignorati.
+    pub(crate) async fn insert_from_dto(
+        conn: &mut SqliteConnection,
+        book_id: i64,
+        dto: &BookDto,
+    ) -> Result<(), sqlx::Error> {
+        for identifier in dto.identifiers.iter().filter_map(|text| Identifier::parse(text).ok()) {
+            Self::insert(&mut *conn, book_id, &identifier).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM book_identifiers WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_by_book(pool: &sqlx::SqlitePool, book_id: i64) -> Result<Vec<BookIdentifier>, sqlx::Error> {
+        let identifiers = sqlx::query_as!(
+            BookIdentifier,
+            "SELECT * FROM book_identifiers WHERE book_id = ? ORDER BY scheme, value",
+            book_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(identifiers)
+    }
+
+    /// Libri che hanno l'identificatore indicato o una sua forma equivalente
+    /// (un ISBN-10 trova anche i libri registrati con l'ISBN-13 corrispondente).
+    pub async fn find_book_ids(pool: &sqlx::SqlitePool, identifier: &Identifier) -> Result<Vec<i64>, sqlx::Error> {
+        Self::lookup(pool, identifier, false).await
+    }
+
+    /// Come `find_book_ids`, ma anche tra i libri nel cestino, messi dopo gli altri
+    pub(crate) async fn find_book_ids_with_trash(
+        pool: &sqlx::SqlitePool,
+        identifier: &Identifier,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        Self::lookup(pool, identifier, true).await
+    }
+
+    async fn lookup(
+        pool: &sqlx::SqlitePool,
+        identifier: &Identifier,
+        with_trash: bool,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        let mut ids = Vec::new();
+        for form in identifier.equivalents() {
+            let scheme = form.scheme.as_str();
+            let found = sqlx::query_scalar!(
+                "SELECT bi.book_id FROM book_identifiers bi
+                 JOIN books b ON b.id = bi.book_id
+                 WHERE bi.scheme = ? AND bi.value = ? AND (? OR b.deleted_at IS NULL)
+                 ORDER BY b.deleted_at IS NOT NULL, bi.book_id",
+                scheme,
+                form.value,
+                with_trash
+            )
+            .fetch_all(pool)
+            .await?;
+            for id in found {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// Registra come identificatori gli ISBN già presenti in `books.isbn`.
+    /// Restituisce il numero di identificatori inseriti davvero e gli id dei libri con un ISBN
+    /// non valido. È idempotente: una seconda esecuzione non inserisce niente e riporta zero.
+    pub async fn migrate_from_books(pool: &sqlx::SqlitePool) -> Result<(u64, Vec<i64>), sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT id AS \"id!\", isbn AS \"isbn!\" FROM books WHERE isbn IS NOT NULL AND trim(isbn) <> ''"
+        )
+        .fetch_all(pool)
+        .await?;
+        let mut tx = pool.begin().await?;
+        let mut migrated = 0;
+        let mut invalid = Vec::new();
+        for row in rows {
+            match Identifier::parse(&row.isbn) {
+                Ok(identifier) => {
+                    let scheme = identifier.scheme.as_str();
+                    migrated += sqlx::query!(
+                        "INSERT OR IGNORE INTO book_identifiers (book_id, scheme, value) VALUES (?, ?, ?)",
+                        row.id,
+                        scheme,
+                        identifier.value
+                    )
+                    .execute(&mut *tx)
+                    .await?
+                    .rows_affected();
+                }
+                Err(_) => invalid.push(row.id),
+            }
+        }
+        tx.commit().await?;
+        Ok((migrated, invalid))
+    }
+}
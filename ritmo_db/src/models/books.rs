@@ -1,7 +1,16 @@
-use sha2::Digest;
 use chrono::Utc;
+use crate::identifiers::Identifier;
+use crate::models::reading_sessions::check_rating;
+use crate::models::{Acquisition, BookFile, BookIdentifier, ReadingStatus};
+use crate::partial_date::PartialDate;
 use ritmo_core::dto::BookDto;
-use sqlx::FromRow;
+use ritmo_db_core::sorting::sort_title;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{FromRow, SqliteConnection};
+use std::path::{Component, Path};
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 
 #[derive(Debug, Clone, FromRow, Default)]
 pub struct Book {
@@ -12,7 +21,9 @@ pub struct Book {
     pub publisher_id: Option<i64>,
     pub format_id: Option<i64>,
     pub series_id: Option<i64>,
-    pub series_index: Option<i64>,
+    /// Posizione nella serie: può essere frazionaria (2.5) o zero per un prequel
+    pub series_index: Option<f64>,
+    /// Data di pubblicazione codificata con `PartialDate::to_key`
     pub publication_date: Option<i64>,
     pub last_modified_date: i64,
     pub isbn: Option<String>,
@@ -24,41 +35,98 @@ pub struct Book {
     pub file_size: Option<i64>,
     pub file_hash: Option<String>,
     pub created_at: i64,
+    /// Data di spostamento nel cestino, None se il libro non è cestinato
+    pub deleted_at: Option<i64>,
+    /// Stato di lettura (vedi `ReadingStatus`), aggiornato dalle sessioni di lettura
+    pub read_status: String,
+    /// Valutazione da 0 a 5
+    pub rating: Option<f64>,
+    /// Chiave di ordinamento del titolo, senza articolo iniziale (vedi `ritmo_db_core::sorting`)
+    pub sort_title: Option<String>,
+    /// Chiavi "cognome, nome" degli autori separate da " & ", aggiornate dai trigger
+    pub sort_author: Option<String>,
+}
+
+/// Esito di `Book::purge_trash`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PurgeReport {
+    /// Libri eliminati dal database
+    pub books: u64,
+    /// File rimasti in archivio, con il motivo
+    pub errors: Vec<(String, String)>,
 }
 
 impl Book {
-    // Metodo per la conversione da DTO al modello
+    pub fn publication(&self) -> Option<PartialDate> {
+        self.publication_date.and_then(PartialDate::from_key)
+    }
+
+    pub fn set_publication(&mut self, date: Option<PartialDate>) {
+        self.publication_date = date.map(|d| d.to_key());
+    }
+
+    // Metodo per la conversione da DTO al modello.
+    // I dati di acquisizione e gli identificatori diversi dall'ISBN non fanno parte del modello:
+    // li salva `save_dto`.
+    // Link, dimensione e hash del file arrivano dal DTO, compilato da `storage_service::book_persistence`.
     pub fn from_dto(dto: &mut BookDto) -> Self {
         let now = Utc::now().timestamp();
 
-        let mut book = Self {
+        Self {
             name: dto.name.clone(),
             original_title: dto.original_title.clone(),
             publisher_id: dto.publisher_id,
             format_id: dto.format_id,
             series_id: dto.series_id,
             series_index: dto.series_index,
-            publication_date: dto.publication_date,
+            publication_date: match dto.publication_date_text.as_deref() {
+                Some(text) => PartialDate::parse_opf(text),
+                None => dto.publication_date.and_then(PartialDate::from_timestamp),
+            }
+            .map(|d| d.to_key()),
             last_modified_date: now,
             isbn: dto.isbn.clone(),
             notes: dto.notes.clone(),
             has_paper: if dto.has_paper { 1 } else { 0 },
             has_cover: if dto.has_cover { 1 } else { 0 },
+            file_link: dto.file_link.clone(),
+            file_size: dto.file_size,
+            file_hash: dto.file_hash.clone(),
             created_at: now,
             ..Default::default()
-        };
-        book.set_book_persistence();
-        book
+        }
     }
 
+    /// Memorizza il libro; se ha un file, lo registra anche in `book_files` come preferito
     pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let book_id = self.insert(&mut tx).await?;
+        tx.commit().await?;
+        Ok(book_id)
+    }
+
+    /// Salva il libro descritto dal DTO insieme ai dati di acquisizione e agli identificatori,
+    /// in un'unica transazione
+    pub async fn save_dto(pool: &sqlx::SqlitePool, dto: &mut BookDto) -> RitmoResult<i64> {
+        let mut tx = pool.begin().await?;
+        let book_id = Self::from_dto(dto).insert(&mut tx).await?;
+        Acquisition::insert_from_dto(&mut tx, book_id, dto).await?;
+        BookIdentifier::insert_from_dto(&mut tx, book_id, dto).await?;
+        tx.commit().await?;
+        Ok(book_id)
+    }
+
+    /// Come `save`, ma sulla connessione (o transazione) del chiamante
+    pub(crate) async fn insert(&self, conn: &mut SqliteConnection) -> Result<i64, sqlx::Error> {
         let now = chrono::Utc::now().timestamp();
+        // Un libro nuovo non ha ancora contenuti, quindi nemmeno una lingua
+        let sort_title = sort_title(&self.name, None);
         let result = sqlx::query!(
             "INSERT INTO books (
                 name, original_title, publisher_id, format_id, series_id, series_index,
                 publication_date, last_modified_date, isbn, notes,
-                has_cover, has_paper, file_link, file_size, file_hash, created_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                has_cover, has_paper, file_link, file_size, file_hash, created_at, sort_title
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             self.name,
             self.original_title,
             self.publisher_id,
@@ -74,17 +142,36 @@ impl Book {
             self.file_link,
             self.file_size,
             self.file_hash,
-            now
+            now,
+            sort_title
             )
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
-        Ok(result.last_insert_rowid())
+        let book_id = result.last_insert_rowid();
+
+        if let Some(file_link) = &self.file_link {
+            let file = BookFile {
+                book_id,
+                format_id: self.format_id,
+                file_link: file_link.clone(),
+                file_size: self.file_size,
+                file_hash: self.file_hash.clone(),
+                is_preferred: 1,
+                ..Default::default()
+            };
+            file.insert(&mut *conn).await?;
+        }
+        // Un ISBN non valido resta solo come testo libero in `books.isbn`
+        if let Some(identifier) = self.isbn.as_deref().and_then(|isbn| Identifier::parse(isbn).ok()) {
+            BookIdentifier::insert(&mut *conn, book_id, &identifier).await?;
+        }
+        Ok(book_id)
     }
 
     pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<Book>, sqlx::Error> {
         let book = sqlx::query_as!(
             Book,
-            "SELECT * FROM books WHERE id = ?",
+            "SELECT * FROM books WHERE id = ? AND deleted_at IS NULL",
             id
             )
             .fetch_optional(pool)
@@ -92,9 +179,39 @@ impl Book {
         Ok(book)
     }
 
+    /// Ricerca esatta per identificatore (`978-88-...`, `asin:B000FA5ZEG`, `doi:10.1000/182`, ...).
+    /// Trattini e spazi sono ignorati, e ISBN-10 e ISBN-13 equivalenti si trovano a vicenda.
+    pub async fn get_by_identifier(pool: &sqlx::SqlitePool, identifier: &str) -> RitmoResult<Option<Book>> {
+        let identifier = Identifier::parse(identifier)?;
+        let Some(book_id) = BookIdentifier::find_book_ids(pool, &identifier).await?.into_iter().next() else {
+            return Ok(None);
+        };
+        Ok(Self::get(pool, book_id).await?)
+    }
+
+    /// Cerca il libro a cui appartiene un file con l'hash indicato, in qualsiasi formato
+    pub async fn get_by_file_hash(pool: &sqlx::SqlitePool, file_hash: &str) -> Result<Option<Book>, sqlx::Error> {
+        let book = sqlx::query_as!(
+            Book,
+            "SELECT * FROM books
+             WHERE deleted_at IS NULL
+               AND id IN (SELECT book_id FROM book_files WHERE file_hash = ?)
+             ORDER BY id
+             LIMIT 1",
+            file_hash
+            )
+            .fetch_optional(pool)
+            .await?;
+        Ok(book)
+    }
+
+    /// Sposta il libro nel cestino. I collegamenti con persone, tag e contenuti restano intatti
+    /// finché il libro non viene eliminato definitivamente con `purge_trash`.
     pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
         let result = sqlx::query!(
-            "DELETE FROM books WHERE id = ?",
+            "UPDATE books SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+            now,
             id
             )
             .execute(pool)
@@ -102,21 +219,155 @@ impl Book {
         Ok(result.rows_affected())
     }
 
+    /// Ripristina un libro dal cestino, insieme a tutti i suoi collegamenti
+    pub async fn restore(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE books SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL",
+            id
+            )
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Imposta lo stato di lettura a mano, ad esempio per un libro letto prima di usare ritmo.
+    /// Lo stato viene ricalcolato alla prossima modifica delle sessioni di lettura.
+    pub async fn set_read_status(pool: &sqlx::SqlitePool, id: i64, status: ReadingStatus) -> Result<u64, sqlx::Error> {
+        let status = status.as_str();
+        let result = sqlx::query!("UPDATE books SET read_status = ? WHERE id = ?", status, id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Imposta (o toglie, con None) la valutazione del libro, da 0 a 5
+    pub async fn set_rating(pool: &sqlx::SqlitePool, id: i64, rating: Option<f64>) -> RitmoResult<u64> {
+        check_rating(rating)?;
+        let result = sqlx::query!("UPDATE books SET rating = ? WHERE id = ?", rating, id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_by_read_status(pool: &sqlx::SqlitePool, status: ReadingStatus) -> Result<Vec<Book>, sqlx::Error> {
+        let status = status.as_str();
+        let books = sqlx::query_as!(
+            Book,
+            "SELECT * FROM books WHERE read_status = ? AND deleted_at IS NULL ORDER BY sort_title, name",
+            status
+            )
+            .fetch_all(pool)
+            .await?;
+        Ok(books)
+    }
+
+    pub async fn list_trash(pool: &sqlx::SqlitePool) -> Result<Vec<Book>, sqlx::Error> {
+        let trashed = sqlx::query_as!(
+            Book,
+            "SELECT * FROM books WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+            )
+            .fetch_all(pool)
+            .await?;
+        Ok(trashed)
+    }
+
+    /// Elimina definitivamente i libri nel cestino da più di `retention_secs` secondi,
+    /// rimuovendo anche i file associati in `storage/books` e `storage/covers`.
+    /// Un file che non si riesce a rimuovere non ferma gli altri: finisce in `PurgeReport::errors`.
+    pub async fn purge_trash(
+        pool: &sqlx::SqlitePool,
+        storage_path: &Path,
+        retention_secs: i64,
+    ) -> RitmoResult<PurgeReport> {
+        let cutoff = chrono::Utc::now().timestamp() - retention_secs;
+        let mut tx = pool.begin().await?;
+        let expired = sqlx::query_scalar!(
+            "SELECT id FROM books WHERE deleted_at IS NOT NULL AND deleted_at <= ?",
+            cutoff
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+
+        let mut file_links = Vec::new();
+        for book_id in &expired {
+            file_links.extend(
+                sqlx::query_scalar!(
+                    "SELECT file_link FROM book_files WHERE book_id = ?1
+                     UNION
+                     SELECT file_link FROM books WHERE id = ?1 AND file_link IS NOT NULL",
+                    book_id
+                )
+                .fetch_all(&mut *tx)
+                .await?,
+            );
+            sqlx::query!("DELETE FROM books WHERE id = ?", book_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        // I file si rimuovono solo a transazione conclusa: le righe non ci sono più,
+        // quindi ogni file lasciato indietro resterebbe orfano e conviene tentarli tutti
+        let mut report = PurgeReport { books: expired.len() as u64, errors: Vec::new() };
+        for file_link in file_links.into_iter().flatten() {
+            if let Err(e) = Self::remove_stored_files(storage_path, &file_link) {
+                report.errors.push((file_link, e.to_string()));
+            }
+        }
+        Ok(report)
+    }
+
+    /// Rimuove il file del libro e le eventuali copertine, che stanno in `covers/`
+    /// nella stessa sottodirectory del file e con lo stesso nome (a meno dell'estensione).
+    fn remove_stored_files(storage_path: &Path, file_link: &str) -> RitmoResult<()> {
+        // Un link con `..` o assoluto porterebbe fuori dall'archivio
+        if !Path::new(file_link).components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(RitmoErr::PathError(format!("percorso fuori dall'archivio: {}", file_link)));
+        }
+        let book_file = storage_path.join(file_link);
+        match std::fs::remove_file(&book_file) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+
+        let relative = Path::new(file_link);
+        let relative = relative.strip_prefix("books").unwrap_or(relative);
+        let (Some(parent), Some(stem)) = (relative.parent(), relative.file_stem()) else {
+            return Ok(());
+        };
+        let covers_dir = storage_path.join("covers").join(parent);
+        let Ok(entries) = std::fs::read_dir(&covers_dir) else {
+            return Ok(());
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_file() && path.file_stem() == Some(stem) {
+                std::fs::remove_file(&path)?;
+            }
+        }
+        Ok(())
+    }
+
     pub async fn list_all(pool: &sqlx::SqlitePool) -> Result<Vec<Book>, sqlx::Error> {
         let all = sqlx::query_as!(
             Book,
-            "SELECT * FROM books ORDER BY name"
+            "SELECT * FROM books WHERE deleted_at IS NULL ORDER BY sort_title, name"
             )
             .fetch_all(pool)
             .await?;
         Ok(all)
     }
 
+    /// Come `list_all`, ma i libri arrivano uno alla volta senza caricare tutta la tabella in memoria
+    pub fn stream_all(pool: &sqlx::SqlitePool) -> BoxStream<'_, Result<Book, sqlx::Error>> {
+        sqlx::query_as!(Book, "SELECT * FROM books WHERE deleted_at IS NULL ORDER BY sort_title, name").fetch(pool)
+    }
+
     pub async fn search(pool: &sqlx::SqlitePool, pattern: &str) -> Result<Vec<Book>, sqlx::Error> {
         let search_pattern = format!("%{}%", pattern);
         let found = sqlx::query_as!(
             Book,
-            "SELECT * FROM books WHERE name LIKE ? OR original_title LIKE ? OR notes LIKE ? OR isbn LIKE ? ORDER BY name",
+            "SELECT * FROM books WHERE deleted_at IS NULL AND (name LIKE ? OR original_title LIKE ? OR notes LIKE ? OR isbn LIKE ?) ORDER BY sort_title, name",
             search_pattern,
             search_pattern,
             search_pattern,
@@ -127,54 +378,22 @@ impl Book {
         Ok(found)
     }
 
-    pub fn set_book_persistence(&mut self) {
-        // Generiamo un hash basato sui metadati del libro
-        let mut hasher = sha2::Sha256::new();
-        
-        // Aggiungiamo i metadati essenziali per generare un hash unico
-        hasher.update(self.name.as_bytes());
-        
-        if let Some(ref title) = self.original_title {
-            hasher.update(title.as_bytes());
-        }
-        
-        if let Some(ref isbn) = self.isbn {
-            hasher.update(isbn.as_bytes());
-        }
-        
-        // Aggiungiamo la data di creazione per ulteriore unicità
-        hasher.update(self.created_at.to_be_bytes());
-        
-        // Per libri di una serie, aggiungiamo anche queste informazioni
-        if let Some(series_id) = self.series_id {
-            hasher.update(series_id.to_be_bytes());
-            
-            if let Some(index) = self.series_index {
-                hasher.update(index.to_be_bytes());
+    /// Come `search`, con i risultati letti man mano dal cursore
+    pub fn stream_search<'a>(pool: &'a sqlx::SqlitePool, pattern: &str) -> BoxStream<'a, Result<Book, sqlx::Error>> {
+        let search_pattern = format!("%{}%", pattern);
+        Box::pin(try_stream! {
+            let mut rows = sqlx::query_as!(
+                Book,
+                "SELECT * FROM books WHERE deleted_at IS NULL AND (name LIKE ? OR original_title LIKE ? OR notes LIKE ? OR isbn LIKE ?) ORDER BY sort_title, name",
+                search_pattern,
+                search_pattern,
+                search_pattern,
+                search_pattern
+            )
+            .fetch(pool);
+            while let Some(book) = rows.try_next().await? {
+                yield book;
             }
-        }
-        
-        // Generiamo il hash completo
-        let hash_result = hasher.finalize();
-        
-        // Prendiamo i primi 16 byte dell'hash (128 bit) per un identificativo conciso ma unico
-        let hash_bytes = &hash_result[..16];
-        let hash_hex = hex::encode(hash_bytes);
-        
-        // Memorizziamo l'hash generato
-        self.file_hash = Some(hash_hex.clone());
-        
-        // Generiamo un percorso file standardizzato basato sull'hash
-        // Assumiamo come formato default "epub" per i nuovi libri
-        let file_path = format!("books/{}/{}/{}.epub", 
-                               &hash_hex[0..2],     // Primi 2 caratteri per la prima directory
-                               &hash_hex[2..4],     // Successivi 2 caratteri per la sottodirectory
-                               hash_hex);           // Nome file basato sull'hash completo
-        
-        // Impostiamo il link al file
-        self.file_link = Some(file_path);
-        
-        // Per ora il file size è 0 perché il file non è stato ancora effettivamente creato
-        self.file_size = Some(0);
+        })
     }
 }
@@ -0,0 +1,13 @@
+use sqlx::FromRow;
+
+/// Elemento di una collezione manuale: un libro oppure un contenuto, in una posizione
+/// scelta dall'utente
+#[derive(Debug, Clone, FromRow, PartialEq, Eq)]
+pub struct CollectionItem {
+    pub id: Option<i64>,
+    pub collection_id: i64,
+    pub book_id: Option<i64>,
+    pub content_id: Option<i64>,
+    pub position: i64,
+    pub added_at: i64,
+}
@@ -0,0 +1,299 @@
+use crate::models::{Book, CollectionItem, Content};
+use crate::ordering::{Member, COLLECTIONS, COLLECTION_ITEMS};
+use crate::smart_filter::{FilterTarget, SmartFilter};
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{FromRow, SqliteConnection};
+
+/// Collezione definita dall'utente, distinta dai tag.
+///
+/// Le collezioni manuali sono liste ordinate di libri e contenuti; quelle smart salvano in
+/// `rules` un `SmartFilter` in JSON che viene valutato a ogni lettura. Entrambe possono essere
+/// annidate tramite `parent_id`, e `position` ne stabilisce l'ordine tra sorelle.
+#[derive(Debug, Clone, FromRow, Default)]
+pub struct Collection {
+    pub id: Option<i64>,
+    pub name: String,
+    /// 'manual' o 'smart'
+    pub kind: String,
+    pub parent_id: Option<i64>,
+    pub position: i64,
+    pub description: Option<String>,
+    pub rules: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Collection {
+    pub fn manual(name: &str, parent_id: Option<i64>) -> Self {
+        Self {
+            name: name.to_string(),
+            kind: "manual".to_string(),
+            parent_id,
+            ..Default::default()
+        }
+    }
+
+    pub fn smart(name: &str, parent_id: Option<i64>, filter: &SmartFilter) -> RitmoResult<Self> {
+        Ok(Self {
+            name: name.to_string(),
+            kind: "smart".to_string(),
+            parent_id,
+            rules: Some(filter.to_json()?),
+            ..Default::default()
+        })
+    }
+
+    pub fn is_smart(&self) -> bool {
+        self.kind == "smart"
+    }
+
+    /// Filtro di una collezione smart
+    pub fn filter(&self) -> RitmoResult<Option<SmartFilter>> {
+        match (&self.rules, self.is_smart()) {
+            (Some(rules), true) => Ok(Some(SmartFilter::from_json(rules)?)),
+            _ => Ok(None),
+        }
+    }
+
+    fn validate(&self) -> RitmoResult<()> {
+        match self.kind.as_str() {
+            "manual" => Ok(()),
+            "smart" => match &self.rules {
+                Some(rules) => SmartFilter::from_json(rules).map(|_| ()),
+                None => Err(RitmoErr::InvalidInput("una collezione smart richiede delle regole".to_string())),
+            },
+            other => Err(RitmoErr::InvalidInput(format!("tipo di collezione sconosciuto: {}", other))),
+        }
+    }
+
+    /// Salva la collezione in fondo alle sorelle
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> RitmoResult<i64> {
+        self.validate()?;
+        let now = chrono::Utc::now().timestamp();
+        let result = sqlx::query!(
+            "INSERT INTO collections (name, kind, parent_id, position, description, rules, created_at, updated_at)
+             VALUES (?1, ?2, ?3,
+                     COALESCE((SELECT MAX(position) FROM collections WHERE parent_id IS ?3), 0) + 1,
+                     ?4, ?5, ?6, ?6)",
+            self.name,
+            self.kind,
+            self.parent_id,
+            self.description,
+            self.rules,
+            now
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<Collection>, sqlx::Error> {
+        let collection = sqlx::query_as!(Collection, "SELECT * FROM collections WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(collection)
+    }
+
+    /// Aggiorna nome, descrizione e regole. Per spostare la collezione usare `move_to`.
+    pub async fn update(&self, pool: &sqlx::SqlitePool) -> RitmoResult<u64> {
+        self.validate()?;
+        let now = chrono::Utc::now().timestamp();
+        let result = sqlx::query!(
+            "UPDATE collections SET name = ?, description = ?, rules = ?, updated_at = ? WHERE id = ?",
+            self.name,
+            self.description,
+            self.rules,
+            now,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Elimina la collezione insieme alle collezioni annidate. I libri e i contenuti non vengono toccati.
+    pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM collections WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Collezioni figlie di `parent_id` (None per quelle di primo livello), nell'ordine scelto
+    pub async fn list_children(pool: &sqlx::SqlitePool, parent_id: Option<i64>) -> Result<Vec<Collection>, sqlx::Error> {
+        let children = sqlx::query_as!(
+            Collection,
+            "SELECT * FROM collections WHERE parent_id IS ? ORDER BY position, id",
+            parent_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(children)
+    }
+
+    /// Sposta la collezione sotto un'altra (o al primo livello), in fondo alle nuove sorelle.
+    /// Non è possibile spostare una collezione dentro sé stessa o una sua discendente.
+    pub async fn move_to(pool: &sqlx::SqlitePool, id: i64, parent_id: Option<i64>) -> RitmoResult<()> {
+        let mut tx = pool.begin().await?;
+        if let Some(parent_id) = parent_id {
+            let cycle = sqlx::query_scalar!(
+                "WITH RECURSIVE ancestors(id, depth) AS (
+                    SELECT ?1, 0
+                    UNION ALL
+                    SELECT c.parent_id, a.depth + 1 FROM collections c JOIN ancestors a ON c.id = a.id
+                    WHERE c.parent_id IS NOT NULL AND a.depth < 64
+                 )
+                 SELECT COUNT(*) FROM ancestors WHERE id = ?2",
+                parent_id,
+                id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if cycle > 0 {
+                return Err(RitmoErr::InvalidInput(
+                    "una collezione non può essere spostata dentro sé stessa".to_string(),
+                ));
+            }
+        }
+        let now = chrono::Utc::now().timestamp();
+        sqlx::query!(
+            "UPDATE collections SET
+                parent_id = ?1,
+                position = COALESCE((SELECT MAX(position) FROM collections WHERE parent_id IS ?1 AND id <> ?2), 0) + 1,
+                updated_at = ?3
+             WHERE id = ?2",
+            parent_id,
+            id,
+            now
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Riordina le collezioni figlie di `parent_id`. `ordered_ids` deve contenere tutte e sole le figlie.
+    pub async fn reorder_children(
+        pool: &sqlx::SqlitePool,
+        parent_id: Option<i64>,
+        ordered_ids: &[i64],
+    ) -> RitmoResult<()> {
+        let mut tx = pool.begin().await?;
+        COLLECTIONS.reorder(&mut tx, parent_id, ordered_ids).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Aggiunge un libro in fondo alla collezione manuale; se c'è già restituisce l'elemento esistente
+    pub async fn add_book(pool: &sqlx::SqlitePool, collection_id: i64, book_id: i64) -> RitmoResult<i64> {
+        let mut tx = pool.begin().await?;
+        Self::check_manual(&mut tx, collection_id).await?;
+        let id = COLLECTION_ITEMS.append(&mut tx, collection_id, Member::Book(book_id)).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Aggiunge un contenuto in fondo alla collezione manuale; se c'è già restituisce l'elemento esistente
+    pub async fn add_content(pool: &sqlx::SqlitePool, collection_id: i64, content_id: i64) -> RitmoResult<i64> {
+        let mut tx = pool.begin().await?;
+        Self::check_manual(&mut tx, collection_id).await?;
+        let id = COLLECTION_ITEMS.append(&mut tx, collection_id, Member::Content(content_id)).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    async fn check_manual(conn: &mut SqliteConnection, collection_id: i64) -> RitmoResult<()> {
+        let kind = sqlx::query_scalar!("SELECT kind FROM collections WHERE id = ?", collection_id)
+            .fetch_optional(&mut *conn)
+            .await?;
+        match kind.as_deref() {
+            Some("manual") => Ok(()),
+            Some(_) => Err(RitmoErr::InvalidInput(
+                "gli elementi di una collezione smart sono calcolati dalle regole".to_string(),
+            )),
+            None => Err(RitmoErr::NoResultsError(format!("collezione {} non trovata", collection_id))),
+        }
+    }
+
+    pub async fn remove_item(pool: &sqlx::SqlitePool, item_id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM collection_items WHERE id = ?", item_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_items(pool: &sqlx::SqlitePool, collection_id: i64) -> Result<Vec<CollectionItem>, sqlx::Error> {
+        let items = sqlx::query_as!(
+            CollectionItem,
+            "SELECT * FROM collection_items WHERE collection_id = ? ORDER BY position, id",
+            collection_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(items)
+    }
+
+    /// Riordina gli elementi della collezione. `ordered_item_ids` deve contenerli tutti.
+    pub async fn reorder_items(pool: &sqlx::SqlitePool, collection_id: i64, ordered_item_ids: &[i64]) -> RitmoResult<()> {
+        let mut tx = pool.begin().await?;
+        COLLECTION_ITEMS.reorder(&mut tx, Some(collection_id), ordered_item_ids).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Sposta un elemento nella posizione indicata (a partire da 1), facendo scorrere gli altri
+    pub async fn move_item(pool: &sqlx::SqlitePool, item_id: i64, new_position: usize) -> RitmoResult<()> {
+        let mut tx = pool.begin().await?;
+        COLLECTION_ITEMS.move_item(&mut tx, item_id, new_position).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Libri della collezione: nell'ordine scelto per quelle manuali, calcolati dalle regole per quelle smart
+    pub async fn books(pool: &sqlx::SqlitePool, id: i64) -> RitmoResult<Vec<Book>> {
+        let collection = Self::get(pool, id)
+            .await?
+            .ok_or_else(|| RitmoErr::NoResultsError(format!("collezione {} non trovata", id)))?;
+        if let Some(filter) = collection.filter()? {
+            if filter.target != FilterTarget::Books {
+                return Ok(Vec::new());
+            }
+            return filter.books(pool).await;
+        }
+        let books = sqlx::query_as!(
+            Book,
+            "SELECT b.* FROM books b
+             JOIN collection_items i ON i.book_id = b.id
+             WHERE i.collection_id = ? AND b.deleted_at IS NULL
+             ORDER BY i.position, i.id",
+            id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(books)
+    }
+
+    /// Contenuti della collezione, come per `books`
+    pub async fn contents(pool: &sqlx::SqlitePool, id: i64) -> RitmoResult<Vec<Content>> {
+        let collection = Self::get(pool, id)
+            .await?
+            .ok_or_else(|| RitmoErr::NoResultsError(format!("collezione {} non trovata", id)))?;
+        if let Some(filter) = collection.filter()? {
+            if filter.target != FilterTarget::Contents {
+                return Ok(Vec::new());
+            }
+            return filter.contents(pool).await;
+        }
+        let contents = sqlx::query_as!(
+            Content,
+            "SELECT c.* FROM contents c
+             JOIN collection_items i ON i.content_id = c.id
+             WHERE i.collection_id = ? AND c.deleted_at IS NULL
+             ORDER BY i.position, i.id",
+            id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(contents)
+    }
+}
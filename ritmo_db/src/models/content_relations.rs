@@ -0,0 +1,55 @@
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::FromRow;
+
+/// Relazione tipizzata tra due contenuti: `content_id` è traduzione, revisione o estratto
+/// di `related_content_id`. Le relazioni si creano con `Work::link`.
+#[derive(Debug, Clone, FromRow, PartialEq, Eq)]
+pub struct ContentRelation {
+    pub content_id: i64,
+    pub related_content_id: i64,
+    pub relation: String,
+    pub created_at: i64,
+}
+
+impl ContentRelation {
+    /// Relazioni in cui compare il contenuto, in entrambe le direzioni
+    pub async fn list_by_content(pool: &sqlx::SqlitePool, content_id: i64) -> Result<Vec<ContentRelation>, sqlx::Error> {
+        let relations = sqlx::query_as!(
+            ContentRelation,
+            "SELECT * FROM content_relations
+             WHERE content_id = ?1 OR related_content_id = ?1
+             ORDER BY created_at, content_id",
+            content_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(relations)
+    }
+}
+
+/// Tipo di relazione tra due contenuti della stessa opera
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentRelationType {
+    TranslationOf,
+    RevisionOf,
+    ExcerptOf,
+}
+
+impl ContentRelationType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::TranslationOf => "translation_of",
+            Self::RevisionOf => "revision_of",
+            Self::ExcerptOf => "excerpt_of",
+        }
+    }
+
+    pub fn parse(relation: &str) -> RitmoResult<Self> {
+        match relation {
+            "translation_of" => Ok(Self::TranslationOf),
+            "revision_of" => Ok(Self::RevisionOf),
+            "excerpt_of" => Ok(Self::ExcerptOf),
+            other => Err(RitmoErr::InvalidInput(format!("relazione sconosciuta: {}", other))),
+        }
+    }
+}
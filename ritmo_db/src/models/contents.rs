@@ -1,6 +1,13 @@
+use crate::models::reading_sessions::check_rating;
+use crate::partial_date::PartialDate;
+use ritmo_db_core::sorting::sort_title;
+use ritmo_errors::RitmoResult;
 use chrono::Utc;
 use ritmo_core::ContentDto;
-use sqlx::FromRow;
+use sqlx::{FromRow, SqliteConnection};
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 
 #[derive(Debug, Clone, FromRow, Default)]
 pub struct Content {
@@ -9,14 +16,36 @@ pub struct Content {
     pub name: String,
     pub original_title: Option<String>,
     pub type_id: Option<i64>,
+    /// Data di pubblicazione codificata con `PartialDate::to_key`
     pub publication_date: Option<i64>,
     pub pages: Option<i64>,
     pub notes: Option<String>,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Data di spostamento nel cestino, None se il contenuto non è cestinato
+    pub deleted_at: Option<i64>,
+    /// Valutazione da 0 a 5
+    pub rating: Option<f64>,
+    /// Opera a cui appartiene il contenuto (lo stesso testo in altre antologie o traduzioni)
+    pub work_id: Option<i64>,
+    pub series_id: Option<i64>,
+    /// Posizione nella serie, anche frazionaria
+    pub series_index: Option<f64>,
+    /// Chiave di ordinamento del titolo nella lingua del contenuto
+    pub sort_title: Option<String>,
+    /// Chiavi degli autori del contenuto, come `Book::sort_author`
+    pub sort_author: Option<String>,
 }
 
 impl Content {
+    pub fn publication(&self) -> Option<PartialDate> {
+        self.publication_date.and_then(PartialDate::from_key)
+    }
+
+    pub fn set_publication(&mut self, date: Option<PartialDate>) {
+        self.publication_date = date.map(|d| d.to_key());
+    }
+
     pub fn from_dto(dto: &ContentDto) -> Self {
         let now = Utc::now().timestamp();
 
@@ -24,7 +53,10 @@ impl Content {
             name: dto.name.clone(),
             original_title: dto.original_title.clone(),
             type_id: dto.type_id,
-            publication_date: dto.publication_date,
+            publication_date: dto
+                .publication_date
+                .and_then(PartialDate::from_timestamp)
+                .map(|d| d.to_key()),
             notes: dto.notes.clone(),
             created_at: now,
             ..Default::default()
@@ -32,20 +64,32 @@ impl Content {
     }
 
     pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+        let mut conn = pool.acquire().await?;
+        self.insert(&mut conn).await
+    }
+
+    /// Come `save`, ma sulla connessione (o transazione) del chiamante
+    pub(crate) async fn insert(&self, conn: &mut SqliteConnection) -> Result<i64, sqlx::Error> {
         let now = chrono::Utc::now().timestamp();
+        // La lingua arriva con i collegamenti in x_contents_languages, che ricalcolano la chiave
+        let sort_title = sort_title(&self.name, None);
         let result = sqlx::query!(
             "INSERT INTO contents (
-                name, original_title, type_id, publication_date, notes, created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?)",
+                name, original_title, type_id, publication_date, notes, series_id, series_index, created_at, updated_at,
+                sort_title
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             self.name,
             self.original_title,
             self.type_id,
             self.publication_date,
             self.notes,
+            self.series_id,
+            self.series_index,
             now,
-            now
+            now,
+            sort_title
             )
-        .execute(pool)
+        .execute(&mut *conn)
         .await?;
         Ok(result.last_insert_rowid())
     }
@@ -53,7 +97,7 @@ impl Content {
     pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<Content>, sqlx::Error> {
         let content = sqlx::query_as!(
             Content,
-            "SELECT * FROM contents WHERE id = ?",
+            "SELECT * FROM contents WHERE id = ? AND deleted_at IS NULL",
             id
             )
             .fetch_optional(pool)
@@ -65,7 +109,8 @@ impl Content {
         let now = chrono::Utc::now().timestamp();
         let result = sqlx::query!(
             "UPDATE contents SET
-                name = ?, original_title = ?, type_id = ?, publication_date = ?, pages = ?, notes = ?, updated_at = ?
+                name = ?, original_title = ?, type_id = ?, publication_date = ?, pages = ?, notes = ?,
+                series_id = ?, series_index = ?, updated_at = ?
             WHERE id = ?",
             self.name,
             self.original_title,
@@ -73,6 +118,8 @@ impl Content {
             self.publication_date,
             self.pages,
             self.notes,
+            self.series_id,
+            self.series_index,
             now,
             self.id
         )
@@ -81,9 +128,32 @@ impl Content {
         Ok(result.rows_affected())
     }
 
+    /// Imposta (o toglie, con None) la valutazione del contenuto, da 0 a 5
+    pub async fn set_rating(pool: &sqlx::SqlitePool, id: i64, rating: Option<f64>) -> RitmoResult<u64> {
+        check_rating(rating)?;
+        let result = sqlx::query!("UPDATE contents SET rating = ? WHERE id = ?", rating, id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Sposta il contenuto nel cestino, lasciando intatti i suoi collegamenti
     pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let result = sqlx::query!(
+            "UPDATE contents SET deleted_at = ? WHERE id = ? AND deleted_at IS NULL",
+            now,
+            id
+            )
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Ripristina un contenuto dal cestino, insieme a tutti i suoi collegamenti
+    pub async fn restore(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
         let result = sqlx::query!(
-            "DELETE FROM contents WHERE id = ?",
+            "UPDATE contents SET deleted_at = NULL WHERE id = ? AND deleted_at IS NOT NULL",
             id
             )
             .execute(pool)
@@ -91,16 +161,47 @@ impl Content {
         Ok(result.rows_affected())
     }
 
+    pub async fn list_trash(pool: &sqlx::SqlitePool) -> Result<Vec<Content>, sqlx::Error> {
+        let trashed = sqlx::query_as!(
+            Content,
+            "SELECT * FROM contents WHERE deleted_at IS NOT NULL ORDER BY deleted_at DESC"
+            )
+            .fetch_all(pool)
+            .await?;
+        Ok(trashed)
+    }
+
+    /// Elimina definitivamente i contenuti nel cestino da più di `retention_secs` secondi
+    pub async fn purge_trash(pool: &sqlx::SqlitePool, retention_secs: i64) -> Result<u64, sqlx::Error> {
+        let cutoff = chrono::Utc::now().timestamp() - retention_secs;
+        let result = sqlx::query!(
+            "DELETE FROM contents WHERE deleted_at IS NOT NULL AND deleted_at <= ?",
+            cutoff
+            )
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
     pub async fn list_all(pool: &sqlx::SqlitePool) -> Result<Vec<Content>, sqlx::Error> {
         let all = sqlx::query_as!(
             Content,
-            "SELECT * FROM contents ORDER BY name"
+            "SELECT * FROM contents WHERE deleted_at IS NULL ORDER BY sort_title, name"
             )
             .fetch_all(pool)
             .await?;
         Ok(all)
     }
 
+    /// Come `list_all`, ma in streaming: adatto a esportazioni e indicizzazioni di librerie grandi
+    pub fn stream_all(pool: &sqlx::SqlitePool) -> BoxStream<'_, Result<Content, sqlx::Error>> {
+        sqlx::query_as!(
+            Content,
+            "SELECT * FROM contents WHERE deleted_at IS NULL ORDER BY sort_title, name"
+        )
+        .fetch(pool)
+    }
+
     pub async fn search(
         pool: &sqlx::SqlitePool,
         pattern: &str,
@@ -108,7 +209,7 @@ impl Content {
         let search_pattern = format!("%{}%", pattern);
         let found = sqlx::query_as!(
             Content,
-            "SELECT * FROM contents WHERE name LIKE ? OR original_title LIKE ? OR notes LIKE ? ORDER BY name",
+            "SELECT * FROM contents WHERE deleted_at IS NULL AND (name LIKE ? OR original_title LIKE ? OR notes LIKE ?) ORDER BY sort_title, name",
             search_pattern,
             search_pattern,
             search_pattern
@@ -117,4 +218,22 @@ impl Content {
         .await?;
         Ok(found)
     }
+
+    /// Come `search`, con i risultati letti man mano dal cursore
+    pub fn stream_search<'a>(pool: &'a sqlx::SqlitePool, pattern: &str) -> BoxStream<'a, Result<Content, sqlx::Error>> {
+        let search_pattern = format!("%{}%", pattern);
+        Box::pin(try_stream! {
+            let mut rows = sqlx::query_as!(
+                Content,
+                "SELECT * FROM contents WHERE deleted_at IS NULL AND (name LIKE ? OR original_title LIKE ? OR notes LIKE ?) ORDER BY sort_title, name",
+                search_pattern,
+                search_pattern,
+                search_pattern
+            )
+            .fetch(pool);
+            while let Some(content) = rows.try_next().await? {
+                yield content;
+            }
+        })
+    }
 }
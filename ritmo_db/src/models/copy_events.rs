@@ -0,0 +1,48 @@
+use sqlx::{FromRow, SqliteConnection};
+
+/// Evento nella storia di una copia cartacea: aggiunta, spostamento, prestito, restituzione,
+/// cambio di condizione. Serve a ricostruire dove è stata ogni copia.
+#[derive(Debug, Clone, FromRow)]
+pub struct CopyEvent {
+    pub id: Option<i64>,
+    pub copy_id: i64,
+    /// 'added', 'moved', 'lent', 'returned' o 'condition'
+    pub event: String,
+    pub location_id: Option<i64>,
+    pub details: Option<String>,
+    pub happened_at: i64,
+}
+
+impl CopyEvent {
+    pub(crate) async fn record(
+        conn: &mut SqliteConnection,
+        copy_id: i64,
+        event: &str,
+        location_id: Option<i64>,
+        details: Option<&str>,
+        happened_at: i64,
+    ) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO copy_events (copy_id, event, location_id, details, happened_at) VALUES (?, ?, ?, ?, ?)",
+            copy_id,
+            event,
+            location_id,
+            details,
+            happened_at
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn list_by_copy(pool: &sqlx::SqlitePool, copy_id: i64) -> Result<Vec<CopyEvent>, sqlx::Error> {
+        let events = sqlx::query_as!(
+            CopyEvent,
+            "SELECT * FROM copy_events WHERE copy_id = ? ORDER BY happened_at, id",
+            copy_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(events)
+    }
+}
@@ -13,6 +13,12 @@ pub struct RunningLanguages {
     pub updated_at: Option<i64>,
 }
 
+impl Default for RunningLanguages {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl RunningLanguages {
     pub fn new() -> Self {
         Self {
@@ -0,0 +1,173 @@
+use crate::models::CopyEvent;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::FromRow;
+
+/// Prestito di una copia cartacea. Una copia può avere un solo prestito aperto alla volta.
+#[derive(Debug, Clone, FromRow)]
+pub struct Loan {
+    pub id: Option<i64>,
+    pub copy_id: i64,
+    pub borrower: String,
+    pub lent_at: i64,
+    pub due_at: Option<i64>,
+    /// None finché la copia non viene restituita
+    pub returned_at: Option<i64>,
+    pub notes: Option<String>,
+}
+
+/// Riga del report dei prestiti scaduti
+#[derive(Debug, Clone, PartialEq)]
+pub struct OverdueLoan {
+    pub loan_id: i64,
+    pub copy_id: i64,
+    pub book_id: i64,
+    pub book_name: String,
+    pub borrower: String,
+    pub lent_at: i64,
+    pub due_at: i64,
+    pub days_overdue: i64,
+}
+
+impl Loan {
+    /// Presta la copia. Fallisce se la copia è già in prestito.
+    pub async fn lend(
+        pool: &sqlx::SqlitePool,
+        copy_id: i64,
+        borrower: &str,
+        lent_at: i64,
+        due_at: Option<i64>,
+    ) -> RitmoResult<i64> {
+        let borrower = borrower.trim();
+        if borrower.is_empty() {
+            return Err(RitmoErr::InvalidInput("il prestito richiede il nome di chi lo riceve".to_string()));
+        }
+        let mut tx = pool.begin().await?;
+        let open = sqlx::query_scalar!(
+            "SELECT borrower FROM loans WHERE copy_id = ? AND returned_at IS NULL",
+            copy_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(current) = open {
+            return Err(RitmoErr::InvalidInput(format!(
+                "la copia {} è già in prestito a {}",
+                copy_id, current
+            )));
+        }
+        let id = sqlx::query!(
+            "INSERT INTO loans (copy_id, borrower, lent_at, due_at) VALUES (?, ?, ?, ?)",
+            copy_id,
+            borrower,
+            lent_at,
+            due_at
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        CopyEvent::record(&mut tx, copy_id, "lent", None, Some(borrower), lent_at).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// Registra la restituzione. Restituisce 0 se il prestito non esiste o era già chiuso.
+    pub async fn return_copy(pool: &sqlx::SqlitePool, id: i64, returned_at: i64) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let Some(loan) = sqlx::query_as!(
+            Loan,
+            "SELECT * FROM loans WHERE id = ? AND returned_at IS NULL",
+            id
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        else {
+            return Ok(0);
+        };
+        let result = sqlx::query!("UPDATE loans SET returned_at = ? WHERE id = ?", returned_at, id)
+            .execute(&mut *tx)
+            .await?;
+        CopyEvent::record(&mut tx, loan.copy_id, "returned", None, Some(&loan.borrower), returned_at).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<Loan>, sqlx::Error> {
+        let loan = sqlx::query_as!(Loan, "SELECT * FROM loans WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(loan)
+    }
+
+    /// Prestito aperto della copia, se c'è
+    pub async fn current(pool: &sqlx::SqlitePool, copy_id: i64) -> Result<Option<Loan>, sqlx::Error> {
+        let loan = sqlx::query_as!(
+            Loan,
+            "SELECT * FROM loans WHERE copy_id = ? AND returned_at IS NULL",
+            copy_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(loan)
+    }
+
+    pub async fn list_open(pool: &sqlx::SqlitePool) -> Result<Vec<Loan>, sqlx::Error> {
+        let loans = sqlx::query_as!(
+            Loan,
+            "SELECT * FROM loans WHERE returned_at IS NULL ORDER BY lent_at, id"
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(loans)
+    }
+
+    pub async fn list_by_copy(pool: &sqlx::SqlitePool, copy_id: i64) -> Result<Vec<Loan>, sqlx::Error> {
+        let loans = sqlx::query_as!(
+            Loan,
+            "SELECT * FROM loans WHERE copy_id = ? ORDER BY lent_at, id",
+            copy_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(loans)
+    }
+
+    pub async fn list_by_borrower(pool: &sqlx::SqlitePool, borrower: &str) -> Result<Vec<Loan>, sqlx::Error> {
+        let loans = sqlx::query_as!(
+            Loan,
+            "SELECT * FROM loans WHERE borrower = ? COLLATE NOCASE ORDER BY lent_at, id",
+            borrower
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(loans)
+    }
+
+    /// Prestiti aperti con la data di restituzione già passata rispetto a `now`,
+    /// dal più in ritardo
+    pub async fn overdue(pool: &sqlx::SqlitePool, now: i64) -> Result<Vec<OverdueLoan>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT l.id AS \"loan_id!\", l.copy_id, c.book_id, b.name AS book_name, l.borrower,
+                    l.lent_at, l.due_at AS \"due_at!\"
+             FROM loans l
+             JOIN book_copies c ON c.id = l.copy_id
+             JOIN books b ON b.id = c.book_id
+             WHERE l.returned_at IS NULL AND l.due_at IS NOT NULL AND l.due_at < ?
+             ORDER BY l.due_at, l.id",
+            now
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| OverdueLoan {
+                loan_id: r.loan_id,
+                copy_id: r.copy_id,
+                book_id: r.book_id,
+                book_name: r.book_name,
+                borrower: r.borrower,
+                lent_at: r.lent_at,
+                due_at: r.due_at,
+                days_overdue: (now - r.due_at) / 86_400,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,88 @@
+use sqlx::FromRow;
+use futures::stream::BoxStream;
+
+/// Luogo in cui si trovano le copie cartacee (stanza, libreria, ripiano, scatola...).
+/// I luoghi possono essere annidati tramite `parent_id`.
+#[derive(Debug, Clone, FromRow, Default)]
+pub struct Location {
+    pub id: Option<i64>,
+    pub name: String,
+    pub parent_id: Option<i64>,
+    pub description: Option<String>,
+    pub created_at: i64,
+}
+
+impl Location {
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+        let result = sqlx::query!(
+            "INSERT INTO locations (name, parent_id, description) VALUES (?, ?, ?)",
+            self.name,
+            self.parent_id,
+            self.description
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<Location>, sqlx::Error> {
+        let location = sqlx::query_as!(Location, "SELECT * FROM locations WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(location)
+    }
+
+    pub async fn update(&self, pool: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE locations SET name = ?, parent_id = ?, description = ? WHERE id = ?",
+            self.name,
+            self.parent_id,
+            self.description,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Elimina il luogo; le copie che vi si trovavano restano senza collocazione
+    pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM locations WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_all(pool: &sqlx::SqlitePool) -> Result<Vec<Location>, sqlx::Error> {
+        let all = sqlx::query_as!(Location, "SELECT * FROM locations ORDER BY name")
+            .fetch_all(pool)
+            .await?;
+        Ok(all)
+    }
+
+    /// Come `list_all`, ma in streaming
+    pub fn stream_all(pool: &sqlx::SqlitePool) -> BoxStream<'_, Result<Location, sqlx::Error>> {
+        sqlx::query_as!(Location, "SELECT * FROM locations ORDER BY name").fetch(pool)
+    }
+
+    /// Percorso completo del luogo, dal più esterno: "Studio / Libreria A / Ripiano 3"
+    pub async fn full_path(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<String>, sqlx::Error> {
+        let names = sqlx::query_scalar!(
+            "WITH RECURSIVE path(id, name, parent_id, depth) AS (
+                SELECT id, name, parent_id, 0 FROM locations WHERE id = ?
+                UNION ALL
+                SELECT l.id, l.name, l.parent_id, p.depth + 1
+                FROM locations l JOIN path p ON l.id = p.parent_id
+                WHERE p.depth < 32
+             )
+             SELECT name AS \"name!: String\" FROM path ORDER BY depth DESC",
+            id
+        )
+        .fetch_all(pool)
+        .await?;
+        if names.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(names.join(" / ")))
+    }
+}
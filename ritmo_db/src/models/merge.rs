@@ -0,0 +1,48 @@
+use sqlx::{Sqlite, Transaction};
+
+/// Risultato della fusione di due entità duplicate
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    /// Entità che resta nel database
+    pub survivor_id: i64,
+    /// Entità eliminata, i cui riferimenti sono stati spostati sulla superstite
+    pub merged_id: i64,
+    /// Riferimenti spostati sulla superstite
+    pub links_moved: u64,
+    /// Riferimenti eliminati perché già presenti sulla superstite
+    pub duplicate_links_removed: u64,
+}
+
+impl MergeSummary {
+    pub fn new(survivor_id: i64, merged_id: i64) -> Self {
+        Self {
+            survivor_id,
+            merged_id,
+            ..Default::default()
+        }
+    }
+}
+
+/// Registra la fusione in `audit_log`. `old_values` è il JSON dell'entità eliminata.
+pub(crate) async fn record_merge(
+    tx: &mut Transaction<'_, Sqlite>,
+    table_name: &str,
+    summary: &MergeSummary,
+    old_values: &str,
+) -> Result<(), sqlx::Error> {
+    let new_values = format!(
+        "{{\"merged_into\":{},\"links_moved\":{},\"duplicate_links_removed\":{}}}",
+        summary.survivor_id, summary.links_moved, summary.duplicate_links_removed
+    );
+    sqlx::query!(
+        "INSERT INTO audit_log (table_name, record_id, operation, old_values, new_values)
+         VALUES (?, ?, 'MERGE', ?, ?)",
+        table_name,
+        summary.survivor_id,
+        old_values,
+        new_values
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
@@ -7,17 +7,34 @@
 ///
 /// La sequenza quindi è:
 /// User -> DTO data -> ML -> Models data
+pub mod acquisitions;
 pub mod aliases;
+pub mod book_copies;
+pub mod book_files;
+pub mod book_identifiers;
 pub mod books;
+pub mod collection_items;
+pub mod collections;
+pub mod content_relations;
 pub mod contents;
+pub mod copy_events;
 pub mod formats;
 pub mod languages;
+pub mod loans;
+pub mod locations;
+pub mod merge;
+pub mod pen_names;
 pub mod people;
 pub mod publishers;
+pub mod reading_order_items;
+pub mod reading_orders;
+pub mod reading_sessions;
+pub mod role_labels;
 pub mod roles;
 pub mod series;
 pub mod tags;
 pub mod types;
+pub mod works;
 pub mod x_books_contents;
 pub mod x_books_people_roles;
 pub mod x_books_tags;
@@ -25,17 +42,34 @@ pub mod x_contents_languages;
 pub mod x_contents_people_roles;
 pub mod x_contents_tags;
 
+pub use self::acquisitions::*;
 pub use self::aliases::*;
+pub use self::book_copies::*;
+pub use self::book_files::*;
+pub use self::book_identifiers::*;
 pub use self::books::*;
+pub use self::collection_items::*;
+pub use self::collections::*;
+pub use self::content_relations::*;
 pub use self::contents::*;
+pub use self::copy_events::*;
 pub use self::formats::*;
 pub use self::languages::*;
+pub use self::loans::*;
+pub use self::locations::*;
+pub use self::merge::*;
+pub use self::pen_names::*;
 pub use self::people::*;
 pub use self::publishers::*;
+pub use self::reading_order_items::*;
+pub use self::reading_orders::*;
+pub use self::reading_sessions::*;
+pub use self::role_labels::*;
 pub use self::roles::*;
 pub use self::series::*;
 pub use self::tags::*;
 pub use self::types::*;
+pub use self::works::*;
 pub use self::x_books_contents::*;
 pub use self::x_books_people_roles::*;
 pub use self::x_books_tags::*;
@@ -0,0 +1,237 @@
+use crate::models::{Book, Content, Person};
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::FromRow;
+
+/// Legame tra uno pseudonimo e una persona reale che scrive sotto quel nome.
+/// Lo pseudonimo è a sua volta una riga di `people`, così i crediti su libri e contenuti
+/// si registrano sotto il nome di penna; uno pseudonimo collettivo (Ellery Queen)
+/// è legato a più persone reali.
+#[derive(Debug, Clone, FromRow, PartialEq)]
+pub struct PenName {
+    pub pen_name_id: i64,
+    pub person_id: i64,
+    pub notes: Option<String>,
+    pub created_at: i64,
+}
+
+/// Come mostrare i crediti registrati sotto uno pseudonimo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameDisplay {
+    /// Il nome con cui l'opera è firmata
+    #[default]
+    PenName,
+    /// Le persone reali dietro lo pseudonimo
+    RealIdentity,
+}
+
+/// Credito di un libro o di un contenuto, risolto secondo `NameDisplay`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credit {
+    pub person_id: i64,
+    pub name: String,
+    pub role_id: i64,
+    /// Pseudonimo sotto cui il credito è registrato, se è stato risolto nella persona reale
+    pub credited_as: Option<i64>,
+}
+
+impl PenName {
+    /// Collega lo pseudonimo `pen_name_id` alla persona reale `person_id`.
+    /// Non sono ammesse catene: una persona reale non può essere a sua volta uno pseudonimo.
+    pub async fn link(
+        pool: &sqlx::SqlitePool,
+        pen_name_id: i64,
+        person_id: i64,
+        notes: Option<&str>,
+    ) -> RitmoResult<()> {
+        if pen_name_id == person_id {
+            return Err(RitmoErr::InvalidInput("una persona non può essere pseudonimo di sé stessa".to_string()));
+        }
+        let mut tx = pool.begin().await?;
+        let chained = sqlx::query_scalar!(
+            "SELECT EXISTS (SELECT 1 FROM pen_names WHERE pen_name_id = ?1 OR person_id = ?2) AS \"chained!: bool\"",
+            person_id,
+            pen_name_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if chained {
+            return Err(RitmoErr::InvalidInput(format!(
+                "{} è a sua volta uno pseudonimo o {} è una persona reale dietro un altro pseudonimo",
+                person_id, pen_name_id
+            )));
+        }
+        sqlx::query!(
+            "INSERT OR IGNORE INTO pen_names (pen_name_id, person_id, notes) VALUES (?, ?, ?)",
+            pen_name_id,
+            person_id,
+            notes
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    pub async fn unlink(pool: &sqlx::SqlitePool, pen_name_id: i64, person_id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM pen_names WHERE pen_name_id = ? AND person_id = ?",
+            pen_name_id,
+            person_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn is_pen_name(pool: &sqlx::SqlitePool, person_id: i64) -> Result<bool, sqlx::Error> {
+        let found = sqlx::query_scalar!(
+            "SELECT EXISTS (SELECT 1 FROM pen_names WHERE pen_name_id = ?) AS \"found!: bool\"",
+            person_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(found)
+    }
+
+    /// Le persone reali dietro lo pseudonimo
+    pub async fn real_people(pool: &sqlx::SqlitePool, pen_name_id: i64) -> Result<Vec<Person>, sqlx::Error> {
+        let people = sqlx::query_as!(
+            Person,
+            "SELECT p.* FROM people p JOIN pen_names pn ON pn.person_id = p.id
+             WHERE pn.pen_name_id = ? ORDER BY p.name",
+            pen_name_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(people)
+    }
+
+    /// Gli pseudonimi usati da una persona reale
+    pub async fn pen_names_of(pool: &sqlx::SqlitePool, person_id: i64) -> Result<Vec<Person>, sqlx::Error> {
+        let people = sqlx::query_as!(
+            Person,
+            "SELECT p.* FROM people p JOIN pen_names pn ON pn.pen_name_id = p.id
+             WHERE pn.person_id = ? ORDER BY p.name",
+            person_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(people)
+    }
+
+    /// Crediti del libro; con `NameDisplay::RealIdentity` ogni pseudonimo viene sostituito
+    /// dalle persone reali collegate (se ce ne sono)
+    pub async fn book_credits(
+        pool: &sqlx::SqlitePool,
+        book_id: i64,
+        display: NameDisplay,
+    ) -> Result<Vec<Credit>, sqlx::Error> {
+        let resolve = display == NameDisplay::RealIdentity;
+        let rows = sqlx::query!(
+            "SELECT p.id AS \"person_id!\", p.name, x.role_id,
+                    CASE WHEN pn.person_id IS NOT NULL THEN x.person_id END AS \"credited_as?: i64\"
+             FROM x_books_people_roles x
+             LEFT JOIN pen_names pn ON pn.pen_name_id = x.person_id AND ?2
+             JOIN people p ON p.id = COALESCE(pn.person_id, x.person_id)
+             WHERE x.book_id = ?1
+             ORDER BY x.role_id, p.name",
+            book_id,
+            resolve
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Credit {
+                person_id: r.person_id,
+                name: r.name,
+                role_id: r.role_id,
+                credited_as: r.credited_as,
+            })
+            .collect())
+    }
+
+    /// Crediti del contenuto, come per `book_credits`
+    pub async fn content_credits(
+        pool: &sqlx::SqlitePool,
+        content_id: i64,
+        display: NameDisplay,
+    ) -> Result<Vec<Credit>, sqlx::Error> {
+        let resolve = display == NameDisplay::RealIdentity;
+        let rows = sqlx::query!(
+            "SELECT p.id AS \"person_id!\", p.name, x.role_id,
+                    CASE WHEN pn.person_id IS NOT NULL THEN x.person_id END AS \"credited_as?: i64\"
+             FROM x_contents_people_roles x
+             LEFT JOIN pen_names pn ON pn.pen_name_id = x.person_id AND ?2
+             JOIN people p ON p.id = COALESCE(pn.person_id, x.person_id)
+             WHERE x.content_id = ?1
+             ORDER BY x.role_id, p.name",
+            content_id,
+            resolve
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| Credit {
+                person_id: r.person_id,
+                name: r.name,
+                role_id: r.role_id,
+                credited_as: r.credited_as,
+            })
+            .collect())
+    }
+
+    /// Libri accreditati alla persona. Con `NameDisplay::RealIdentity` la ricerca comprende
+    /// anche gli pseudonimi della persona e, se la persona è uno pseudonimo, le persone reali dietro di esso.
+    pub async fn books_by_person(
+        pool: &sqlx::SqlitePool,
+        person_id: i64,
+        display: NameDisplay,
+    ) -> Result<Vec<Book>, sqlx::Error> {
+        let resolve = display == NameDisplay::RealIdentity;
+        let books = sqlx::query_as!(
+            Book,
+            "SELECT b.* FROM books b
+             WHERE b.deleted_at IS NULL AND b.id IN (
+                SELECT x.book_id FROM x_books_people_roles x WHERE x.person_id IN (
+                    SELECT ?1
+                    UNION SELECT pen_name_id FROM pen_names WHERE person_id = ?1 AND ?2
+                    UNION SELECT person_id FROM pen_names WHERE pen_name_id = ?1 AND ?2
+                )
+             )
+             ORDER BY b.name",
+            person_id,
+            resolve
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(books)
+    }
+
+    /// Contenuti accreditati alla persona, come per `books_by_person`
+    pub async fn contents_by_person(
+        pool: &sqlx::SqlitePool,
+        person_id: i64,
+        display: NameDisplay,
+    ) -> Result<Vec<Content>, sqlx::Error> {
+        let resolve = display == NameDisplay::RealIdentity;
+        let contents = sqlx::query_as!(
+            Content,
+            "SELECT c.* FROM contents c
+             WHERE c.deleted_at IS NULL AND c.id IN (
+                SELECT x.content_id FROM x_contents_people_roles x WHERE x.person_id IN (
+                    SELECT ?1
+                    UNION SELECT pen_name_id FROM pen_names WHERE person_id = ?1 AND ?2
+                    UNION SELECT person_id FROM pen_names WHERE pen_name_id = ?1 AND ?2
+                )
+             )
+             ORDER BY c.name",
+            person_id,
+            resolve
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(contents)
+    }
+}
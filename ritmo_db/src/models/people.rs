@@ -1,4 +1,12 @@
+use crate::models::merge::{record_merge, MergeSummary};
+use crate::partial_date::PartialDate;
+use ritmo_db_core::normalize::normalize_key;
+use ritmo_db_core::sorting::sort_name;
+use ritmo_errors::{RitmoErr, RitmoResult};
 use sqlx::FromRow;
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 
 #[derive(Debug, Clone, FromRow)]
 pub struct Person {
@@ -11,10 +19,13 @@ pub struct Person {
     pub title: Option<String>,
     pub suffix: Option<String>,
     pub nationality: Option<String>,
+    /// Date di nascita e di morte codificate con `PartialDate::to_key`
     pub birth_date: Option<i64>,
     pub death_date: Option<i64>,
     pub biography: Option<String>,
     pub normalized_key: Option<String>,
+    /// Chiave "cognome, nome" per l'ordinamento, ricalcolata dai trigger quando cambia il nome
+    pub sort_name: Option<String>,
     pub confidence: f64,
     pub source: String,
     pub verified: i64,
@@ -23,14 +34,33 @@ pub struct Person {
 }
 
 impl Person {
+    pub fn birth(&self) -> Option<PartialDate> {
+        self.birth_date.and_then(PartialDate::from_key)
+    }
+
+    pub fn death(&self) -> Option<PartialDate> {
+        self.death_date.and_then(PartialDate::from_key)
+    }
+
+    pub fn set_birth(&mut self, date: Option<PartialDate>) {
+        self.birth_date = date.map(|d| d.to_key());
+    }
+
+    pub fn set_death(&mut self, date: Option<PartialDate>) {
+        self.death_date = date.map(|d| d.to_key());
+    }
+
+    /// Memorizza la persona; se manca, la chiave normalizzata viene calcolata dal nome
     pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
         let now = chrono::Utc::now().timestamp();
+        let normalized_key = self.normalized_key.clone().unwrap_or_else(|| normalize_key(&self.name));
+        let sort_name = sort_name(&self.name, self.surname.as_deref(), self.given_name.as_deref());
         let result = sqlx::query!(
             "INSERT INTO people (
                 name, display_name, given_name, surname, middle_names, title, suffix, nationality,
-                birth_date, death_date, biography, normalized_key, confidence, source, verified,
+                birth_date, death_date, biography, normalized_key, sort_name, confidence, source, verified,
                 created_at, updated_at
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
             self.name,
             self.display_name,
             self.given_name,
@@ -42,7 +72,8 @@ impl Person {
             self.birth_date,
             self.death_date,
             self.biography,
-            self.normalized_key,
+            normalized_key,
+            sort_name,
             self.confidence,
             self.source,
             self.verified,
@@ -107,16 +138,37 @@ impl Person {
         Ok(result.rows_affected())
     }
 
+    /// Persone il cui nome, o uno dei cui alias, ha la stessa chiave normalizzata di `name`
+    pub async fn find_by_key(pool: &sqlx::SqlitePool, name: &str) -> Result<Vec<Person>, sqlx::Error> {
+        let key = normalize_key(name);
+        let people = sqlx::query_as!(
+            Person,
+            "SELECT * FROM people
+             WHERE normalized_key = ?1 COLLATE NOCASE
+                OR id IN (SELECT person_id FROM aliases WHERE alias_normalized = ?1 COLLATE NOCASE)
+             ORDER BY sort_name, name",
+            key
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(people)
+    }
+
     pub async fn list_all(pool: &sqlx::SqlitePool) -> Result<Vec<Person>, sqlx::Error> {
         let all = sqlx::query_as!(
             Person,
-            "SELECT * FROM people ORDER BY name"
+            "SELECT * FROM people ORDER BY sort_name, name"
             )
             .fetch_all(pool)
             .await?;
         Ok(all)
     }
 
+    /// Come `list_all`, una persona alla volta; memoria costante anche con tabelle molto grandi
+    pub fn stream_all(pool: &sqlx::SqlitePool) -> BoxStream<'_, Result<Person, sqlx::Error>> {
+        sqlx::query_as!(Person, "SELECT * FROM people ORDER BY sort_name, name").fetch(pool)
+    }
+
     pub async fn search(
         pool: &sqlx::SqlitePool,
         pattern: &str,
@@ -124,7 +176,7 @@ impl Person {
         let search_pattern = format!("%{}%", pattern);
         let found = sqlx::query_as!(
             Person,
-            "SELECT * FROM people WHERE name LIKE ? OR display_name LIKE ? OR given_name LIKE ? OR surname LIKE ? OR biography LIKE ? ORDER BY name",
+            "SELECT * FROM people WHERE name LIKE ? OR display_name LIKE ? OR given_name LIKE ? OR surname LIKE ? OR biography LIKE ? ORDER BY sort_name, name",
         search_pattern,
         search_pattern,
         search_pattern,
@@ -135,4 +187,165 @@ impl Person {
         .await?;
         Ok(found)
     }
+
+    /// Come `search`, con i risultati letti man mano dal cursore
+    pub fn stream_search<'a>(pool: &'a sqlx::SqlitePool, pattern: &str) -> BoxStream<'a, Result<Person, sqlx::Error>> {
+        let search_pattern = format!("%{}%", pattern);
+        Box::pin(try_stream! {
+            let mut rows = sqlx::query_as!(
+                Person,
+                "SELECT * FROM people WHERE name LIKE ? OR display_name LIKE ? OR given_name LIKE ? OR surname LIKE ? OR biography LIKE ? ORDER BY sort_name, name",
+                search_pattern,
+                search_pattern,
+                search_pattern,
+                search_pattern,
+                search_pattern
+            )
+            .fetch(pool);
+            while let Some(person) = rows.try_next().await? {
+                yield person;
+            }
+        })
+    }
+
+    /// Fonde la persona `merged_id` in `survivor_id` in un'unica transazione:
+    /// sposta i ruoli su libri e contenuti e gli alias, elimina i collegamenti duplicati,
+    /// trasforma il nome della persona eliminata in un alias, completa i campi vuoti
+    /// della superstite e registra l'operazione in `audit_log`.
+    pub async fn merge(
+        pool: &sqlx::SqlitePool,
+        survivor_id: i64,
+        merged_id: i64,
+    ) -> RitmoResult<MergeSummary> {
+        if survivor_id == merged_id {
+            return Err(RitmoErr::MergeError(format!(
+                "impossibile fondere la persona {} con se stessa",
+                survivor_id
+            )));
+        }
+        let mut tx = pool.begin().await?;
+
+        let survivor_exists = sqlx::query_scalar!("SELECT id FROM people WHERE id = ?", survivor_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let old_values = sqlx::query_scalar!(
+            "SELECT json_object(
+                'id', id, 'name', name, 'display_name', display_name, 'given_name', given_name,
+                'surname', surname, 'nationality', nationality, 'normalized_key', normalized_key,
+                'source', source, 'verified', verified
+             ) AS \"json!: String\"
+             FROM people WHERE id = ?",
+            merged_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let (Some(_), Some(old_values)) = (survivor_exists, old_values) else {
+            return Err(RitmoErr::MergeError(format!(
+                "persone {} e {} non trovate entrambe",
+                survivor_id, merged_id
+            )));
+        };
+
+        let mut summary = MergeSummary::new(survivor_id, merged_id);
+
+        summary.links_moved += sqlx::query!(
+            "UPDATE OR IGNORE x_books_people_roles SET person_id = ? WHERE person_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        summary.duplicate_links_removed += sqlx::query!(
+            "DELETE FROM x_books_people_roles WHERE person_id = ?",
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        summary.links_moved += sqlx::query!(
+            "UPDATE OR IGNORE x_contents_people_roles SET person_id = ? WHERE person_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        summary.duplicate_links_removed += sqlx::query!(
+            "DELETE FROM x_contents_people_roles WHERE person_id = ?",
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        // Pseudonimi: la superstite eredita i legami; quelli che la collegherebbero a sé stessa
+        // o che esistono già vengono ignorati e spariscono col CASCADE
+        sqlx::query!(
+            "UPDATE OR IGNORE pen_names SET pen_name_id = ? WHERE pen_name_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "UPDATE OR IGNORE pen_names SET person_id = ? WHERE person_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Gli alias rimasti sulla persona eliminata sono duplicati e spariscono col CASCADE
+        sqlx::query!(
+            "UPDATE OR IGNORE aliases SET person_id = ? WHERE person_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query!(
+            "INSERT OR IGNORE INTO aliases (name, person_id, alias_normalized, confidence)
+             SELECT l.name, s.id, l.normalized_key, MIN(l.confidence, 0.9)
+             FROM people l, people s
+             WHERE l.id = ? AND s.id = ? AND l.name <> s.name",
+            merged_id,
+            survivor_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE people SET
+                display_name = COALESCE(people.display_name, l.display_name),
+                given_name = COALESCE(people.given_name, l.given_name),
+                surname = COALESCE(people.surname, l.surname),
+                middle_names = COALESCE(people.middle_names, l.middle_names),
+                title = COALESCE(people.title, l.title),
+                suffix = COALESCE(people.suffix, l.suffix),
+                nationality = COALESCE(people.nationality, l.nationality),
+                birth_date = COALESCE(people.birth_date, l.birth_date),
+                death_date = COALESCE(people.death_date, l.death_date),
+                biography = COALESCE(people.biography, l.biography),
+                normalized_key = COALESCE(people.normalized_key, l.normalized_key),
+                confidence = MAX(people.confidence, l.confidence),
+                verified = MAX(people.verified, l.verified)
+             FROM (SELECT * FROM people WHERE id = ?) AS l
+             WHERE people.id = ?",
+            merged_id,
+            survivor_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        record_merge(&mut tx, "people", &summary, &old_values).await?;
+
+        sqlx::query!("DELETE FROM people WHERE id = ?", merged_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(summary)
+    }
 }
@@ -1,5 +1,10 @@
+use crate::models::merge::{record_merge, MergeSummary};
+use ritmo_errors::{RitmoErr, RitmoResult};
 use ritmo_core::PublisherDto;
 use sqlx::FromRow;
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 
 #[derive(Debug, Clone, FromRow, Default)]
 pub struct Publisher {
@@ -94,6 +99,11 @@ impl Publisher {
         Ok(publishers)
     }
 
+    /// Come `list_all`, ma in streaming
+    pub fn stream_all(pool: &sqlx::SqlitePool) -> BoxStream<'_, Result<Publisher, sqlx::Error>> {
+        sqlx::query_as!(Publisher, "SELECT * FROM publishers ORDER BY name").fetch(pool)
+    }
+
     pub async fn search(
         pool: &sqlx::SqlitePool,
         pattern: &str,
@@ -111,4 +121,90 @@ impl Publisher {
         .await?;
         Ok(publishers)
     }
+
+    /// Come `search`, ma in streaming
+    pub fn stream_search<'a>(pool: &'a sqlx::SqlitePool, pattern: &str) -> BoxStream<'a, Result<Publisher, sqlx::Error>> {
+        let search_pattern = format!("%{}%", pattern);
+        Box::pin(try_stream! {
+            let mut rows = sqlx::query_as!(
+                Publisher,
+                "SELECT * FROM publishers WHERE name LIKE ? OR country LIKE ? OR website LIKE ? OR notes LIKE ? ORDER BY name",
+                search_pattern,
+                search_pattern,
+                search_pattern,
+                search_pattern
+            )
+            .fetch(pool);
+            while let Some(publisher) = rows.try_next().await? {
+                yield publisher;
+            }
+        })
+    }
+
+    /// Fonde l'editore `merged_id` in `survivor_id` in un'unica transazione:
+    /// sposta i libri sull'editore superstite, completa i campi vuoti della superstite
+    /// e registra l'operazione in `audit_log`.
+    pub async fn merge(
+        pool: &sqlx::SqlitePool,
+        survivor_id: i64,
+        merged_id: i64,
+    ) -> RitmoResult<MergeSummary> {
+        if survivor_id == merged_id {
+            return Err(RitmoErr::MergeError(format!(
+                "impossibile fondere l'editore {} con se stesso",
+                survivor_id
+            )));
+        }
+        let mut tx = pool.begin().await?;
+
+        let survivor_exists = sqlx::query_scalar!("SELECT id FROM publishers WHERE id = ?", survivor_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let old_values = sqlx::query_scalar!(
+            "SELECT json_object('id', id, 'name', name, 'country', country, 'website', website, 'notes', notes) AS \"json!: String\"
+             FROM publishers WHERE id = ?",
+            merged_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let (Some(_), Some(old_values)) = (survivor_exists, old_values) else {
+            return Err(RitmoErr::MergeError(format!(
+                "editori {} e {} non trovati entrambi",
+                survivor_id, merged_id
+            )));
+        };
+
+        let mut summary = MergeSummary::new(survivor_id, merged_id);
+
+        summary.links_moved += sqlx::query!(
+            "UPDATE books SET publisher_id = ? WHERE publisher_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query!(
+            "UPDATE publishers SET
+                country = COALESCE(publishers.country, l.country),
+                website = COALESCE(publishers.website, l.website),
+                notes = COALESCE(publishers.notes, l.notes)
+             FROM (SELECT * FROM publishers WHERE id = ?) AS l
+             WHERE publishers.id = ?",
+            merged_id,
+            survivor_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        record_merge(&mut tx, "publishers", &summary, &old_values).await?;
+
+        sqlx::query!("DELETE FROM publishers WHERE id = ?", merged_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(summary)
+    }
 }
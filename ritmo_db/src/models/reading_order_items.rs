@@ -0,0 +1,11 @@
+use sqlx::FromRow;
+
+/// Elemento di un ordine di lettura: un libro oppure un contenuto, nella posizione prevista
+#[derive(Debug, Clone, FromRow, PartialEq, Eq)]
+pub struct ReadingOrderItem {
+    pub id: Option<i64>,
+    pub order_id: i64,
+    pub book_id: Option<i64>,
+    pub content_id: Option<i64>,
+    pub position: i64,
+}
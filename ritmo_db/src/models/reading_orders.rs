@@ -0,0 +1,195 @@
+use crate::models::ReadingOrderItem;
+use crate::ordering::{Member, READING_ORDER_ITEMS};
+use ritmo_errors::RitmoResult;
+use sqlx::FromRow;
+
+/// Ordine di lettura con nome di una serie (pubblicazione, cronologico, consigliato...),
+/// indipendente da `series_index`
+#[derive(Debug, Clone, FromRow, Default)]
+pub struct ReadingOrder {
+    pub id: Option<i64>,
+    pub series_id: i64,
+    pub name: String,
+    pub description: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Voce di un ordine di lettura con il nome dell'elemento e il suo stato di lettura
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReadingOrderEntry {
+    pub item_id: i64,
+    pub book_id: Option<i64>,
+    pub content_id: Option<i64>,
+    pub name: String,
+    pub position: i64,
+    /// Libro letto o abbandonato; un contenuto lo è se lo è almeno un libro che lo contiene
+    pub done: bool,
+}
+
+impl ReadingOrder {
+    pub fn new(series_id: i64, name: &str) -> Self {
+        Self {
+            series_id,
+            name: name.to_string(),
+            ..Default::default()
+        }
+    }
+
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let result = sqlx::query!(
+            "INSERT INTO reading_orders (series_id, name, description, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+            self.series_id,
+            self.name,
+            self.description,
+            now,
+            now
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<ReadingOrder>, sqlx::Error> {
+        let order = sqlx::query_as!(ReadingOrder, "SELECT * FROM reading_orders WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(order)
+    }
+
+    pub async fn get_by_name(
+        pool: &sqlx::SqlitePool,
+        series_id: i64,
+        name: &str,
+    ) -> Result<Option<ReadingOrder>, sqlx::Error> {
+        let order = sqlx::query_as!(
+            ReadingOrder,
+            "SELECT * FROM reading_orders WHERE series_id = ? AND name = ?",
+            series_id,
+            name
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(order)
+    }
+
+    pub async fn update(&self, pool: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let result = sqlx::query!(
+            "UPDATE reading_orders SET name = ?, description = ?, updated_at = ? WHERE id = ?",
+            self.name,
+            self.description,
+            now,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM reading_orders WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_by_series(pool: &sqlx::SqlitePool, series_id: i64) -> Result<Vec<ReadingOrder>, sqlx::Error> {
+        let orders = sqlx::query_as!(
+            ReadingOrder,
+            "SELECT * FROM reading_orders WHERE series_id = ? ORDER BY name",
+            series_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(orders)
+    }
+
+    /// Aggiunge un libro in fondo all'ordine; se c'è già restituisce l'elemento esistente.
+    /// Il libro non deve per forza appartenere alla serie (spin-off, racconti collegati).
+    pub async fn add_book(pool: &sqlx::SqlitePool, order_id: i64, book_id: i64) -> Result<i64, sqlx::Error> {
+        let mut conn = pool.acquire().await?;
+        READING_ORDER_ITEMS.append(&mut conn, order_id, Member::Book(book_id)).await
+    }
+
+    /// Aggiunge un contenuto in fondo all'ordine, come `add_book`
+    pub async fn add_content(pool: &sqlx::SqlitePool, order_id: i64, content_id: i64) -> Result<i64, sqlx::Error> {
+        let mut conn = pool.acquire().await?;
+        READING_ORDER_ITEMS.append(&mut conn, order_id, Member::Content(content_id)).await
+    }
+
+    pub async fn remove_item(pool: &sqlx::SqlitePool, item_id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM reading_order_items WHERE id = ?", item_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_items(pool: &sqlx::SqlitePool, order_id: i64) -> Result<Vec<ReadingOrderItem>, sqlx::Error> {
+        let items = sqlx::query_as!(
+            ReadingOrderItem,
+            "SELECT * FROM reading_order_items WHERE order_id = ? ORDER BY position, id",
+            order_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(items)
+    }
+
+    /// Riordina gli elementi dell'ordine di lettura. `ordered_item_ids` deve contenerli tutti.
+    pub async fn reorder_items(pool: &sqlx::SqlitePool, order_id: i64, ordered_item_ids: &[i64]) -> RitmoResult<()> {
+        let mut tx = pool.begin().await?;
+        READING_ORDER_ITEMS.reorder(&mut tx, Some(order_id), ordered_item_ids).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Sposta un elemento nella posizione indicata (a partire da 1), facendo scorrere gli altri
+    pub async fn move_item(pool: &sqlx::SqlitePool, item_id: i64, new_position: usize) -> RitmoResult<()> {
+        let mut tx = pool.begin().await?;
+        READING_ORDER_ITEMS.move_item(&mut tx, item_id, new_position).await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Le voci dell'ordine, con nome e stato di lettura; libri e contenuti cestinati sono esclusi
+    pub async fn entries(pool: &sqlx::SqlitePool, order_id: i64) -> Result<Vec<ReadingOrderEntry>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT i.id AS \"item_id!\", i.book_id, i.content_id, i.position,
+                    COALESCE(b.name, c.name) AS \"name!: String\",
+                    CASE
+                        WHEN b.id IS NOT NULL THEN b.read_status IN ('read', 'abandoned')
+                        ELSE EXISTS (
+                            SELECT 1 FROM x_books_contents bc JOIN books rb ON rb.id = bc.book_id
+                            WHERE bc.content_id = c.id AND rb.read_status IN ('read', 'abandoned')
+                              AND rb.deleted_at IS NULL
+                        )
+                    END AS \"done!: bool\"
+             FROM reading_order_items i
+             LEFT JOIN books b ON b.id = i.book_id AND b.deleted_at IS NULL
+             LEFT JOIN contents c ON c.id = i.content_id AND c.deleted_at IS NULL
+             WHERE i.order_id = ? AND (b.id IS NOT NULL OR c.id IS NOT NULL)
+             ORDER BY i.position, i.id",
+            order_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| ReadingOrderEntry {
+                item_id: r.item_id,
+                book_id: r.book_id,
+                content_id: r.content_id,
+                name: r.name,
+                position: r.position,
+                done: r.done,
+            })
+            .collect())
+    }
+
+    /// Il primo elemento dell'ordine non ancora letto; None se l'ordine è stato completato
+    pub async fn next_unread(pool: &sqlx::SqlitePool, order_id: i64) -> Result<Option<ReadingOrderEntry>, sqlx::Error> {
+        Ok(Self::entries(pool, order_id).await?.into_iter().find(|e| !e.done))
+    }
+}
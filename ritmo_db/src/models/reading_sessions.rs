@@ -0,0 +1,217 @@
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{FromRow, SqliteConnection};
+
+/// Stato di lettura di un libro (colonna `books.read_status`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadingStatus {
+    Unread,
+    Reading,
+    Read,
+    Abandoned,
+}
+
+impl ReadingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unread => "unread",
+            Self::Reading => "reading",
+            Self::Read => "read",
+            Self::Abandoned => "abandoned",
+        }
+    }
+
+    pub fn parse(status: &str) -> RitmoResult<Self> {
+        match status {
+            "unread" => Ok(Self::Unread),
+            "reading" => Ok(Self::Reading),
+            "read" => Ok(Self::Read),
+            "abandoned" => Ok(Self::Abandoned),
+            other => Err(RitmoErr::InvalidInput(format!("stato di lettura sconosciuto: {}", other))),
+        }
+    }
+}
+
+/// Una lettura di un libro. Le riletture sono sessioni separate, così restano le date
+/// di ogni lettura; lo stato in `books.read_status` segue la sessione più recente.
+#[derive(Debug, Clone, FromRow)]
+pub struct ReadingSession {
+    pub id: Option<i64>,
+    pub book_id: i64,
+    /// 'reading', 'read' o 'abandoned'
+    pub status: String,
+    /// Percentuale di avanzamento, da 0 a 100
+    pub progress: f64,
+    pub started_at: i64,
+    pub finished_at: Option<i64>,
+    pub notes: Option<String>,
+}
+
+impl ReadingSession {
+    /// Inizia una nuova lettura del libro
+    pub async fn start(pool: &sqlx::SqlitePool, book_id: i64, started_at: i64) -> Result<i64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let id = sqlx::query!(
+            "INSERT INTO reading_sessions (book_id, status, progress, started_at) VALUES (?, 'reading', 0, ?)",
+            book_id,
+            started_at
+        )
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        Self::sync_book_status(&mut tx, book_id).await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<ReadingSession>, sqlx::Error> {
+        let session = sqlx::query_as!(
+            ReadingSession,
+            "SELECT * FROM reading_sessions WHERE id = ?",
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(session)
+    }
+
+    pub async fn update_progress(pool: &sqlx::SqlitePool, id: i64, progress: f64) -> RitmoResult<u64> {
+        if !(0.0..=100.0).contains(&progress) {
+            return Err(RitmoErr::InvalidInput(format!("avanzamento non valido: {}", progress)));
+        }
+        let result = sqlx::query!(
+            "UPDATE reading_sessions SET progress = ? WHERE id = ?",
+            progress,
+            id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Conclude la lettura: avanzamento al 100% e libro segnato come letto
+    pub async fn finish(pool: &sqlx::SqlitePool, id: i64, finished_at: i64) -> Result<u64, sqlx::Error> {
+        Self::close(pool, id, ReadingStatus::Read, finished_at).await
+    }
+
+    /// Interrompe la lettura senza finirla; l'avanzamento raggiunto resta registrato
+    pub async fn abandon(pool: &sqlx::SqlitePool, id: i64, abandoned_at: i64) -> Result<u64, sqlx::Error> {
+        Self::close(pool, id, ReadingStatus::Abandoned, abandoned_at).await
+    }
+
+    async fn close(
+        pool: &sqlx::SqlitePool,
+        id: i64,
+        status: ReadingStatus,
+        at: i64,
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let Some(book_id) = sqlx::query_scalar!("SELECT book_id FROM reading_sessions WHERE id = ?", id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(0);
+        };
+        let status = status.as_str();
+        let result = sqlx::query!(
+            "UPDATE reading_sessions SET
+                status = ?1,
+                finished_at = ?2,
+                progress = CASE WHEN ?1 = 'read' THEN 100 ELSE progress END
+             WHERE id = ?3",
+            status,
+            at,
+            id
+        )
+        .execute(&mut *tx)
+        .await?;
+        Self::sync_book_status(&mut tx, book_id).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let Some(book_id) = sqlx::query_scalar!("SELECT book_id FROM reading_sessions WHERE id = ?", id)
+            .fetch_optional(&mut *tx)
+            .await?
+        else {
+            return Ok(0);
+        };
+        let result = sqlx::query!("DELETE FROM reading_sessions WHERE id = ?", id)
+            .execute(&mut *tx)
+            .await?;
+        Self::sync_book_status(&mut tx, book_id).await?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Riporta in `books.read_status` lo stato della sessione più recente. Una rilettura
+    /// abbandonata non cancella una lettura completata in precedenza.
+    async fn sync_book_status(conn: &mut SqliteConnection, book_id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "UPDATE books SET read_status = COALESCE(
+                (SELECT CASE
+                    WHEN s.status = 'abandoned'
+                     AND EXISTS (SELECT 1 FROM reading_sessions r WHERE r.book_id = ?1 AND r.status = 'read')
+                    THEN 'read'
+                    ELSE s.status
+                 END
+                 FROM reading_sessions s
+                 WHERE s.book_id = ?1
+                 ORDER BY s.started_at DESC, s.id DESC
+                 LIMIT 1),
+                'unread')
+             WHERE id = ?1",
+            book_id
+        )
+        .execute(&mut *conn)
+        .await?;
+        Ok(())
+    }
+
+    /// Sessione in corso del libro, se c'è
+    pub async fn current(pool: &sqlx::SqlitePool, book_id: i64) -> Result<Option<ReadingSession>, sqlx::Error> {
+        let session = sqlx::query_as!(
+            ReadingSession,
+            "SELECT * FROM reading_sessions
+             WHERE book_id = ? AND status = 'reading'
+             ORDER BY started_at DESC, id DESC
+             LIMIT 1",
+            book_id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(session)
+    }
+
+    pub async fn list_by_book(pool: &sqlx::SqlitePool, book_id: i64) -> Result<Vec<ReadingSession>, sqlx::Error> {
+        let sessions = sqlx::query_as!(
+            ReadingSession,
+            "SELECT * FROM reading_sessions WHERE book_id = ? ORDER BY started_at, id",
+            book_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(sessions)
+    }
+
+    /// Quante volte il libro è stato letto fino in fondo
+    pub async fn read_count(pool: &sqlx::SqlitePool, book_id: i64) -> Result<i64, sqlx::Error> {
+        let count = sqlx::query_scalar!(
+            "SELECT COUNT(*) FROM reading_sessions WHERE book_id = ? AND status = 'read'",
+            book_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(count)
+    }
+}
+
+pub(crate) fn check_rating(rating: Option<f64>) -> RitmoResult<()> {
+    match rating {
+        Some(r) if !(0.0..=5.0).contains(&r) => {
+            Err(RitmoErr::InvalidInput(format!("valutazione non valida: {}", r)))
+        }
+        _ => Ok(()),
+    }
+}
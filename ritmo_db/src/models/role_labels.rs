@@ -0,0 +1,47 @@
+use sqlx::FromRow;
+
+/// Etichetta di un ruolo in una lingua (codice ISO a 2 caratteri)
+#[derive(Debug, Clone, FromRow, PartialEq, Eq)]
+pub struct RoleLabel {
+    pub role_id: i64,
+    pub language: String,
+    pub label: String,
+}
+
+impl RoleLabel {
+    /// Inserisce l'etichetta, sostituendo quella esistente per la stessa lingua
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query!(
+            "INSERT INTO role_labels (role_id, language, label) VALUES (?, ?, ?)
+             ON CONFLICT(role_id, language) DO UPDATE SET label = excluded.label",
+            self.role_id,
+            self.language,
+            self.label
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete(pool: &sqlx::SqlitePool, role_id: i64, language: &str) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "DELETE FROM role_labels WHERE role_id = ? AND language = ?",
+            role_id,
+            language
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_by_role(pool: &sqlx::SqlitePool, role_id: i64) -> Result<Vec<RoleLabel>, sqlx::Error> {
+        let labels = sqlx::query_as!(
+            RoleLabel,
+            "SELECT role_id, language, label FROM role_labels WHERE role_id = ? ORDER BY language",
+            role_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(labels)
+    }
+}
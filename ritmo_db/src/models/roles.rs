@@ -1,18 +1,33 @@
 use ritmo_errors::RitmoResult;
-use sqlx::FromRow;
+use sqlx::{FromRow, SqliteConnection};
+use futures::stream::BoxStream;
 
+/// Ruolo di una persona in un libro o in un contenuto.
+///
+/// Il campo `code` contiene il codice MARC relator (aut, trl, edt, ...), stabile e indipendente
+/// dalla lingua: è quello che va usato nelle query, mentre `name` e le etichette in `role_labels`
+/// servono solo per la visualizzazione.
 #[derive(Debug, Clone, FromRow)]
 pub struct Role {
     pub id: Option<i64>,
+    pub code: Option<String>,
     pub name: String,
     pub description: Option<String>,
     pub created_at: i64,
 }
 
 impl Role {
+    pub const AUTHOR: &'static str = "aut";
+    pub const TRANSLATOR: &'static str = "trl";
+    pub const EDITOR: &'static str = "edt";
+    pub const ILLUSTRATOR: &'static str = "ill";
+    pub const NARRATOR: &'static str = "nrt";
+    pub const INTRODUCTION_AUTHOR: &'static str = "aui";
+
     pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
         let rec = sqlx::query!(
-                "INSERT INTO roles (name, description) VALUES (?, ?)",
+                "INSERT INTO roles (code, name, description) VALUES (?, ?, ?)",
+                self.code,
                 self.name,
                 self.description
             )
@@ -26,7 +41,68 @@ impl Role {
     pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> RitmoResult<Option<Role>> {
         let result = sqlx::query_as!(
             Role,
-            "SELECT id, name, description, created_at FROM roles WHERE id = ?",
+            "SELECT id, code, name, description, created_at FROM roles WHERE id = ?",
+            id
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(result)
+    }
+
+    pub async fn get_by_code(pool: &sqlx::SqlitePool, code: &str) -> Result<Option<Role>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            Role,
+            "SELECT id, code, name, description, created_at FROM roles WHERE code = ?",
+            code
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(result)
+    }
+
+    /// Cerca un ruolo per codice, per nome o per etichetta in una qualsiasi lingua
+    /// (es. "Autore", "Author" e "aut" restituiscono lo stesso ruolo).
+    pub async fn find_by_label(pool: &sqlx::SqlitePool, label: &str) -> Result<Option<Role>, sqlx::Error> {
+        let mut conn = pool.acquire().await?;
+        Self::find_by_label_in(&mut conn, label).await
+    }
+
+    /// Come `find_by_label`, su una connessione già aperta. Il codice ha la precedenza,
+    /// poi il nome e infine le etichette.
+    pub(crate) async fn find_by_label_in(
+        conn: &mut SqliteConnection,
+        label: &str,
+    ) -> Result<Option<Role>, sqlx::Error> {
+        let label = label.trim();
+        let result = sqlx::query_as!(
+            Role,
+            "SELECT r.id, r.code, r.name, r.description, r.created_at
+             FROM roles r
+             WHERE r.code = ?1 COLLATE NOCASE
+                OR r.name = ?1 COLLATE NOCASE
+                OR r.id IN (SELECT role_id FROM role_labels WHERE label = ?1 COLLATE NOCASE)
+             ORDER BY r.code = ?1 COLLATE NOCASE DESC, r.name = ?1 COLLATE NOCASE DESC, r.id
+             LIMIT 1",
+            label
+        )
+        .fetch_optional(&mut *conn)
+        .await?;
+        Ok(result)
+    }
+
+    /// Nome del ruolo nella lingua richiesta (codice ISO a 2 caratteri), con fallback su `name`
+    pub async fn localized_name(
+        pool: &sqlx::SqlitePool,
+        id: i64,
+        language: &str,
+    ) -> Result<Option<String>, sqlx::Error> {
+        let result = sqlx::query_scalar!(
+            "SELECT COALESCE(
+                (SELECT label FROM role_labels WHERE role_id = r.id AND language = ?),
+                r.name
+             ) AS \"label!: String\"
+             FROM roles r WHERE r.id = ?",
+            language,
             id
         )
         .fetch_optional(pool)
@@ -34,6 +110,25 @@ impl Role {
         Ok(result)
     }
 
+    pub async fn list_all(pool: &sqlx::SqlitePool) -> Result<Vec<Role>, sqlx::Error> {
+        let all = sqlx::query_as!(
+            Role,
+            "SELECT id, code, name, description, created_at FROM roles ORDER BY name"
+            )
+            .fetch_all(pool)
+            .await?;
+        Ok(all)
+    }
+
+    /// Come `list_all`, ma in streaming
+    pub fn stream_all(pool: &sqlx::SqlitePool) -> BoxStream<'_, Result<Role, sqlx::Error>> {
+        sqlx::query_as!(
+            Role,
+            "SELECT id, code, name, description, created_at FROM roles ORDER BY name"
+        )
+        .fetch(pool)
+    }
+
     pub async fn update(
         pool: &sqlx::SqlitePool,
         id: i64,
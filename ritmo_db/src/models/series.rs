@@ -1,4 +1,9 @@
+use crate::models::merge::{record_merge, MergeSummary};
+use ritmo_errors::{RitmoErr, RitmoResult};
 use sqlx::FromRow;
+use async_stream::try_stream;
+use futures::stream::BoxStream;
+use futures::TryStreamExt;
 
 #[derive(Debug, Clone, FromRow)]
 pub struct Series {
@@ -9,18 +14,40 @@ pub struct Series {
     pub completed: i64,
     pub created_at: i64,
     pub updated_at: i64,
+    /// Serie che contiene questa (universo → ciclo → trilogia)
+    pub parent_id: Option<i64>,
+}
+
+/// Libro o contenuto che fa parte di una serie
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeriesMember {
+    pub book_id: Option<i64>,
+    pub content_id: Option<i64>,
+    pub name: String,
+    pub series_index: Option<f64>,
+}
+
+/// Confronto tra gli indici posseduti e il numero di volumi dichiarato dalla serie
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SeriesGaps {
+    pub total_books: Option<i64>,
+    /// Indici posseduti, compresi quelli frazionari (2.5) e lo zero dei prequel
+    pub owned: Vec<f64>,
+    /// Indici interi da 1 a `total_books` che mancano
+    pub missing: Vec<i64>,
 }
 
 impl Series {
     pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
         let now = chrono::Utc::now().timestamp();
         let result = sqlx::query!(
-            "INSERT INTO series (name, description, total_books, completed, created_at, updated_at)
-             VALUES (?, ?, ?, ?, ?, ?)",
+            "INSERT INTO series (name, description, total_books, completed, parent_id, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
             self.name,
             self.description,
             self.total_books,
             self.completed,
+            self.parent_id,
             now,
             now
         )
@@ -54,6 +81,8 @@ impl Series {
         Ok(series)
     }
 
+    /// Aggiorna i dati della serie; per cambiare serie madre si usa `move_to`.
+    /// `completed` viene ricalcolato dal database quando `total_books` è valorizzato.
     pub async fn update(&self, pool: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
         let now = chrono::Utc::now().timestamp();
         let result = sqlx::query!(
@@ -90,6 +119,11 @@ impl Series {
         Ok(all)
     }
 
+    /// Come `list_all`, ma in streaming
+    pub fn stream_all(pool: &sqlx::SqlitePool) -> BoxStream<'_, Result<Series, sqlx::Error>> {
+        sqlx::query_as!(Series, "SELECT * FROM series ORDER BY name").fetch(pool)
+    }
+
     pub async fn search(pool: &sqlx::SqlitePool, pattern: &str) -> Result<Vec<Series>, sqlx::Error> {
         let search_pattern = format!("%{}%", pattern);
         let found = sqlx::query_as!(
@@ -102,4 +136,303 @@ impl Series {
         .await?;
         Ok(found)
     }
+
+    /// Come `search`, ma in streaming
+    pub fn stream_search<'a>(pool: &'a sqlx::SqlitePool, pattern: &str) -> BoxStream<'a, Result<Series, sqlx::Error>> {
+        let search_pattern = format!("%{}%", pattern);
+        Box::pin(try_stream! {
+            let mut rows = sqlx::query_as!(
+                Series,
+                "SELECT * FROM series WHERE name LIKE ? OR description LIKE ? ORDER BY name",
+                search_pattern,
+                search_pattern
+            )
+            .fetch(pool);
+            while let Some(series) = rows.try_next().await? {
+                yield series;
+            }
+        })
+    }
+
+    /// Fonde la serie `merged_id` in `survivor_id` in un'unica transazione:
+    /// sposta i libri sulla serie superstite, completa i campi vuoti della superstite
+    /// e registra l'operazione in `audit_log`.
+    pub async fn merge(
+        pool: &sqlx::SqlitePool,
+        survivor_id: i64,
+        merged_id: i64,
+    ) -> RitmoResult<MergeSummary> {
+        if survivor_id == merged_id {
+            return Err(RitmoErr::MergeError(format!(
+                "impossibile fondere la serie {} con se stessa",
+                survivor_id
+            )));
+        }
+        let mut tx = pool.begin().await?;
+
+        let survivor_exists = sqlx::query_scalar!("SELECT id FROM series WHERE id = ?", survivor_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let old_values = sqlx::query_scalar!(
+            "SELECT json_object('id', id, 'name', name, 'description', description, 'total_books', total_books, 'completed', completed) AS \"json!: String\"
+             FROM series WHERE id = ?",
+            merged_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let (Some(_), Some(old_values)) = (survivor_exists, old_values) else {
+            return Err(RitmoErr::MergeError(format!(
+                "serie {} e {} non trovate entrambe",
+                survivor_id, merged_id
+            )));
+        };
+
+        let mut summary = MergeSummary::new(survivor_id, merged_id);
+
+        summary.links_moved += sqlx::query!(
+            "UPDATE books SET series_id = ? WHERE series_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        summary.links_moved += sqlx::query!(
+            "UPDATE contents SET series_id = ? WHERE series_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        // Gli ordini di lettura passano alla serie superstite; se ne ha già uno con lo stesso
+        // nome, gli elementi mancanti vengono accodati a quello e il doppione sparisce con la serie
+        summary.links_moved += sqlx::query!(
+            "UPDATE OR IGNORE reading_orders SET series_id = ? WHERE series_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        summary.links_moved += sqlx::query!(
+            "INSERT OR IGNORE INTO reading_order_items (order_id, book_id, content_id, position)
+             SELECT s.id, i.book_id, i.content_id,
+                    (SELECT COALESCE(MAX(position), 0) FROM reading_order_items WHERE order_id = s.id) + i.position
+             FROM reading_orders m
+             JOIN reading_orders s ON s.series_id = ?1 AND s.name = m.name
+             JOIN reading_order_items i ON i.order_id = m.id
+             WHERE m.series_id = ?2
+             ORDER BY i.position, i.id",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        // Come per i tag: se la superstite sta nel sottoalbero della serie eliminata (anche a più
+        // livelli di distanza) le sottoserie salgono al genitore di quest'ultima, altrimenti
+        // passano alla superstite
+        let inside = sqlx::query_scalar!(
+            "WITH RECURSIVE ancestors(id, depth) AS (
+                SELECT ?1, 0
+                UNION ALL
+                SELECT s.parent_id, a.depth + 1 FROM series s JOIN ancestors a ON s.id = a.id
+                WHERE s.parent_id IS NOT NULL AND a.depth < 64
+             )
+             SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = ?2) AS \"inside!: bool\"",
+            survivor_id,
+            merged_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if inside {
+            sqlx::query!(
+                "UPDATE series SET parent_id = (SELECT parent_id FROM series WHERE id = ?1) WHERE parent_id = ?1",
+                merged_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE series SET parent_id = ? WHERE parent_id = ?",
+                survivor_id,
+                merged_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        // La superstite eredita il genitore solo se non ne ha uno e se quel genitore non sta sotto
+        // di lei, cosa che succede quando è la serie eliminata a trovarsi nel suo sottoalbero
+        sqlx::query!(
+            "WITH RECURSIVE ancestors(id, depth) AS (
+                SELECT parent_id, 0 FROM series WHERE id = ?1 AND parent_id IS NOT NULL
+                UNION ALL
+                SELECT s.parent_id, a.depth + 1 FROM series s JOIN ancestors a ON s.id = a.id
+                WHERE s.parent_id IS NOT NULL AND a.depth < 64
+             )
+             UPDATE series SET
+                description = COALESCE(series.description, l.description),
+                total_books = COALESCE(series.total_books, l.total_books),
+                parent_id = CASE
+                    WHEN series.parent_id IS NOT NULL THEN series.parent_id
+                    WHEN EXISTS (SELECT 1 FROM ancestors WHERE id = series.id) THEN NULL
+                    ELSE l.parent_id END,
+                completed = MAX(series.completed, l.completed)
+             FROM (SELECT * FROM series WHERE id = ?1) AS l
+             WHERE series.id = ?2",
+            merged_id,
+            survivor_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        record_merge(&mut tx, "series", &summary, &old_values).await?;
+
+        sqlx::query!("DELETE FROM series WHERE id = ?", merged_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(summary)
+    }
+
+    /// Sposta la serie sotto `parent_id` (None la rende di primo livello), rifiutando i cicli
+    pub async fn move_to(pool: &sqlx::SqlitePool, id: i64, parent_id: Option<i64>) -> RitmoResult<()> {
+        let mut tx = pool.begin().await?;
+        if let Some(parent_id) = parent_id {
+            let cycle = sqlx::query_scalar!(
+                "WITH RECURSIVE ancestors(id, depth) AS (
+                    SELECT ?1, 0
+                    UNION ALL
+                    SELECT s.parent_id, a.depth + 1 FROM series s JOIN ancestors a ON s.id = a.id
+                    WHERE s.parent_id IS NOT NULL AND a.depth < 64
+                 )
+                 SELECT COUNT(*) FROM ancestors WHERE id = ?2",
+                parent_id,
+                id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if cycle > 0 {
+                return Err(RitmoErr::InvalidInput(
+                    "una serie non può essere spostata dentro sé stessa".to_string(),
+                ));
+            }
+        }
+        sqlx::query!("UPDATE series SET parent_id = ? WHERE id = ?", parent_id, id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Serie figlie di `parent_id`; con None restituisce le serie di primo livello
+    pub async fn list_children(pool: &sqlx::SqlitePool, parent_id: Option<i64>) -> Result<Vec<Series>, sqlx::Error> {
+        let children = sqlx::query_as!(
+            Series,
+            "SELECT * FROM series WHERE parent_id IS ? ORDER BY name",
+            parent_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(children)
+    }
+
+    /// La serie e tutte le sue discendenti, a qualsiasi profondità
+    pub async fn subtree(pool: &sqlx::SqlitePool, id: i64) -> Result<Vec<Series>, sqlx::Error> {
+        let all = sqlx::query_as!(
+            Series,
+            "WITH RECURSIVE tree(id, depth) AS (
+                SELECT ?, 0
+                UNION ALL
+                SELECT s.id, t.depth + 1 FROM series s JOIN tree t ON s.parent_id = t.id
+                WHERE t.depth < 64
+             )
+             SELECT s.* FROM series s JOIN tree t ON t.id = s.id ORDER BY t.depth, s.name",
+            id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(all)
+    }
+
+    /// Inserisce un contenuto nella serie, ad esempio un racconto con indice 2.5
+    pub async fn add_content(
+        pool: &sqlx::SqlitePool,
+        series_id: i64,
+        content_id: i64,
+        series_index: Option<f64>,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE contents SET series_id = ?, series_index = ? WHERE id = ?",
+            series_id,
+            series_index,
+            content_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn remove_content(pool: &sqlx::SqlitePool, content_id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!(
+            "UPDATE contents SET series_id = NULL, series_index = NULL WHERE id = ?",
+            content_id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Libri e contenuti della serie in ordine di indice; quelli senza indice vanno in fondo
+    pub async fn members(pool: &sqlx::SqlitePool, series_id: i64) -> Result<Vec<SeriesMember>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT book_id AS \"book_id?: i64\", content_id AS \"content_id?: i64\", name AS \"name!\",
+                    series_index AS \"series_index?: f64\"
+             FROM (
+                SELECT id AS book_id, NULL AS content_id, name, series_index
+                FROM books WHERE series_id = ?1 AND deleted_at IS NULL
+                UNION ALL
+                SELECT NULL, id, name, series_index
+                FROM contents WHERE series_id = ?1 AND deleted_at IS NULL
+             )
+             ORDER BY series_index IS NULL, series_index, name",
+            series_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| SeriesMember {
+                book_id: r.book_id,
+                content_id: r.content_id,
+                name: r.name,
+                series_index: r.series_index,
+            })
+            .collect())
+    }
+
+    /// Confronta gli indici posseduti (libri e contenuti non cestinati) con `total_books`
+    pub async fn gaps(pool: &sqlx::SqlitePool, series_id: i64) -> RitmoResult<SeriesGaps> {
+        let Some(series) = Self::get(pool, series_id).await? else {
+            return Err(RitmoErr::NoResultsError(format!("serie {} non trovata", series_id)));
+        };
+        let mut owned: Vec<f64> = Self::members(pool, series_id)
+            .await?
+            .into_iter()
+            .filter_map(|m| m.series_index)
+            .collect();
+        owned.sort_by(f64::total_cmp);
+        owned.dedup();
+        let missing = (1..=series.total_books.unwrap_or(0))
+            .filter(|i| !owned.contains(&(*i as f64)))
+            .collect();
+        Ok(SeriesGaps {
+            total_books: series.total_books,
+            owned,
+            missing,
+        })
+    }
 }
@@ -1,12 +1,49 @@
 use chrono::Utc;
 use ritmo_core::ContentDto;
+use crate::models::merge::{record_merge, MergeSummary};
+use crate::models::{Book, Content};
+use ritmo_errors::{RitmoErr, RitmoResult};
 use sqlx::FromRow;
 
+/// Categoria di un tag (colonna `tags.category`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagCategory {
+    Genre,
+    Mood,
+    Setting,
+    Personal,
+}
+
+impl TagCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Genre => "genre",
+            Self::Mood => "mood",
+            Self::Setting => "setting",
+            Self::Personal => "personal",
+        }
+    }
+
+    pub fn parse(category: &str) -> RitmoResult<Self> {
+        match category.trim().to_lowercase().as_str() {
+            "genre" => Ok(Self::Genre),
+            "mood" => Ok(Self::Mood),
+            "setting" => Ok(Self::Setting),
+            "personal" => Ok(Self::Personal),
+            other => Err(RitmoErr::InvalidInput(format!("categoria di tag sconosciuta: {}", other))),
+        }
+    }
+}
+
 #[derive(Debug, Clone, FromRow, Default)]
 pub struct Tag {
     pub id: Option<i64>,
     pub name: String,
     pub created_at: Option<i64>,
+    /// Tag più generale: cercando "fantascienza" si trovano anche i libri con "cyberpunk"
+    pub parent_id: Option<i64>,
+    /// Vedi `TagCategory`
+    pub category: Option<String>,
 }
 
 impl Tag {
@@ -14,8 +51,10 @@ impl Tag {
         let now = chrono::Utc::now().timestamp();
         let result =
             sqlx::query!(
-                "INSERT INTO tags (name, created_at) VALUES (?, ?)",
+                "INSERT INTO tags (name, parent_id, category, created_at) VALUES (?, ?, ?, ?)",
                 self.name,
+                self.parent_id,
+                self.category,
                 now
                 )
                 .execute(pool)
@@ -30,13 +69,14 @@ impl Tag {
             id: None,
             name: content_dto.name.clone(),
             created_at: Some(now),
+            ..Default::default()
         }
     }
 
     pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<Tag>, sqlx::Error> {
         let result = sqlx::query_as!(
             Tag,
-            "SELECT id, name, created_at FROM tags WHERE id = ?",
+            "SELECT id, name, created_at, parent_id, category FROM tags WHERE id = ?",
             id
         )
         .fetch_optional(pool)
@@ -55,6 +95,163 @@ impl Tag {
         Ok(())
     }
 
+    pub async fn get_by_name(pool: &sqlx::SqlitePool, name: &str) -> Result<Option<Tag>, sqlx::Error> {
+        let result = sqlx::query_as!(
+            Tag,
+            "SELECT id, name, created_at, parent_id, category FROM tags WHERE name = ? COLLATE NOCASE",
+            name
+        )
+        .fetch_optional(pool)
+        .await?;
+        Ok(result)
+    }
+
+    /// Rinomina il tag. Se il nuovo nome appartiene già a un altro tag, i due vengono fusi
+    /// e i collegamenti passano a quello esistente. Restituisce l'id del tag che porta il nome.
+    pub async fn rename(pool: &sqlx::SqlitePool, id: i64, name: &str) -> RitmoResult<i64> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(RitmoErr::InvalidInput("il nome del tag non può essere vuoto".to_string()));
+        }
+        match Self::get_by_name(pool, name).await? {
+            Some(Tag { id: Some(existing), .. }) if existing != id => {
+                Self::merge(pool, existing, id).await?;
+                Ok(existing)
+            }
+            _ => {
+                Self::update(pool, id, name).await?;
+                Ok(id)
+            }
+        }
+    }
+
+    pub async fn set_category(
+        pool: &sqlx::SqlitePool,
+        id: i64,
+        category: Option<TagCategory>,
+    ) -> Result<u64, sqlx::Error> {
+        let category = category.map(|c| c.as_str());
+        let result = sqlx::query!("UPDATE tags SET category = ? WHERE id = ?", category, id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_by_category(pool: &sqlx::SqlitePool, category: TagCategory) -> Result<Vec<Tag>, sqlx::Error> {
+        let category = category.as_str();
+        let tags = sqlx::query_as!(
+            Tag,
+            "SELECT id, name, created_at, parent_id, category FROM tags WHERE category = ? ORDER BY name",
+            category
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(tags)
+    }
+
+    /// Sposta il tag, con tutto il suo sottoalbero, sotto `parent_id` (None lo rende di primo livello)
+    pub async fn move_to(pool: &sqlx::SqlitePool, id: i64, parent_id: Option<i64>) -> RitmoResult<()> {
+        let mut tx = pool.begin().await?;
+        if let Some(parent_id) = parent_id {
+            let cycle = sqlx::query_scalar!(
+                "WITH RECURSIVE ancestors(id, depth) AS (
+                    SELECT ?1, 0
+                    UNION ALL
+                    SELECT t.parent_id, a.depth + 1 FROM tags t JOIN ancestors a ON t.id = a.id
+                    WHERE t.parent_id IS NOT NULL AND a.depth < 64
+                 )
+                 SELECT COUNT(*) FROM ancestors WHERE id = ?2",
+                parent_id,
+                id
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+            if cycle > 0 {
+                return Err(RitmoErr::InvalidInput(
+                    "un tag non può essere spostato dentro sé stesso".to_string(),
+                ));
+            }
+        }
+        sqlx::query!("UPDATE tags SET parent_id = ? WHERE id = ?", parent_id, id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Tag figli di `parent_id`; con None restituisce i tag di primo livello
+    pub async fn list_children(pool: &sqlx::SqlitePool, parent_id: Option<i64>) -> Result<Vec<Tag>, sqlx::Error> {
+        let tags = sqlx::query_as!(
+            Tag,
+            "SELECT id, name, created_at, parent_id, category FROM tags WHERE parent_id IS ? ORDER BY name",
+            parent_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(tags)
+    }
+
+    /// Il tag e tutti i suoi discendenti
+    pub async fn subtree(pool: &sqlx::SqlitePool, id: i64) -> Result<Vec<Tag>, sqlx::Error> {
+        let tags = sqlx::query_as!(
+            Tag,
+            "WITH RECURSIVE tree(id, depth) AS (
+                SELECT ?, 0
+                UNION ALL
+                SELECT t.id, tree.depth + 1 FROM tags t JOIN tree ON t.parent_id = tree.id
+                WHERE tree.depth < 64
+             )
+             SELECT t.id, t.name, t.created_at, t.parent_id, t.category
+             FROM tags t JOIN tree ON tree.id = t.id ORDER BY tree.depth, t.name",
+            id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(tags)
+    }
+
+    /// Libri con il tag o con uno dei suoi discendenti
+    pub async fn books(pool: &sqlx::SqlitePool, id: i64) -> Result<Vec<Book>, sqlx::Error> {
+        let books = sqlx::query_as!(
+            Book,
+            "WITH RECURSIVE tree(id, depth) AS (
+                SELECT ?, 0
+                UNION ALL
+                SELECT t.id, tree.depth + 1 FROM tags t JOIN tree ON t.parent_id = tree.id
+                WHERE tree.depth < 64
+             )
+             SELECT b.* FROM books b
+             WHERE b.deleted_at IS NULL
+               AND b.id IN (SELECT x.book_id FROM x_books_tags x WHERE x.tag_id IN (SELECT id FROM tree))
+             ORDER BY b.name",
+            id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(books)
+    }
+
+    /// Contenuti con il tag o con uno dei suoi discendenti
+    pub async fn contents(pool: &sqlx::SqlitePool, id: i64) -> Result<Vec<Content>, sqlx::Error> {
+        let contents = sqlx::query_as!(
+            Content,
+            "WITH RECURSIVE tree(id, depth) AS (
+                SELECT ?, 0
+                UNION ALL
+                SELECT t.id, tree.depth + 1 FROM tags t JOIN tree ON t.parent_id = tree.id
+                WHERE tree.depth < 64
+             )
+             SELECT c.* FROM contents c
+             WHERE c.deleted_at IS NULL
+               AND c.id IN (SELECT x.content_id FROM x_contents_tags x WHERE x.tag_id IN (SELECT id FROM tree))
+             ORDER BY c.name",
+            id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(contents)
+    }
+
     pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<(), sqlx::Error> {
         sqlx::query!(
             "DELETE FROM tags WHERE id = ?",
@@ -64,4 +261,126 @@ impl Tag {
             .await?;
         Ok(())
     }
+
+    /// Fonde il tag `merged_id` in `survivor_id` in un'unica transazione:
+    /// sposta i collegamenti a libri e contenuti eliminando i duplicati, sposta i tag figli sotto
+    /// la superstite, completa i campi vuoti della superstite e registra l'operazione in `audit_log`.
+    pub async fn merge(
+        pool: &sqlx::SqlitePool,
+        survivor_id: i64,
+        merged_id: i64,
+    ) -> RitmoResult<MergeSummary> {
+        if survivor_id == merged_id {
+            return Err(RitmoErr::MergeError(format!(
+                "impossibile fondere il tag {} con se stesso",
+                survivor_id
+            )));
+        }
+        let mut tx = pool.begin().await?;
+
+        let survivor_exists = sqlx::query_scalar!("SELECT id FROM tags WHERE id = ?", survivor_id)
+            .fetch_optional(&mut *tx)
+            .await?;
+        let old_values = sqlx::query_scalar!(
+            "SELECT json_object('id', id, 'name', name, 'description', description,
+                                'parent_id', parent_id, 'category', category) AS \"json!: String\"
+             FROM tags WHERE id = ?",
+            merged_id
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        let (Some(_), Some(old_values)) = (survivor_exists, old_values) else {
+            return Err(RitmoErr::MergeError(format!(
+                "tag {} e {} non trovati entrambi",
+                survivor_id, merged_id
+            )));
+        };
+
+        let mut summary = MergeSummary::new(survivor_id, merged_id);
+
+        summary.links_moved += sqlx::query!(
+            "UPDATE OR IGNORE x_books_tags SET tag_id = ? WHERE tag_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        summary.duplicate_links_removed += sqlx::query!(
+            "DELETE FROM x_books_tags WHERE tag_id = ?",
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        summary.links_moved += sqlx::query!(
+            "UPDATE OR IGNORE x_contents_tags SET tag_id = ? WHERE tag_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        summary.duplicate_links_removed += sqlx::query!(
+            "DELETE FROM x_contents_tags WHERE tag_id = ?",
+            merged_id
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        // I figli passano alla superstite; se però la superstite sta nel sottoalbero del tag eliminato
+        // (ad esempio ne è figlia) il tag eliminato viene tolto dalla catena e i figli salgono di un livello
+        let inside = sqlx::query_scalar!(
+            "WITH RECURSIVE ancestors(id, depth) AS (
+                SELECT ?1, 0
+                UNION ALL
+                SELECT t.parent_id, a.depth + 1 FROM tags t JOIN ancestors a ON t.id = a.id
+                WHERE t.parent_id IS NOT NULL AND a.depth < 64
+             )
+             SELECT EXISTS (SELECT 1 FROM ancestors WHERE id = ?2) AS \"inside!: bool\"",
+            survivor_id,
+            merged_id
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if inside {
+            sqlx::query!(
+                "UPDATE tags SET parent_id = (SELECT parent_id FROM tags WHERE id = ?1) WHERE parent_id = ?1",
+                merged_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        } else {
+            sqlx::query!(
+                "UPDATE tags SET parent_id = ? WHERE parent_id = ?",
+                survivor_id,
+                merged_id
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query!(
+            "UPDATE tags SET
+                description = COALESCE(tags.description, l.description),
+                category = COALESCE(tags.category, l.category)
+             FROM (SELECT * FROM tags WHERE id = ?) AS l
+             WHERE tags.id = ?",
+            merged_id,
+            survivor_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        record_merge(&mut tx, "tags", &summary, &old_values).await?;
+
+        sqlx::query!("DELETE FROM tags WHERE id = ?", merged_id)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(summary)
+    }
 }
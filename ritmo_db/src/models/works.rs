@@ -0,0 +1,312 @@
+use crate::models::merge::{record_merge, MergeSummary};
+use crate::models::ContentRelationType;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{FromRow, Sqlite, SqliteConnection, Transaction};
+use futures::stream::BoxStream;
+
+/// Opera: raggruppa i contenuti che rappresentano lo stesso testo, ad esempio lo stesso racconto
+/// pubblicato in tre antologie, o un romanzo e le sue traduzioni.
+#[derive(Debug, Clone, FromRow, Default)]
+pub struct Work {
+    pub id: Option<i64>,
+    pub title: String,
+    /// Codice ISO a 2 caratteri della lingua originale
+    pub original_language: Option<String>,
+    /// Data della prima pubblicazione, codificata con `PartialDate::to_key`
+    pub first_published: Option<i64>,
+    pub notes: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Una comparsa dell'opera: un contenuto in un libro (o un contenuto non ancora in nessun libro)
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkAppearance {
+    pub content_id: i64,
+    pub content_name: String,
+    pub book_id: Option<i64>,
+    pub book_name: Option<String>,
+    /// Codici ISO delle lingue del contenuto, separati da virgola
+    pub languages: Option<String>,
+    /// Relazione del contenuto con un altro contenuto dell'opera (translation_of, ...)
+    pub relation: Option<String>,
+}
+
+impl Work {
+    pub async fn save(&self, pool: &sqlx::SqlitePool) -> Result<i64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let result = sqlx::query!(
+            "INSERT INTO works (title, original_language, first_published, notes, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            self.title,
+            self.original_language,
+            self.first_published,
+            self.notes,
+            now,
+            now
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.last_insert_rowid())
+    }
+
+    pub async fn get(pool: &sqlx::SqlitePool, id: i64) -> Result<Option<Work>, sqlx::Error> {
+        let work = sqlx::query_as!(Work, "SELECT * FROM works WHERE id = ?", id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(work)
+    }
+
+    pub async fn update(&self, pool: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
+        let now = chrono::Utc::now().timestamp();
+        let result = sqlx::query!(
+            "UPDATE works SET title = ?, original_language = ?, first_published = ?, notes = ?, updated_at = ?
+             WHERE id = ?",
+            self.title,
+            self.original_language,
+            self.first_published,
+            self.notes,
+            now,
+            self.id
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Elimina l'opera; i contenuti restano, senza opera
+    pub async fn delete(pool: &sqlx::SqlitePool, id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("DELETE FROM works WHERE id = ?", id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn list_all(pool: &sqlx::SqlitePool) -> Result<Vec<Work>, sqlx::Error> {
+        let all = sqlx::query_as!(Work, "SELECT * FROM works ORDER BY title")
+            .fetch_all(pool)
+            .await?;
+        Ok(all)
+    }
+
+    /// Come `list_all`, un'opera alla volta
+    pub fn stream_all(pool: &sqlx::SqlitePool) -> BoxStream<'_, Result<Work, sqlx::Error>> {
+        sqlx::query_as!(Work, "SELECT * FROM works ORDER BY title").fetch(pool)
+    }
+
+    /// Crea un'opera a partire da un contenuto (titolo originale, se c'è, altrimenti il titolo)
+    /// e ve lo collega. Se il contenuto ha già un'opera restituisce quella.
+    pub async fn from_content(pool: &sqlx::SqlitePool, content_id: i64) -> RitmoResult<i64> {
+        let mut tx = pool.begin().await?;
+        let work_id = Self::ensure_for_content(&mut tx, content_id).await?;
+        tx.commit().await?;
+        Ok(work_id)
+    }
+
+    /// Opera del contenuto, creata se manca, sulla transazione del chiamante
+    pub(crate) async fn ensure_for_content(conn: &mut SqliteConnection, content_id: i64) -> RitmoResult<i64> {
+        let Some(content) = sqlx::query!(
+            "SELECT name, original_title, publication_date, work_id FROM contents WHERE id = ?",
+            content_id
+        )
+        .fetch_optional(&mut *conn)
+        .await?
+        else {
+            return Err(RitmoErr::NoResultsError(format!("contenuto {} non trovato", content_id)));
+        };
+        if let Some(work_id) = content.work_id {
+            return Ok(work_id);
+        }
+        let now = chrono::Utc::now().timestamp();
+        let title = content.original_title.unwrap_or(content.name);
+        let work_id = sqlx::query!(
+            "INSERT INTO works (title, first_published, created_at, updated_at) VALUES (?, ?, ?, ?)",
+            title,
+            content.publication_date,
+            now,
+            now
+        )
+        .execute(&mut *conn)
+        .await?
+        .last_insert_rowid();
+        sqlx::query!("UPDATE contents SET work_id = ? WHERE id = ?", work_id, content_id)
+            .execute(&mut *conn)
+            .await?;
+        Ok(work_id)
+    }
+
+    pub async fn add_content(pool: &sqlx::SqlitePool, work_id: i64, content_id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("UPDATE contents SET work_id = ? WHERE id = ?", work_id, content_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn remove_content(pool: &sqlx::SqlitePool, content_id: i64) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query!("UPDATE contents SET work_id = NULL WHERE id = ?", content_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Collega due contenuti con una relazione tipizzata (`content_id` è traduzione,
+    /// revisione o estratto di `related_content_id`). I due contenuti finiscono nella stessa
+    /// opera: quella dell'originale, creata se manca; se il contenuto derivato aveva già
+    /// un'altra opera, questa viene fusa in quella dell'originale.
+    pub async fn link(
+        pool: &sqlx::SqlitePool,
+        content_id: i64,
+        related_content_id: i64,
+        relation: ContentRelationType,
+    ) -> RitmoResult<i64> {
+        if content_id == related_content_id {
+            return Err(RitmoErr::InvalidInput("un contenuto non può essere collegato a sé stesso".to_string()));
+        }
+        let mut tx = pool.begin().await?;
+        let work_id = Self::ensure_for_content(&mut tx, related_content_id).await?;
+        let current = sqlx::query_scalar!("SELECT work_id FROM contents WHERE id = ?", content_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or_else(|| RitmoErr::NoResultsError(format!("contenuto {} non trovato", content_id)))?;
+        match current {
+            Some(other) if other != work_id => {
+                Self::merge_in(&mut tx, work_id, other).await?;
+            }
+            Some(_) => {}
+            None => {
+                sqlx::query!("UPDATE contents SET work_id = ? WHERE id = ?", work_id, content_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        let relation = relation.as_str();
+        sqlx::query!(
+            "INSERT OR IGNORE INTO content_relations (content_id, related_content_id, relation) VALUES (?, ?, ?)",
+            content_id,
+            related_content_id,
+            relation
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(work_id)
+    }
+
+    pub async fn unlink(
+        pool: &sqlx::SqlitePool,
+        content_id: i64,
+        related_content_id: i64,
+        relation: ContentRelationType,
+    ) -> Result<u64, sqlx::Error> {
+        let relation = relation.as_str();
+        let result = sqlx::query!(
+            "DELETE FROM content_relations WHERE content_id = ? AND related_content_id = ? AND relation = ?",
+            content_id,
+            related_content_id,
+            relation
+        )
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    /// Fonde due opere duplicate: i contenuti di `merged_id` passano a `survivor_id`
+    pub async fn merge(pool: &sqlx::SqlitePool, survivor_id: i64, merged_id: i64) -> RitmoResult<MergeSummary> {
+        if survivor_id == merged_id {
+            return Err(RitmoErr::MergeError(format!(
+                "impossibile fondere l'opera {} con se stessa",
+                survivor_id
+            )));
+        }
+        let mut tx = pool.begin().await?;
+        let summary = Self::merge_in(&mut tx, survivor_id, merged_id).await?;
+        tx.commit().await?;
+        Ok(summary)
+    }
+
+    async fn merge_in(
+        tx: &mut Transaction<'_, Sqlite>,
+        survivor_id: i64,
+        merged_id: i64,
+    ) -> RitmoResult<MergeSummary> {
+        let survivor_exists = sqlx::query_scalar!("SELECT id FROM works WHERE id = ?", survivor_id)
+            .fetch_optional(&mut **tx)
+            .await?;
+        let old_values = sqlx::query_scalar!(
+            "SELECT json_object('id', id, 'title', title, 'original_language', original_language,
+                                'first_published', first_published, 'notes', notes) AS \"json!: String\"
+             FROM works WHERE id = ?",
+            merged_id
+        )
+        .fetch_optional(&mut **tx)
+        .await?;
+        let (Some(_), Some(old_values)) = (survivor_exists, old_values) else {
+            return Err(RitmoErr::MergeError(format!(
+                "opere {} e {} non trovate entrambe",
+                survivor_id, merged_id
+            )));
+        };
+
+        let mut summary = MergeSummary::new(survivor_id, merged_id);
+        summary.links_moved += sqlx::query!(
+            "UPDATE contents SET work_id = ? WHERE work_id = ?",
+            survivor_id,
+            merged_id
+        )
+        .execute(&mut **tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query!(
+            "UPDATE works SET
+                original_language = COALESCE(works.original_language, l.original_language),
+                first_published = COALESCE(MIN(works.first_published, l.first_published), works.first_published, l.first_published),
+                notes = COALESCE(works.notes, l.notes)
+             FROM (SELECT * FROM works WHERE id = ?) AS l
+             WHERE works.id = ?",
+            merged_id,
+            survivor_id
+        )
+        .execute(&mut **tx)
+        .await?;
+
+        record_merge(tx, "works", &summary, &old_values).await?;
+        sqlx::query!("DELETE FROM works WHERE id = ?", merged_id)
+            .execute(&mut **tx)
+            .await?;
+        Ok(summary)
+    }
+
+    /// Tutte le comparse dell'opera, in ogni libro e in ogni lingua
+    pub async fn appearances(pool: &sqlx::SqlitePool, work_id: i64) -> Result<Vec<WorkAppearance>, sqlx::Error> {
+        let rows = sqlx::query!(
+            "SELECT c.id AS \"content_id!\", c.name AS content_name,
+                    b.id AS \"book_id?\", b.name AS \"book_name?\",
+                    (SELECT GROUP_CONCAT(l.iso_code_2char, ',')
+                     FROM x_contents_languages cl JOIN running_languages l ON l.id = cl.language_id
+                     WHERE cl.content_id = c.id AND l.language_role = 'Actual') AS \"languages?: String\",
+                    (SELECT r.relation FROM content_relations r WHERE r.content_id = c.id
+                     ORDER BY r.created_at LIMIT 1) AS \"relation?: String\"
+             FROM contents c
+             LEFT JOIN x_books_contents bc ON bc.content_id = c.id
+             LEFT JOIN books b ON b.id = bc.book_id AND b.deleted_at IS NULL
+             WHERE c.work_id = ? AND c.deleted_at IS NULL
+               AND (bc.book_id IS NULL OR b.id IS NOT NULL)
+             ORDER BY c.publication_date, c.id, b.publication_date, b.id",
+            work_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| WorkAppearance {
+                content_id: r.content_id,
+                content_name: r.content_name,
+                book_id: r.book_id,
+                book_name: r.book_name,
+                languages: r.languages,
+                relation: r.relation,
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,129 @@
+//! Liste ordinate per `position`: elementi delle collezioni, collezioni sorelle ed elementi
+//! degli ordini di lettura. Le posizioni partono da 1 e un riordino le riscrive tutte senza buchi.
+
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::SqliteConnection;
+
+/// Tabella con una colonna `position` e la colonna che raggruppa gli elementi della stessa lista.
+/// I nomi sono costanti del crate, mai input dell'utente, e finiscono direttamente nell'SQL.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct OrderedList {
+    table: &'static str,
+    parent: &'static str,
+}
+
+pub(crate) const COLLECTIONS: OrderedList = OrderedList { table: "collections", parent: "parent_id" };
+pub(crate) const COLLECTION_ITEMS: OrderedList = OrderedList { table: "collection_items", parent: "collection_id" };
+pub(crate) const READING_ORDER_ITEMS: OrderedList = OrderedList { table: "reading_order_items", parent: "order_id" };
+
+/// Libro o contenuto da mettere in una lista
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Member {
+    Book(i64),
+    Content(i64),
+}
+
+impl Member {
+    fn column(&self) -> &'static str {
+        match self {
+            Self::Book(_) => "book_id",
+            Self::Content(_) => "content_id",
+        }
+    }
+
+    fn id(&self) -> i64 {
+        match self {
+            Self::Book(id) | Self::Content(id) => *id,
+        }
+    }
+}
+
+impl OrderedList {
+    /// Aggiunge il libro o il contenuto in fondo alla lista; se c'è già restituisce l'elemento esistente
+    pub(crate) async fn append(
+        &self,
+        conn: &mut SqliteConnection,
+        parent_id: i64,
+        member: Member,
+    ) -> Result<i64, sqlx::Error> {
+        let (table, parent, column) = (self.table, self.parent, member.column());
+        sqlx::query(&format!(
+            "INSERT OR IGNORE INTO {table} ({parent}, {column}, position)
+             VALUES (?1, ?2, COALESCE((SELECT MAX(position) FROM {table} WHERE {parent} = ?1), 0) + 1)"
+        ))
+        .bind(parent_id)
+        .bind(member.id())
+        .execute(&mut *conn)
+        .await?;
+        sqlx::query_scalar(&format!("SELECT id FROM {table} WHERE {parent} = ? AND {column} = ?"))
+            .bind(parent_id)
+            .bind(member.id())
+            .fetch_one(&mut *conn)
+            .await
+    }
+
+    /// Riscrive le posizioni della lista di `parent_id` (None per le collezioni di primo livello)
+    /// nell'ordine dato, che deve contenere tutti e soli i suoi elementi
+    pub(crate) async fn reorder(
+        &self,
+        conn: &mut SqliteConnection,
+        parent_id: Option<i64>,
+        ordered_ids: &[i64],
+    ) -> RitmoResult<()> {
+        let current: Vec<i64> =
+            sqlx::query_scalar(&format!("SELECT id FROM {} WHERE {} IS ?", self.table, self.parent))
+                .bind(parent_id)
+                .fetch_all(&mut *conn)
+                .await?;
+        check_same_set(&current, ordered_ids)?;
+        let update = format!("UPDATE {} SET position = ? WHERE id = ?", self.table);
+        for (position, id) in ordered_ids.iter().enumerate() {
+            sqlx::query(&update)
+                .bind(position as i64 + 1)
+                .bind(id)
+                .execute(&mut *conn)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Sposta un elemento nella posizione indicata (a partire da 1), facendo scorrere gli altri
+    pub(crate) async fn move_item(
+        &self,
+        conn: &mut SqliteConnection,
+        item_id: i64,
+        new_position: usize,
+    ) -> RitmoResult<()> {
+        let (table, parent) = (self.table, self.parent);
+        let select = format!("SELECT {parent} FROM {table} WHERE id = ?");
+        let Some(parent_id) = sqlx::query_scalar::<_, Option<i64>>(&select)
+            .bind(item_id)
+            .fetch_optional(&mut *conn)
+            .await?
+        else {
+            return Err(RitmoErr::NoResultsError(format!("elemento {} non trovato", item_id)));
+        };
+        let mut ids: Vec<i64> =
+            sqlx::query_scalar(&format!("SELECT id FROM {table} WHERE {parent} IS ? AND id <> ? ORDER BY position, id"))
+                .bind(parent_id)
+                .bind(item_id)
+                .fetch_all(&mut *conn)
+                .await?;
+        let index = new_position.saturating_sub(1).min(ids.len());
+        ids.insert(index, item_id);
+        self.reorder(conn, parent_id, &ids).await
+    }
+}
+
+fn check_same_set(current: &[i64], ordered: &[i64]) -> RitmoResult<()> {
+    let mut a = current.to_vec();
+    let mut b = ordered.to_vec();
+    a.sort_unstable();
+    b.sort_unstable();
+    if a != b {
+        return Err(RitmoErr::InvalidInput(
+            "il nuovo ordine deve contenere tutti e soli gli elementi esistenti".to_string(),
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,405 @@
+//! Date parziali e storiche ("1605", "marzo 1922", "c. 1350", "500 a.C.").
+//!
+//! Nel database le date sono salvate come INTEGER con [`PartialDate::to_key`]: la chiave
+//! è ordinabile direttamente in SQL, anche per le date avanti Cristo.
+
+use chrono::{Datelike, NaiveDate};
+use ritmo_errors::{RitmoErr, RitmoResult};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Chiave in `system_config` che indica che le colonne data usano già la codifica di `PartialDate`
+const ENCODING_CONFIG_KEY: &str = "date_encoding";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PartialDate {
+    /// Anno storico, sempre positivo (non esiste l'anno 0): per le date a.C. vale `bce`
+    pub year: i32,
+    pub month: Option<u8>,
+    pub day: Option<u8>,
+    /// Data approssimativa ("circa 1350")
+    pub circa: bool,
+    /// Avanti Cristo
+    pub bce: bool,
+}
+
+impl PartialDate {
+    /// Crea una data validando mese e giorno; il giorno richiede il mese
+    pub fn new(year: i32, month: Option<u8>, day: Option<u8>) -> RitmoResult<Self> {
+        let date = Self { year, month, day, circa: false, bce: false };
+        date.validate()?;
+        Ok(date)
+    }
+
+    pub fn with_circa(mut self) -> Self {
+        self.circa = true;
+        self
+    }
+
+    pub fn with_bce(mut self) -> RitmoResult<Self> {
+        self.bce = true;
+        self.validate()?;
+        Ok(self)
+    }
+
+    fn validate(&self) -> RitmoResult<()> {
+        let invalid = |msg: &str| Err(RitmoErr::InvalidInput(format!("data non valida: {}", msg)));
+        if self.year <= 0 || self.year > 9999 {
+            return invalid("anno fuori intervallo");
+        }
+        match (self.month, self.day) {
+            (None, Some(_)) => return invalid("giorno senza mese"),
+            (Some(m), _) if !(1..=12).contains(&m) => return invalid("mese fuori intervallo"),
+            (Some(m), Some(d)) => {
+                // Calendario gregoriano prolettico, con anno astronomico per le date a.C.
+                let year = if self.bce { 1 - self.year } else { self.year };
+                if NaiveDate::from_ymd_opt(year, m as u32, d as u32).is_none() {
+                    return invalid("giorno inesistente");
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Anno con segno: negativo per le date a.C. (500 a.C. → -500)
+    pub fn signed_year(&self) -> i32 {
+        if self.bce { -self.year } else { self.year }
+    }
+
+    /// Codifica ordinabile usata nelle colonne del database:
+    /// `((anno_con_segno * 100 + mese) * 100 + giorno) * 10 + circa`,
+    /// con mese e giorno a 0 se sconosciuti. Una data meno precisa precede quelle più precise
+    /// dello stesso anno o mese.
+    pub fn to_key(&self) -> i64 {
+        let year = self.signed_year() as i64;
+        let month = self.month.unwrap_or(0) as i64;
+        let day = self.day.unwrap_or(0) as i64;
+        ((year * 100 + month) * 100 + day) * 10 + self.circa as i64
+    }
+
+    /// Decodifica una chiave prodotta da [`to_key`](Self::to_key)
+    pub fn from_key(key: i64) -> Option<Self> {
+        let circa = match key.rem_euclid(10) {
+            0 => false,
+            1 => true,
+            _ => return None,
+        };
+        let rest = key.div_euclid(10);
+        let day = rest.rem_euclid(100) as u8;
+        let rest = rest.div_euclid(100);
+        let month = rest.rem_euclid(100) as u8;
+        let year = rest.div_euclid(100);
+        let year = i32::try_from(year).ok()?;
+
+        let mut date = Self::new(
+            year.abs(),
+            (month > 0).then_some(month),
+            (day > 0).then_some(day),
+        )
+        .ok()?;
+        date.circa = circa;
+        if year < 0 {
+            date = date.with_bce().ok()?;
+        }
+        Some(date)
+    }
+
+    /// Data completa da un timestamp Unix (il formato usato prima di `PartialDate`)
+    pub fn from_timestamp(timestamp: i64) -> Option<Self> {
+        let date = chrono::DateTime::from_timestamp(timestamp, 0)?.date_naive();
+        if date.year() <= 0 {
+            return None;
+        }
+        Self::new(date.year(), Some(date.month() as u8), Some(date.day() as u8)).ok()
+    }
+
+    /// Interpreta una data scritta a mano: `1605`, `1922-03`, `15/03/1922`, `March 1922`,
+    /// `15 marzo 1922`, `c. 1350`, `1350?`, `500 BC`, `500 a.C.`, ...
+    pub fn parse(text: &str) -> RitmoResult<Self> {
+        let original = text;
+        let mut text = text.trim().to_lowercase();
+        let mut circa = false;
+        let mut bce = false;
+
+        for prefix in ["circa ", "about ", "ca. ", "ca ", "c. ", "c.", "c ", "~"] {
+            if let Some(rest) = text.strip_prefix(prefix) {
+                circa = true;
+                text = rest.trim().to_string();
+                break;
+            }
+        }
+        if let Some(rest) = text.strip_suffix('?') {
+            circa = true;
+            text = rest.trim().to_string();
+        }
+        for suffix in ["b.c.e.", "b.c.", "bce", "bc", "a.c.", "a.c", "a. c."] {
+            if let Some(rest) = text.strip_suffix(suffix) {
+                bce = true;
+                text = rest.trim().to_string();
+                break;
+            }
+        }
+        if !bce {
+            for suffix in ["c.e.", "ce", "a.d.", "ad", "d.c.", "d. c."] {
+                if let Some(rest) = text.strip_suffix(suffix) {
+                    text = rest.trim().to_string();
+                    break;
+                }
+            }
+        }
+
+        let (year, month, day) = Self::parse_parts(&text).ok_or_else(|| {
+            RitmoErr::InvalidInput(format!("data non riconosciuta: {}", original))
+        })?;
+        let mut date = Self::new(year, month, day)?;
+        date.circa = circa;
+        if bce {
+            date = date.with_bce()?;
+        }
+        Ok(date)
+    }
+
+    /// Data letta da un file OPF (`dc:date`), tipicamente ISO 8601 con orario.
+    /// La data "indefinita" di Calibre (anno 101) viene ignorata.
+    pub fn parse_opf(text: &str) -> Option<Self> {
+        let text = text.trim();
+        if text.starts_with("0101-01-01") || text.starts_with("0100-12-31") {
+            return None;
+        }
+        Self::parse(text).ok()
+    }
+
+    fn parse_parts(text: &str) -> Option<(i32, Option<u8>, Option<u8>)> {
+        // ISO 8601, eventualmente con orario (es. OPF "2001-05-12T00:00:00+00:00"); quello che
+        // segue la data deve essere davvero un orario, altrimenti "1922 march" perderebbe il mese
+        let date_part = text.split(['t', ' ']).next().unwrap_or(text);
+        let time = text[date_part.len()..].get(1..).unwrap_or_default();
+        let is_time = time.is_empty() || (time.starts_with(|c: char| c.is_ascii_digit()) && time.contains(':'));
+        let iso: Vec<&str> = date_part.split('-').collect();
+        if is_time && iso[0].len() >= 3 && iso.len() <= 3 && iso.iter().all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit())) {
+            let year = iso[0].parse().ok()?;
+            let month = iso.get(1).map(|m| m.parse()).transpose().ok()?;
+            let day = iso.get(2).map(|d| d.parse()).transpose().ok()?;
+            return Some((year, month, day));
+        }
+
+        let tokens: Vec<&str> = text
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|t| !t.is_empty())
+            .collect();
+        let month_name = tokens.iter().find_map(|t| month_from_name(t));
+        let numbers: Vec<&str> = tokens
+            .iter()
+            .copied()
+            .filter(|t| t.chars().all(|c| c.is_ascii_digit()))
+            .collect();
+        // Ogni token deve essere un numero o un nome di mese
+        if tokens.len() != numbers.len() + month_name.is_some() as usize {
+            return None;
+        }
+        let num = |s: &str| s.parse::<i32>().ok();
+        // Mesi e giorni oltre 255 non entrano in un u8: sono date sbagliate, non vanno troncati
+        let small = |s: &str| s.parse::<u8>().ok();
+
+        match (month_name, numbers.as_slice()) {
+            (None, [year]) => Some((num(year)?, None, None)),
+            (Some(month), [year]) => Some((num(year)?, Some(month), None)),
+            // "15 March 1922", "March 15, 1922" o "1922 march 15": l'anno è quello lungo
+            (Some(month), [day, year]) if year.len() >= 3 => Some((num(year)?, Some(month), Some(small(day)?))),
+            (Some(month), [year, day]) if year.len() >= 3 => Some((num(year)?, Some(month), Some(small(day)?))),
+            // "1922/03" oppure "03/1922"
+            (None, [a, b]) if a.len() >= 3 => Some((num(a)?, Some(small(b)?), None)),
+            (None, [a, b]) if b.len() >= 3 => Some((num(b)?, Some(small(a)?), None)),
+            // "1922/03/15" oppure, all'europea, "15/03/1922"
+            (None, [a, b, c]) if a.len() >= 3 => Some((num(a)?, Some(small(b)?), Some(small(c)?))),
+            (None, [a, b, c]) => Some((num(c)?, Some(small(b)?), Some(small(a)?))),
+            _ => None,
+        }
+    }
+
+    /// Converte le date salvate come timestamp Unix nella codifica di `PartialDate`.
+    /// Va eseguita una sola volta sulle librerie create prima dell'introduzione delle date
+    /// parziali; le librerie nuove sono già marcate e la funzione non fa nulla.
+    /// Restituisce il numero di valori convertiti.
+    pub async fn migrate_timestamps(pool: &sqlx::SqlitePool) -> Result<u64, sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let done = sqlx::query_scalar!(
+            "SELECT value FROM system_config WHERE key = ?",
+            ENCODING_CONFIG_KEY
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .flatten();
+        if done.as_deref() == Some("partial") {
+            return Ok(0);
+        }
+
+        let mut converted = 0;
+        converted += sqlx::query!(
+            "UPDATE books SET publication_date =
+                ((CAST(strftime('%Y', publication_date, 'unixepoch') AS INTEGER) * 100
+                  + CAST(strftime('%m', publication_date, 'unixepoch') AS INTEGER)) * 100
+                  + CAST(strftime('%d', publication_date, 'unixepoch') AS INTEGER)) * 10
+             WHERE publication_date IS NOT NULL"
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        converted += sqlx::query!(
+            "UPDATE contents SET publication_date =
+                ((CAST(strftime('%Y', publication_date, 'unixepoch') AS INTEGER) * 100
+                  + CAST(strftime('%m', publication_date, 'unixepoch') AS INTEGER)) * 100
+                  + CAST(strftime('%d', publication_date, 'unixepoch') AS INTEGER)) * 10
+             WHERE publication_date IS NOT NULL"
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        converted += sqlx::query!(
+            "UPDATE people SET
+                birth_date = CASE WHEN birth_date IS NULL THEN NULL ELSE
+                    ((CAST(strftime('%Y', birth_date, 'unixepoch') AS INTEGER) * 100
+                      + CAST(strftime('%m', birth_date, 'unixepoch') AS INTEGER)) * 100
+                      + CAST(strftime('%d', birth_date, 'unixepoch') AS INTEGER)) * 10 END,
+                death_date = CASE WHEN death_date IS NULL THEN NULL ELSE
+                    ((CAST(strftime('%Y', death_date, 'unixepoch') AS INTEGER) * 100
+                      + CAST(strftime('%m', death_date, 'unixepoch') AS INTEGER)) * 100
+                      + CAST(strftime('%d', death_date, 'unixepoch') AS INTEGER)) * 10 END
+             WHERE birth_date IS NOT NULL OR death_date IS NOT NULL"
+        )
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        sqlx::query!(
+            "INSERT INTO system_config (key, value, description)
+             VALUES (?, 'partial', 'Codifica delle colonne data (PartialDate)')
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            ENCODING_CONFIG_KEY
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(converted)
+    }
+}
+
+fn month_from_name(token: &str) -> Option<u8> {
+    const MONTHS: [&[&str]; 12] = [
+        &["jan", "january", "gen", "gennaio", "janvier", "januar", "ene", "enero"],
+        &["feb", "february", "febbraio", "fev", "février", "fevrier", "februar", "febrero"],
+        &["mar", "march", "marzo", "mars", "märz", "marz"],
+        &["apr", "april", "aprile", "avr", "avril", "abr", "abril"],
+        &["may", "mag", "maggio", "mai", "mayo"],
+        &["jun", "june", "giu", "giugno", "juin", "juni", "junio"],
+        &["jul", "july", "lug", "luglio", "juillet", "juli", "julio"],
+        &["aug", "august", "ago", "agosto", "août", "aout"],
+        &["sep", "sept", "september", "set", "settembre", "septembre", "septiembre"],
+        &["oct", "october", "ott", "ottobre", "octobre", "okt", "oktober", "octubre"],
+        &["nov", "november", "novembre", "noviembre"],
+        &["dec", "december", "dic", "dicembre", "décembre", "decembre", "dez", "dezember", "diciembre"],
+    ];
+    MONTHS
+        .iter()
+        .position(|names| names.contains(&token))
+        .map(|i| i as u8 + 1)
+}
+
+impl Ord for PartialDate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.to_key().cmp(&other.to_key())
+    }
+}
+
+impl PartialOrd for PartialDate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Formato riconosciuto da [`PartialDate::parse`]: `1922-03-15`, `c. 1350`, `500 BCE`
+impl fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.circa {
+            write!(f, "c. ")?;
+        }
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "-{:02}", month)?;
+        }
+        if let Some(day) = self.day {
+            write!(f, "-{:02}", day)?;
+        }
+        if self.bce {
+            write!(f, " BCE")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_free_text() {
+        assert_eq!(PartialDate::parse("1605").unwrap(), PartialDate::new(1605, None, None).unwrap());
+        assert_eq!(PartialDate::parse("March 1922").unwrap(), PartialDate::new(1922, Some(3), None).unwrap());
+        assert_eq!(PartialDate::parse("15 marzo 1922").unwrap(), PartialDate::new(1922, Some(3), Some(15)).unwrap());
+        assert_eq!(PartialDate::parse("15/03/1922").unwrap(), PartialDate::new(1922, Some(3), Some(15)).unwrap());
+
+        let circa = PartialDate::parse("c. 1350").unwrap();
+        assert!(circa.circa);
+        assert_eq!(circa.year, 1350);
+
+        let bce = PartialDate::parse("500 a.C.").unwrap();
+        assert!(bce.bce);
+        assert_eq!(bce.signed_year(), -500);
+
+        assert_eq!(PartialDate::parse("1922 March").unwrap(), PartialDate::new(1922, Some(3), None).unwrap());
+        assert_eq!(PartialDate::parse("1922 march 15").unwrap(), PartialDate::new(1922, Some(3), Some(15)).unwrap());
+        assert_eq!(PartialDate::parse("1922-03-15 10:30").unwrap(), PartialDate::new(1922, Some(3), Some(15)).unwrap());
+
+        // Valori oltre u8 non vengono troncati (259 diventerebbe 3)
+        assert!(PartialDate::parse("1922/259").is_err());
+        assert!(PartialDate::parse("1922/03/271").is_err());
+        assert!(PartialDate::parse("31/02/1922").is_err());
+        assert!(PartialDate::parse("domani").is_err());
+    }
+
+    #[test]
+    fn test_parse_opf() {
+        assert_eq!(
+            PartialDate::parse_opf("2001-05-12T00:00:00+00:00"),
+            Some(PartialDate::new(2001, Some(5), Some(12)).unwrap())
+        );
+        assert_eq!(PartialDate::parse_opf("1922"), Some(PartialDate::new(1922, None, None).unwrap()));
+        assert_eq!(PartialDate::parse_opf("0101-01-01T00:00:00+00:00"), None);
+    }
+
+    #[test]
+    fn test_key_roundtrip_and_ordering() {
+        let dates = [
+            PartialDate::parse("501 BCE").unwrap(),
+            PartialDate::parse("500 BCE").unwrap(),
+            PartialDate::parse("c. 500 BCE").unwrap(),
+            PartialDate::parse("1350").unwrap(),
+            PartialDate::parse("c. 1350").unwrap(),
+            PartialDate::parse("1922").unwrap(),
+            PartialDate::parse("1922-03").unwrap(),
+            PartialDate::parse("1922-03-15").unwrap(),
+        ];
+        for pair in dates.windows(2) {
+            assert!(pair[0].to_key() < pair[1].to_key(), "{} < {}", pair[0], pair[1]);
+        }
+        for date in dates {
+            assert_eq!(PartialDate::from_key(date.to_key()), Some(date));
+            assert_eq!(PartialDate::parse(&date.to_string()).unwrap(), date);
+        }
+        assert_eq!(
+            PartialDate::from_timestamp(315532800),
+            Some(PartialDate::new(1980, Some(1), Some(1)).unwrap())
+        );
+    }
+}
@@ -0,0 +1,245 @@
+//! Filtri salvati delle collezioni "smart": le regole sono memorizzate in JSON in
+//! `collections.rules` e vengono valutate ogni volta che si apre la collezione.
+
+use crate::models::{Book, Content};
+use crate::partial_date::PartialDate;
+use ritmo_db_core::sorting;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use serde::{Deserialize, Serialize};
+use sqlx::{QueryBuilder, Sqlite};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterTarget {
+    Books,
+    Contents,
+}
+
+/// Singola condizione di un filtro. I confronti sui nomi ignorano maiuscole e minuscole.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "field", content = "value", rename_all = "snake_case")]
+pub enum FilterRule {
+    /// Titolo o titolo originale che contiene il testo
+    NameContains(String),
+    Tag(String),
+    /// Persona collegata con qualsiasi ruolo (il nome può essere parziale)
+    Person(String),
+    /// Solo per i libri
+    Series(String),
+    /// Solo per i libri
+    Publisher(String),
+    /// Solo per i libri (EPUB, PDF, ...)
+    Format(String),
+    /// Codice ISO a 2 o 3 caratteri; per i libri vale la lingua dei contenuti
+    Language(String),
+    /// Solo per i libri: unread, reading, read, abandoned
+    ReadStatus(String),
+    MinRating(f64),
+    /// Pubblicati da questa data in poi (formato di `PartialDate::parse`)
+    PublishedFrom(String),
+    /// Pubblicati prima di questa data
+    PublishedBefore(String),
+    /// Solo per i libri: ha almeno una copia cartacea
+    HasPaper(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortField {
+    /// Titolo senza articolo iniziale (`sort_title`)
+    #[default]
+    Name,
+    /// Autori in forma "cognome, nome" (`sort_author`)
+    Author,
+    PublicationDate,
+    CreatedAt,
+    Rating,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmartFilter {
+    pub target: FilterTarget,
+    /// true: tutte le regole devono valere (AND); false: ne basta una (OR)
+    #[serde(default = "default_match_all")]
+    pub match_all: bool,
+    #[serde(default)]
+    pub rules: Vec<FilterRule>,
+    #[serde(default)]
+    pub sort_by: SortField,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+fn default_match_all() -> bool {
+    true
+}
+
+impl SmartFilter {
+    pub fn new(target: FilterTarget, rules: Vec<FilterRule>) -> Self {
+        Self {
+            target,
+            match_all: true,
+            rules,
+            sort_by: SortField::default(),
+            descending: false,
+        }
+    }
+
+    pub fn to_json(&self) -> RitmoResult<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    pub fn from_json(json: &str) -> RitmoResult<Self> {
+        let filter: Self = serde_json::from_str(json)
+            .map_err(|e| RitmoErr::InvalidInput(format!("regole della collezione non valide: {}", e)))?;
+        filter.validate()?;
+        Ok(filter)
+    }
+
+    /// Verifica che le regole siano applicabili al tipo di elemento e che le date siano leggibili
+    pub fn validate(&self) -> RitmoResult<()> {
+        let mut probe = QueryBuilder::<Sqlite>::new("");
+        for rule in &self.rules {
+            push_rule(&mut probe, self.target, rule)?;
+        }
+        Ok(())
+    }
+
+    pub async fn books(&self, pool: &sqlx::SqlitePool) -> RitmoResult<Vec<Book>> {
+        if self.target != FilterTarget::Books {
+            return Err(RitmoErr::InvalidInput("il filtro non riguarda i libri".to_string()));
+        }
+        let mut qb = QueryBuilder::new("SELECT * FROM books WHERE deleted_at IS NULL");
+        self.push_conditions(&mut qb)?;
+        Ok(qb.build_query_as::<Book>().fetch_all(pool).await?)
+    }
+
+    pub async fn contents(&self, pool: &sqlx::SqlitePool) -> RitmoResult<Vec<Content>> {
+        if self.target != FilterTarget::Contents {
+            return Err(RitmoErr::InvalidInput("il filtro non riguarda i contenuti".to_string()));
+        }
+        let mut qb = QueryBuilder::new("SELECT * FROM contents WHERE deleted_at IS NULL");
+        self.push_conditions(&mut qb)?;
+        Ok(qb.build_query_as::<Content>().fetch_all(pool).await?)
+    }
+
+    fn push_conditions(&self, qb: &mut QueryBuilder<'_, Sqlite>) -> RitmoResult<()> {
+        if !self.rules.is_empty() {
+            qb.push(" AND (");
+            for (i, rule) in self.rules.iter().enumerate() {
+                if i > 0 {
+                    qb.push(if self.match_all { " AND " } else { " OR " });
+                }
+                push_rule(qb, self.target, rule)?;
+            }
+            qb.push(")");
+        }
+        let column = match self.sort_by {
+            SortField::Name => "sort_title",
+            SortField::Author => "sort_author",
+            SortField::PublicationDate => "publication_date",
+            SortField::CreatedAt => "created_at",
+            SortField::Rating => "rating",
+        };
+        let direction = if self.descending { "DESC" } else { "ASC" };
+        qb.push(format!(
+            " ORDER BY {} IS NULL, {} {}, name COLLATE {}, id",
+            column, column, direction, sorting::COLLATION
+        ));
+        Ok(())
+    }
+}
+
+fn push_rule(qb: &mut QueryBuilder<'_, Sqlite>, target: FilterTarget, rule: &FilterRule) -> RitmoResult<()> {
+    let books = target == FilterTarget::Books;
+    let books_only = |name: &str| {
+        if books {
+            Ok(())
+        } else {
+            Err(RitmoErr::InvalidInput(format!("la regola '{}' vale solo per i libri", name)))
+        }
+    };
+    let date_key = |text: &str| PartialDate::parse(text).map(|d| d.to_key());
+
+    match rule {
+        FilterRule::NameContains(text) => {
+            qb.push("(name LIKE '%' || ")
+                .push_bind(text.clone())
+                .push(" || '%' OR original_title LIKE '%' || ")
+                .push_bind(text.clone())
+                .push(" || '%')");
+        }
+        // Il tag comprende i suoi discendenti
+        FilterRule::Tag(tag) => {
+            qb.push(if books {
+                "id IN (SELECT x.book_id FROM x_books_tags x WHERE x.tag_id IN ("
+            } else {
+                "id IN (SELECT x.content_id FROM x_contents_tags x WHERE x.tag_id IN ("
+            })
+            .push("WITH RECURSIVE tree(id, depth) AS (SELECT id, 0 FROM tags WHERE name = ")
+            .push_bind(tag.clone())
+            .push(
+                " COLLATE NOCASE UNION ALL SELECT t.id, tree.depth + 1 FROM tags t JOIN tree ON t.parent_id = tree.id \
+                 WHERE tree.depth < 64) SELECT id FROM tree))",
+            );
+        }
+        FilterRule::Person(name) => {
+            qb.push(if books {
+                "id IN (SELECT x.book_id FROM x_books_people_roles x JOIN people p ON p.id = x.person_id WHERE p.name LIKE '%' || "
+            } else {
+                "id IN (SELECT x.content_id FROM x_contents_people_roles x JOIN people p ON p.id = x.person_id WHERE p.name LIKE '%' || "
+            })
+            .push_bind(name.clone())
+            .push(" || '%')");
+        }
+        FilterRule::Series(name) => {
+            books_only("series")?;
+            qb.push("series_id IN (SELECT id FROM series WHERE name = ")
+                .push_bind(name.clone())
+                .push(" COLLATE NOCASE)");
+        }
+        FilterRule::Publisher(name) => {
+            books_only("publisher")?;
+            qb.push("publisher_id IN (SELECT id FROM publishers WHERE name = ")
+                .push_bind(name.clone())
+                .push(" COLLATE NOCASE)");
+        }
+        FilterRule::Format(name) => {
+            books_only("format")?;
+            qb.push("id IN (SELECT bf.book_id FROM book_files bf JOIN formats f ON f.id = bf.format_id WHERE f.name = ")
+                .push_bind(name.clone())
+                .push(" COLLATE NOCASE)");
+        }
+        FilterRule::Language(code) => {
+            qb.push(if books {
+                "id IN (SELECT bc.book_id FROM x_books_contents bc JOIN x_contents_languages cl ON cl.content_id = bc.content_id"
+            } else {
+                "id IN (SELECT cl.content_id FROM x_contents_languages cl"
+            })
+            .push(" JOIN running_languages l ON l.id = cl.language_id WHERE l.iso_code_2char = ")
+            .push_bind(code.clone())
+            .push(" COLLATE NOCASE OR l.iso_code_3char = ")
+            .push_bind(code.clone())
+            .push(" COLLATE NOCASE)");
+        }
+        FilterRule::ReadStatus(status) => {
+            books_only("read_status")?;
+            crate::models::ReadingStatus::parse(status)?;
+            qb.push("read_status = ").push_bind(status.clone());
+        }
+        FilterRule::MinRating(rating) => {
+            qb.push("rating >= ").push_bind(*rating);
+        }
+        FilterRule::PublishedFrom(date) => {
+            qb.push("publication_date >= ").push_bind(date_key(date)?);
+        }
+        FilterRule::PublishedBefore(date) => {
+            qb.push("publication_date < ").push_bind(date_key(date)?);
+        }
+        FilterRule::HasPaper(has_paper) => {
+            books_only("has_paper")?;
+            qb.push("has_paper = ").push_bind(*has_paper as i64);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use crate::models::{BookCopy, BookFile, BookIdentifier};
+use crate::partial_date::PartialDate;
+use sqlx::SqlitePool;
+
+/// Riepilogo di un aggiornamento della biblioteca
+#[derive(Debug)]
+pub struct UpgradeReport {
+    /// File copiati da `books.file_link` in `book_files`
+    pub book_files: u64,
+    /// Date convertite da timestamp Unix alla codifica di `PartialDate`
+    pub dates: u64,
+    /// ISBN di `books.isbn` registrati come identificatori
+    pub identifiers: u64,
+    /// Libri con un ISBN non valido, rimasto solo in `books.isbn`
+    pub invalid_isbns: Vec<i64>,
+    /// Copie cartacee create per i libri segnati con `has_paper`
+    pub copies: u64,
+}
+
+/// Porta i dati di una biblioteca creata con una versione precedente nelle tabelle attuali.
+/// Ogni passo è idempotente: sulle biblioteche già aggiornate non cambia niente.
+pub async fn upgrade_library(pool: &SqlitePool) -> Result<UpgradeReport, sqlx::Error> {
+    let book_files = BookFile::migrate_from_books(pool).await?;
+    let dates = PartialDate::migrate_timestamps(pool).await?;
+    let (identifiers, invalid_isbns) = BookIdentifier::migrate_from_books(pool).await?;
+    Ok(UpgradeReport {
+        book_files,
+        dates,
+        identifiers,
+        invalid_isbns,
+        copies: BookCopy::migrate_from_has_paper(pool).await?,
+    })
+}
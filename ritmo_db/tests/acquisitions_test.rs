@@ -0,0 +1,92 @@
+mod common;
+
+use chrono::{TimeZone, Utc};
+use ritmo_core::dto::BookDto;
+use ritmo_db::models::{Acquisition, AcquisitionSource, Book, BookCopy};
+
+fn date(year: i32, month: u32, day: u32) -> i64 {
+    Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap().timestamp()
+}
+
+fn acquisition(book_id: i64, at: i64, source: AcquisitionSource, price: Option<f64>) -> Acquisition {
+    Acquisition {
+        book_id,
+        acquired_at: Some(at),
+        source: source.as_str().to_string(),
+        price,
+        currency: price.map(|_| "EUR".to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_acquisitions_by_year_and_spending() {
+    let (_dir, pool) = common::setup_pool().await;
+    let book_id = Book { name: "Lessico famigliare".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    let copy_id = BookCopy { book_id, ..Default::default() }.save(&pool).await.unwrap();
+
+    let paper = Acquisition {
+        copy_id: Some(copy_id),
+        vendor: Some("Bancarella di Porta Palazzo".to_string()),
+        provenance: Some("Ex libris di un precedente proprietario".to_string()),
+        ..acquisition(book_id, date(2025, 3, 1), AcquisitionSource::LibrarySale, Some(2.0))
+    };
+    paper.save(&pool).await.unwrap();
+    acquisition(book_id, date(2026, 1, 10), AcquisitionSource::Shop, Some(12.5)).save(&pool).await.unwrap();
+    acquisition(book_id, date(2026, 6, 30), AcquisitionSource::Shop, Some(7.5)).save(&pool).await.unwrap();
+    acquisition(book_id, date(2026, 7, 1), AcquisitionSource::Gift, None).save(&pool).await.unwrap();
+
+    assert_eq!(Acquisition::list_by_year(&pool, 2026).await.unwrap().len(), 3);
+    assert_eq!(Acquisition::list_by_year(&pool, 2025).await.unwrap()[0].copy_id, Some(copy_id));
+
+    let spending = Acquisition::total_spent_by_source(&pool).await.unwrap();
+    let shop = spending.iter().find(|s| s.source == "shop").unwrap();
+    assert_eq!((shop.total, shop.count), (20.0, 2));
+    assert!(spending.iter().all(|s| s.source != "gift"));
+
+    let invalid = Acquisition { source: "stolen".to_string(), ..acquisition(book_id, 0, AcquisitionSource::Other, None) };
+    assert!(invalid.save(&pool).await.is_err());
+    assert_eq!(AcquisitionSource::parse("Library sale").unwrap(), AcquisitionSource::LibrarySale);
+}
+
+#[tokio::test]
+async fn test_save_dto_keeps_acquisition() {
+    let (_dir, pool) = common::setup_pool().await;
+    let mut dto = BookDto {
+        name: "La luna e i falò".to_string(),
+        acquisition_date: Some(date(2026, 2, 14)),
+        acquisition_source: Some("gift".to_string()),
+        acquisition_vendor: Some("Zia Carla".to_string()),
+        ..Default::default()
+    };
+    let book_id = Book::save_dto(&pool, &mut dto).await.unwrap();
+    let saved = Acquisition::list_by_book(&pool, book_id).await.unwrap();
+    assert_eq!(saved.len(), 1);
+    assert_eq!((saved[0].source.as_str(), saved[0].vendor.as_deref()), ("gift", Some("Zia Carla")));
+
+    // Basta la sola valuta perché l'acquisizione venga registrata
+    let mut dto = BookDto {
+        name: "Paesi tuoi".to_string(),
+        acquisition_currency: Some("EUR".to_string()),
+        ..Default::default()
+    };
+    let book_id = Book::save_dto(&pool, &mut dto).await.unwrap();
+    let saved = Acquisition::list_by_book(&pool, book_id).await.unwrap();
+    assert_eq!((saved.len(), saved[0].currency.as_deref()), (1, Some("EUR")));
+
+    // Senza dati di acquisizione non viene creato niente
+    let mut dto = BookDto { name: "Il diavolo sulle colline".to_string(), ..Default::default() };
+    let book_id = Book::save_dto(&pool, &mut dto).await.unwrap();
+    assert!(Acquisition::list_by_book(&pool, book_id).await.unwrap().is_empty());
+
+    let mut dto = BookDto {
+        name: "Il carcere".to_string(),
+        acquisition_source: Some("stolen".to_string()),
+        ..Default::default()
+    };
+    assert!(Book::save_dto(&pool, &mut dto).await.is_err());
+    assert!(Book::list_all(&pool).await.unwrap().iter().all(|b| b.name != "Il carcere"));
+}
@@ -0,0 +1,100 @@
+mod common;
+
+use ritmo_db::models::{Book, BookFile, Format};
+
+async fn format(pool: &sqlx::SqlitePool, name: &str) -> i64 {
+    Format { id: None, name: name.to_string(), description: None, created_at: 0 }
+        .create(pool)
+        .await
+        .unwrap()
+}
+
+fn file(book_id: i64, format_id: i64, link: &str, hash: &str) -> BookFile {
+    BookFile {
+        book_id,
+        format_id: Some(format_id),
+        file_link: link.to_string(),
+        file_hash: Some(hash.to_string()),
+        file_size: Some(1024),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_multiple_formats_and_preferred_file() {
+    let (_dir, pool) = common::setup_pool().await;
+    let epub = format(&pool, "EPUB").await;
+    let pdf = format(&pool, "PDF").await;
+
+    let book_id = Book { name: "Le città invisibili".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    let epub_id = file(book_id, epub, "books/aa/bb/aabb.epub", "aabb").add(&pool).await.unwrap();
+    let pdf_id = file(book_id, pdf, "books/cc/dd/ccdd.pdf", "ccdd").add(&pool).await.unwrap();
+
+    // Il primo file diventa il preferito e viene riportato in books
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.file_link.as_deref(), Some("books/aa/bb/aabb.epub"));
+
+    BookFile::set_preferred(&pool, book_id, pdf_id).await.unwrap();
+    assert_eq!(BookFile::preferred(&pool, book_id).await.unwrap().unwrap().id, Some(pdf_id));
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.file_hash.as_deref(), Some("ccdd"));
+    assert_eq!(book.format_id, Some(pdf));
+
+    let picked = BookFile::pick_format(&pool, book_id, &["azw3", "epub"]).await.unwrap().unwrap();
+    assert_eq!(picked.id, Some(epub_id));
+
+    let found = Book::get_by_file_hash(&pool, "aabb").await.unwrap().unwrap();
+    assert_eq!(found.id, Some(book_id));
+
+    // Rimuovendo il preferito ne viene scelto un altro
+    let removed = BookFile::remove(&pool, pdf_id).await.unwrap().unwrap();
+    assert_eq!(removed.file_link, "books/cc/dd/ccdd.pdf");
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.file_link.as_deref(), Some("books/aa/bb/aabb.epub"));
+}
+
+#[tokio::test]
+async fn test_replace_format_keeps_preferred() {
+    let (_dir, pool) = common::setup_pool().await;
+    let epub = format(&pool, "EPUB").await;
+
+    let book_id = Book { name: "Se una notte d'inverno un viaggiatore".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    file(book_id, epub, "books/11/22/old.epub", "old").add(&pool).await.unwrap();
+
+    let replaced = file(book_id, epub, "books/33/44/new.epub", "new")
+        .replace_format(&pool)
+        .await
+        .unwrap();
+    assert_eq!(replaced.len(), 1);
+    assert_eq!(replaced[0].file_link, "books/11/22/old.epub");
+
+    let files = BookFile::list_by_book(&pool, book_id).await.unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].is_preferred, 1);
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.file_link.as_deref(), Some("books/33/44/new.epub"));
+}
+
+#[tokio::test]
+async fn test_migrate_single_file_books() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    // Libro memorizzato alla vecchia maniera, con il file solo in books
+    sqlx::query("INSERT INTO books (name, file_link, file_hash) VALUES ('Palomar', 'books/55/66/palomar.epub', 'palomar')")
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    assert_eq!(BookFile::migrate_from_books(&pool).await.unwrap(), 1);
+    assert_eq!(BookFile::migrate_from_books(&pool).await.unwrap(), 0);
+
+    let files = BookFile::list_by_hash(&pool, "palomar").await.unwrap();
+    assert_eq!(files.len(), 1);
+    assert_eq!(files[0].is_preferred, 1);
+}
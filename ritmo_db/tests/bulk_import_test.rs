@@ -0,0 +1,130 @@
+mod common;
+
+use ritmo_core::dto::{BookDto, PersonDto, TagDto};
+use ritmo_core::ContentDto;
+use ritmo_db::bulk_import::{BulkImportOptions, BulkImporter};
+use ritmo_db_core::maintenance::DeferredIndexesLock;
+use std::sync::{Arc, Mutex};
+
+fn book(i: usize) -> BookDto {
+    BookDto {
+        name: format!("Libro {}", i),
+        publisher_name: format!("Editore {}", i % 3),
+        format_name: "epub".to_string(),
+        people: vec![PersonDto {
+            person_id: None,
+            person_name: format!("Autore {}", i % 5),
+            person_role: "Author".to_string(),
+        }],
+        tags: vec![TagDto { name: format!("genere {}", i % 4), is_book_tag: true, is_content_tag: false }],
+        contents: vec![ContentDto {
+            name: format!("Racconto {}", i),
+            type_name: "Racconto".to_string(),
+            people: vec![format!("autore {}", i % 5)],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+async fn count(pool: &sqlx::SqlitePool, sql: &str) -> i64 {
+    sqlx::query_scalar(sql).fetch_one(pool).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_bulk_import_reuses_lookups() {
+    let (_dir, pool) = common::setup_pool().await;
+    common::person("Autore 0").save(&pool).await.unwrap();
+    let batches = Arc::new(Mutex::new(Vec::new()));
+    let seen = batches.clone();
+
+    let report = BulkImporter::new(&pool)
+        .with_options(BulkImportOptions { batch_size: 10, ..Default::default() })
+        .on_progress(move |p| seen.lock().unwrap().push(p.processed))
+        .import((0..25).map(book))
+        .await
+        .unwrap();
+
+    assert_eq!((report.books, report.contents), (25, 25));
+    assert_eq!(report.people_created, 4);
+    assert_eq!((report.publishers_created, report.tags_created, report.formats_created), (3, 4, 1));
+    assert!(report.unresolved_roles.is_empty());
+    assert_eq!(*batches.lock().unwrap(), [10, 20, 25]);
+    assert_eq!(count(&pool, "SELECT COUNT(*) FROM people").await, 5);
+    assert_eq!(count(&pool, "SELECT COUNT(*) FROM x_books_people_roles").await, 25);
+    // Libro e racconto accreditano la stessa persona anche se il nome differisce per le maiuscole
+    assert_eq!(count(&pool, "SELECT COUNT(DISTINCT person_id) FROM x_contents_people_roles").await, 5);
+    assert_eq!(count(&pool, "SELECT COUNT(*) FROM x_books_contents").await, 25);
+}
+
+#[tokio::test]
+async fn test_roles_are_resolved_by_label() {
+    let (_dir, pool) = common::setup_pool().await;
+    let roles_before = count(&pool, "SELECT COUNT(*) FROM roles").await;
+    let mut dto = book(0);
+    dto.people = ["Autore", "Traduttore", "Scenografo"]
+        .iter()
+        .enumerate()
+        .map(|(i, role)| PersonDto { person_id: None, person_name: format!("Persona {}", i), person_role: role.to_string() })
+        .collect();
+
+    let report = BulkImporter::new(&pool).import([dto]).await.unwrap();
+    assert_eq!(report.unresolved_roles, ["Scenografo"]);
+    assert_eq!(count(&pool, "SELECT COUNT(*) FROM roles").await, roles_before);
+    let codes: Vec<String> = sqlx::query_scalar(
+        "SELECT r.code FROM x_books_people_roles x JOIN roles r ON r.id = x.role_id
+         JOIN people p ON p.id = x.person_id ORDER BY p.name",
+    )
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(codes, ["aut", "trl", "aut"]);
+}
+
+#[tokio::test]
+async fn test_deferred_indexes_are_rebuilt() {
+    let (_dir, pool) = common::setup_pool().await;
+    let indexes = "SELECT COUNT(*) FROM sqlite_master WHERE type = 'index' AND sql IS NOT NULL";
+    let before = count(&pool, indexes).await;
+
+    let report = BulkImporter::new(&pool)
+        .with_options(BulkImportOptions { batch_size: 7, defer_indexes: true })
+        .import((0..20).map(book))
+        .await
+        .unwrap();
+
+    assert_eq!(report.books, 20);
+    assert_eq!(count(&pool, indexes).await, before);
+    let integrity: String = sqlx::query_scalar("PRAGMA integrity_check").fetch_one(&pool).await.unwrap();
+    assert_eq!(integrity, "ok");
+    assert_eq!(count(&pool, "SELECT COUNT(*) FROM system_config WHERE key = 'deferred_indexes'").await, 0);
+
+    // Un'importazione interrotta lascia l'indice eliminato e la sua definizione in sospeso:
+    // l'apertura del database lo ricrea
+    let definition: String =
+        sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE name = 'idx_acquisitions_book'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+    sqlx::query("DROP INDEX idx_acquisitions_book").execute(&pool).await.unwrap();
+    sqlx::query("INSERT INTO system_config (key, value) VALUES ('deferred_indexes', ?)")
+        .bind(serde_json::to_string(&[definition]).unwrap())
+        .execute(&pool)
+        .await
+        .unwrap();
+    // Finché l'importazione che li ha eliminati è viva (qui: il lock è tenuto) non si tocca niente
+    let mut conn = pool.acquire().await.unwrap();
+    let lock = DeferredIndexesLock::try_acquire(&mut conn).await.unwrap().unwrap();
+    assert!(DeferredIndexesLock::try_acquire(&mut conn).await.unwrap().is_none());
+    let db = ritmo_db_core::Database::from_pool(pool.clone()).await.unwrap();
+    assert_eq!(count(db.pool(), indexes).await, before - 1);
+    let deferred = BulkImporter::new(&pool).with_options(BulkImportOptions { batch_size: 7, defer_indexes: true });
+    assert!(deferred.import((20..22).map(book)).await.is_err());
+
+    // Terminata quell'importazione, l'apertura del database li ricrea
+    drop(lock);
+    drop(conn);
+    let db = ritmo_db_core::Database::from_pool(pool).await.unwrap();
+    assert_eq!(count(db.pool(), indexes).await, before);
+    assert_eq!(count(db.pool(), "SELECT COUNT(*) FROM system_config WHERE key = 'deferred_indexes'").await, 0);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use ritmo_db::models::{Book, BookTag, Collection, Content, Tag};
+use ritmo_db::smart_filter::{FilterRule, FilterTarget, SmartFilter, SortField};
+
+async fn book(pool: &sqlx::SqlitePool, name: &str) -> i64 {
+    Book { name: name.to_string(), ..Default::default() }.save(pool).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_manual_collection_ordering_and_nesting() {
+    let (_dir, pool) = common::setup_pool().await;
+    let a = book(&pool, "Fosca").await;
+    let b = book(&pool, "Senilità").await;
+    let content_id = Content { name: "La coscienza di Zeno".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+
+    let shelf = Collection::manual("Da rileggere", None).save(&pool).await.unwrap();
+    let first = Collection::add_book(&pool, shelf, a).await.unwrap();
+    let second = Collection::add_book(&pool, shelf, b).await.unwrap();
+    assert_eq!(Collection::add_book(&pool, shelf, a).await.unwrap(), first);
+    let third = Collection::add_content(&pool, shelf, content_id).await.unwrap();
+
+    Collection::move_item(&pool, second, 1).await.unwrap();
+    let names: Vec<String> = Collection::books(&pool, shelf).await.unwrap().into_iter().map(|b| b.name).collect();
+    assert_eq!(names, ["Senilità", "Fosca"]);
+    assert_eq!(Collection::contents(&pool, shelf).await.unwrap().len(), 1);
+    assert!(Collection::reorder_items(&pool, shelf, &[first, second]).await.is_err());
+    Collection::reorder_items(&pool, shelf, &[third, first, second]).await.unwrap();
+    let order: Vec<i64> = Collection::list_items(&pool, shelf).await.unwrap().into_iter().filter_map(|i| i.id).collect();
+    assert_eq!(order, [third, first, second]);
+
+    // Annidamento: niente cicli, e l'ordine tra sorelle è modificabile
+    let child = Collection::manual("Ottocento", Some(shelf)).save(&pool).await.unwrap();
+    let other = Collection::manual("Novecento", Some(shelf)).save(&pool).await.unwrap();
+    assert!(Collection::move_to(&pool, shelf, Some(child)).await.is_err());
+    Collection::reorder_children(&pool, Some(shelf), &[other, child]).await.unwrap();
+    let children: Vec<String> = Collection::list_children(&pool, Some(shelf))
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|c| c.name)
+        .collect();
+    assert_eq!(children, ["Novecento", "Ottocento"]);
+
+    Collection::delete(&pool, shelf).await.unwrap();
+    assert!(Collection::get(&pool, child).await.unwrap().is_none());
+    assert!(Book::get(&pool, a).await.unwrap().is_some());
+}
+
+#[tokio::test]
+async fn test_smart_collection_is_evaluated_live() {
+    let (_dir, pool) = common::setup_pool().await;
+    let tag_id = Tag { name: "giallo".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    let a = book(&pool, "Il giorno della civetta").await;
+    let b = book(&pool, "A ciascuno il suo").await;
+    book(&pool, "Il Gattopardo").await;
+    BookTag::create(&pool, &BookTag { book_id: a, tag_id }).await.unwrap();
+    Book::set_rating(&pool, a, Some(4.0)).await.unwrap();
+
+    let filter = SmartFilter {
+        sort_by: SortField::Name,
+        ..SmartFilter::new(FilterTarget::Books, vec![FilterRule::Tag("Giallo".to_string()), FilterRule::MinRating(3.0)])
+    };
+    let smart = Collection::smart("Gialli migliori", None, &filter).unwrap().save(&pool).await.unwrap();
+    assert_eq!(Collection::books(&pool, smart).await.unwrap().len(), 1);
+    assert!(Collection::add_book(&pool, smart, b).await.is_err());
+
+    // Le regole sono valutate a ogni lettura
+    BookTag::create(&pool, &BookTag { book_id: b, tag_id }).await.unwrap();
+    Book::set_rating(&pool, b, Some(5.0)).await.unwrap();
+    let names: Vec<String> = Collection::books(&pool, smart).await.unwrap().into_iter().map(|b| b.name).collect();
+    assert_eq!(names, ["A ciascuno il suo", "Il giorno della civetta"]);
+
+    // Regole non applicabili ai contenuti vengono rifiutate al salvataggio
+    let invalid = SmartFilter::new(FilterTarget::Contents, vec![FilterRule::Series("Montalbano".to_string())]);
+    assert!(Collection::smart("Errata", None, &invalid).unwrap().save(&pool).await.is_err());
+}
@@ -0,0 +1,45 @@
+#![allow(dead_code)]
+
+use ritmo_db::models::Person;
+use ritmo_db_core::connection::create_connection_pool;
+use sqlx::SqlitePool;
+use tempfile::TempDir;
+
+/// Crea un database di test copiando il template in una directory temporanea.
+/// La directory va tenuta in vita per tutta la durata del test.
+pub async fn setup_pool() -> (TempDir, SqlitePool) {
+    let temp_dir = tempfile::tempdir().expect("Impossibile creare directory temporanea");
+    let db_path = temp_dir.path().join("ritmo.db");
+    let template = concat!(env!("CARGO_MANIFEST_DIR"), "/assets/template.db");
+    std::fs::copy(template, &db_path).expect("Impossibile copiare il template");
+
+    let pool = create_connection_pool(&db_path, false)
+        .await
+        .expect("Impossibile aprire il database di test");
+    (temp_dir, pool)
+}
+
+/// Persona minima per i test
+pub fn person(name: &str) -> Person {
+    Person {
+        id: None,
+        name: name.to_string(),
+        display_name: None,
+        given_name: None,
+        surname: None,
+        middle_names: None,
+        title: None,
+        suffix: None,
+        nationality: None,
+        birth_date: None,
+        death_date: None,
+        biography: None,
+        normalized_key: None,
+        sort_name: None,
+        confidence: 1.0,
+        source: "test".to_string(),
+        verified: 0,
+        created_at: 0,
+        updated_at: 0,
+    }
+}
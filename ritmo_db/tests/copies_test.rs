@@ -0,0 +1,86 @@
+mod common;
+
+use ritmo_db::models::{Book, BookCopy, CopyEvent, Loan, Location};
+
+const DAY: i64 = 86_400;
+
+async fn setup_copy(pool: &sqlx::SqlitePool) -> (i64, i64, i64) {
+    let book_id = Book { name: "Il partigiano Johnny".to_string(), ..Default::default() }
+        .save(pool)
+        .await
+        .unwrap();
+    let study = Location { name: "Studio".to_string(), ..Default::default() }.save(pool).await.unwrap();
+    let shelf = Location { name: "Ripiano 3".to_string(), parent_id: Some(study), ..Default::default() }
+        .save(pool)
+        .await
+        .unwrap();
+    let copy_id = BookCopy {
+        book_id,
+        location_id: Some(shelf),
+        condition: Some("good".to_string()),
+        binding: Some("brossura".to_string()),
+        edition: Some("Einaudi, 1968".to_string()),
+        ..Default::default()
+    }
+    .save(pool)
+    .await
+    .unwrap();
+    (book_id, study, copy_id)
+}
+
+#[tokio::test]
+async fn test_copies_and_locations() {
+    let (_dir, pool) = common::setup_pool().await;
+    let (book_id, study, copy_id) = setup_copy(&pool).await;
+
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().has_paper, 1);
+    let copy = BookCopy::get(&pool, copy_id).await.unwrap().unwrap();
+    let path = Location::full_path(&pool, copy.location_id.unwrap()).await.unwrap();
+    assert_eq!(path.as_deref(), Some("Studio / Ripiano 3"));
+    // Le copie sui ripiani risultano anche nella stanza che li contiene
+    assert_eq!(BookCopy::list_by_location(&pool, study).await.unwrap().len(), 1);
+
+    BookCopy::move_to(&pool, copy_id, Some(study)).await.unwrap();
+    BookCopy { condition: Some("fair".to_string()), ..copy }.update(&pool).await.unwrap();
+    let events: Vec<String> = CopyEvent::list_by_copy(&pool, copy_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|e| e.event)
+        .collect();
+    assert_eq!(events, ["added", "moved", "condition"]);
+
+    BookCopy::delete(&pool, copy_id).await.unwrap();
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().has_paper, 0);
+}
+
+#[tokio::test]
+async fn test_loans_and_overdue_report() {
+    let (_dir, pool) = common::setup_pool().await;
+    let (_book_id, _study, copy_id) = setup_copy(&pool).await;
+    let now = 1_700_000_000;
+
+    let loan_id = Loan::lend(&pool, copy_id, "Marta", now - 40 * DAY, Some(now - 10 * DAY)).await.unwrap();
+    assert!(Loan::lend(&pool, copy_id, "Luca", now, None).await.is_err());
+
+    let overdue = Loan::overdue(&pool, now).await.unwrap();
+    assert_eq!(overdue.len(), 1);
+    assert_eq!(overdue[0].borrower, "Marta");
+    assert_eq!(overdue[0].book_name, "Il partigiano Johnny");
+    assert_eq!(overdue[0].days_overdue, 10);
+
+    assert_eq!(Loan::return_copy(&pool, loan_id, now).await.unwrap(), 1);
+    assert_eq!(Loan::return_copy(&pool, loan_id, now).await.unwrap(), 0);
+    assert!(Loan::overdue(&pool, now).await.unwrap().is_empty());
+    assert!(Loan::current(&pool, copy_id).await.unwrap().is_none());
+
+    Loan::lend(&pool, copy_id, "Luca", now, None).await.unwrap();
+    assert_eq!(Loan::list_by_copy(&pool, copy_id).await.unwrap().len(), 2);
+    let loan_events = CopyEvent::list_by_copy(&pool, copy_id)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|e| e.event == "lent" || e.event == "returned")
+        .count();
+    assert_eq!(loan_events, 3);
+}
@@ -0,0 +1,164 @@
+mod common;
+
+use ritmo_core::dto::{BookDto, PersonDto};
+use ritmo_core::service::import_service::{
+    DuplicateLayer, DuplicatePolicies, DuplicatePolicy, ImportOptions, ImportService, ImportStatus, SameFilePolicy,
+};
+use ritmo_core::service::storage_service::StorageService;
+use ritmo_db::bulk_import::BulkImporter;
+use ritmo_db::duplicates::find_duplicate;
+use ritmo_db::models::{Book, BookFile};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+fn opf(title: &str, isbn: &str) -> String {
+    format!(
+        r#"<package><metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>{}</dc:title>
+    <dc:creator opf:role="aut">Italo Calvino</dc:creator>
+    <dc:identifier opf:scheme="ISBN">{}</dc:identifier>
+</metadata></package>"#,
+        title, isbn
+    )
+}
+
+fn write(dir: &Path, name: &str, bytes: &[u8]) -> PathBuf {
+    fs::create_dir_all(dir).unwrap();
+    let path = dir.join(name);
+    fs::write(&path, bytes).unwrap();
+    path
+}
+
+#[tokio::test]
+async fn test_identifier_attaches_and_title_asks() {
+    let (dir, pool) = common::setup_pool().await;
+    let source = dir.path().join("libri");
+    let epub = write(&source.join("epub"), "barone.epub", b"epub del barone");
+    write(&source.join("epub"), "barone.opf", opf("Il barone rampante", "9788804668237").as_bytes());
+    let pdf = write(&source.join("pdf"), "barone.pdf", b"pdf del barone");
+    write(&source.join("pdf"), "barone.opf", opf("Il barone rampante", "978-88-04-66823-7").as_bytes());
+    let edition = write(&source, "Italo Calvino - Il barone rampamte.mobi", b"mobi di un'altra edizione");
+
+    let storage = StorageService::new(dir.path().join("storage"));
+    let mut service = ImportService::new(BulkImporter::new(&pool), storage).with_resolver(|_, found| {
+        assert_eq!(found.layer, DuplicateLayer::Title);
+        DuplicatePolicy::NewEdition
+    });
+    let report = service.import_paths(&[epub, pdf, edition]).await;
+
+    let ImportStatus::Added { book_id } = report.files[0].status else { panic!("{:?}", report.files[0]) };
+    assert_eq!(report.files[1].status, ImportStatus::Attached { book_id });
+    let decision = report.files[1].decision.as_ref().unwrap();
+    assert_eq!((decision.found.layer, decision.policy, decision.asked), (DuplicateLayer::Identifier, DuplicatePolicy::AttachFormat, false));
+    assert_eq!(BookFile::list_by_book(&pool, book_id).await.unwrap().len(), 2);
+
+    let ImportStatus::Added { book_id: edition_id } = report.files[2].status else { panic!("{:?}", report.files[2]) };
+    let decision = report.files[2].decision.as_ref().unwrap();
+    assert_eq!((decision.found.book_id, decision.policy, decision.asked), (book_id, DuplicatePolicy::NewEdition, true));
+    assert!(decision.found.score >= 0.85 && decision.found.score < 1.0);
+    let works: Vec<Option<i64>> = sqlx::query_scalar(
+        "SELECT c.work_id FROM contents c JOIN x_books_contents x ON x.content_id = c.id
+         WHERE x.book_id IN (?, ?) ORDER BY x.book_id",
+    )
+    .bind(book_id)
+    .bind(edition_id)
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+    assert_eq!(works.len(), 2);
+    assert!(works[0].is_some() && works[0] == works[1]);
+}
+
+#[tokio::test]
+async fn test_policies_without_resolver() {
+    let (dir, pool) = common::setup_pool().await;
+    let source = dir.path().join("libri");
+    let first = write(&source, "Italo Calvino - Il barone rampante.epub", b"epub del barone");
+    let copy = write(&source.join("copie"), "barone.epub", b"epub del barone");
+    let similar = write(&source, "Italo Calvino - Il Barone rampante!.pdf", b"pdf del barone");
+    let other_author = write(&source, "Primo Levi - Il barone rampante.pdf", b"un altro libro");
+
+    let storage = StorageService::new(dir.path().join("storage"));
+    // Un file identico non può diventare un'altra edizione né un formato
+    assert!("edition".parse::<SameFilePolicy>().is_err());
+    assert!("attach".parse::<SameFilePolicy>().is_err());
+    let duplicates = DuplicatePolicies { hash: SameFilePolicy::Skip, ..Default::default() };
+    let options = ImportOptions { duplicates, ..Default::default() };
+    let mut service = ImportService::new(BulkImporter::new(&pool), storage).with_options(options);
+    let report = service.import_paths(&[first]).await;
+    let ImportStatus::Added { book_id } = report.files[0].status else { panic!("{:?}", report.files[0]) };
+
+    let report = service.import_paths(&[copy, similar, other_author]).await;
+    assert_eq!(report.files[0].status, ImportStatus::Duplicate { book_id: Some(book_id), duplicate_of: None });
+    assert_eq!(report.files[0].decision.as_ref().unwrap().policy, DuplicatePolicy::Skip);
+    assert_eq!(report.files[1].status, ImportStatus::AwaitingDecision { book_id });
+    assert_eq!(report.files[1].decision.as_ref().unwrap().found.layer, DuplicateLayer::Title);
+    assert!(matches!(report.files[2].status, ImportStatus::Added { .. }));
+    assert!(report.files[2].decision.is_none());
+    assert_eq!((report.duplicates(), report.awaiting_decision(), report.added()), (1, 1, 1));
+    assert_eq!(Book::list_all(&pool).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_other_identifiers_and_trash() {
+    let (dir, pool) = common::setup_pool().await;
+    let source = dir.path().join("libri");
+    let asin = r#"<package><metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Le città invisibili</dc:title>
+    <dc:identifier opf:scheme="AMAZON">b00hvw7lma</dc:identifier>
+</metadata></package>"#;
+    let epub = write(&source.join("epub"), "citta.epub", b"epub delle citta");
+    write(&source.join("epub"), "citta.opf", asin.replace("Le città", "Città").as_bytes());
+    let pdf = write(&source.join("pdf"), "citta.pdf", b"pdf delle citta");
+    write(&source.join("pdf"), "citta.opf", asin.as_bytes());
+
+    let storage = StorageService::new(dir.path().join("storage"));
+    let mut service = ImportService::new(BulkImporter::new(&pool), storage.clone());
+    let report = service.import_paths(&[epub.clone(), pdf]).await;
+    let ImportStatus::Added { book_id } = report.files[0].status else { panic!("{:?}", report.files[0]) };
+    // Nessun ISBN: l'ASIN salvato in `book_identifiers` basta a riconoscere il libro
+    assert_eq!(report.files[1].status, ImportStatus::Attached { book_id });
+    assert_eq!(report.files[1].decision.as_ref().unwrap().found.layer, DuplicateLayer::Identifier);
+
+    assert!(!report.files[1].decision.as_ref().unwrap().found.in_trash);
+
+    // Nel cestino il libro vale ancora come duplicato, per ogni livello
+    Book::delete(&pool, book_id).await.unwrap();
+    let other = write(&source.join("mobi"), "citta.mobi", b"mobi delle citta");
+    write(&source.join("mobi"), "citta.opf", asin.as_bytes());
+    let duplicates = DuplicatePolicies { identifier: DuplicatePolicy::Skip, ..Default::default() };
+    let options = ImportOptions { duplicates, ..Default::default() };
+    let mut service = ImportService::new(BulkImporter::new(&pool), storage).with_options(options);
+    let report = service.import_paths(&[epub, other]).await;
+    for (file, layer) in report.files.iter().zip([DuplicateLayer::Hash, DuplicateLayer::Identifier]) {
+        assert_eq!(file.status, ImportStatus::Duplicate { book_id: Some(book_id), duplicate_of: None });
+        let found = &file.decision.as_ref().unwrap().found;
+        assert_eq!((found.layer, found.in_trash), (layer, true));
+    }
+    assert_eq!(Book::list_all(&pool).await.unwrap().len(), 0);
+}
+
+#[tokio::test]
+async fn test_title_layer_resolves_role_labels() {
+    let (_dir, pool) = common::setup_pool().await;
+    let person = |name: &str, role: &str| PersonDto {
+        person_id: None,
+        person_name: name.to_string(),
+        person_role: role.to_string(),
+    };
+    let existing = BookDto {
+        name: "Il barone rampante".to_string(),
+        people: vec![person("Italo Calvino", "Autore")],
+        ..Default::default()
+    };
+    BulkImporter::new(&pool).import([existing]).await.unwrap();
+
+    // Il traduttore viene prima, e l'autore è indicato con il nome inglese del ruolo
+    let incoming = BookDto {
+        name: "Il barone rampante".to_string(),
+        people: vec![person("Mario Rossi", "Traduttore"), person("Italo Calvino", "Author")],
+        ..Default::default()
+    };
+    let found = find_duplicate(&pool, &incoming).await.unwrap().unwrap();
+    assert_eq!(found.layer, DuplicateLayer::Title);
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use ritmo_db::models::{Book, BookCopy, BookTag, Tag};
+use ritmo_db_core::events::{ChangeEvent, ChangeFilter, ChangeKind, EntityKind};
+use ritmo_db_core::maintenance::prune_change_log;
+use ritmo_db_core::Database;
+use std::time::Duration;
+
+async fn next(subscription: &mut ritmo_db_core::events::ChangeSubscription) -> ChangeEvent {
+    tokio::time::timeout(Duration::from_secs(5), subscription.recv())
+        .await
+        .expect("nessun evento ricevuto")
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_committed_writes_are_published() {
+    let (_dir, pool) = common::setup_pool().await;
+    let db = Database::from_pool(pool).await.unwrap();
+    let mut books = db.subscribe_filtered(ChangeFilter::default().entity(EntityKind::Book));
+    let mut links = db.subscribe_filtered(ChangeFilter::default().kind(ChangeKind::Linked));
+
+    let book_id = Book { name: "Il barone rampante".to_string(), ..Default::default() }
+        .save(db.pool())
+        .await
+        .unwrap();
+    let tag_id = Tag { name: "classici".to_string(), ..Default::default() }.save(db.pool()).await.unwrap();
+    BookTag::create(db.pool(), &BookTag { book_id, tag_id }).await.unwrap();
+    Book::delete(db.pool(), book_id).await.unwrap();
+
+    let created = next(&mut books).await;
+    assert_eq!((created.kind, created.id), (ChangeKind::Created, book_id));
+    let linked = next(&mut links).await;
+    assert_eq!(linked.entity, EntityKind::BookTag);
+    assert_eq!((linked.id, linked.related_id), (book_id, Some(tag_id)));
+    assert_eq!(next(&mut books).await.kind, ChangeKind::Deleted);
+}
+
+#[tokio::test]
+async fn test_rolled_back_writes_are_not_published() {
+    let (_dir, pool) = common::setup_pool().await;
+    let db = Database::from_pool(pool).await.unwrap();
+    let mut events = db.subscribe_filtered(ChangeFilter::default().entity(EntityKind::Tag));
+
+    let mut tx = db.pool().begin().await.unwrap();
+    sqlx::query("INSERT INTO tags (name) VALUES ('annullato')").execute(&mut *tx).await.unwrap();
+    tx.rollback().await.unwrap();
+    let tag_id = Tag { name: "confermato".to_string(), ..Default::default() }.save(db.pool()).await.unwrap();
+
+    let event = next(&mut events).await;
+    assert_eq!((event.kind, event.id), (ChangeKind::Created, tag_id));
+}
+
+#[tokio::test]
+async fn test_newer_tables_publish_without_timestamp_copies() {
+    let (_dir, pool) = common::setup_pool().await;
+    let db = Database::from_pool(pool).await.unwrap();
+    let mut copies = db.subscribe_filtered(ChangeFilter::default().entity(EntityKind::BookCopy));
+    let mut books = db.subscribe_filtered(ChangeFilter::default().entity(EntityKind::Book));
+
+    let book_id = Book { name: "Lessico famigliare".to_string(), ..Default::default() }
+        .save(db.pool())
+        .await
+        .unwrap();
+    let copy_id = BookCopy { book_id, ..Default::default() }.save(db.pool()).await.unwrap();
+    let copy = next(&mut copies).await;
+    assert_eq!((copy.kind, copy.id, copy.related_id), (ChangeKind::Created, copy_id, Some(book_id)));
+
+    // Due modifiche uguali in commit separati restano due eventi; la riscrittura di
+    // `last_modified_date` fatta dal trigger non ne aggiunge altri
+    for notes in ["prima", "seconda"] {
+        sqlx::query("UPDATE books SET notes = ? WHERE id = ?")
+            .bind(notes)
+            .bind(book_id)
+            .execute(db.pool())
+            .await
+            .unwrap();
+    }
+    Book::delete(db.pool(), book_id).await.unwrap();
+    assert_eq!(next(&mut books).await.kind, ChangeKind::Created);
+    // La copia aggiorna `has_paper`, poi arrivano le due note e lo spostamento nel cestino
+    let mut kinds = Vec::new();
+    for _ in 0..4 {
+        kinds.push(next(&mut books).await.kind);
+    }
+    assert_eq!(kinds, [ChangeKind::Updated, ChangeKind::Updated, ChangeKind::Updated, ChangeKind::Deleted]);
+
+    let removed = prune_change_log(db.pool(), i64::MAX).await.unwrap();
+    assert!(removed >= 6);
+    let left: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM change_log").fetch_one(db.pool()).await.unwrap();
+    assert_eq!(left, 0);
+}
+
+#[tokio::test]
+async fn test_dispatcher_survives_errors() {
+    let (_dir, pool) = common::setup_pool().await;
+    let db = Database::from_pool(pool).await.unwrap();
+    let mut books = db.subscribe_filtered(ChangeFilter::default().entity(EntityKind::Book));
+
+    // Senza `change_log` la lettura fallisce; i trigger scrivono nella tabella rinominata
+    sqlx::query("ALTER TABLE change_log RENAME TO change_log_fuori").execute(db.pool()).await.unwrap();
+    let book_id = Book { name: "La tregua".to_string(), ..Default::default() }.save(db.pool()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    sqlx::query("ALTER TABLE change_log_fuori RENAME TO change_log").execute(db.pool()).await.unwrap();
+
+    // Il dispatcher riprende da dove si era fermato, senza perdere l'evento
+    let created = next(&mut books).await;
+    assert_eq!((created.kind, created.id), (ChangeKind::Created, book_id));
+}
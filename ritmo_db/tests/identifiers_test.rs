@@ -0,0 +1,54 @@
+mod common;
+
+use ritmo_db::identifiers::{Identifier, IdentifierScheme};
+use ritmo_db::models::{Book, BookIdentifier};
+
+#[tokio::test]
+async fn test_save_registers_isbn_and_lookup_is_format_insensitive() {
+    let (_dir, pool) = common::setup_pool().await;
+    let book_id = Book {
+        name: "Se una notte d'inverno un viaggiatore".to_string(),
+        isbn: Some("0-306-40615-2".to_string()),
+        ..Default::default()
+    }
+    .save(&pool)
+    .await
+    .unwrap();
+
+    let ids = BookIdentifier::list_by_book(&pool, book_id).await.unwrap();
+    assert_eq!(ids.len(), 1);
+    assert_eq!(ids[0].scheme, "isbn10");
+    assert_eq!(ids[0].value, "0306406152");
+
+    // Lo stesso libro si trova con l'ISBN-13 equivalente, con o senza trattini
+    for query in ["978-0-306-40615-7", "9780306406157", "0306406152", "isbn:0 306 40615 2"] {
+        let found = Book::get_by_identifier(&pool, query).await.unwrap().unwrap();
+        assert_eq!(found.id, Some(book_id), "query {}", query);
+    }
+    assert!(Book::get_by_identifier(&pool, "9780306406158").await.is_err());
+}
+
+#[tokio::test]
+async fn test_other_schemes_and_migration() {
+    let (_dir, pool) = common::setup_pool().await;
+    let book_id = Book { name: "Il barone rampante".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    let asin = Identifier::new(IdentifierScheme::Asin, "b000fa5zeg").unwrap();
+    let first = BookIdentifier::add(&pool, book_id, &asin).await.unwrap();
+    assert_eq!(BookIdentifier::add(&pool, book_id, &asin).await.unwrap(), first);
+    let found = Book::get_by_identifier(&pool, "asin:B000FA5ZEG").await.unwrap().unwrap();
+    assert_eq!(found.id, Some(book_id));
+
+    // Libri con ISBN inseriti prima della tabella degli identificatori
+    sqlx::query("INSERT INTO books (name, isbn) VALUES ('Vecchio', '978-88-452-9286-6'), ('Rotto', 'n/d')")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let (migrated, invalid) = BookIdentifier::migrate_from_books(&pool).await.unwrap();
+    assert_eq!(migrated, 1);
+    assert_eq!(invalid.len(), 1);
+    assert!(Book::get_by_identifier(&pool, "9788845292866").await.unwrap().is_some());
+    assert_eq!(BookIdentifier::migrate_from_books(&pool).await.unwrap().0, 0);
+}
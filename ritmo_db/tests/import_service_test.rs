@@ -0,0 +1,71 @@
+mod common;
+
+use ritmo_core::service::import_service::{ImportOptions, ImportService, ImportStatus};
+use ritmo_core::service::storage_service::StorageService;
+use ritmo_db::bulk_import::BulkImporter;
+use ritmo_db::models::{Book, Person};
+use std::fs;
+use std::path::Path;
+
+const OPF: &str = r#"<package><metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+    <dc:title>Il barone rampante</dc:title>
+    <dc:creator opf:role="aut">Italo Calvino</dc:creator>
+    <dc:publisher>Einaudi</dc:publisher>
+</metadata></package>"#;
+
+fn library(dir: &Path) {
+    fs::create_dir_all(dir.join("calvino")).unwrap();
+    fs::write(dir.join("calvino/barone.epub"), b"epub del barone").unwrap();
+    fs::write(dir.join("calvino/barone.opf"), OPF).unwrap();
+    fs::write(dir.join("Primo Levi - La tregua.pdf"), b"pdf della tregua").unwrap();
+    fs::write(dir.join("copia del barone.epub"), b"epub del barone").unwrap();
+    fs::write(dir.join("note.txt.bak"), b"ignorato").unwrap();
+}
+
+#[tokio::test]
+async fn test_import_directory_reports_each_file() {
+    let (dir, pool) = common::setup_pool().await;
+    let source = dir.path().join("da_importare");
+    library(&source);
+    let storage = StorageService::new(dir.path().join("storage"));
+    let mut service = ImportService::new(BulkImporter::new(&pool), storage.clone());
+
+    let report = service.import_paths(&[source.clone(), source.join("mancante.epub")]).await;
+    assert_eq!((report.added(), report.duplicates(), report.failed()), (2, 1, 1));
+    let barone = report.files.iter().find(|f| f.path.ends_with("calvino/barone.epub")).unwrap();
+    let ImportStatus::Added { book_id } = barone.status else { panic!("{:?}", barone.status) };
+
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.name, "Il barone rampante");
+    assert_eq!(book.sort_author.as_deref(), Some("calvino, italo"));
+    let link = book.file_link.unwrap();
+    assert_eq!(Some(link.as_str()), barone.file_link.as_deref());
+    assert_eq!(fs::read(storage.resolve(&link)).unwrap(), b"epub del barone");
+    assert!(Person::find_by_key(&pool, "Primo Levi").await.unwrap().len() == 1);
+
+    // Una seconda importazione riconosce tutto come già presente
+    let again = service.import_paths(&[source]).await;
+    assert_eq!((again.added(), again.duplicates()), (0, 3));
+}
+
+#[tokio::test]
+async fn test_dry_run_changes_nothing() {
+    let (dir, pool) = common::setup_pool().await;
+    let source = dir.path().join("da_importare");
+    library(&source);
+    let storage = StorageService::new(dir.path().join("storage"));
+    let options = ImportOptions { dry_run: true, ..Default::default() };
+    let mut service = ImportService::new(BulkImporter::new(&pool), storage).with_options(options);
+
+    let report = service.import_paths(&[source.join("calvino/barone.epub")]).await;
+    assert!(report.dry_run);
+    assert!(matches!(report.files[0].status, ImportStatus::Added { .. }));
+    let created: Vec<(&str, &str)> = report.files[0].created.iter().map(|e| (e.table, e.name.as_str())).collect();
+    assert!(created.contains(&("people", "Italo Calvino")));
+    assert!(created.contains(&("publishers", "Einaudi")));
+    assert!(created.contains(&("formats", "EPUB")));
+
+    assert!(Book::list_all(&pool).await.unwrap().is_empty());
+    assert!(Person::find_by_key(&pool, "Italo Calvino").await.unwrap().is_empty());
+    assert!(!dir.path().join("storage/books").exists());
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use ritmo_db::models::{
+    Alias, Book, BookPersonRole, BookTag, Person, Role, Tag,
+};
+
+#[tokio::test]
+async fn test_merge_people_moves_links_and_creates_alias() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    let mut survivor = common::person("Isaac Asimov");
+    survivor.normalized_key = Some("asimov isaac".to_string());
+    let survivor_id = survivor.save(&pool).await.unwrap();
+    let mut duplicate = common::person("I. Asimov");
+    duplicate.nationality = Some("US".to_string());
+    let duplicate_id = duplicate.save(&pool).await.unwrap();
+
+    let author = Role::get_by_code(&pool, Role::AUTHOR).await.unwrap().unwrap().id.unwrap();
+    let editor = Role::get_by_code(&pool, Role::EDITOR).await.unwrap().unwrap().id.unwrap();
+    let book_id = Book { name: "Io, robot".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    // Stesso libro e ruolo su entrambe: dopo la fusione deve restarne uno solo
+    for person_id in [survivor_id, duplicate_id] {
+        BookPersonRole::create(&pool, &BookPersonRole { book_id, person_id, role_id: author })
+            .await
+            .unwrap();
+    }
+    BookPersonRole::create(&pool, &BookPersonRole { book_id, person_id: duplicate_id, role_id: editor })
+        .await
+        .unwrap();
+
+    let summary = Person::merge(&pool, survivor_id, duplicate_id).await.unwrap();
+    assert_eq!(summary.links_moved, 1);
+    assert_eq!(summary.duplicate_links_removed, 1);
+
+    assert!(Person::get(&pool, duplicate_id).await.unwrap().is_none());
+    let merged = Person::get(&pool, survivor_id).await.unwrap().unwrap();
+    assert_eq!(merged.nationality.as_deref(), Some("US"));
+
+    let links = BookPersonRole::list_by_book(&pool, book_id).await.unwrap();
+    assert_eq!(links.len(), 2);
+    assert!(links.iter().all(|l| l.person_id == survivor_id));
+
+    let aliases = Alias::list_by_person(&pool, survivor_id).await.unwrap();
+    assert_eq!(aliases.len(), 1);
+    assert_eq!(aliases[0].name, "I. Asimov");
+
+    let merges: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM audit_log WHERE table_name = 'people' AND operation = 'MERGE' AND record_id = ?",
+    )
+    .bind(survivor_id)
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!(merges, 1);
+}
+
+#[tokio::test]
+async fn test_merge_tags_removes_duplicate_links() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    let sf = Tag { name: "fantascienza".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    let sci_fi = Tag { name: "sci-fi".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    let book1 = Book { name: "Fondazione".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    let book2 = Book { name: "Dune".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+
+    BookTag::create(&pool, &BookTag { book_id: book1, tag_id: sf }).await.unwrap();
+    BookTag::create(&pool, &BookTag { book_id: book1, tag_id: sci_fi }).await.unwrap();
+    BookTag::create(&pool, &BookTag { book_id: book2, tag_id: sci_fi }).await.unwrap();
+
+    let summary = Tag::merge(&pool, sf, sci_fi).await.unwrap();
+    assert_eq!(summary.links_moved, 1);
+    assert_eq!(summary.duplicate_links_removed, 1);
+    assert!(Tag::get(&pool, sci_fi).await.unwrap().is_none());
+    assert_eq!(BookTag::list_by_tag(&pool, sf).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_merge_rejects_invalid_ids() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    let id = Tag { name: "giallo".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    assert!(Tag::merge(&pool, id, id).await.is_err());
+    assert!(Tag::merge(&pool, id, id + 100).await.is_err());
+    assert!(Tag::get(&pool, id).await.unwrap().is_some());
+}
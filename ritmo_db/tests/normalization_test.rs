@@ -0,0 +1,64 @@
+mod common;
+
+use ritmo_db::models::{Alias, Person};
+use ritmo_db_core::maintenance::recompute_normalized_keys;
+
+async fn key(pool: &sqlx::SqlitePool, person_id: i64) -> Option<String> {
+    sqlx::query_scalar("SELECT normalized_key FROM people WHERE id = ?")
+        .bind(person_id)
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_keys_follow_the_shared_normalizer() {
+    let (_dir, pool) = common::setup_pool().await;
+    let folded: String = sqlx::query_scalar("SELECT ritmo_normalize(' Stanisław  Lem ')").fetch_one(&pool).await.unwrap();
+    assert_eq!(folded, "stanislaw lem");
+
+    // Scrittura SQL diretta: la chiave la calcola il trigger
+    let raw = sqlx::query("INSERT INTO people (name) VALUES ('Gabriel García Márquez')")
+        .execute(&pool)
+        .await
+        .unwrap()
+        .last_insert_rowid();
+    assert_eq!(key(&pool, raw).await.as_deref(), Some("gabriel garcia marquez"));
+    sqlx::query("UPDATE people SET name = 'Gabo García' WHERE id = ?").bind(raw).execute(&pool).await.unwrap();
+    assert_eq!(key(&pool, raw).await.as_deref(), Some("gabo garcia"));
+
+    let saved = common::person("Karel Čapek").save(&pool).await.unwrap();
+    assert_eq!(key(&pool, saved).await.as_deref(), Some("karel capek"));
+    Alias { name: "K. Čapek".to_string(), person_id: Some(saved), ..Default::default() }.save(&pool).await.unwrap();
+    assert_eq!(Person::find_by_key(&pool, "k capek").await.unwrap()[0].id, Some(saved));
+    assert_eq!(Person::find_by_key(&pool, "GABO GARCIA").await.unwrap()[0].id, Some(raw));
+
+    // Una chiave impostata a mano non viene toccata da un cambio di nome
+    sqlx::query("UPDATE people SET normalized_key = 'capek karel' WHERE id = ?").bind(saved).execute(&pool).await.unwrap();
+    sqlx::query("UPDATE people SET name = 'Karel Capek' WHERE id = ?").bind(saved).execute(&pool).await.unwrap();
+    assert_eq!(key(&pool, saved).await.as_deref(), Some("capek karel"));
+}
+
+#[tokio::test]
+async fn test_recompute_replaces_stale_keys() {
+    let (_dir, pool) = common::setup_pool().await;
+    let person_id = common::person("Émile Zola").save(&pool).await.unwrap();
+    common::person("Italo Calvino").save(&pool).await.unwrap();
+    sqlx::query("INSERT INTO aliases (name, person_id, alias_normalized) VALUES ('É. Zola', ?, 'é. zola')")
+        .bind(person_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    // Chiavi scritte con il vecchio LOWER(TRIM(...))
+    sqlx::query("UPDATE people SET normalized_key = LOWER(TRIM(name)) WHERE id = ?")
+        .bind(person_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let report = recompute_normalized_keys(&pool).await.unwrap();
+    assert_eq!((report.people, report.aliases), (1, 1));
+    assert_eq!(key(&pool, person_id).await.as_deref(), Some("emile zola"));
+    assert_eq!(Person::find_by_key(&pool, "E. Zola").await.unwrap().len(), 1);
+    assert_eq!(recompute_normalized_keys(&pool).await.unwrap(), Default::default());
+}
@@ -0,0 +1,82 @@
+mod common;
+
+use ritmo_db::models::{Book, Content, Person};
+use ritmo_db::partial_date::PartialDate;
+
+#[tokio::test]
+async fn test_partial_dates_roundtrip_and_sort_in_sql() {
+    let (_dir, pool) = common::setup_pool().await;
+    for (name, date) in [
+        ("Ulysses", "February 1922"),
+        ("Odissea", "c. 700 BCE"),
+        ("Don Chisciotte", "1605"),
+        ("Il Decameron", "c. 1350"),
+    ] {
+        let mut content = Content { name: name.to_string(), ..Default::default() };
+        content.set_publication(Some(PartialDate::parse(date).unwrap()));
+        content.save(&pool).await.unwrap();
+    }
+
+    let names: Vec<String> = sqlx::query_scalar("SELECT name FROM contents ORDER BY publication_date")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(names, ["Odissea", "Il Decameron", "Don Chisciotte", "Ulysses"]);
+
+    let contents = Content::list_all(&pool).await.unwrap();
+    let odissea = contents.iter().find(|c| c.name == "Odissea").unwrap();
+    let date = odissea.publication().unwrap();
+    assert!(date.bce && date.circa);
+    assert_eq!(date.to_string(), "c. 0700 BCE");
+
+    let mut person = common::person("Miguel de Cervantes");
+    person.set_birth(Some(PartialDate::parse("29 September 1547").unwrap()));
+    person.set_death(Some(PartialDate::parse("1616-04-22").unwrap()));
+    let id = person.save(&pool).await.unwrap();
+    let saved = Person::get(&pool, id).await.unwrap().unwrap();
+    assert_eq!(saved.birth(), Some(PartialDate::new(1547, Some(9), Some(29)).unwrap()));
+    assert_eq!(saved.death().unwrap().year, 1616);
+}
+
+#[tokio::test]
+async fn test_migrate_timestamps() {
+    let (_dir, pool) = common::setup_pool().await;
+    // Le librerie nuove usano già la codifica di PartialDate
+    assert_eq!(PartialDate::migrate_timestamps(&pool).await.unwrap(), 0);
+
+    // Simula una libreria precedente, con le date salvate come timestamp Unix
+    sqlx::query("DELETE FROM system_config WHERE key = 'date_encoding'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let book_id = Book {
+        name: "Il nome della rosa".to_string(),
+        publication_date: Some(315532800), // 1980-01-01
+        ..Default::default()
+    }
+    .save(&pool)
+    .await
+    .unwrap();
+
+    assert_eq!(PartialDate::migrate_timestamps(&pool).await.unwrap(), 1);
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.publication(), Some(PartialDate::new(1980, Some(1), Some(1)).unwrap()));
+    assert_eq!(PartialDate::migrate_timestamps(&pool).await.unwrap(), 0);
+}
+
+#[test]
+fn test_book_dto_keeps_date_precision() {
+    // Solo anno e mese nei metadati: il timestamp direbbe 1° giugno, la data resta "giugno 1957"
+    let mut dto = ritmo_core::dto::BookDto {
+        name: "Il barone rampante".to_string(),
+        publication_date: Some(-397180800),
+        publication_date_text: Some("1957-06".to_string()),
+        ..Default::default()
+    };
+    let book = Book::from_dto(&mut dto);
+    assert_eq!(book.publication(), Some(PartialDate::new(1957, Some(6), None).unwrap()));
+
+    dto.publication_date_text = None;
+    let book = Book::from_dto(&mut dto);
+    assert_eq!(book.publication(), Some(PartialDate::new(1957, Some(6), Some(1)).unwrap()));
+}
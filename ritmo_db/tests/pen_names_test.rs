@@ -0,0 +1,65 @@
+mod common;
+
+use ritmo_db::models::{Book, BookPersonRole, NameDisplay, PenName, Person};
+
+async fn author_role(pool: &sqlx::SqlitePool) -> i64 {
+    sqlx::query_scalar("SELECT id FROM roles WHERE code = 'aut'")
+        .fetch_one(pool)
+        .await
+        .unwrap()
+}
+
+async fn credited_book(pool: &sqlx::SqlitePool, name: &str, person_id: i64, role_id: i64) -> i64 {
+    let book_id = Book { name: name.to_string(), ..Default::default() }.save(pool).await.unwrap();
+    BookPersonRole::create(pool, &BookPersonRole { book_id, person_id, role_id })
+        .await
+        .unwrap();
+    book_id
+}
+
+#[tokio::test]
+async fn test_collective_pen_name_credits() {
+    let (_dir, pool) = common::setup_pool().await;
+    let role_id = author_role(&pool).await;
+    let queen = common::person("Ellery Queen").save(&pool).await.unwrap();
+    let dannay = common::person("Frederic Dannay").save(&pool).await.unwrap();
+    let lee = common::person("Manfred B. Lee").save(&pool).await.unwrap();
+    PenName::link(&pool, queen, dannay, None).await.unwrap();
+    PenName::link(&pool, queen, lee, Some("cugino di Dannay")).await.unwrap();
+    assert!(PenName::link(&pool, dannay, lee, None).await.is_err());
+    assert!(PenName::is_pen_name(&pool, queen).await.unwrap());
+
+    let book_id = credited_book(&pool, "Il mistero del cappello romano", queen, role_id).await;
+    let signed = PenName::book_credits(&pool, book_id, NameDisplay::PenName).await.unwrap();
+    assert_eq!(signed.len(), 1);
+    assert_eq!(signed[0].name, "Ellery Queen");
+
+    let real = PenName::book_credits(&pool, book_id, NameDisplay::RealIdentity).await.unwrap();
+    let names: Vec<&str> = real.iter().map(|c| c.name.as_str()).collect();
+    assert_eq!(names, ["Frederic Dannay", "Manfred B. Lee"]);
+    assert!(real.iter().all(|c| c.credited_as == Some(queen)));
+    assert_eq!(PenName::real_people(&pool, queen).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_search_across_pen_names() {
+    let (_dir, pool) = common::setup_pool().await;
+    let role_id = author_role(&pool).await;
+    let bachman = common::person("Richard Bachman").save(&pool).await.unwrap();
+    let king = common::person("Stephen King").save(&pool).await.unwrap();
+    PenName::link(&pool, bachman, king, None).await.unwrap();
+    credited_book(&pool, "L'uomo in fuga", bachman, role_id).await;
+    credited_book(&pool, "Shining", king, role_id).await;
+
+    assert_eq!(PenName::books_by_person(&pool, king, NameDisplay::PenName).await.unwrap().len(), 1);
+    let all = PenName::books_by_person(&pool, king, NameDisplay::RealIdentity).await.unwrap();
+    assert_eq!(all.len(), 2);
+    assert_eq!(PenName::books_by_person(&pool, bachman, NameDisplay::RealIdentity).await.unwrap().len(), 2);
+    let pen_names: Vec<String> = PenName::pen_names_of(&pool, king).await.unwrap().into_iter().map(|p| p.name).collect();
+    assert_eq!(pen_names, ["Richard Bachman"]);
+
+    // Fondendo un duplicato della persona reale i legami passano alla superstite
+    let duplicate = common::person("S. King").save(&pool).await.unwrap();
+    Person::merge(&pool, duplicate, king).await.unwrap();
+    assert_eq!(PenName::real_people(&pool, bachman).await.unwrap()[0].id, Some(duplicate));
+}
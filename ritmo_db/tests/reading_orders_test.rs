@@ -0,0 +1,121 @@
+mod common;
+
+use ritmo_db::models::{Book, Content, ReadingOrder, ReadingStatus, Series};
+
+async fn book(pool: &sqlx::SqlitePool, name: &str, series_id: i64, series_index: f64) -> i64 {
+    Book {
+        name: name.to_string(),
+        series_id: Some(series_id),
+        series_index: Some(series_index),
+        ..Default::default()
+    }
+    .save(pool)
+    .await
+    .unwrap()
+}
+
+async fn series(pool: &sqlx::SqlitePool, name: &str) -> i64 {
+    Series {
+        id: None,
+        name: name.to_string(),
+        description: None,
+        total_books: None,
+        completed: 0,
+        created_at: 0,
+        updated_at: 0,
+        parent_id: None,
+    }
+    .save(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_chronological_order_and_next_unread() {
+    let (_dir, pool) = common::setup_pool().await;
+    let narnia = Series {
+        id: None,
+        name: "Le cronache di Narnia".to_string(),
+        description: None,
+        total_books: Some(7),
+        completed: 0,
+        created_at: 0,
+        updated_at: 0,
+        parent_id: None,
+    }
+    .save(&pool)
+    .await
+    .unwrap();
+    let wardrobe = book(&pool, "Il leone, la strega e l'armadio", narnia, 1.0).await;
+    let caspian = book(&pool, "Il principe Caspian", narnia, 2.0).await;
+    let nephew = book(&pool, "Il nipote del mago", narnia, 6.0).await;
+    let story = Content { name: "Un racconto di Narnia".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+
+    let order = ReadingOrder::new(narnia, "Cronologico").save(&pool).await.unwrap();
+    let first = ReadingOrder::add_book(&pool, order, wardrobe).await.unwrap();
+    ReadingOrder::add_book(&pool, order, caspian).await.unwrap();
+    let prequel = ReadingOrder::add_book(&pool, order, nephew).await.unwrap();
+    ReadingOrder::add_content(&pool, order, story).await.unwrap();
+    assert_eq!(ReadingOrder::add_book(&pool, order, wardrobe).await.unwrap(), first);
+    ReadingOrder::move_item(&pool, prequel, 1).await.unwrap();
+    assert_eq!(ReadingOrder::list_by_series(&pool, narnia).await.unwrap().len(), 1);
+
+    let next = ReadingOrder::next_unread(&pool, order).await.unwrap().unwrap();
+    assert_eq!(next.book_id, Some(nephew));
+    Book::set_read_status(&pool, nephew, ReadingStatus::Read).await.unwrap();
+    Book::set_read_status(&pool, wardrobe, ReadingStatus::Abandoned).await.unwrap();
+    assert_eq!(ReadingOrder::next_unread(&pool, order).await.unwrap().unwrap().book_id, Some(caspian));
+
+    // Il contenuto risulta letto quando è letto un libro che lo contiene
+    Book::set_read_status(&pool, caspian, ReadingStatus::Read).await.unwrap();
+    sqlx::query("INSERT INTO x_books_contents (book_id, content_id) VALUES (?, ?)")
+        .bind(caspian)
+        .bind(story)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert!(ReadingOrder::next_unread(&pool, order).await.unwrap().is_none());
+    // Per i contenuti vale la stessa regola dei libri: anche abbandonato conta come finito
+    Book::set_read_status(&pool, caspian, ReadingStatus::Abandoned).await.unwrap();
+    assert!(ReadingOrder::next_unread(&pool, order).await.unwrap().is_none());
+
+    // L'ordine di pubblicazione resta indipendente
+    assert!(ReadingOrder::new(narnia, "Cronologico").save(&pool).await.is_err());
+    let publication = ReadingOrder::new(narnia, "Pubblicazione").save(&pool).await.unwrap();
+    assert!(ReadingOrder::next_unread(&pool, publication).await.unwrap().is_none());
+    assert_eq!(ReadingOrder::entries(&pool, order).await.unwrap().len(), 4);
+}
+
+#[tokio::test]
+async fn test_series_merge_keeps_reading_orders() {
+    let (_dir, pool) = common::setup_pool().await;
+    let survivor = series(&pool, "Narnia").await;
+    let merged = series(&pool, "Cronache di Narnia").await;
+    let wardrobe = book(&pool, "Il leone, la strega e l'armadio", survivor, 1.0).await;
+    let nephew = book(&pool, "Il nipote del mago", merged, 6.0).await;
+    let horse = book(&pool, "Il cavallo e il ragazzo", merged, 5.0).await;
+
+    let kept = ReadingOrder::new(survivor, "Cronologico").save(&pool).await.unwrap();
+    ReadingOrder::add_book(&pool, kept, wardrobe).await.unwrap();
+    // Stesso nome nelle due serie: gli elementi confluiscono nell'ordine superstite
+    let twin = ReadingOrder::new(merged, "Cronologico").save(&pool).await.unwrap();
+    ReadingOrder::add_book(&pool, twin, nephew).await.unwrap();
+    ReadingOrder::add_book(&pool, twin, wardrobe).await.unwrap();
+    ReadingOrder::add_book(&pool, twin, horse).await.unwrap();
+    let publication = ReadingOrder::new(merged, "Pubblicazione").save(&pool).await.unwrap();
+    ReadingOrder::add_book(&pool, publication, horse).await.unwrap();
+
+    Series::merge(&pool, survivor, merged).await.unwrap();
+
+    let orders = ReadingOrder::list_by_series(&pool, survivor).await.unwrap();
+    let names: Vec<&str> = orders.iter().map(|o| o.name.as_str()).collect();
+    assert_eq!(names, ["Cronologico", "Pubblicazione"]);
+    assert_eq!(orders[1].id, Some(publication));
+    let books: Vec<Option<i64>> =
+        ReadingOrder::entries(&pool, kept).await.unwrap().into_iter().map(|e| e.book_id).collect();
+    assert_eq!(books, [Some(wardrobe), Some(nephew), Some(horse)]);
+    assert!(ReadingOrder::get(&pool, twin).await.unwrap().is_none());
+}
@@ -0,0 +1,59 @@
+mod common;
+
+use ritmo_db::models::{Book, Content, ReadingSession, ReadingStatus};
+
+async fn book(pool: &sqlx::SqlitePool, name: &str) -> i64 {
+    Book { name: name.to_string(), ..Default::default() }.save(pool).await.unwrap()
+}
+
+#[tokio::test]
+async fn test_sessions_drive_book_status() {
+    let (_dir, pool) = common::setup_pool().await;
+    let book_id = book(&pool, "Il sistema periodico").await;
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().read_status, "unread");
+
+    let first = ReadingSession::start(&pool, book_id, 1_600_000_000).await.unwrap();
+    ReadingSession::update_progress(&pool, first, 40.0).await.unwrap();
+    assert!(ReadingSession::update_progress(&pool, first, 140.0).await.is_err());
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().read_status, "reading");
+
+    ReadingSession::finish(&pool, first, 1_600_500_000).await.unwrap();
+    let session = ReadingSession::get(&pool, first).await.unwrap().unwrap();
+    assert_eq!(session.progress, 100.0);
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().read_status, "read");
+
+    // Una rilettura abbandonata resta una sessione a parte e il libro resta letto
+    let second = ReadingSession::start(&pool, book_id, 1_700_000_000).await.unwrap();
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().read_status, "reading");
+    ReadingSession::abandon(&pool, second, 1_700_100_000).await.unwrap();
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().read_status, "read");
+    assert_eq!(ReadingSession::list_by_book(&pool, book_id).await.unwrap().len(), 2);
+    assert_eq!(ReadingSession::read_count(&pool, book_id).await.unwrap(), 1);
+    assert!(ReadingSession::current(&pool, book_id).await.unwrap().is_none());
+
+    let read = Book::list_by_read_status(&pool, ReadingStatus::Read).await.unwrap();
+    assert_eq!(read.len(), 1);
+}
+
+#[tokio::test]
+async fn test_ratings_and_library_stats() {
+    let (_dir, pool) = common::setup_pool().await;
+    let a = book(&pool, "La tregua").await;
+    let b = book(&pool, "La chiave a stella").await;
+    Book::set_rating(&pool, a, Some(4.5)).await.unwrap();
+    Book::set_rating(&pool, b, Some(3.5)).await.unwrap();
+    assert!(Book::set_rating(&pool, b, Some(6.0)).await.is_err());
+    Book::set_read_status(&pool, b, ReadingStatus::Abandoned).await.unwrap();
+
+    let content_id = Content { name: "Ferro".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    Content::set_rating(&pool, content_id, Some(5.0)).await.unwrap();
+    assert_eq!(Content::get(&pool, content_id).await.unwrap().unwrap().rating, Some(5.0));
+
+    let (rated, avg, unread, abandoned): (i64, f64, i64, i64) = sqlx::query_as(
+        "SELECT rated, avg_rating, unread_count, abandoned_count FROM LibraryStats WHERE entity_type = 'books'",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    assert_eq!((rated, avg, unread, abandoned), (2, 4.0, 1, 1));
+}
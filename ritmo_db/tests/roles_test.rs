@@ -0,0 +1,65 @@
+mod common;
+
+use ritmo_db::models::{Book, BookPersonRole, Role, RoleLabel};
+
+#[tokio::test]
+async fn test_roles_are_seeded_with_relator_codes() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    let author = Role::get_by_code(&pool, Role::AUTHOR).await.unwrap().unwrap();
+    assert_eq!(author.name, "Author");
+
+    let labels = RoleLabel::list_by_role(&pool, author.id.unwrap()).await.unwrap();
+    assert!(labels.iter().any(|l| l.language == "it" && l.label == "Autore"));
+
+    let translator = Role::get_by_code(&pool, Role::TRANSLATOR).await.unwrap().unwrap();
+    let it_name = Role::localized_name(&pool, translator.id.unwrap(), "it").await.unwrap();
+    assert_eq!(it_name.as_deref(), Some("Traduttore"));
+    // Lingua senza etichetta: si ricade sul nome
+    let xx_name = Role::localized_name(&pool, translator.id.unwrap(), "xx").await.unwrap();
+    assert_eq!(xx_name.as_deref(), Some("Translator"));
+}
+
+#[tokio::test]
+async fn test_find_by_label_is_language_independent() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    let it = Role::find_by_label(&pool, "autore").await.unwrap().unwrap();
+    let en = Role::find_by_label(&pool, "Author").await.unwrap().unwrap();
+    let code = Role::find_by_label(&pool, "aut").await.unwrap().unwrap();
+    assert_eq!(it.id, en.id);
+    assert_eq!(en.id, code.id);
+    assert!(Role::find_by_label(&pool, "nessuno").await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_books_without_author_uses_relator_code() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    let book = Book {
+        name: "Il nome della rosa".to_string(),
+        ..Default::default()
+    };
+    let book_id = book.save(&pool).await.unwrap();
+    let person_id = common::person("Umberto Eco").save(&pool).await.unwrap();
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM BooksWithoutAuthor")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+
+    let role = Role::find_by_label(&pool, "Autore").await.unwrap().unwrap();
+    BookPersonRole::create(
+        &pool,
+        &BookPersonRole { book_id, person_id, role_id: role.id.unwrap() },
+    )
+    .await
+    .unwrap();
+
+    let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM BooksWithoutAuthor")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
+}
@@ -0,0 +1,97 @@
+mod common;
+
+use ritmo_db::models::{Book, Content, Series};
+
+async fn series(pool: &sqlx::SqlitePool, name: &str, total_books: Option<i64>, parent_id: Option<i64>) -> i64 {
+    Series {
+        id: None,
+        name: name.to_string(),
+        description: None,
+        total_books,
+        completed: 0,
+        created_at: 0,
+        updated_at: 0,
+        parent_id,
+    }
+    .save(pool)
+    .await
+    .unwrap()
+}
+
+async fn book(pool: &sqlx::SqlitePool, name: &str, series_id: i64, series_index: f64) -> i64 {
+    Book {
+        name: name.to_string(),
+        series_id: Some(series_id),
+        series_index: Some(series_index),
+        ..Default::default()
+    }
+    .save(pool)
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_fractional_indices_gaps_and_completion() {
+    let (_dir, pool) = common::setup_pool().await;
+    let id = series(&pool, "Fondazione", Some(3), None).await;
+    book(&pool, "Preludio alla Fondazione", id, 0.0).await;
+    book(&pool, "Fondazione", id, 1.0).await;
+    let novella = Content { name: "Il mulo".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    Series::add_content(&pool, id, novella, Some(2.5)).await.unwrap();
+
+    let gaps = Series::gaps(&pool, id).await.unwrap();
+    assert_eq!(gaps.owned, [0.0, 1.0, 2.5]);
+    assert_eq!(gaps.missing, [2, 3]);
+    assert_eq!(Series::get(&pool, id).await.unwrap().unwrap().completed, 0);
+
+    book(&pool, "Fondazione e Impero", id, 2.0).await;
+    let last = book(&pool, "Seconda Fondazione", id, 3.0).await;
+    assert!(Series::gaps(&pool, id).await.unwrap().missing.is_empty());
+    assert_eq!(Series::get(&pool, id).await.unwrap().unwrap().completed, 1);
+    let names: Vec<String> = Series::members(&pool, id).await.unwrap().into_iter().map(|m| m.name).collect();
+    assert_eq!(names[2], "Fondazione e Impero");
+    assert_eq!(names[3], "Il mulo");
+
+    // Un volume cestinato torna a mancare
+    Book::delete(&pool, last).await.unwrap();
+    assert_eq!(Series::gaps(&pool, id).await.unwrap().missing, [3]);
+    assert_eq!(Series::get(&pool, id).await.unwrap().unwrap().completed, 0);
+}
+
+#[tokio::test]
+async fn test_sub_series_tree() {
+    let (_dir, pool) = common::setup_pool().await;
+    let universe = series(&pool, "Cosmere", None, None).await;
+    let cycle = series(&pool, "Mistborn", None, Some(universe)).await;
+    let trilogy = series(&pool, "Era 1", Some(3), Some(cycle)).await;
+
+    let children: Vec<String> = Series::list_children(&pool, Some(cycle)).await.unwrap().into_iter().map(|s| s.name).collect();
+    assert_eq!(children, ["Era 1"]);
+    assert_eq!(Series::subtree(&pool, universe).await.unwrap().len(), 3);
+    assert!(Series::move_to(&pool, universe, Some(trilogy)).await.is_err());
+
+    Series::move_to(&pool, trilogy, None).await.unwrap();
+    assert_eq!(Series::list_children(&pool, None).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_merge_keeps_the_tree_acyclic() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    // La superstite è due livelli sotto la serie eliminata: Cosmere → Mistborn → Era 1
+    let universe = series(&pool, "Cosmere", None, None).await;
+    let cycle = series(&pool, "Mistborn", None, Some(universe)).await;
+    let trilogy = series(&pool, "Era 1", Some(3), Some(cycle)).await;
+    Series::merge(&pool, trilogy, universe).await.unwrap();
+    assert_eq!(Series::get(&pool, cycle).await.unwrap().unwrap().parent_id, None);
+    assert_eq!(Series::get(&pool, trilogy).await.unwrap().unwrap().parent_id, Some(cycle));
+    assert_eq!(Series::subtree(&pool, cycle).await.unwrap().len(), 2);
+
+    // Caso opposto: la serie eliminata sta sotto la superstite, che non eredita il suo genitore
+    let root = series(&pool, "Discworld", None, None).await;
+    let middle = series(&pool, "Guardie", None, Some(root)).await;
+    let leaf = series(&pool, "Guardie! Guardie!", None, Some(middle)).await;
+    Series::merge(&pool, root, leaf).await.unwrap();
+    assert_eq!(Series::get(&pool, root).await.unwrap().unwrap().parent_id, None);
+    assert_eq!(Series::get(&pool, middle).await.unwrap().unwrap().parent_id, Some(root));
+}
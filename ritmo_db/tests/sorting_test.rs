@@ -0,0 +1,77 @@
+mod common;
+
+use ritmo_db::models::{Book, BookPersonRole, Content, ContentLanguage, NewContentLanguage, Person};
+use ritmo_db_core::maintenance::recompute_sort_keys;
+
+async fn german(pool: &sqlx::SqlitePool) -> i64 {
+    sqlx::query(
+        "INSERT INTO running_languages (iso_code_2char, iso_code_3char, official_name, language_role)
+         VALUES ('de', 'deu', 'Tedesco', 'Actual')",
+    )
+    .execute(pool)
+    .await
+    .unwrap()
+    .last_insert_rowid()
+}
+
+#[tokio::test]
+async fn test_titles_sort_without_articles_in_their_language() {
+    let (_dir, pool) = common::setup_pool().await;
+    for name in ["Zanna Bianca", "Il nome della rosa", "Émile", "L'isola del tesoro"] {
+        Book { name: name.to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    }
+    let names: Vec<String> = Book::list_all(&pool).await.unwrap().into_iter().map(|b| b.name).collect();
+    assert_eq!(names, ["Émile", "L'isola del tesoro", "Il nome della rosa", "Zanna Bianca"]);
+
+    // L'articolo tedesco si riconosce solo quando il contenuto ha una lingua
+    let book_id = Book { name: "Die Verwandlung".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    let content_id = Content { name: "Die Verwandlung".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    sqlx::query("INSERT INTO x_books_contents (book_id, content_id) VALUES (?, ?)")
+        .bind(book_id)
+        .bind(content_id)
+        .execute(&pool)
+        .await
+        .unwrap();
+    assert_eq!(Content::get(&pool, content_id).await.unwrap().unwrap().sort_title.as_deref(), Some("die verwandlung"));
+    let language_id = german(&pool).await;
+    ContentLanguage::create(&pool, &NewContentLanguage { content_id, language_id }).await.unwrap();
+    assert_eq!(Content::get(&pool, content_id).await.unwrap().unwrap().sort_title.as_deref(), Some("verwandlung"));
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().sort_title.as_deref(), Some("verwandlung"));
+
+    // Le righe scritte senza trigger vengono sistemate dal ricalcolo
+    sqlx::query("UPDATE books SET sort_title = NULL").execute(&pool).await.unwrap();
+    let report = recompute_sort_keys(&pool).await.unwrap();
+    assert_eq!(report.books, 5);
+    assert_eq!(Book::get(&pool, book_id).await.unwrap().unwrap().sort_title.as_deref(), Some("verwandlung"));
+}
+
+#[tokio::test]
+async fn test_authors_sort_by_surname() {
+    let (_dir, pool) = common::setup_pool().await;
+    let role_id: i64 = sqlx::query_scalar("SELECT id FROM roles WHERE code = 'aut'").fetch_one(&pool).await.unwrap();
+    let eco = common::person("Umberto Eco").save(&pool).await.unwrap();
+    let calvino = common::person("Italo Calvino").save(&pool).await.unwrap();
+    common::person("Émile Zola").save(&pool).await.unwrap();
+    let names: Vec<String> = Person::list_all(&pool).await.unwrap().into_iter().map(|p| p.name).collect();
+    assert_eq!(names, ["Italo Calvino", "Umberto Eco", "Émile Zola"]);
+
+    let book_id = Book { name: "Dialoghi".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    for person_id in [eco, calvino] {
+        BookPersonRole::create(&pool, &BookPersonRole { book_id, person_id, role_id }).await.unwrap();
+    }
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.sort_author.as_deref(), Some("eco, umberto & calvino, italo"));
+
+    let mut person = Person::get(&pool, calvino).await.unwrap().unwrap();
+    person.name = "Italo Svevo".to_string();
+    person.update(&pool).await.unwrap();
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.sort_author.as_deref(), Some("eco, umberto & svevo, italo"));
+
+    // La collazione Unicode è disponibile per le query costruite a runtime
+    let ordered: Vec<String> = sqlx::query_scalar("SELECT name FROM people ORDER BY name COLLATE ritmo_unicode")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+    assert_eq!(ordered, ["Émile Zola", "Italo Svevo", "Umberto Eco"]);
+}
@@ -0,0 +1,46 @@
+mod common;
+
+use futures::{StreamExt, TryStreamExt};
+use ritmo_db::models::{Book, Content, Person};
+
+#[tokio::test]
+async fn test_streams_match_lists() {
+    let (_dir, pool) = common::setup_pool().await;
+    for name in ["Le città invisibili", "Il castello dei destini incrociati", "Palomar"] {
+        Book { name: name.to_string(), ..Default::default() }.save(&pool).await.unwrap();
+        Content { name: name.to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    }
+    common::person("Italo Calvino").save(&pool).await.unwrap();
+    common::person("Cesare Pavese").save(&pool).await.unwrap();
+
+    let listed: Vec<String> = Book::list_all(&pool).await.unwrap().into_iter().map(|b| b.name).collect();
+    let streamed: Vec<String> = Book::stream_all(&pool).map_ok(|b| b.name).try_collect().await.unwrap();
+    assert_eq!(streamed, listed);
+    assert_eq!(Content::stream_all(&pool).try_collect::<Vec<_>>().await.unwrap().len(), 3);
+
+    let found: Vec<Book> = Book::stream_search(&pool, "castello").try_collect().await.unwrap();
+    assert_eq!(found.len(), 1);
+    let person = Person::stream_search(&pool, "calvino").next().await.unwrap().unwrap();
+    assert_eq!(person.name, "Italo Calvino");
+}
+
+#[tokio::test]
+async fn test_dropped_stream_releases_connection() {
+    let (_dir, pool) = common::setup_pool().await;
+    for i in 0..50 {
+        Book { name: format!("Libro {:02}", i), ..Default::default() }.save(&pool).await.unwrap();
+    }
+
+    // Interrompere la lettura a metà non deve lasciare occupata la connessione
+    for _ in 0..20 {
+        let first = Book::stream_all(&pool).take(5).try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(first[0].name, "Libro 00");
+    }
+    let mut count = 0;
+    let mut rows = Book::stream_search(&pool, "Libro");
+    while let Some(book) = rows.try_next().await.unwrap() {
+        assert!(book.name.starts_with("Libro"));
+        count += 1;
+    }
+    assert_eq!(count, 50);
+}
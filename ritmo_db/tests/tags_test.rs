@@ -0,0 +1,68 @@
+mod common;
+
+use ritmo_db::models::{Book, BookTag, Tag, TagCategory};
+use ritmo_db::smart_filter::{FilterRule, FilterTarget, SmartFilter};
+
+async fn tag(pool: &sqlx::SqlitePool, name: &str, parent_id: Option<i64>) -> i64 {
+    Tag {
+        name: name.to_string(),
+        parent_id,
+        category: Some(TagCategory::Genre.as_str().to_string()),
+        ..Default::default()
+    }
+    .save(pool)
+    .await
+    .unwrap()
+}
+
+async fn tagged_book(pool: &sqlx::SqlitePool, name: &str, tag_id: i64) -> i64 {
+    let book_id = Book { name: name.to_string(), ..Default::default() }.save(pool).await.unwrap();
+    BookTag::create(pool, &BookTag { book_id, tag_id }).await.unwrap();
+    book_id
+}
+
+#[tokio::test]
+async fn test_parent_tag_includes_descendants() {
+    let (_dir, pool) = common::setup_pool().await;
+    let sf = tag(&pool, "fantascienza", None).await;
+    let cyberpunk = tag(&pool, "cyberpunk", Some(sf)).await;
+    let steampunk = tag(&pool, "steampunk", Some(cyberpunk)).await;
+    tagged_book(&pool, "Neuromante", cyberpunk).await;
+    tagged_book(&pool, "La macchina della realtà", steampunk).await;
+    tagged_book(&pool, "Fondazione", sf).await;
+
+    assert_eq!(Tag::books(&pool, sf).await.unwrap().len(), 3);
+    assert_eq!(Tag::books(&pool, cyberpunk).await.unwrap().len(), 2);
+    let filter = SmartFilter::new(FilterTarget::Books, vec![FilterRule::Tag("Fantascienza".to_string())]);
+    assert_eq!(filter.books(&pool).await.unwrap().len(), 3);
+    assert_eq!(Tag::list_by_category(&pool, TagCategory::Genre).await.unwrap().len(), 3);
+
+    // Spostamento di un sottoalbero: steampunk segue cyberpunk, niente cicli
+    assert!(Tag::move_to(&pool, sf, Some(steampunk)).await.is_err());
+    Tag::move_to(&pool, cyberpunk, None).await.unwrap();
+    assert_eq!(Tag::books(&pool, sf).await.unwrap().len(), 1);
+    assert_eq!(Tag::subtree(&pool, cyberpunk).await.unwrap().len(), 2);
+    assert_eq!(Tag::list_children(&pool, None).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_rename_onto_existing_tag_merges() {
+    let (_dir, pool) = common::setup_pool().await;
+    let sf = tag(&pool, "fantascienza", None).await;
+    let sci_fi = tag(&pool, "sci-fi", None).await;
+    let space_opera = tag(&pool, "space opera", Some(sci_fi)).await;
+    let book_id = tagged_book(&pool, "Dune", sci_fi).await;
+    BookTag::create(&pool, &BookTag { book_id, tag_id: sf }).await.unwrap();
+
+    assert_eq!(Tag::rename(&pool, sci_fi, "Fantascienza").await.unwrap(), sf);
+    assert!(Tag::get(&pool, sci_fi).await.unwrap().is_none());
+    assert_eq!(Tag::get(&pool, space_opera).await.unwrap().unwrap().parent_id, Some(sf));
+    assert_eq!(BookTag::list_by_tag(&pool, sf).await.unwrap().len(), 1);
+    assert_eq!(Tag::rename(&pool, sf, "science fiction").await.unwrap(), sf);
+
+    // Fondere un tag nel proprio figlio toglie il padre dalla catena
+    let child = tag(&pool, "hard sf", Some(sf)).await;
+    Tag::merge(&pool, child, sf).await.unwrap();
+    assert_eq!(Tag::get(&pool, child).await.unwrap().unwrap().parent_id, None);
+    assert_eq!(Tag::get(&pool, space_opera).await.unwrap().unwrap().parent_id, None);
+}
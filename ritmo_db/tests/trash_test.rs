@@ -0,0 +1,98 @@
+mod common;
+
+use ritmo_db::models::{Book, BookTag, Content, Tag};
+
+#[tokio::test]
+async fn test_book_soft_delete_and_restore_keeps_links() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    let book_id = Book { name: "Il Gattopardo".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    let tag_id = Tag { name: "classici".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    BookTag::create(&pool, &BookTag { book_id, tag_id }).await.unwrap();
+
+    assert_eq!(Book::delete(&pool, book_id).await.unwrap(), 1);
+    assert!(Book::get(&pool, book_id).await.unwrap().is_none());
+    assert!(Book::list_all(&pool).await.unwrap().is_empty());
+    assert_eq!(Book::list_trash(&pool).await.unwrap().len(), 1);
+    // Il cestino non tocca i collegamenti
+    assert_eq!(BookTag::list_by_book(&pool, book_id).await.unwrap().len(), 1);
+
+    let trashed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM Trash WHERE entity_type = 'books'")
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!(trashed, 1);
+
+    assert_eq!(Book::restore(&pool, book_id).await.unwrap(), 1);
+    assert!(Book::get(&pool, book_id).await.unwrap().is_some());
+    assert!(Book::list_trash(&pool).await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_purge_trash_removes_rows_and_files() {
+    let (dir, pool) = common::setup_pool().await;
+    let storage = dir.path().join("storage");
+    let book_file = storage.join("books/ab/cd/abcd1234.epub");
+    let cover_file = storage.join("covers/ab/cd/abcd1234.jpg");
+    for file in [&book_file, &cover_file] {
+        std::fs::create_dir_all(file.parent().unwrap()).unwrap();
+        std::fs::write(file, b"data").unwrap();
+    }
+
+    // Un link che esce dall'archivio non va seguito, ma non deve fermare gli altri file
+    let outside = dir.path().join("fuori.epub");
+    std::fs::write(&outside, b"data").unwrap();
+    let escaping_id = Book { name: "Fuori".to_string(), file_link: Some("../fuori.epub".to_string()), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    Book::delete(&pool, escaping_id).await.unwrap();
+
+    let book_id = Book {
+        name: "Il barone rampante".to_string(),
+        file_link: Some("books/ab/cd/abcd1234.epub".to_string()),
+        ..Default::default()
+    }
+    .save(&pool)
+    .await
+    .unwrap();
+    let tag_id = Tag { name: "calvino".to_string(), ..Default::default() }.save(&pool).await.unwrap();
+    BookTag::create(&pool, &BookTag { book_id, tag_id }).await.unwrap();
+    Book::delete(&pool, book_id).await.unwrap();
+
+    // Con una conservazione lunga non si elimina nulla
+    assert_eq!(Book::purge_trash(&pool, &storage, 3600).await.unwrap().books, 0);
+    assert!(book_file.exists());
+
+    let report = Book::purge_trash(&pool, &storage, 0).await.unwrap();
+    assert_eq!(report.books, 2);
+    assert_eq!(report.errors.len(), 1);
+    assert_eq!(report.errors[0].0, "../fuori.epub");
+    assert!(outside.exists());
+    assert!(Book::list_trash(&pool).await.unwrap().is_empty());
+    assert!(BookTag::list_by_book(&pool, book_id).await.unwrap().is_empty());
+    assert!(!book_file.exists());
+    assert!(!cover_file.exists());
+}
+
+#[tokio::test]
+async fn test_content_soft_delete() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    let content_id = Content { name: "Il cavaliere inesistente".to_string(), ..Default::default() }
+        .save(&pool)
+        .await
+        .unwrap();
+    Content::delete(&pool, content_id).await.unwrap();
+    assert!(Content::search(&pool, "cavaliere").await.unwrap().is_empty());
+
+    Content::restore(&pool, content_id).await.unwrap();
+    assert_eq!(Content::search(&pool, "cavaliere").await.unwrap().len(), 1);
+
+    Content::delete(&pool, content_id).await.unwrap();
+    assert_eq!(Content::purge_trash(&pool, 0).await.unwrap(), 1);
+    assert!(Content::list_trash(&pool).await.unwrap().is_empty());
+}
@@ -0,0 +1,50 @@
+mod common;
+
+use ritmo_db::models::{Book, BookFile};
+use ritmo_db::partial_date::PartialDate;
+use ritmo_db::upgrade::upgrade_library;
+
+#[tokio::test]
+async fn test_upgrade_library() {
+    let (_dir, pool) = common::setup_pool().await;
+
+    // Libro salvato da una versione precedente: file, ISBN e copia cartacea solo in books
+    sqlx::query(
+        "INSERT INTO books (name, file_link, file_hash, isbn, has_paper)
+         VALUES ('Palomar', 'books/55/66/palomar.epub', 'palomar', '978-88-452-9286-6', 1)",
+    )
+        .execute(&pool)
+        .await
+        .unwrap();
+    // Date ancora salvate come timestamp Unix
+    sqlx::query("DELETE FROM system_config WHERE key = 'date_encoding'")
+        .execute(&pool)
+        .await
+        .unwrap();
+    let book_id = Book {
+        name: "Il nome della rosa".to_string(),
+        publication_date: Some(315532800), // 1980-01-01
+        ..Default::default()
+    }
+    .save(&pool)
+    .await
+    .unwrap();
+
+    let report = upgrade_library(&pool).await.unwrap();
+    assert_eq!(report.book_files, 1);
+    assert_eq!(report.dates, 1);
+    assert_eq!(report.identifiers, 1);
+    assert!(report.invalid_isbns.is_empty());
+    assert!(Book::get_by_identifier(&pool, "9788845292866").await.unwrap().is_some());
+    assert_eq!(report.copies, 1);
+    let book = Book::get(&pool, book_id).await.unwrap().unwrap();
+    assert_eq!(book.publication(), Some(PartialDate::new(1980, Some(1), Some(1)).unwrap()));
+    assert_eq!(BookFile::list_by_hash(&pool, "palomar").await.unwrap().len(), 1);
+
+    // Una seconda esecuzione non trova più niente da fare
+    let report = upgrade_library(&pool).await.unwrap();
+    assert_eq!(report.book_files, 0);
+    assert_eq!(report.dates, 0);
+    assert_eq!(report.identifiers, 0);
+    assert_eq!(report.copies, 0);
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use ritmo_core::service::import_service::ImportStatus;
+use ritmo_core::service::storage_service::StorageService;
+use ritmo_core::service::watch_service::{WatchOptions, WatchService, FAILED_DIR, PROCESSED_DIR};
+use ritmo_db::bulk_import::BulkImporter;
+use ritmo_db::models::Book;
+use std::fs;
+use std::time::Duration;
+
+const OPF_WITH_TITLE: &str = "<package><metadata><dc:title>Senza titolo</dc:title></metadata></package>";
+
+fn immediate() -> WatchOptions {
+    WatchOptions { settle: Duration::ZERO, poll_interval: Duration::from_millis(20), ..Default::default() }
+}
+
+#[tokio::test]
+async fn test_inbox_files_are_moved_and_recorded() {
+    let (dir, pool) = common::setup_pool().await;
+    let inbox = dir.path().join("arrivi");
+    fs::create_dir_all(&inbox).unwrap();
+    fs::write(inbox.join("Italo Calvino - Il barone rampante.epub"), b"epub del barone").unwrap();
+    fs::write(inbox.join("senza titolo.epub"), b"epub rovinato").unwrap();
+    fs::write(inbox.join("senza titolo.opf"), "<package><metadata></metadata></package>").unwrap();
+    fs::write(inbox.join("in arrivo.epub.part"), b"download a meta").unwrap();
+    let storage = StorageService::new(dir.path().join("storage"));
+
+    let mut service = WatchService::new(BulkImporter::new(&pool), storage.clone(), &inbox).unwrap().with_options(immediate());
+    assert_eq!(service.scan().unwrap(), 2);
+    let files = service.process_ready().await;
+    assert_eq!(files.len(), 2);
+    assert!(matches!(files[0].report.status, ImportStatus::Added { .. }));
+    assert!(matches!(files[1].report.status, ImportStatus::Failed { .. }));
+    assert!(inbox.join(PROCESSED_DIR).join("Italo Calvino - Il barone rampante.epub").is_file());
+    assert!(inbox.join(FAILED_DIR).join("senza titolo.epub").is_file());
+    assert!(inbox.join(FAILED_DIR).join("senza titolo.opf").is_file());
+    assert!(inbox.join("in arrivo.epub.part").is_file());
+    assert_eq!(Book::list_all(&pool).await.unwrap().len(), 1);
+
+    // Dopo un riavvio lo stesso contenuto, anche con un altro nome, non viene reimportato
+    drop(service);
+    fs::write(inbox.join("barone (copia).epub"), b"epub del barone").unwrap();
+    let mut service = WatchService::new(BulkImporter::new(&pool), storage, &inbox).unwrap().with_options(immediate());
+    // I fallimenti non sono nel registro
+    assert_eq!(service.record().len(), 1);
+    service.scan().unwrap();
+    let files = service.process_ready().await;
+    assert!(files[0].from_record);
+    assert!(matches!(files[0].report.status, ImportStatus::Duplicate { book_id: Some(_), .. }));
+    assert!(inbox.join(PROCESSED_DIR).join("barone (copia).epub").is_file());
+    assert_eq!(Book::list_all(&pool).await.unwrap().len(), 1);
+
+    // Il file scartato, sistemato e depositato di nuovo, viene riprovato
+    fs::rename(inbox.join(FAILED_DIR).join("senza titolo.epub"), inbox.join("senza titolo.epub")).unwrap();
+    fs::write(inbox.join("senza titolo.opf"), OPF_WITH_TITLE).unwrap();
+    service.scan().unwrap();
+    let files = service.process_ready().await;
+    assert!(!files[0].from_record);
+    assert!(matches!(files[0].report.status, ImportStatus::Added { .. }));
+    assert_eq!(Book::list_all(&pool).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_a_file_that_cannot_be_moved_does_not_stop_the_others() {
+    let (dir, pool) = common::setup_pool().await;
+    let inbox = dir.path().join("arrivi");
+    fs::create_dir_all(&inbox).unwrap();
+    let storage = StorageService::new(dir.path().join("storage"));
+    let mut service = WatchService::new(BulkImporter::new(&pool), storage, &inbox).unwrap().with_options(immediate());
+
+    // `processed` non è più una cartella: il libro importato non può essere spostato
+    fs::remove_dir(inbox.join(PROCESSED_DIR)).unwrap();
+    fs::write(inbox.join(PROCESSED_DIR), b"").unwrap();
+    fs::write(inbox.join("Primo Levi - La tregua.pdf"), b"pdf della tregua").unwrap();
+    fs::write(inbox.join("vuoto.epub"), b"epub rovinato").unwrap();
+    fs::write(inbox.join("vuoto.opf"), "<package><metadata></metadata></package>").unwrap();
+    service.scan().unwrap();
+    let files = service.process_ready().await;
+
+    assert_eq!(files.len(), 1);
+    assert!(files[0].moved_to.starts_with(inbox.join(FAILED_DIR)));
+    assert!(inbox.join("Primo Levi - La tregua.pdf").is_file());
+    assert_eq!(service.record().len(), 1);
+    assert_eq!(Book::list_all(&pool).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_run_imports_files_dropped_while_watching() {
+    let (dir, pool) = common::setup_pool().await;
+    let inbox = dir.path().join("arrivi");
+    fs::create_dir_all(&inbox).unwrap();
+    let storage = StorageService::new(dir.path().join("storage"));
+    let mut service = WatchService::new(BulkImporter::new(&pool), storage, &inbox).unwrap().with_options(immediate());
+
+    let dropped = inbox.join("Primo Levi - La tregua.pdf");
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        fs::write(dropped, b"pdf della tregua").unwrap();
+    });
+    let (done, shutdown) = tokio::sync::oneshot::channel();
+    let mut done = Some(done);
+    let mut seen = Vec::new();
+    let run = service.run(
+        async {
+            let _ = shutdown.await;
+        },
+        |file| {
+            seen.push(file.clone());
+            if let Some(done) = done.take() {
+                let _ = done.send(());
+            }
+        },
+    );
+    tokio::time::timeout(Duration::from_secs(10), run).await.expect("nessun file importato").unwrap();
+
+    assert_eq!(seen.len(), 1);
+    assert_eq!(seen[0].report.title.as_deref(), Some("La tregua"));
+    assert!(seen[0].moved_to.ends_with("processed/Primo Levi - La tregua.pdf"));
+    assert_eq!(Book::list_all(&pool).await.unwrap()[0].name, "La tregua");
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use futures::StreamExt;
+use ritmo_db::models::{Book, Person};
+use ritmo_db_core::connection::create_connection_pool;
+use ritmo_db_core::watch::{current_version, watch_changes};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_changes_from_another_connection_pool_are_seen() {
+    let (dir, frontend) = common::setup_pool().await;
+    // Il secondo pool simula un altro processo che apre la stessa libreria
+    let importer = create_connection_pool(dir.path().join("ritmo.db"), false).await.unwrap();
+
+    let since = current_version(&frontend).await.unwrap();
+    let mut changes = Box::pin(watch_changes(&frontend, since));
+
+    Book { name: "Il sentiero dei nidi di ragno".to_string(), ..Default::default() }
+        .save(&importer)
+        .await
+        .unwrap();
+    common::person("Italo Calvino").save(&importer).await.unwrap();
+
+    let mut seen = std::collections::BTreeSet::new();
+    let mut version = since;
+    while seen.len() < 2 {
+        let changed = tokio::time::timeout(Duration::from_secs(5), changes.next())
+            .await
+            .expect("nessuna modifica vista")
+            .unwrap()
+            .unwrap();
+        assert_eq!(changed.since, version);
+        assert!(!changed.truncated);
+        version = changed.version;
+        seen.extend(changed.tables);
+    }
+    assert!(seen.contains("books") && seen.contains("people"));
+    assert_eq!(version, current_version(&importer).await.unwrap());
+
+    // Ripartendo da una versione vecchia si ricevono subito le modifiche arretrate
+    let mut replay = Box::pin(watch_changes(&frontend, since));
+    let changed = replay.next().await.unwrap().unwrap();
+    assert_eq!(changed.version, version);
+    assert!(Person::list_all(&frontend).await.unwrap().iter().any(|p| p.name == "Italo Calvino"));
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use ritmo_db::models::{Book, Content, ContentRelation, ContentRelationType, Work};
+
+async fn content(pool: &sqlx::SqlitePool, name: &str, original_title: Option<&str>) -> i64 {
+    Content {
+        name: name.to_string(),
+        original_title: original_title.map(str::to_string),
+        ..Default::default()
+    }
+    .save(pool)
+    .await
+    .unwrap()
+}
+
+async fn in_book(pool: &sqlx::SqlitePool, book_name: &str, content_id: i64) -> i64 {
+    let book_id = Book { name: book_name.to_string(), ..Default::default() }.save(pool).await.unwrap();
+    sqlx::query("INSERT INTO x_books_contents (book_id, content_id) VALUES (?, ?)")
+        .bind(book_id)
+        .bind(content_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    book_id
+}
+
+#[tokio::test]
+async fn test_translations_share_a_work_and_list_appearances() {
+    let (_dir, pool) = common::setup_pool().await;
+    let original = content(&pool, "The Dead", None).await;
+    let translation = content(&pool, "I morti", Some("The Dead")).await;
+    in_book(&pool, "Dubliners", original).await;
+    in_book(&pool, "Gente di Dublino", translation).await;
+    // Lo stesso testo ristampato in un'antologia
+    sqlx::query("INSERT INTO x_books_contents (book_id, content_id) VALUES (?, ?)")
+        .bind(in_book(&pool, "Racconti irlandesi", translation).await)
+        .bind(original)
+        .execute(&pool)
+        .await
+        .unwrap();
+
+    let work_id = Work::link(&pool, translation, original, ContentRelationType::TranslationOf).await.unwrap();
+    assert_eq!(Work::get(&pool, work_id).await.unwrap().unwrap().title, "The Dead");
+    assert_eq!(Content::get(&pool, translation).await.unwrap().unwrap().work_id, Some(work_id));
+
+    let appearances = Work::appearances(&pool, work_id).await.unwrap();
+    assert_eq!(appearances.len(), 4);
+    let translated: Vec<_> = appearances.iter().filter(|a| a.content_id == translation).collect();
+    assert_eq!(translated.len(), 2);
+    assert!(translated.iter().all(|a| a.relation.as_deref() == Some("translation_of")));
+
+    let relations = ContentRelation::list_by_content(&pool, original).await.unwrap();
+    assert_eq!(relations.len(), 1);
+    assert_eq!(relations[0].relation, "translation_of");
+}
+
+#[tokio::test]
+async fn test_link_merges_existing_works() {
+    let (_dir, pool) = common::setup_pool().await;
+    let original = content(&pool, "Il fu Mattia Pascal", None).await;
+    let excerpt = content(&pool, "Mattia Pascal (estratto)", None).await;
+    let first = Work::from_content(&pool, original).await.unwrap();
+    let second = Work::from_content(&pool, excerpt).await.unwrap();
+    assert_ne!(first, second);
+
+    let work_id = Work::link(&pool, excerpt, original, ContentRelationType::ExcerptOf).await.unwrap();
+    assert_eq!(work_id, first);
+    assert!(Work::get(&pool, second).await.unwrap().is_none());
+    assert_eq!(Work::appearances(&pool, first).await.unwrap().len(), 2);
+    assert!(Work::link(&pool, original, original, ContentRelationType::RevisionOf).await.is_err());
+}
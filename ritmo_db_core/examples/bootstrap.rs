@@ -1,3 +1,4 @@
+use ritmo_db_core::create_full_database_library;
 use std::path::PathBuf;
 
 #[tokio::main]
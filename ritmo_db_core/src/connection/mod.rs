@@ -1,5 +1,5 @@
 pub mod pool;
 pub mod options;
 
-pub use pool::create_connection_pool;
+pub use pool::{create_connection_pool, create_pool_options};
 pub use options::create_sqlite_options;
\ No newline at end of file
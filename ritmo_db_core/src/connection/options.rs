@@ -7,7 +7,7 @@ pub fn create_sqlite_options<P: AsRef<Path>>(db_path: P, create: bool) -> RitmoR
     let database_url = format!("sqlite:///{}", db_path.as_ref().to_string_lossy());
     
     let mut options = SqliteConnectOptions::from_str(&database_url)
-        .map_err(|e| RitmoErr::SqlxError(e))?
+        .map_err(RitmoErr::SqlxError)?
         .create_if_missing(create)
         .journal_mode(SqliteJournalMode::Wal)
         .synchronous(SqliteSynchronous::Normal);
@@ -1,3 +1,5 @@
+use crate::normalize::prepare_connection;
+use sqlx::sqlite::SqlitePoolOptions;
 use sqlx::{Pool, Sqlite};
 use std::{fs, path::Path};
 use ritmo_errors::{RitmoErr, RitmoResult};
@@ -16,9 +18,15 @@ pub async fn create_connection_pool<P: AsRef<Path>>(db_path: P, create: bool) ->
     }
 
     let options = create_sqlite_options(path, create)?;
-    let pool = sqlx::SqlitePool::connect_with(options)
+    let pool = create_pool_options()
+        .connect_with(options)
         .await
-        .map_err(|e| RitmoErr::SqlxError(e))?;
+        .map_err(RitmoErr::SqlxError)?;
 
     Ok(pool)
-}
\ No newline at end of file
+}
+/// Opzioni comuni a tutti i pool: ogni nuova connessione riceve le funzioni `ritmo_normalize`,
+/// `ritmo_sort_title` e `ritmo_sort_name`, la collazione `ritmo_unicode` e i trigger TEMP che le usano
+pub fn create_pool_options() -> SqlitePoolOptions {
+    SqlitePoolOptions::new().after_connect(|conn, _meta| Box::pin(async move { prepare_connection(conn).await }))
+}
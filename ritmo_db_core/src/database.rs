@@ -1,3 +1,6 @@
+use crate::maintenance::restore_deferred_indexes;
+use crate::events::{start_dispatcher, ChangeEvent, ChangeFilter, ChangeSubscription, EVENT_CHANNEL_CAPACITY};
+use crate::watch::{watch_changes, TablesChanged};
 use ritmo_errors::RitmoResult;
 use ritmo_errors::RitmoErr;
 use serde_json::Value;
@@ -5,11 +8,16 @@ use serde_json::Value;
 use sqlx::{SqlitePool, Row, FromRow};
 use chrono::Utc;
 use std::collections::HashMap;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 
 /// Struttura principale che rappresenta un database RitmoDB
 pub struct Database {
     pool: SqlitePool,
     db_metadata: DatabaseMetadata,
+    /// Canale su cui vengono pubblicate le modifiche confermate
+    events: broadcast::Sender<ChangeEvent>,
+    dispatcher: JoinHandle<()>,
 }
 
 /// Metadati del database
@@ -43,13 +51,40 @@ impl Database {
         
         // Esegui eventuali migrazioni o verifiche di schema
         Self::verify_schema(&pool).await?;
-        
+
+        // Indici lasciati in sospeso da un'importazione massiva interrotta
+        let restored = restore_deferred_indexes(&mut *pool.acquire().await?).await?;
+        if restored > 0 {
+            tracing::warn!("ricreati {} indici rimasti in sospeso da un'importazione interrotta", restored);
+        }
+
+        let (events, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+        let dispatcher = start_dispatcher(&pool, events.clone()).await?;
+
         Ok(Database {
             pool,
             db_metadata,
+            events,
+            dispatcher,
         })
     }
 
+    /// Riceve tutte le modifiche confermate dopo la sottoscrizione
+    pub fn subscribe(&self) -> broadcast::Receiver<ChangeEvent> {
+        self.events.subscribe()
+    }
+
+    /// Modifiche confermate da qualsiasi processo dopo la versione `since` del registro;
+    /// vedi `watch::watch_changes`
+    pub fn watch_changes(&self, since: i64) -> impl futures::Stream<Item = RitmoResult<TablesChanged>> {
+        watch_changes(&self.pool, since)
+    }
+
+    /// Riceve solo le modifiche accettate dal filtro
+    pub fn subscribe_filtered(&self, filter: ChangeFilter) -> ChangeSubscription {
+        ChangeSubscription::new(self.events.subscribe(), filter)
+    }
+
     /// Configura SQLite per prestazioni ottimali
     async fn configure_sqlite(pool: &SqlitePool) -> RitmoResult<()> {
         // Abilita foreign keys (importante per integrità referenziale)
@@ -101,7 +136,7 @@ impl Database {
             ("publishers", "table"),
             ("series", "table"),
             ("tags", "table"),
-            ("languages", "table"),
+            ("running_languages", "table"),
             ("formats", "table"),
             ("contents", "table"),
             ("aliases", "table"),
@@ -190,7 +225,7 @@ impl Database {
                     format!("Failed to update metadata timestamp: {}", e)
                 ))?;
 
-                return Ok(metadata)
+                Ok(metadata)
             }
             None => {
                 // Se non ci sono metadati, creane di nuovi
@@ -271,7 +306,7 @@ impl Database {
         }
 
         // Conta record nelle tabelle principali usando query! macro
-        let main_tables = ["books", "people", "publishers", "series", "tags", "languages"];
+        let main_tables = ["books", "people", "publishers", "series", "tags", "running_languages"];
         
         for table in &main_tables {
             // Usa dynamic query per nomi tabella variabili
@@ -389,10 +424,17 @@ impl Database {
 
     /// Chiudi la connessione al database
     pub async fn close(self) {
+        self.dispatcher.abort();
         self.pool.close().await;
     }
 }
 
+impl Drop for Database {
+    fn drop(&mut self) {
+        self.dispatcher.abort();
+    }
+}
+
 impl Default for DatabaseMetadata {
     fn default() -> Self {
         let now = Utc::now().timestamp();
@@ -436,7 +478,7 @@ mod tests {
         // Crea altre tabelle richieste
         let tables = [
             "books", "people", "publishers", "series", "tags", 
-            "languages", "formats", "contents", "aliases", "roles", "types",
+            "running_languages", "formats", "contents", "aliases", "roles", "types",
             "x_books_contents", "x_books_people_roles", "x_books_tags",
             "x_contents_languages", "x_contents_people_roles", "x_contents_tags"
         ];
@@ -0,0 +1,325 @@
+//! Eventi di modifica del database.
+//!
+//! Ogni scrittura sulle tabelle principali e sui collegamenti viene registrata dai trigger
+//! in `change_log`. Le righe diventano visibili solo al commit della transazione, quindi un evento
+//! non viene mai pubblicato per una scrittura annullata. `Database` legge il registro quando
+//! `PRAGMA data_version` cambia e pubblica gli eventi su un canale broadcast.
+
+use ritmo_errors::RitmoResult;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, SqliteConnection, SqlitePool};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Intervallo con cui il dispatcher controlla se ci sono nuovi commit
+pub const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Attesa massima tra due tentativi del dispatcher dopo un errore
+pub const EVENT_MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// Capacità del canale: un sottoscrittore più lento perde gli eventi più vecchi
+pub const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Tipo di modifica
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ChangeKind {
+    Created,
+    Updated,
+    /// Eliminazione definitiva o spostamento nel cestino
+    Deleted,
+    Linked,
+    Unlinked,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Updated => "updated",
+            Self::Deleted => "deleted",
+            Self::Linked => "linked",
+            Self::Unlinked => "unlinked",
+        }
+    }
+
+    pub fn parse(operation: &str) -> Option<Self> {
+        match operation {
+            "created" => Some(Self::Created),
+            "updated" => Some(Self::Updated),
+            "deleted" => Some(Self::Deleted),
+            "linked" => Some(Self::Linked),
+            "unlinked" => Some(Self::Unlinked),
+            _ => None,
+        }
+    }
+}
+
+/// Entità modificata; per i collegamenti indica la tabella cross
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum EntityKind {
+    Book,
+    Content,
+    Person,
+    Publisher,
+    Series,
+    Tag,
+    Format,
+    Type,
+    Work,
+    Collection,
+    BookFile,
+    BookIdentifier,
+    ReadingOrder,
+    ReadingOrderItem,
+    BookCopy,
+    Loan,
+    Acquisition,
+    PenName,
+    BookContent,
+    BookPerson,
+    BookTag,
+    ContentLanguage,
+    ContentPerson,
+    ContentTag,
+}
+
+impl EntityKind {
+    pub fn table(&self) -> &'static str {
+        match self {
+            Self::Book => "books",
+            Self::Content => "contents",
+            Self::Person => "people",
+            Self::Publisher => "publishers",
+            Self::Series => "series",
+            Self::Tag => "tags",
+            Self::Format => "formats",
+            Self::Type => "types",
+            Self::Work => "works",
+            Self::Collection => "collections",
+            Self::BookFile => "book_files",
+            Self::BookIdentifier => "book_identifiers",
+            Self::ReadingOrder => "reading_orders",
+            Self::ReadingOrderItem => "reading_order_items",
+            Self::BookCopy => "book_copies",
+            Self::Loan => "loans",
+            Self::Acquisition => "acquisitions",
+            Self::PenName => "pen_names",
+            Self::BookContent => "x_books_contents",
+            Self::BookPerson => "x_books_people_roles",
+            Self::BookTag => "x_books_tags",
+            Self::ContentLanguage => "x_contents_languages",
+            Self::ContentPerson => "x_contents_people_roles",
+            Self::ContentTag => "x_contents_tags",
+        }
+    }
+
+    pub fn from_table(table: &str) -> Option<Self> {
+        match table {
+            "books" => Some(Self::Book),
+            "contents" => Some(Self::Content),
+            "people" => Some(Self::Person),
+            "publishers" => Some(Self::Publisher),
+            "series" => Some(Self::Series),
+            "tags" => Some(Self::Tag),
+            "formats" => Some(Self::Format),
+            "types" => Some(Self::Type),
+            "works" => Some(Self::Work),
+            "collections" => Some(Self::Collection),
+            "book_files" => Some(Self::BookFile),
+            "book_identifiers" => Some(Self::BookIdentifier),
+            "reading_orders" => Some(Self::ReadingOrder),
+            "reading_order_items" => Some(Self::ReadingOrderItem),
+            "book_copies" => Some(Self::BookCopy),
+            "loans" => Some(Self::Loan),
+            "acquisitions" => Some(Self::Acquisition),
+            "pen_names" => Some(Self::PenName),
+            "x_books_contents" => Some(Self::BookContent),
+            "x_books_people_roles" => Some(Self::BookPerson),
+            "x_books_tags" => Some(Self::BookTag),
+            "x_contents_languages" => Some(Self::ContentLanguage),
+            "x_contents_people_roles" => Some(Self::ContentPerson),
+            "x_contents_tags" => Some(Self::ContentTag),
+            _ => None,
+        }
+    }
+
+    /// Vero per le tabelle cross e per gli pseudonimi, che collegano due persone
+    pub fn is_link(&self) -> bool {
+        *self == Self::PenName || self.table().starts_with("x_")
+    }
+}
+
+/// Una modifica già confermata (commit) nel database
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    /// Id progressivo della riga in `change_log`
+    pub seq: i64,
+    pub kind: ChangeKind,
+    pub entity: EntityKind,
+    /// Id dell'entità; per i collegamenti è il primo lato (libro, contenuto o persona reale)
+    pub id: i64,
+    /// Per i collegamenti l'altro lato (contenuto, persona, tag, lingua o pseudonimo); per le righe
+    /// che dipendono da un'altra è il proprietario: il libro di file, identificatori, copie e
+    /// acquisizioni, la serie di un ordine di lettura, l'ordine di un suo elemento, la copia di un prestito
+    pub related_id: Option<i64>,
+    pub changed_at: i64,
+}
+
+/// Filtro per i sottoscrittori; le liste vuote accettano tutto
+#[derive(Debug, Clone, Default)]
+pub struct ChangeFilter {
+    pub entities: Vec<EntityKind>,
+    pub kinds: Vec<ChangeKind>,
+}
+
+impl ChangeFilter {
+    pub fn entity(mut self, entity: EntityKind) -> Self {
+        self.entities.push(entity);
+        self
+    }
+
+    pub fn kind(mut self, kind: ChangeKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    pub fn matches(&self, event: &ChangeEvent) -> bool {
+        (self.entities.is_empty() || self.entities.contains(&event.entity))
+            && (self.kinds.is_empty() || self.kinds.contains(&event.kind))
+    }
+}
+
+/// Ricevitore di eventi che scarta quelli non accettati dal filtro
+pub struct ChangeSubscription {
+    receiver: broadcast::Receiver<ChangeEvent>,
+    filter: ChangeFilter,
+}
+
+impl ChangeSubscription {
+    pub fn new(receiver: broadcast::Receiver<ChangeEvent>, filter: ChangeFilter) -> Self {
+        Self { receiver, filter }
+    }
+
+    /// Attende il prossimo evento accettato dal filtro; None quando il database viene chiuso.
+    /// Se il sottoscrittore è rimasto indietro gli eventi persi vengono saltati.
+    pub async fn recv(&mut self) -> Option<ChangeEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.filter.matches(&event) => return Some(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    tracing::warn!("sottoscrittore in ritardo, {} eventi persi", skipped);
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+/// Legge da `change_log` le modifiche successive a `after_seq`, in ordine
+pub async fn read_changes(conn: &mut SqliteConnection, after_seq: i64) -> RitmoResult<Vec<ChangeEvent>> {
+    let rows = sqlx::query!(
+        "SELECT id AS \"id!\", table_name, operation, entity_id, related_id, changed_at
+         FROM change_log WHERE id > ? ORDER BY id",
+        after_seq
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|r| {
+            Some(ChangeEvent {
+                seq: r.id,
+                kind: ChangeKind::parse(&r.operation)?,
+                entity: EntityKind::from_table(&r.table_name)?,
+                id: r.entity_id,
+                related_id: r.related_id,
+                changed_at: r.changed_at,
+            })
+        })
+        .collect())
+}
+
+/// Ultimo id presente in `change_log`
+pub async fn last_change_seq(conn: &mut SqliteConnection) -> RitmoResult<i64> {
+    let seq = sqlx::query_scalar!("SELECT COALESCE(MAX(id), 0) AS \"seq!: i64\" FROM change_log")
+        .fetch_one(&mut *conn)
+        .await?;
+    Ok(seq)
+}
+
+/// Valore di `PRAGMA data_version`: cambia quando un'altra connessione fa commit
+pub async fn data_version(conn: &mut SqliteConnection) -> RitmoResult<i64> {
+    let version: i64 = sqlx::query_scalar("PRAGMA data_version").fetch_one(&mut *conn).await?;
+    Ok(version)
+}
+
+/// Avvia il task che pubblica su `sender` le modifiche confermate dopo la chiamata.
+/// Usa una connessione dedicata, così vede i commit di tutte le connessioni del pool.
+/// Un errore (anche solo SQLITE_BUSY) non ferma il task: la connessione viene riaperta e la lettura
+/// riprende dall'ultimo evento pubblicato, con attese crescenti fino a `EVENT_MAX_BACKOFF`.
+pub(crate) async fn start_dispatcher(
+    pool: &SqlitePool,
+    sender: broadcast::Sender<ChangeEvent>,
+) -> RitmoResult<JoinHandle<()>> {
+    let mut conn = pool.connect_options().connect().await?;
+    let version = data_version(&mut conn).await?;
+    let seq = last_change_seq(&mut conn).await?;
+    let mut dispatcher = Dispatcher {
+        options: pool.connect_options(),
+        conn: Some(conn),
+        version: Some(version),
+        seq,
+        sender,
+    };
+    Ok(tokio::spawn(async move {
+        let mut wait = EVENT_POLL_INTERVAL;
+        loop {
+            tokio::time::sleep(wait).await;
+            match dispatcher.poll().await {
+                Ok(()) => wait = EVENT_POLL_INTERVAL,
+                Err(e) => {
+                    wait = (wait * 2).min(EVENT_MAX_BACKOFF);
+                    tracing::warn!("dispatcher degli eventi: {}; nuovo tentativo tra {:?}", e, wait);
+                    dispatcher.conn = None;
+                    dispatcher.version = None;
+                }
+            }
+        }
+    }))
+}
+
+struct Dispatcher {
+    options: Arc<SqliteConnectOptions>,
+    conn: Option<SqliteConnection>,
+    /// None dopo una riconnessione: `data_version` vale solo per la stessa connessione
+    version: Option<i64>,
+    /// Ultimo evento pubblicato
+    seq: i64,
+    sender: broadcast::Sender<ChangeEvent>,
+}
+
+impl Dispatcher {
+    async fn poll(&mut self) -> RitmoResult<()> {
+        let conn = match self.conn.as_mut() {
+            Some(conn) => conn,
+            None => self.conn.insert(self.options.connect().await?),
+        };
+        let current = data_version(conn).await?;
+        if self.version == Some(current) {
+            return Ok(());
+        }
+        // Le righe non vengono fuse: due modifiche uguali in commit diversi restano due eventi.
+        // Le riscritture dei soli timestamp fatte dai trigger non arrivano nel registro,
+        // perché i trigger di `change_log` ignorano quelle colonne (`AFTER UPDATE OF`).
+        for event in read_changes(conn, self.seq).await? {
+            self.seq = event.seq;
+            let _ = self.sender.send(event);
+        }
+        // Solo a lettura riuscita: se fallisce, al prossimo giro si riprova
+        self.version = Some(current);
+        Ok(())
+    }
+}
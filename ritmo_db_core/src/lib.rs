@@ -1,8 +1,12 @@
 pub mod config;
 pub mod connection;
 pub mod database;
+pub mod events;
 pub mod maintenance;
+pub mod normalize;
+pub mod sorting;
 pub mod library;
+pub mod watch;
 
 pub use database::Database;
 pub use library::create_full_database_library;
@@ -280,7 +284,7 @@ impl LibraryConfig {
 
         println!("Connecting to database: {}", db_url);
 
-        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        let pool = connection::create_pool_options()
             .max_connections(self.max_db_connections)
             .connect(&db_url)
             .await
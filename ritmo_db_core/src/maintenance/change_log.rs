@@ -0,0 +1,14 @@
+use sqlx::{Pool, Sqlite};
+use ritmo_errors::{RitmoErr, RitmoResult};
+
+/// Elimina dal registro delle modifiche le righe più vecchie di `older_than` (timestamp).
+/// I processi che leggono il registro devono averle già consumate.
+pub async fn prune_change_log(pool: &Pool<Sqlite>, older_than: i64) -> RitmoResult<u64> {
+    let result = sqlx::query!("DELETE FROM change_log WHERE changed_at < ?", older_than)
+        .execute(pool)
+        .await
+        .map_err(|e| RitmoErr::DatabaseQueryFailed(format!(
+            "Errore durante la pulizia del registro delle modifiche: {}", e
+        )))?;
+    Ok(result.rows_affected())
+}
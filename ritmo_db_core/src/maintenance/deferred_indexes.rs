@@ -0,0 +1,92 @@
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{Acquire, SqliteConnection};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::PathBuf;
+
+/// Chiave in `system_config` con le definizioni (JSON) degli indici eliminati da un'importazione
+/// massiva e non ancora ricreati
+pub const DEFERRED_INDEXES_KEY: &str = "deferred_indexes";
+
+/// Suffisso del file di lock accanto al database, come `-wal` e `-shm`
+const LOCK_SUFFIX: &str = "-import.lock";
+
+/// Lock esclusivo sugli indici rimandati, tenuto da chi li elimina per tutta l'importazione.
+/// È un lock del sistema operativo su un file accanto al database: se il processo termina,
+/// anche in modo brusco, il lock sparisce con lui e gli indici si possono ricreare.
+pub struct DeferredIndexesLock {
+    /// None per i database in memoria, che nessun altro può aprire
+    _file: Option<File>,
+}
+
+impl DeferredIndexesLock {
+    /// Prende il lock; None se lo tiene un'importazione ancora in corso, in questo o in un altro processo
+    pub async fn try_acquire(conn: &mut SqliteConnection) -> RitmoResult<Option<Self>> {
+        let Some(path) = lock_path(conn).await? else {
+            return Ok(Some(Self { _file: None }));
+        };
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(&path)?;
+        match file.try_lock() {
+            Ok(()) => Ok(Some(Self { _file: Some(file) })),
+            Err(TryLockError::WouldBlock) => Ok(None),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+
+    /// Ricrea gli indici rimasti in sospeso in `system_config` e cancella la chiave.
+    /// Restituisce il numero di indici ricreati.
+    pub async fn restore(&self, conn: &mut SqliteConnection) -> RitmoResult<usize> {
+        let failed = |e: sqlx::Error| RitmoErr::DatabaseQueryFailed(format!("Errore nel ripristino degli indici: {}", e));
+        let mut tx = conn.begin().await.map_err(failed)?;
+        let Some(pending) = pending(&mut tx).await? else { return Ok(0) };
+        let definitions: Vec<String> = serde_json::from_str(&pending)
+            .map_err(|e| RitmoErr::DatabaseQueryFailed(format!("Indici in sospeso illeggibili: {}", e)))?;
+        for sql in &definitions {
+            // Le definizioni vengono da sqlite_master; IF NOT EXISTS rende il ripristino ripetibile
+            let sql = sql.replacen("CREATE INDEX ", "CREATE INDEX IF NOT EXISTS ", 1);
+            sqlx::query(&sql).execute(&mut *tx).await.map_err(failed)?;
+        }
+        sqlx::query("DELETE FROM system_config WHERE key = ?")
+            .bind(DEFERRED_INDEXES_KEY)
+            .execute(&mut *tx)
+            .await
+            .map_err(failed)?;
+        tx.commit().await.map_err(failed)?;
+        if !definitions.is_empty() {
+            sqlx::query("ANALYZE").execute(&mut *conn).await.map_err(failed)?;
+        }
+        Ok(definitions.len())
+    }
+}
+
+/// Ricrea gli indici lasciati in sospeso da un'importazione interrotta.
+/// Se l'importazione che li ha eliminati è ancora in corso non tocca niente e restituisce 0.
+pub async fn restore_deferred_indexes(conn: &mut SqliteConnection) -> RitmoResult<usize> {
+    if pending(conn).await?.is_none() {
+        return Ok(0);
+    }
+    match DeferredIndexesLock::try_acquire(conn).await? {
+        Some(lock) => lock.restore(conn).await,
+        None => Ok(0),
+    }
+}
+
+async fn pending(conn: &mut SqliteConnection) -> RitmoResult<Option<String>> {
+    let pending: Option<String> = sqlx::query_scalar("SELECT value FROM system_config WHERE key = ?")
+        .bind(DEFERRED_INDEXES_KEY)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| RitmoErr::DatabaseQueryFailed(format!("Errore nel ripristino degli indici: {}", e)))?
+        .flatten();
+    Ok(pending)
+}
+
+/// File di lock del database principale; None se il database non ha un file
+async fn lock_path(conn: &mut SqliteConnection) -> RitmoResult<Option<PathBuf>> {
+    let databases: Vec<(i64, String, String)> = sqlx::query_as("PRAGMA database_list").fetch_all(&mut *conn).await?;
+    Ok(databases
+        .into_iter()
+        .find(|(_, name, _)| name == "main")
+        .map(|(_, _, file)| file)
+        .filter(|file| !file.is_empty())
+        .map(|file| PathBuf::from(format!("{}{}", file, LOCK_SUFFIX))))
+}
@@ -1,7 +1,15 @@
 pub mod backup;
+pub mod change_log;
+pub mod deferred_indexes;
 pub mod vacuum;
 pub mod integrity;
+pub mod normalization;
+pub mod sort_keys;
 
 pub use backup::backup_database;
+pub use change_log::prune_change_log;
+pub use deferred_indexes::{restore_deferred_indexes, DeferredIndexesLock, DEFERRED_INDEXES_KEY};
 pub use vacuum::perform_vacuum;
-pub use integrity::check_integrity;
\ No newline at end of file
+pub use integrity::check_integrity;
+pub use normalization::{recompute_normalized_keys, NormalizedKeysReport};
+pub use sort_keys::{recompute_sort_keys, SortKeysReport};
\ No newline at end of file
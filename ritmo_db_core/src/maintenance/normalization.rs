@@ -0,0 +1,42 @@
+use crate::normalize::register_normalizer;
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{Acquire, Pool, Sqlite};
+
+/// Righe aggiornate da `recompute_normalized_keys`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NormalizedKeysReport {
+    pub people: u64,
+    pub aliases: u64,
+}
+
+/// Ricalcola tutte le chiavi `people.normalized_key` e `aliases.alias_normalized` con `ritmo_normalize`,
+/// così le ricerche nel database e il modulo ML confrontano le stesse chiavi.
+/// Elimina anche i vecchi trigger `normalize_person_name` e `normalize_alias_name`, che non aggiornavano nulla.
+pub async fn recompute_normalized_keys(pool: &Pool<Sqlite>) -> RitmoResult<NormalizedKeysReport> {
+    let mut conn = pool.acquire().await?;
+    // Il pool potrebbe non essere stato creato con `create_pool_options`
+    register_normalizer(&mut conn).await?;
+    let mut tx = conn.begin().await?;
+    sqlx::query("DROP TRIGGER IF EXISTS main.normalize_person_name").execute(&mut *tx).await?;
+    sqlx::query("DROP TRIGGER IF EXISTS main.normalize_alias_name").execute(&mut *tx).await?;
+
+    let people = sqlx::query(
+        "UPDATE people SET normalized_key = ritmo_normalize(name)
+         WHERE normalized_key IS NOT ritmo_normalize(name)",
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| RitmoErr::DatabaseQueryFailed(format!("Errore nel ricalcolo delle chiavi delle persone: {}", e)))?
+    .rows_affected();
+    let aliases = sqlx::query(
+        "UPDATE aliases SET alias_normalized = ritmo_normalize(name)
+         WHERE alias_normalized IS NOT ritmo_normalize(name)",
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(|e| RitmoErr::DatabaseQueryFailed(format!("Errore nel ricalcolo delle chiavi degli alias: {}", e)))?
+    .rows_affected();
+
+    tx.commit().await?;
+    Ok(NormalizedKeysReport { people, aliases })
+}
@@ -0,0 +1,68 @@
+use crate::sorting::{book_language, content_language, register_sorting};
+use ritmo_errors::{RitmoErr, RitmoResult};
+use sqlx::{Acquire, Pool, Sqlite};
+
+/// Righe aggiornate da `recompute_sort_keys`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SortKeysReport {
+    pub people: u64,
+    pub books: u64,
+    pub contents: u64,
+}
+
+/// Ricalcola le chiavi di ordinamento di persone, libri e contenuti. Serve per le righe scritte
+/// prima che esistessero le colonne o da connessioni senza i trigger TEMP (la CLI di sqlite3).
+/// Per libri e contenuti conta sia il titolo che gli autori.
+pub async fn recompute_sort_keys(pool: &Pool<Sqlite>) -> RitmoResult<SortKeysReport> {
+    let mut conn = pool.acquire().await?;
+    // Il pool potrebbe non essere stato creato con `create_pool_options`
+    register_sorting(&mut conn).await?;
+    let mut tx = conn.begin().await?;
+    let failed = |what: &'static str| {
+        move |e: sqlx::Error| RitmoErr::DatabaseQueryFailed(format!("Errore nel ricalcolo delle chiavi {}: {}", what, e))
+    };
+
+    // Prima le persone: le chiavi degli autori si compongono dalle loro
+    let people = sqlx::query(
+        "UPDATE people SET sort_name = ritmo_sort_name(name, surname, given_name)
+         WHERE sort_name IS NOT ritmo_sort_name(name, surname, given_name)",
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(failed("delle persone"))?
+    .rows_affected();
+
+    let mut books = 0;
+    let mut contents = 0;
+    for (table, link, column, language, counter) in [
+        ("books", "x_books_people_roles", "book_id", book_language("books.id"), &mut books),
+        ("contents", "x_contents_people_roles", "content_id", content_language("contents.id"), &mut contents),
+    ] {
+        let authors = format!(
+            "(SELECT group_concat(sort_name, ' & ') FROM (
+                SELECT p.sort_name FROM {link} x
+                JOIN people p ON p.id = x.person_id
+                JOIN roles r ON r.id = x.role_id
+                WHERE x.{column} = {table}.id AND r.code = 'aut'
+                ORDER BY x.rowid))",
+            link = link,
+            column = column,
+            table = table
+        );
+        let title = format!("ritmo_sort_title(name, {})", language);
+        *counter = sqlx::query(&format!(
+            "UPDATE {table} SET sort_title = {title}, sort_author = {authors}
+             WHERE sort_title IS NOT {title} OR sort_author IS NOT {authors}",
+            table = table,
+            title = title,
+            authors = authors
+        ))
+        .execute(&mut *tx)
+        .await
+        .map_err(failed(if table == "books" { "dei libri" } else { "dei contenuti" }))?
+        .rows_affected();
+    }
+
+    tx.commit().await?;
+    Ok(SortKeysReport { people, books, contents })
+}
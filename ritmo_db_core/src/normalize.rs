@@ -0,0 +1,188 @@
+//! Normalizzazione dei nomi per il confronto di identità.
+//!
+//! `normalize_key` è l'unico algoritmo usato per `people.normalized_key` e `aliases.alias_normalized`:
+//! lo usano il codice Rust, il modulo ML e, tramite la funzione SQL `ritmo_normalize`, i trigger del database.
+//!
+//! La funzione SQL è registrata su ogni connessione del pool, e con lei i trigger che la usano.
+//! I trigger sono TEMP, cioè creati per connessione: un trigger o un indice nello schema principale
+//! che chiama `ritmo_normalize` renderebbe le tabelle non scrivibili da qualsiasi connessione
+//! senza la funzione (la CLI di sqlite3, le macro di sqlx in compilazione). Per lo stesso motivo
+//! le ricerche usano le colonne memorizzate e i loro indici, non indici su espressione.
+
+use crate::sorting;
+use libsqlite3_sys as ffi;
+use sqlx::{Executor, Row, SqliteConnection};
+use std::ffi::{c_int, c_void, CStr};
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+/// Nome della funzione SQL che applica `normalize_key`
+pub const NORMALIZE_FUNCTION: &str = "ritmo_normalize";
+
+/// Trigger TEMP che tengono aggiornate le chiavi normalizzate; la chiave viene ricalcolata solo
+/// se manca o se derivava dal nome precedente, così una chiave impostata a mano resta.
+const NORMALIZATION_TRIGGERS: &str = "
+CREATE TEMP TRIGGER IF NOT EXISTS normalize_person_insert
+    AFTER INSERT ON main.people
+    FOR EACH ROW
+    WHEN NEW.normalized_key IS NULL
+BEGIN
+    UPDATE people SET normalized_key = ritmo_normalize(NEW.name) WHERE id = NEW.id;
+END;
+CREATE TEMP TRIGGER IF NOT EXISTS normalize_person_rename
+    AFTER UPDATE OF name ON main.people
+    FOR EACH ROW
+    WHEN NEW.name IS NOT OLD.name
+        AND (NEW.normalized_key IS NULL OR NEW.normalized_key = ritmo_normalize(OLD.name))
+BEGIN
+    UPDATE people SET normalized_key = ritmo_normalize(NEW.name) WHERE id = NEW.id;
+END;
+CREATE TEMP TRIGGER IF NOT EXISTS normalize_alias_insert
+    AFTER INSERT ON main.aliases
+    FOR EACH ROW
+    WHEN NEW.alias_normalized IS NULL
+BEGIN
+    UPDATE aliases SET alias_normalized = ritmo_normalize(NEW.name) WHERE id = NEW.id;
+END;
+CREATE TEMP TRIGGER IF NOT EXISTS normalize_alias_rename
+    AFTER UPDATE OF name ON main.aliases
+    FOR EACH ROW
+    WHEN NEW.name IS NOT OLD.name
+        AND (NEW.alias_normalized IS NULL OR NEW.alias_normalized = ritmo_normalize(OLD.name))
+BEGIN
+    UPDATE aliases SET alias_normalized = ritmo_normalize(NEW.name) WHERE id = NEW.id;
+END;
+";
+
+/// Chiave di confronto di un nome: minuscolo, senza accenti né punteggiatura, spazi singoli.
+/// Le cifre restano, così "Autore 1" e "Autore 2" non si confondono.
+/// "García Márquez, Gabriel" e "garcia  marquez gabriel" hanno la stessa chiave.
+pub fn normalize_key(text: &str) -> String {
+    let folded: String = text
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .map(|c| match c {
+            // Lettere che la decomposizione Unicode non separa dal segno diacritico
+            'đ' => 'd',
+            'ł' => 'l',
+            'ø' => 'o',
+            c if c.is_alphanumeric() || c.is_whitespace() => c,
+            _ => ' ',
+        })
+        .collect();
+    folded.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Testo dell'argomento SQL, None se NULL
+///
+/// # Safety
+/// `value` deve essere un argomento valido della chiamata in corso.
+pub(crate) unsafe fn value_text(value: *mut ffi::sqlite3_value) -> Option<String> {
+    if ffi::sqlite3_value_type(value) == ffi::SQLITE_NULL {
+        return None;
+    }
+    let text = ffi::sqlite3_value_text(value);
+    let len = ffi::sqlite3_value_bytes(value);
+    let bytes = if text.is_null() { &[][..] } else { std::slice::from_raw_parts(text, len as usize) };
+    Some(String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// Imposta il risultato testuale della funzione; SQLite ne fa una copia
+///
+/// # Safety
+/// `ctx` deve essere il contesto della chiamata in corso.
+pub(crate) unsafe fn result_text(ctx: *mut ffi::sqlite3_context, text: &str) {
+    ffi::sqlite3_result_text(ctx, text.as_ptr().cast(), text.len() as c_int, ffi::SQLITE_TRANSIENT());
+}
+
+/// Implementazione C di `ritmo_normalize(testo)`; NULL resta NULL
+unsafe extern "C" fn normalize_sql(ctx: *mut ffi::sqlite3_context, argc: c_int, argv: *mut *mut ffi::sqlite3_value) {
+    if argc != 1 {
+        ffi::sqlite3_result_error(ctx, c"ritmo_normalize richiede un argomento".as_ptr(), -1);
+        return;
+    }
+    match value_text(*argv) {
+        Some(text) => result_text(ctx, &normalize_key(&text)),
+        None => ffi::sqlite3_result_null(ctx),
+    }
+}
+
+/// Funzione scalare deterministica implementata in Rust
+pub(crate) type ScalarFunction =
+    unsafe extern "C" fn(*mut ffi::sqlite3_context, c_int, *mut *mut ffi::sqlite3_value);
+
+/// Registra una funzione scalare senza stato sulla connessione; registrarla di nuovo la sostituisce
+pub(crate) async fn register_function(
+    conn: &mut SqliteConnection,
+    name: &CStr,
+    args: c_int,
+    function: ScalarFunction,
+) -> Result<(), sqlx::Error> {
+    let mut handle = conn.lock_handle().await?;
+    // SAFETY: l'handle è bloccato per tutta la chiamata; la funzione non ha stato
+    // (pApp nullo, nessun distruttore) e SQLite copia il nome.
+    let rc = unsafe {
+        ffi::sqlite3_create_function_v2(
+            handle.as_raw_handle().as_ptr(),
+            name.as_ptr(),
+            args,
+            ffi::SQLITE_UTF8 | ffi::SQLITE_DETERMINISTIC | ffi::SQLITE_INNOCUOUS,
+            std::ptr::null_mut::<c_void>(),
+            Some(function),
+            None,
+            None,
+            None,
+        )
+    };
+    if rc != ffi::SQLITE_OK {
+        return Err(sqlx::Error::Protocol(format!(
+            "impossibile registrare {} (codice SQLite {})",
+            name.to_string_lossy(),
+            rc
+        )));
+    }
+    Ok(())
+}
+
+/// Registra `ritmo_normalize` sulla connessione. Si può chiamare più volte.
+pub async fn register_normalizer(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    register_function(conn, c"ritmo_normalize", 1, normalize_sql).await
+}
+
+/// Prepara una nuova connessione: registra le funzioni e la collazione di `sorting`
+/// e crea i trigger che le usano.
+/// Su un database ancora senza schema i trigger non vengono creati; li avranno le connessioni
+/// aperte dopo la creazione delle tabelle.
+pub async fn prepare_connection(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    register_normalizer(conn).await?;
+    sorting::register_sorting(conn).await?;
+    let row = conn
+        .fetch_one("SELECT COUNT(*) FROM main.sqlite_master WHERE type = 'table' AND name IN ('people', 'aliases')")
+        .await?;
+    if row.try_get::<i64, _>(0)? == 2 {
+        conn.execute(NORMALIZATION_TRIGGERS).await?;
+    }
+    sorting::create_sorting_triggers(conn).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accents_and_punctuation_are_folded() {
+        assert_eq!(normalize_key("  Gabriel García   Márquez "), "gabriel garcia marquez");
+        assert_eq!(normalize_key("D'Annunzio, Gabriele"), "d annunzio gabriele");
+        assert_eq!(normalize_key("Stanisław Lem"), "stanislaw lem");
+        assert_eq!(normalize_key("Đilas"), "dilas");
+        assert_eq!(normalize_key("Karel Čapek"), "karel capek");
+    }
+
+    #[test]
+    fn test_non_latin_letters_and_digits_are_kept() {
+        assert_eq!(normalize_key("Фёдор Достоевский"), "федор достоевскии");
+        assert_eq!(normalize_key("J.R.R. Tolkien"), "j r r tolkien");
+        assert_eq!(normalize_key("Papa Giovanni XXIII, 1881-1963"), "papa giovanni xxiii 1881 1963");
+    }
+}
@@ -0,0 +1,292 @@
+//! Chiavi di ordinamento per titoli e autori e collazione Unicode.
+//!
+//! `books.sort_title`, `contents.sort_title` e `people.sort_name` sono chiavi già ripiegate
+//! (minuscolo, senza accenti) e senza articolo iniziale: ordinate in binario danno l'ordine
+//! di un catalogo, "Il nome della rosa" sotto N ed "Émile" prima di "Zola". Essendo colonne
+//! normali si indicizzano e si ordinano anche da connessioni senza le funzioni Rust.
+//!
+//! Le chiavi che dipendono da Rust sono mantenute da trigger TEMP tramite `ritmo_sort_title`
+//! e `ritmo_sort_name`; `sort_author` di libri e contenuti si ricompone dalle chiavi delle persone
+//! con trigger normali dello schema. Per le query costruite a runtime ogni connessione del pool
+//! ha anche la collazione `ritmo_unicode`, da usare su colonne senza chiave memorizzata.
+
+use crate::normalize::{normalize_key, register_function, result_text, value_text};
+use libsqlite3_sys as ffi;
+use sqlx::{Executor, Row, SqliteConnection};
+use std::cmp::Ordering;
+use std::ffi::c_int;
+
+/// Nome della funzione SQL `ritmo_sort_title(titolo, lingua)`
+pub const SORT_TITLE_FUNCTION: &str = "ritmo_sort_title";
+
+/// Nome della funzione SQL `ritmo_sort_name(nome, cognome, nome_proprio)`
+pub const SORT_NAME_FUNCTION: &str = "ritmo_sort_name";
+
+/// Nome della collazione registrata su ogni connessione
+pub const COLLATION: &str = "ritmo_unicode";
+
+/// Lingua usata per gli articoli quando un titolo non ha una lingua nota
+pub const DEFAULT_LANGUAGE: &str = "it";
+
+/// Particelle che fanno parte del cognome ("Ludwig van Beethoven", "Lorenzo da Ponte")
+const SURNAME_PARTICLES: &[&str] = &[
+    "da", "dal", "dalla", "de", "dei", "degli", "del", "della", "des", "di", "do", "dos", "das", "du", "la", "le",
+    "lo", "van", "von", "der", "den", "ten", "ter", "zu", "af",
+];
+
+/// Suffissi che restano dopo il nome proprio ("King, Martin Luther jr")
+const NAME_SUFFIXES: &[&str] = &["jr", "jr.", "sr", "sr.", "ii", "iii", "iv"];
+
+/// Articoli iniziali da ignorare, per codice ISO 639-1 o 639-2 della lingua.
+/// Le forme elise ("l'", "un'") si riconoscono dall'apostrofo e non serve lo spazio.
+fn articles(language: &str) -> &'static [&'static str] {
+    match language.to_lowercase().as_str() {
+        "it" | "ita" => &["il", "lo", "la", "i", "gli", "le", "un", "uno", "una", "l'", "un'"],
+        "en" | "eng" => &["the", "a", "an"],
+        "fr" | "fra" | "fre" => &["le", "la", "les", "un", "une", "l'"],
+        "de" | "deu" | "ger" => &["der", "die", "das", "ein", "eine"],
+        "es" | "spa" => &["el", "la", "los", "las", "un", "una"],
+        "pt" | "por" => &["o", "a", "os", "as", "um", "uma"],
+        _ => &[],
+    }
+}
+
+/// Toglie l'articolo iniziale, se il titolo non si riduce a nulla
+fn strip_article(title: &str, articles: &[&str]) -> String {
+    // L'apostrofo tipografico conta come quello semplice
+    let title = title.trim_start().replace('’', "'");
+    for article in articles {
+        if title.get(..article.len()).is_none_or(|head| head.to_lowercase() != *article) {
+            continue;
+        }
+        let rest = &title[article.len()..];
+        let separated = article.ends_with('\'') || rest.starts_with(char::is_whitespace);
+        if separated && !normalize_key(rest).is_empty() {
+            return rest.to_string();
+        }
+    }
+    title
+}
+
+/// Chiave di ordinamento di un titolo: senza l'articolo iniziale della lingua, poi ripiegata
+/// come `normalize_key`. Senza lingua valgono gli articoli italiani e "the".
+pub fn sort_title(title: &str, language: Option<&str>) -> String {
+    let stripped = match language.map(articles) {
+        Some(list) if !list.is_empty() => strip_article(title, list),
+        _ => {
+            let fallback: Vec<&str> = articles(DEFAULT_LANGUAGE).iter().copied().chain(["the"]).collect();
+            strip_article(title, &fallback)
+        }
+    };
+    normalize_key(&stripped)
+}
+
+/// Chiave "cognome, nome" di una persona. Usa cognome e nome proprio se noti; altrimenti
+/// li ricava dal nome completo: un nome già nella forma "Cognome, Nome" resta com'è, se no
+/// il cognome è l'ultima parola con le particelle che la precedono.
+pub fn sort_name(name: &str, surname: Option<&str>, given_name: Option<&str>) -> String {
+    let join = |surname: &str, given: &str| {
+        let (surname, given) = (normalize_key(surname), normalize_key(given));
+        if given.is_empty() {
+            surname
+        } else {
+            format!("{}, {}", surname, given)
+        }
+    };
+    if let Some(surname) = surname.filter(|s| !s.trim().is_empty()) {
+        return join(surname, given_name.unwrap_or_default());
+    }
+    if let Some((surname, given)) = name.split_once(',') {
+        return join(surname, given);
+    }
+
+    let mut words: Vec<&str> = name.split_whitespace().collect();
+    let mut suffixes = Vec::new();
+    while words.len() > 2 && words.last().is_some_and(|w| NAME_SUFFIXES.contains(&w.to_lowercase().as_str())) {
+        suffixes.insert(0, words.pop().unwrap_or_default());
+    }
+    if words.len() < 2 {
+        return normalize_key(name);
+    }
+    let mut start = words.len() - 1;
+    while start > 1 && SURNAME_PARTICLES.contains(&words[start - 1].to_lowercase().as_str()) {
+        start -= 1;
+    }
+    let given: Vec<&str> = words[..start].iter().chain(suffixes.iter()).copied().collect();
+    join(&words[start..].join(" "), &given.join(" "))
+}
+
+/// Confronto della collazione `ritmo_unicode`: prima sul testo ripiegato, senza maiuscole né
+/// accenti, poi sul testo originale, così l'ordine resta totale e stabile
+pub fn compare(a: &str, b: &str) -> Ordering {
+    normalize_key(a).cmp(&normalize_key(b)).then_with(|| a.cmp(b))
+}
+
+/// Implementazione C di `ritmo_sort_title(titolo, lingua)`; un titolo NULL dà NULL
+unsafe extern "C" fn sort_title_sql(ctx: *mut ffi::sqlite3_context, argc: c_int, argv: *mut *mut ffi::sqlite3_value) {
+    if argc != 2 {
+        ffi::sqlite3_result_error(ctx, c"ritmo_sort_title richiede due argomenti".as_ptr(), -1);
+        return;
+    }
+    let args = std::slice::from_raw_parts(argv, 2);
+    match value_text(args[0]) {
+        Some(title) => result_text(ctx, &sort_title(&title, value_text(args[1]).as_deref())),
+        None => ffi::sqlite3_result_null(ctx),
+    }
+}
+
+/// Implementazione C di `ritmo_sort_name(nome, cognome, nome_proprio)`; un nome NULL dà NULL
+unsafe extern "C" fn sort_name_sql(ctx: *mut ffi::sqlite3_context, argc: c_int, argv: *mut *mut ffi::sqlite3_value) {
+    if argc != 3 {
+        ffi::sqlite3_result_error(ctx, c"ritmo_sort_name richiede tre argomenti".as_ptr(), -1);
+        return;
+    }
+    let args = std::slice::from_raw_parts(argv, 3);
+    match value_text(args[0]) {
+        Some(name) => {
+            let key = sort_name(&name, value_text(args[1]).as_deref(), value_text(args[2]).as_deref());
+            result_text(ctx, &key)
+        }
+        None => ffi::sqlite3_result_null(ctx),
+    }
+}
+
+/// Registra `ritmo_sort_title`, `ritmo_sort_name` e la collazione `ritmo_unicode`
+pub async fn register_sorting(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    register_function(conn, c"ritmo_sort_title", 2, sort_title_sql).await?;
+    register_function(conn, c"ritmo_sort_name", 3, sort_name_sql).await?;
+    conn.lock_handle().await?.create_collation(COLLATION, compare)
+}
+
+/// Lingua di un contenuto: quella dell'edizione ('Actual'), poi l'originale
+pub(crate) fn content_language(content_id: &str) -> String {
+    format!(
+        "(SELECT l.iso_code_2char FROM x_contents_languages cl
+            JOIN running_languages l ON l.id = cl.language_id
+          WHERE cl.content_id = {}
+          ORDER BY CASE l.language_role WHEN 'Actual' THEN 0 WHEN 'Original' THEN 1 ELSE 2 END, cl.rowid
+          LIMIT 1)",
+        content_id
+    )
+}
+
+/// Lingua di un libro: la prima dei suoi contenuti, con la stessa precedenza
+pub(crate) fn book_language(book_id: &str) -> String {
+    format!(
+        "(SELECT l.iso_code_2char FROM x_books_contents bc
+            JOIN x_contents_languages cl ON cl.content_id = bc.content_id
+            JOIN running_languages l ON l.id = cl.language_id
+          WHERE bc.book_id = {}
+          ORDER BY CASE l.language_role WHEN 'Actual' THEN 0 WHEN 'Original' THEN 1 ELSE 2 END, bc.rowid, cl.rowid
+          LIMIT 1)",
+        book_id
+    )
+}
+
+/// UPDATE che ricalcola `sort_title` delle righe scelte da `filter`; le righe già
+/// aggiornate non vengono toccate, così non scattano i trigger di modifica
+fn refresh_title(table: &str, filter: &str, language: &str) -> String {
+    format!(
+        "UPDATE {table} SET sort_title = ritmo_sort_title(name, {language})
+         WHERE {filter} AND sort_title IS NOT ritmo_sort_title(name, {language});",
+        table = table,
+        filter = filter,
+        language = language
+    )
+}
+
+/// Trigger TEMP che mantengono `sort_title` e `people.sort_name`
+pub fn sorting_triggers() -> String {
+    let book = |id: &str| refresh_title("books", &format!("id = {}", id), &book_language("books.id"));
+    let content = |id: &str| refresh_title("contents", &format!("id = {}", id), &content_language("contents.id"));
+    let books_of = |content_id: &str| {
+        refresh_title(
+            "books",
+            &format!("id IN (SELECT book_id FROM x_books_contents WHERE content_id = {})", content_id),
+            &book_language("books.id"),
+        )
+    };
+    let person = "UPDATE people SET sort_name = ritmo_sort_name(name, surname, given_name)
+         WHERE id = NEW.id AND sort_name IS NOT ritmo_sort_name(name, surname, given_name);";
+
+    format!(
+        "
+CREATE TEMP TRIGGER IF NOT EXISTS sort_book_insert AFTER INSERT ON main.books FOR EACH ROW
+BEGIN {book_new} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_book_rename AFTER UPDATE OF name ON main.books FOR EACH ROW
+BEGIN {book_new} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_content_insert AFTER INSERT ON main.contents FOR EACH ROW
+BEGIN {content_new} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_content_rename AFTER UPDATE OF name ON main.contents FOR EACH ROW
+BEGIN {content_new} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_book_content_insert AFTER INSERT ON main.x_books_contents FOR EACH ROW
+BEGIN {book_link_new} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_book_content_delete AFTER DELETE ON main.x_books_contents FOR EACH ROW
+BEGIN {book_link_old} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_content_language_insert AFTER INSERT ON main.x_contents_languages FOR EACH ROW
+BEGIN {content_link_new} {books_new} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_content_language_delete AFTER DELETE ON main.x_contents_languages FOR EACH ROW
+BEGIN {content_link_old} {books_old} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_person_insert AFTER INSERT ON main.people FOR EACH ROW
+BEGIN {person} END;
+CREATE TEMP TRIGGER IF NOT EXISTS sort_person_rename AFTER UPDATE OF name, surname, given_name ON main.people FOR EACH ROW
+BEGIN {person} END;
+",
+        book_new = book("NEW.id"),
+        content_new = content("NEW.id"),
+        book_link_new = book("NEW.book_id"),
+        book_link_old = book("OLD.book_id"),
+        content_link_new = content("NEW.content_id"),
+        content_link_old = content("OLD.content_id"),
+        books_new = books_of("NEW.content_id"),
+        books_old = books_of("OLD.content_id"),
+        person = person,
+    )
+}
+
+/// Crea i trigger di ordinamento, se lo schema ha già le colonne delle chiavi
+pub async fn create_sorting_triggers(conn: &mut SqliteConnection) -> Result<(), sqlx::Error> {
+    let row = conn
+        .fetch_one(
+            "SELECT (SELECT COUNT(*) FROM pragma_table_info('books') WHERE name = 'sort_title')
+                  + (SELECT COUNT(*) FROM pragma_table_info('contents') WHERE name = 'sort_title')
+                  + (SELECT COUNT(*) FROM pragma_table_info('people') WHERE name = 'sort_name')",
+        )
+        .await?;
+    if row.try_get::<i64, _>(0)? == 3 {
+        conn.execute(sorting_triggers().as_str()).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_leading_articles_follow_the_language() {
+        assert_eq!(sort_title("Il nome della rosa", Some("it")), "nome della rosa");
+        assert_eq!(sort_title("L'isola del tesoro", Some("it")), "isola del tesoro");
+        assert_eq!(sort_title("L’amica geniale", None), "amica geniale");
+        assert_eq!(sort_title("The Shining", None), "shining");
+        assert_eq!(sort_title("A Tale of Two Cities", Some("en")), "tale of two cities");
+        assert_eq!(sort_title("A ciascuno il suo", Some("it")), "a ciascuno il suo");
+        assert_eq!(sort_title("Der Prozess", Some("de")), "prozess");
+        assert_eq!(sort_title("El Aleph", Some("spa")), "aleph");
+        assert_eq!(sort_title("Ilona", Some("it")), "ilona");
+        assert_eq!(sort_title("Il", Some("it")), "il");
+        assert_eq!(sort_title("Émile", None), "emile");
+    }
+
+    #[test]
+    fn test_names_sort_by_surname() {
+        assert_eq!(sort_name("Umberto Eco", None, None), "eco, umberto");
+        assert_eq!(sort_name("Ludwig van Beethoven", None, None), "van beethoven, ludwig");
+        assert_eq!(sort_name("Martin Luther King Jr.", None, None), "king, martin luther jr");
+        assert_eq!(sort_name("García Márquez, Gabriel", None, None), "garcia marquez, gabriel");
+        assert_eq!(sort_name("Gabriel García Márquez", Some("García Márquez"), Some("Gabriel")), "garcia marquez, gabriel");
+        assert_eq!(sort_name("Omero", None, None), "omero");
+        assert_eq!(compare("Émile", "Zola"), Ordering::Less);
+        assert_eq!(compare("eco", "Eco"), Ordering::Greater);
+    }
+}
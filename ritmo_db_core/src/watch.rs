@@ -0,0 +1,110 @@
+//! Notifica delle modifiche fatte da altri processi.
+//!
+//! Un frontend che tiene aperta la libreria (GUI, server web) non sa quando la CLI importa libri.
+//! `watch_changes` controlla `PRAGMA data_version` su una connessione dedicata e, quando cambia,
+//! legge da `change_log` quali tabelle sono state modificate dall'ultima versione vista.
+//! La versione è l'id dell'ultima riga di `change_log`, quindi sopravvive ai riavvii.
+
+use crate::events::{data_version, last_change_seq};
+use futures::Stream;
+use ritmo_errors::RitmoResult;
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::{ConnectOptions, SqliteConnection, SqlitePool};
+use std::collections::BTreeSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Intervallo con cui `watch_changes` controlla se ci sono nuovi commit
+pub const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Tabelle modificate tra `since` e `version`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TablesChanged {
+    pub since: i64,
+    pub version: i64,
+    pub tables: BTreeSet<String>,
+    /// Il registro è stato potato dopo `since`: l'elenco delle tabelle può essere incompleto
+    /// e conviene ricaricare tutto
+    pub truncated: bool,
+}
+
+/// Versione corrente del registro delle modifiche, da passare a `watch_changes`
+pub async fn current_version(pool: &SqlitePool) -> RitmoResult<i64> {
+    let mut conn = pool.acquire().await?;
+    last_change_seq(&mut conn).await
+}
+
+/// Flusso delle modifiche confermate dopo la versione `since`, fatte da qualsiasi processo
+/// (compreso quello corrente). Se la connessione dedicata cade, il flusso restituisce l'errore
+/// e al passo successivo riprova a connettersi.
+pub fn watch_changes(pool: &SqlitePool, since: i64) -> impl Stream<Item = RitmoResult<TablesChanged>> {
+    let watcher = Watcher {
+        options: pool.connect_options(),
+        conn: None,
+        data_version: None,
+        since,
+    };
+    futures::stream::unfold(watcher, |mut watcher| async move {
+        let next = watcher.next().await;
+        Some((next, watcher))
+    })
+}
+
+struct Watcher {
+    options: Arc<SqliteConnectOptions>,
+    conn: Option<SqliteConnection>,
+    data_version: Option<i64>,
+    since: i64,
+}
+
+impl Watcher {
+    async fn next(&mut self) -> RitmoResult<TablesChanged> {
+        loop {
+            match self.poll().await {
+                Ok(Some(changed)) => return Ok(changed),
+                Ok(None) => tokio::time::sleep(WATCH_POLL_INTERVAL).await,
+                Err(e) => {
+                    self.conn = None;
+                    self.data_version = None;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    async fn poll(&mut self) -> RitmoResult<Option<TablesChanged>> {
+        let conn = match self.conn.as_mut() {
+            Some(conn) => conn,
+            None => self.conn.insert(self.options.connect().await?),
+        };
+        let version = data_version(conn).await?;
+        if self.data_version == Some(version) {
+            return Ok(None);
+        }
+        self.data_version = Some(version);
+
+        let bounds = sqlx::query!(
+            "SELECT COALESCE(MIN(id), 0) AS \"first!: i64\", COALESCE(MAX(id), 0) AS \"last!: i64\" FROM change_log"
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+        if bounds.last <= self.since {
+            return Ok(None);
+        }
+        let tables = sqlx::query_scalar!(
+            "SELECT DISTINCT table_name FROM change_log WHERE id > ? AND id <= ?",
+            self.since,
+            bounds.last
+        )
+        .fetch_all(&mut *conn)
+        .await?;
+        let changed = TablesChanged {
+            since: self.since,
+            version: bounds.last,
+            tables: tables.into_iter().collect(),
+            truncated: bounds.first > self.since + 1,
+        };
+        self.since = bounds.last;
+        Ok(Some(changed))
+    }
+}
@@ -1,7 +1,7 @@
 use crate::people::parse_names::ParsedName;
 use human_name::Name;
 use strsim::levenshtein;
-use unicode_normalization::UnicodeNormalization;
+use ritmo_db_core::normalize::normalize_key;
 
 pub struct MLStringUtils {
     pub name_variants: std::collections::HashMap<String, Vec<String>>,
@@ -12,35 +12,9 @@ impl MLStringUtils {
         MLStringUtils { name_variants }
     }
 
+    /// Stessa chiave di `people.normalized_key`: l'algoritmo è condiviso con il database
     pub fn normalize_string(&self, text: &str) -> String {
-        let normalized = text
-            .nfc()
-            .collect::<String>()
-            .to_lowercase()
-            .chars()
-            .map(|c| match c {
-                'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => 'a',
-                'è' | 'é' | 'ê' | 'ë' => 'e',
-                'ì' | 'í' | 'î' | 'ï' => 'i',
-                'ō' | 'ò' | 'ó' | 'ô' | 'õ' | 'ö' => 'o',
-                'ù' | 'ú' | 'û' | 'ü' => 'u',
-                'ç' => 'c',
-                'ñ' => 'n',
-                'ý' | 'ÿ' => 'y',
-                'č' | 'ć' => 'c',
-                'š' => 's',
-                'ž' => 'z',
-                'đ' => 'd',
-                'ł' => 'l',
-                c if c.is_alphabetic() || c.is_whitespace() => c,
-                _ => ' ',
-            })
-            .collect::<String>();
-
-        normalized
-            .split_whitespace()
-            .collect::<Vec<&str>>()
-            .join(" ")
+        normalize_key(text)
     }
 
     pub fn normalized_levenshtein(&self, s1: &str, s2: &str) -> f64 {